@@ -0,0 +1,5 @@
+fn main() {
+    // Scaffolding for the UniFFI bindings (src/pwgen.udl, src/mobile.rs)
+    #[cfg(feature = "uniffi")]
+    uniffi::generate_scaffolding("src/pwgen.udl").expect("src/pwgen.udl is a valid UniFFI interface");
+}
@@ -0,0 +1,62 @@
+/*
+ * C interface to pwgen's derivation (the `ffi` feature, see src/ffi.rs).
+ *
+ * Build the shared library with
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ * and link against target/release/libpwgen.so (.dylib on macOS, pwgen.dll on Windows).
+ *
+ * Every function returns PWGEN_OK or a negative PWGEN_ERR_* code; pwgen_last_error()
+ * describes the last failure on the calling thread. Strings are UTF-8 and NUL-terminated.
+ * Passwords are returned in heap strings the caller releases with pwgen_string_free(),
+ * which zeroizes them first.
+ */
+
+#ifndef PWGEN_H
+#define PWGEN_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define PWGEN_OK 0
+#define PWGEN_ERR_NULL (-1)     /* a required pointer argument was null */
+#define PWGEN_ERR_UTF8 (-2)     /* a string argument was not valid UTF-8 */
+#define PWGEN_ERR_INVALID (-3)  /* empty master, unknown profile, bad policy or pattern */
+#define PWGEN_ERR_DERIVE (-4)   /* the KDF or the PRNG failed */
+#define PWGEN_ERR_INTERNAL (-5) /* a bug inside the library */
+
+/* The master secret with its KDF and algorithm version. */
+typedef struct PwgenMaster PwgenMaster;
+
+/* Balanced Argon2id profile and algorithm v1 until changed. */
+int pwgen_master_new(const char *master, PwgenMaster **out);
+/* "fast", "balanced" or "paranoid", as --kdf-profile. */
+int pwgen_master_set_kdf_profile(PwgenMaster *handle, const char *profile);
+/* Custom Argon2id costs, as kdf-params in the config file. */
+int pwgen_master_set_kdf_params(PwgenMaster *handle, uint32_t mem_kib, uint32_t t_cost, uint32_t p_cost);
+/* 1, 2, 3 or 5 (4 with the blake3 feature), as --algo-version. */
+int pwgen_master_set_algo_version(PwgenMaster *handle, uint32_t version);
+/* Zeroizes and frees; NULL is ignored. */
+void pwgen_master_free(PwgenMaster *handle);
+
+/* As pwgen generate; site is normalized as with --site-mode exact (a URL counts as its
+ * host). username may be NULL; policy NULL for the default policy, else an
+ * encoded policy as printed in --json output. */
+int pwgen_generate(const PwgenMaster *handle, const char *site, const char *username,
+                   const char *policy, uint32_t version, char **out);
+/* As pwgen generate --pattern, with site normalized likewise. username may be NULL. */
+int pwgen_generate_pattern(const PwgenMaster *handle, const char *site, const char *username,
+                           const char *pattern, uint32_t version, char **out);
+
+/* Zeroizes and frees a string returned above; NULL is ignored. */
+void pwgen_string_free(char *s);
+/* Message of the last error on this thread, or NULL; valid until the next failing call. */
+const char *pwgen_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PWGEN_H */
@@ -0,0 +1,797 @@
+//! `pwgen agent` and `pwgen client`: the agent holds the master secret in locked memory and
+//! derives site keys over a Unix socket, like ssh-agent, so the master is typed once per
+//! session and each site's Argon2 run happens once.
+//!
+//! Protocol: one request line per connection, answered by one line.
+//!
+//! ```text
+//! DERIVE <kdf> <site> <input> <ad>   ->   OK <key> | ERR <message>
+//! LOCK                               ->   OK -
+//! UNLOCK <input> <secret>            ->   OK -
+//! ```
+//!
+//! `<kdf>` is `argon2id:m=<kib>,t=<t>,p=<p>` or `pbkdf2:<iterations>`; the other fields are
+//! hex, with `-` for an empty value. For `DERIVE`, `<input>` is appended to the agent's KDF
+//! input (the client's YubiKey digest, if any) and `<ad>` is Argon2's associated data. For
+//! `UNLOCK`, `<input>` is the agent's new KDF input and `<secret>` the Argon2 secret.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use pwgen::format::Format;
+use pwgen::kdf::{self, Kdf};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Environment variable naming the agent socket, for both `pwgen agent` and `pwgen client`.
+pub const SOCKET_ENV: &str = "PWGEN_AGENT_SOCK";
+
+/// Site passed to `resolve_inputs` when the agent starts; the agent itself serves every site.
+pub const AGENT_SITE: &str = "pwgen-agent";
+
+/// Number of site keys the agent caches before it starts over.
+const CACHE_SLOTS: usize = 256;
+
+/// Longest request line the agent reads.
+const MAX_REQUEST: u64 = 4096;
+
+/// How long either side waits on a stalled peer.
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `$PWGEN_AGENT_SOCK`, else `agent.sock` in a `pwgen` directory under `$XDG_RUNTIME_DIR`
+/// (or a per-user directory in the temp dir).
+pub fn default_socket() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(SOCKET_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|p| !p.is_empty()) {
+        Some(runtime) => PathBuf::from(runtime).join("pwgen"),
+        None => std::env::temp_dir().join(format!("pwgen-{}", user_id()?)),
+    };
+    Some(dir.join("agent.sock"))
+}
+
+/// Secrets the agent derives with: the KDF input (master, keyfile and pepper digests) and the
+/// Argon2 secret, both in locked memory.
+pub struct Secrets {
+    input: LockedBuf,
+    argon2_secret: Option<LockedBuf>,
+}
+
+impl Secrets {
+    /// Copies `input` (see `kdf::MasterInput::kdf_input`) and the Argon2 secret into locked
+    /// memory; the caller zeroizes its own copies.
+    pub fn new(input: &[u8], argon2_secret: Option<&[u8]>) -> Self {
+        Secrets {
+            input: LockedBuf::from_slice(input),
+            argon2_secret: argon2_secret.map(LockedBuf::from_slice),
+        }
+    }
+
+    /// Whether all secrets, and the key cache, could be locked into memory.
+    fn locked(&self) -> bool {
+        self.input.locked && self.argon2_secret.as_ref().is_none_or(|s| s.locked)
+    }
+}
+
+/// Site keys derived so far, in one locked buffer indexed by the SHA-256 of the request (so
+/// the index holds no site names or YubiKey digests).
+struct Cache {
+    slots: LockedBuf,
+    index: HashMap<[u8; 32], usize>,
+}
+
+impl Cache {
+    fn new() -> Self {
+        Cache { slots: LockedBuf::zeroed(CACHE_SLOTS * kdf::KDF_OUT_LEN), index: HashMap::new() }
+    }
+
+    fn get(&self, request: &str) -> Option<&[u8]> {
+        let slot = *self.index.get(&Self::id(request))?;
+        Some(&self.slots[slot * kdf::KDF_OUT_LEN..(slot + 1) * kdf::KDF_OUT_LEN])
+    }
+
+    fn insert(&mut self, request: &str, key: &[u8; kdf::KDF_OUT_LEN]) {
+        if self.index.len() == CACHE_SLOTS {
+            self.clear();
+        }
+        let slot = self.index.len();
+        self.slots[slot * kdf::KDF_OUT_LEN..(slot + 1) * kdf::KDF_OUT_LEN].copy_from_slice(key);
+        self.index.insert(Self::id(request), slot);
+    }
+
+    fn clear(&mut self) {
+        self.slots.zeroize();
+        self.index.clear();
+    }
+
+    fn id(request: &str) -> [u8; 32] {
+        Sha256::digest(request.as_bytes()).into()
+    }
+}
+
+/// Buffer locked into RAM (so it is never swapped out) and zeroized on drop.
+///
+/// On Unix it has pages of its own, mapped between two inaccessible guard pages and excluded
+/// from core dumps, with the data ending at the upper guard page, so an overrun faults
+/// instead of reading or writing neighbouring heap memory. It falls back to the heap if the
+/// pages cannot be mapped.
+struct LockedBuf {
+    memory: Memory,
+    locked: bool,
+}
+
+enum Memory {
+    #[cfg(unix)]
+    Guarded(guarded::Pages),
+    Heap(Box<[u8]>),
+}
+
+impl LockedBuf {
+    fn zeroed(len: usize) -> Self {
+        #[cfg(unix)]
+        let memory = match guarded::Pages::new(len) {
+            Ok(pages) => Memory::Guarded(pages),
+            Err(_) => Memory::Heap(vec![0u8; len].into_boxed_slice()),
+        };
+        #[cfg(not(unix))]
+        let memory = Memory::Heap(vec![0u8; len].into_boxed_slice());
+        let mut buf = LockedBuf { memory, locked: false };
+        buf.locked = lock_memory(&buf);
+        buf
+    }
+
+    fn from_slice(bytes: &[u8]) -> Self {
+        let mut locked = LockedBuf::zeroed(bytes.len());
+        locked.copy_from_slice(bytes);
+        locked
+    }
+}
+
+impl std::ops::Deref for LockedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.memory {
+            #[cfg(unix)]
+            Memory::Guarded(pages) => pages.as_slice(),
+            Memory::Heap(buf) => buf,
+        }
+    }
+}
+
+impl std::ops::DerefMut for LockedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match &mut self.memory {
+            #[cfg(unix)]
+            Memory::Guarded(pages) => pages.as_mut_slice(),
+            Memory::Heap(buf) => buf,
+        }
+    }
+}
+
+impl Drop for LockedBuf {
+    fn drop(&mut self) {
+        self.zeroize();
+        if self.locked {
+            unlock_memory(self);
+        }
+    }
+}
+
+/// Bound agent socket; connections wait in the backlog until `serve` runs.
+pub struct Listener(unix::Listener);
+
+/// Creates the socket's directory (mode 0700) if needed, replaces a stale socket and binds,
+/// failing if another agent is listening.
+pub fn bind(socket: &Path) -> io::Result<Listener> {
+    unix::bind(socket).map(Listener)
+}
+
+/// The socket passed by systemd socket activation (`LISTEN_PID` and `LISTEN_FDS`), if the
+/// agent was started that way. Ownership, permissions and the socket file are then systemd's.
+pub fn activated() -> io::Result<Option<Listener>> {
+    Ok(unix::activated()?.map(Listener))
+}
+
+/// When the agent locks itself: wiping the master and cached keys until `pwgen agent unlock`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Limits {
+    /// Lock after this long without a `DERIVE` request
+    pub idle_timeout: Option<Duration>,
+    /// Lock this long after the master was given, however busy the agent is
+    pub max_ttl: Option<Duration>,
+}
+
+/// Agent state: the secrets while unlocked, and the key cache.
+struct Agent {
+    secrets: Option<Secrets>,
+    cache: Cache,
+    limits: Limits,
+    unlocked_at: Instant,
+    last_used: Instant,
+}
+
+impl Agent {
+    /// Earliest time the agent must lock itself, if it is unlocked and has limits.
+    fn deadline(&self) -> Option<Instant> {
+        self.secrets.as_ref()?;
+        let idle = self.limits.idle_timeout.map(|t| self.last_used + t);
+        let ttl = self.limits.max_ttl.map(|t| self.unlocked_at + t);
+        idle.into_iter().chain(ttl).min()
+    }
+
+    /// Locks the agent if a limit has passed.
+    fn expire(&mut self, now: Instant) {
+        if self.secrets.is_none() {
+            return;
+        }
+        if self.limits.max_ttl.is_some_and(|t| now >= self.unlocked_at + t) {
+            self.lock("maximum lifetime reached");
+        } else if self.limits.idle_timeout.is_some_and(|t| now >= self.last_used + t) {
+            self.lock("idle timeout");
+        }
+    }
+
+    /// Drops (and so zeroizes) the secrets and wipes the cached site keys.
+    fn lock(&mut self, reason: &str) {
+        if self.secrets.take().is_some() {
+            eprintln!("pwgen agent locked ({})", reason);
+        }
+        self.cache.clear();
+    }
+
+    fn unlock(&mut self, secrets: Secrets) {
+        if !secrets.locked() {
+            eprintln!("warning: could not lock the new secrets into memory; they may be swapped to disk (raise `ulimit -l`)");
+        }
+        self.cache.clear();
+        self.secrets = Some(secrets);
+        self.unlocked_at = Instant::now();
+        self.last_used = self.unlocked_at;
+        eprintln!("pwgen agent unlocked");
+    }
+
+    /// Answers one request line: `Ok` with the response payload, or `Err` with a message.
+    fn respond(&mut self, request: &str) -> Result<Zeroizing<String>, String> {
+        self.expire(Instant::now());
+        match request.split_once(' ').map_or(request, |(command, _)| command) {
+            "DERIVE" => {
+                let key = self.derive(request)?;
+                self.last_used = Instant::now();
+                Ok(key)
+            }
+            "LOCK" if request == "LOCK" => {
+                self.lock("lock requested");
+                Ok(Zeroizing::new("-".to_string()))
+            }
+            "UNLOCK" => {
+                let mut fields = request.split(' ').skip(1);
+                let (Some(input), Some(secret), None) = (fields.next(), fields.next(), fields.next()) else {
+                    return Err("malformed request".to_string());
+                };
+                let input = Zeroizing::new(decode_field(input).ok_or("malformed input")?);
+                let secret = Zeroizing::new(decode_field(secret).ok_or("malformed secret")?);
+                if input.is_empty() {
+                    return Err("empty master secret".to_string());
+                }
+                self.unlock(Secrets::new(&input, Some(secret.as_slice()).filter(|s| !s.is_empty())));
+                Ok(Zeroizing::new("-".to_string()))
+            }
+            _ => Err("malformed request".to_string()),
+        }
+    }
+
+    fn derive(&mut self, request: &str) -> Result<Zeroizing<String>, String> {
+        let Some(secrets) = &self.secrets else {
+            return Err("locked (run pwgen agent unlock)".to_string());
+        };
+        derive(request, secrets, &mut self.cache)
+    }
+}
+
+/// Answers requests until the process is stopped. The agent starts unlocked with `secrets`,
+/// or locked without them.
+pub fn serve(listener: &Listener, secrets: Option<Secrets>, limits: Limits) -> io::Result<()> {
+    harden_process();
+    let cache = Cache::new();
+    if !secrets.as_ref().is_none_or(Secrets::locked) || !cache.slots.locked {
+        eprintln!("warning: could not lock the agent's memory; secrets may be swapped to disk (raise `ulimit -l`)");
+    }
+    let now = Instant::now();
+    let mut agent = Agent { secrets, cache, limits, unlocked_at: now, last_used: now };
+    match unix::local_path(&listener.0) {
+        Some(socket) => {
+            eprintln!("pwgen agent listening on {}{}", socket.display(), if agent.secrets.is_none() { " (locked)" } else { "" });
+            eprintln!("Use it with: export {}={}", SOCKET_ENV, socket.display());
+        }
+        None => eprintln!("pwgen agent listening"),
+    }
+    unix::accept_loop(&listener.0, &mut agent)
+}
+
+/// Asks the agent to wipe its master and cached keys.
+pub fn lock(socket: &Path) -> Result<(), String> {
+    unix::request(socket, "LOCK\n").map(drop)
+}
+
+/// Hands the running agent new secrets, replacing any it holds and wiping its cache.
+pub fn unlock(socket: &Path, secrets: &Secrets) -> Result<(), String> {
+    let input = Zeroizing::new(encode_field(&secrets.input));
+    let argon2_secret = Zeroizing::new(encode_field(secrets.argon2_secret.as_ref().map_or(&[][..], |s| s)));
+    let request = Zeroizing::new(format!("UNLOCK {} {}\n", *input, *argon2_secret));
+    unix::request(socket, &request).map(drop)
+}
+
+/// Answers a `DERIVE` request, from the cache if it was answered before.
+fn derive(request: &str, secrets: &Secrets, cache: &mut Cache) -> Result<Zeroizing<String>, String> {
+    let mut fields = request.split(' ');
+    let (Some("DERIVE"), Some(kdf), Some(site), Some(input), Some(ad), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err("malformed request".to_string());
+    };
+    if let Some(key) = cache.get(request) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(cached = true, "site key");
+        return Ok(Zeroizing::new(Format::Hex.encode(key)));
+    }
+
+    let scheme = parse_kdf(kdf).ok_or("malformed KDF")?;
+    let site = String::from_utf8(decode_field(site).ok_or("malformed site")?).map_err(|_| "malformed site")?;
+    let client_input = Zeroizing::new(decode_field(input).ok_or("malformed input")?);
+    let ad = decode_field(ad).ok_or("malformed associated data")?;
+
+    let mut full_input = LockedBuf::zeroed(secrets.input.len() + client_input.len());
+    full_input[..secrets.input.len()].copy_from_slice(&secrets.input);
+    full_input[secrets.input.len()..].copy_from_slice(&client_input);
+    let extras = kdf::KdfExtras {
+        secret: secrets.argon2_secret.as_ref().map(|s| &s[..]),
+        ad: Some(ad.as_slice()).filter(|ad| !ad.is_empty()),
+    };
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("kdf", kdf = %scheme.context_id()).entered();
+    let key = scheme.derive_locked(&full_input, &site, &extras).map_err(|e| e.to_string())?;
+    #[cfg(feature = "tracing")]
+    drop(span);
+    cache.insert(request, &key);
+    let hex = Zeroizing::new(Format::Hex.encode(&key[..]));
+    drop(key);
+    Ok(hex)
+}
+
+/// KDF computed by a running agent (`pwgen client`); `scheme` is sent with each request.
+#[derive(Copy, Clone)]
+pub struct Remote<'a> {
+    pub socket: &'a Path,
+    pub scheme: kdf::KdfScheme,
+}
+
+impl Kdf for Remote<'_> {
+    fn derive(&self, master: &[u8], site: &str) -> Result<[u8; kdf::KDF_OUT_LEN], kdf::KdfError> {
+        self.derive_with_extras(master, site, &kdf::KdfExtras::default())
+    }
+
+    /// `master` is only the client's part of the KDF input; the agent prepends its own.
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &kdf::KdfExtras<'_>,
+    ) -> Result<[u8; kdf::KDF_OUT_LEN], kdf::KdfError> {
+        if extras.secret.is_some() {
+            return Err(kdf::KdfError::Backend("the Argon2 secret is held by the agent".to_string()));
+        }
+        let request = format!(
+            "DERIVE {} {} {} {}\n",
+            kdf_field(&self.scheme),
+            encode_field(site.as_bytes()),
+            encode_field(master),
+            encode_field(extras.ad.unwrap_or_default())
+        );
+        let reply = Zeroizing::new(unix::request(self.socket, &request).map_err(kdf::KdfError::Backend)?);
+        let bytes = Zeroizing::new(decode_field(&reply).unwrap_or_default());
+        let mut key = [0u8; kdf::KDF_OUT_LEN];
+        if bytes.len() != key.len() {
+            return Err(kdf::KdfError::Backend("malformed agent response".to_string()));
+        }
+        key.copy_from_slice(&bytes);
+        Ok(key)
+    }
+
+    fn context_id(&self) -> String {
+        self.scheme.context_id()
+    }
+}
+
+fn kdf_field(scheme: &kdf::KdfScheme) -> String {
+    match scheme {
+        kdf::KdfScheme::Argon2id(params) => format!("argon2id:{}", params),
+        kdf::KdfScheme::Pbkdf2Sha256 { iterations } => format!("pbkdf2:{}", iterations),
+    }
+}
+
+fn parse_kdf(field: &str) -> Option<kdf::KdfScheme> {
+    if let Some(iterations) = field.strip_prefix("pbkdf2:") {
+        return iterations.parse().ok().filter(|&i| i > 0).map(|iterations| kdf::KdfScheme::Pbkdf2Sha256 { iterations });
+    }
+    let mut costs = field.strip_prefix("argon2id:")?.split(',');
+    let mut cost = |name: &str| costs.next()?.strip_prefix(name)?.parse().ok();
+    let params = kdf::KdfParams { mem_kib: cost("m=")?, t_cost: cost("t=")?, p_cost: cost("p=")? };
+    params.validate().ok()?;
+    // Each request sizes the agent's Argon2 memory, so a client cannot make it allocate terabytes
+    params.check_served().ok()?;
+    Some(kdf::KdfScheme::Argon2id(params))
+}
+
+fn encode_field(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        "-".to_string()
+    } else {
+        Format::Hex.encode(bytes)
+    }
+}
+
+fn decode_field(field: &str) -> Option<Vec<u8>> {
+    if field == "-" {
+        return Some(Vec::new());
+    }
+    if !field.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..field.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(field.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(unix)]
+fn user_id() -> Option<u32> {
+    // SAFETY: geteuid cannot fail
+    Some(unsafe { libc::geteuid() })
+}
+
+#[cfg(not(unix))]
+fn user_id() -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn lock_memory(buf: &[u8]) -> bool {
+    // SAFETY: locks pages of a live allocation; it is unlocked before being freed
+    buf.is_empty() || unsafe { libc::mlock(buf.as_ptr().cast(), buf.len()) } == 0
+}
+
+#[cfg(unix)]
+fn unlock_memory(buf: &[u8]) {
+    if !buf.is_empty() {
+        // SAFETY: unlocks the pages locked in `lock_memory`
+        unsafe {
+            libc::munlock(buf.as_ptr().cast(), buf.len());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_memory(_buf: &[u8]) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn unlock_memory(_buf: &[u8]) {}
+
+#[cfg(unix)]
+mod guarded {
+    use std::io;
+    use std::ptr::{self, NonNull};
+
+    /// Anonymous pages holding `len` bytes, between two `PROT_NONE` guard pages.
+    pub struct Pages {
+        base: NonNull<u8>,
+        total: usize,
+        data: NonNull<u8>,
+        len: usize,
+    }
+
+    impl Pages {
+        /// Maps zeroed pages for `len` bytes; the bytes end where the upper guard page starts.
+        pub fn new(len: usize) -> io::Result<Pages> {
+            // SAFETY: sysconf has no preconditions
+            let page = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096);
+            let data_len = len.div_ceil(page).max(1) * page;
+            let total = data_len + 2 * page;
+            // SAFETY: a fresh private anonymous mapping, checked below; nothing else refers to it
+            let base = unsafe {
+                libc::mmap(ptr::null_mut(), total, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANON, -1, 0)
+            };
+            if base == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            let base = base.cast::<u8>();
+            // SAFETY: both guard pages lie inside the mapping made above
+            let guarded = unsafe {
+                libc::mprotect(base.cast(), page, libc::PROT_NONE) == 0
+                    && libc::mprotect(base.add(page + data_len).cast(), page, libc::PROT_NONE) == 0
+            };
+            #[cfg(target_os = "linux")]
+            // SAFETY: advice on the data pages of the mapping; failure only means they may be dumped
+            unsafe {
+                libc::madvise(base.add(page).cast(), data_len, libc::MADV_DONTDUMP);
+            }
+            let pages = Pages {
+                base: NonNull::new(base).ok_or_else(|| io::Error::other("mmap returned null"))?,
+                total,
+                // SAFETY: `page + data_len - len` is within the mapping
+                data: NonNull::new(unsafe { base.add(page + data_len - len) }).ok_or_else(|| io::Error::other("null data"))?,
+                len,
+            };
+            if !guarded {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(pages)
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: `data..data + len` is mapped readable and writable for the life of `self`
+            unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.len) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            // SAFETY: as in `as_slice`, and `&mut self` makes the borrow unique
+            unsafe { std::slice::from_raw_parts_mut(self.data.as_ptr(), self.len) }
+        }
+    }
+
+    impl Drop for Pages {
+        fn drop(&mut self) {
+            // SAFETY: unmaps exactly the mapping made in `new`, which nothing refers to any more
+            unsafe {
+                libc::munmap(self.base.as_ptr().cast(), self.total);
+            }
+        }
+    }
+}
+
+/// Keeps the agent's memory out of core dumps and, on Linux, away from same-user debuggers.
+#[cfg(unix)]
+fn harden_process() {
+    let no_core = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: plain syscalls on this process with valid arguments
+    unsafe {
+        libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+        #[cfg(target_os = "linux")]
+        libc::prctl(libc::PR_SET_DUMPABLE, 0);
+    }
+}
+
+#[cfg(not(unix))]
+fn harden_process() {}
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use zeroize::Zeroizing;
+
+    use super::{Agent, IO_TIMEOUT, MAX_REQUEST};
+
+    pub(super) type Listener = UnixListener;
+
+    pub(super) fn bind(socket: &Path) -> io::Result<UnixListener> {
+        if let Some(dir) = socket.parent().filter(|d| !d.as_os_str().is_empty()) {
+            if !dir.exists() {
+                std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+            }
+            let meta = std::fs::metadata(dir)?;
+            if meta.uid() != super::user_id().unwrap_or_default() || meta.mode() & 0o077 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} must be owned by you and not accessible to others (chmod 700)", dir.display()),
+                ));
+            }
+        }
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("an agent is already listening on {}", socket.display()),
+                ));
+            }
+            std::fs::remove_file(socket)?;
+        }
+        let listener = UnixListener::bind(socket)?;
+        std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))?;
+        Ok(listener)
+    }
+
+    /// First descriptor passed by systemd (`SD_LISTEN_FDS_START`).
+    const LISTEN_FDS_START: i32 = 3;
+
+    pub(super) fn activated() -> io::Result<Option<UnixListener>> {
+        let for_us = std::env::var("LISTEN_PID").is_ok_and(|pid| pid.parse() == Ok(std::process::id()));
+        let fds = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse::<i32>().ok());
+        // Single-threaded here; unset so the variables are not taken as meant for a child
+        for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            std::env::remove_var(var);
+        }
+        match (for_us, fds) {
+            (false, _) | (true, None | Some(0)) => Ok(None),
+            (true, Some(1)) => {
+                // SAFETY: F_SETFD only changes the descriptor's flags, and fails if it is not open
+                if unsafe { libc::fcntl(LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // SAFETY: systemd passed the listening socket as this descriptor for the agent's
+                // sole use
+                Ok(Some(unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) }))
+            }
+            (true, Some(n)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("systemd passed {} sockets; the agent takes exactly one (one ListenStream=)", n),
+            )),
+        }
+    }
+
+    /// Filesystem path the listener is bound to, if any.
+    pub(super) fn local_path(listener: &UnixListener) -> Option<PathBuf> {
+        listener.local_addr().ok()?.as_pathname().map(Path::to_path_buf)
+    }
+
+    /// Accepts connections one at a time, waking up to lock the agent when a limit passes.
+    pub(super) fn accept_loop(listener: &UnixListener, agent: &mut Agent) -> io::Result<()> {
+        loop {
+            let timeout = agent.deadline().map(|d| d.saturating_duration_since(Instant::now()));
+            if !wait_readable(listener, timeout)? {
+                agent.expire(Instant::now());
+                continue;
+            }
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = handle(stream, agent) {
+                        eprintln!("warning: agent request failed: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Waits until a connection is pending (`true`) or `timeout` passes (`false`).
+    fn wait_readable(listener: &UnixListener, timeout: Option<Duration>) -> io::Result<bool> {
+        // Round up, so the deadline has passed when poll returns
+        let timeout_ms = timeout.map_or(-1, |t| i32::try_from(t.as_micros().div_ceil(1000)).unwrap_or(i32::MAX));
+        let mut poll = libc::pollfd { fd: listener.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        // SAFETY: `poll` points to one valid pollfd for the duration of the call
+        match unsafe { libc::poll(&mut poll, 1, timeout_ms) } {
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(err)
+                }
+            }
+            n => Ok(n > 0),
+        }
+    }
+
+    /// Answers one connection, from the agent's own user only.
+    fn handle(stream: UnixStream, agent: &mut Agent) -> io::Result<()> {
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut writer = &stream;
+        let uid = peer_uid(&stream)?;
+        if Some(uid) != super::user_id() {
+            writer.write_all(b"ERR permission denied\n")?;
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("rejected connection from uid {}", uid)));
+        }
+
+        let mut line = Zeroizing::new(String::new());
+        BufReader::new(&stream).take(MAX_REQUEST).read_line(&mut line)?;
+        // Only the command is logged: the rest of a request may hold the master
+        #[cfg(feature = "tracing")]
+        let _span = {
+            let command = line.split([' ', '\n']).next().unwrap_or_default();
+            let command = ["DERIVE", "LOCK", "UNLOCK"].into_iter().find(|known| *known == command).unwrap_or("unknown");
+            tracing::info_span!("agent_request", command).entered()
+        };
+        let response = match agent.respond(line.trim_end_matches('\n')) {
+            Ok(payload) => Zeroizing::new(format!("OK {}\n", *payload)),
+            Err(message) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(%message, "request refused");
+                Zeroizing::new(format!("ERR {}\n", message))
+            }
+        };
+        writer.write_all(response.as_bytes())
+    }
+
+    /// Sends one request and returns the `OK` payload, or the agent's error message.
+    pub(super) fn request(socket: &Path, request: &str) -> Result<String, String> {
+        let stream = UnixStream::connect(socket)
+            .map_err(|e| format!("cannot reach the agent at {}: {} (is `pwgen agent` running?)", socket.display(), e))?;
+        let exchange = || -> io::Result<String> {
+            stream.set_read_timeout(Some(IO_TIMEOUT))?;
+            stream.set_write_timeout(Some(IO_TIMEOUT))?;
+            (&stream).write_all(request.as_bytes())?;
+            let mut line = String::new();
+            BufReader::new(&stream).take(MAX_REQUEST).read_line(&mut line)?;
+            Ok(line)
+        };
+        let reply = Zeroizing::new(exchange().map_err(|e| format!("agent connection failed: {}", e))?);
+        let reply = reply.trim_end_matches('\n');
+        match reply.split_once(' ') {
+            Some(("OK", payload)) => Ok(payload.to_string()),
+            Some(("ERR", message)) => Err(format!("agent: {}", message)),
+            _ => Err("malformed agent response".to_string()),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+        let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `cred` and `len` describe a valid ucred buffer for SO_PEERCRED
+        let rc = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&mut cred as *mut libc::ucred).cast(),
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(cred.uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+        let (mut uid, mut gid) = (0, 0);
+        // SAFETY: getpeereid writes the peer's ids into the two out-parameters
+        if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(uid)
+    }
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::Agent;
+
+    pub(super) struct Listener;
+
+    pub(super) fn bind(_socket: &Path) -> io::Result<Listener> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "the agent is only supported on Unix"))
+    }
+
+    pub(super) fn activated() -> io::Result<Option<Listener>> {
+        Ok(None)
+    }
+
+    pub(super) fn local_path(_listener: &Listener) -> Option<PathBuf> {
+        None
+    }
+
+    pub(super) fn accept_loop(_listener: &Listener, _agent: &mut Agent) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(super) fn request(_socket: &Path, _request: &str) -> Result<String, String> {
+        Err("the agent is only supported on Unix".to_string())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::kdf;
+use crate::generator::{draw_tokens, site_stream, GenError};
+use crate::pattern;
+
+/// Answer template: two pronounceable lowercase words (about 40 bits), easy to read out
+/// over the phone. Changing it changes every answer.
+const ANSWER_PATTERN: &str = "cvcvcv cvcvcv";
+
+/// Longest accepted question, in bytes after normalization.
+pub const MAX_QUESTION_LEN: usize = 256;
+
+/// Normalizes a security question so trivial rewording maps to the same answer:
+/// lowercase, whitespace collapsed to single spaces, trailing `?`/`.`/`:` removed.
+pub fn normalize_question(question: &str) -> String {
+    let lowered = question.to_lowercase();
+    let collapsed = lowered.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed
+        .trim_end_matches(['?', '.', ':'])
+        .trim_end()
+        .to_string()
+}
+
+/// Generates a deterministic made-up answer for a (site, question) pair.
+///
+/// The normalized question is bound into the context (`answer=<question>`), so every
+/// question on every site gets an independent answer.
+pub fn generate_answer<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    question: &str,
+    version: u32,
+) -> Result<String, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+    let question = normalize_question(question);
+
+    if question.is_empty() {
+        return Err(GenError::InvalidInput("question must be nonempty"));
+    }
+    if question.len() > MAX_QUESTION_LEN {
+        return Err(GenError::InvalidInput("question must be at most 256 bytes"));
+    }
+
+    let tokens = pattern::parse(ANSWER_PATTERN)?;
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"answer", question.as_bytes(), version)?;
+    draw_tokens(&mut rng, &tokens)
+}
@@ -0,0 +1,85 @@
+//! `pwgen type`: types a derived password into the focused window with synthesized keystrokes
+//! (X11, macOS and Windows, through enigo), so it never passes through the clipboard.
+
+/// A key pressed after the password, as written in `--suffix`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SuffixKey {
+    Tab,
+    Enter,
+}
+
+/// Most keys `--suffix` may press.
+pub const MAX_SUFFIX_KEYS: usize = 8;
+
+/// Parses `--suffix`: a sequence of up to `MAX_SUFFIX_KEYS` `{TAB}` and `{ENTER}` (in any
+/// case), e.g. `{TAB}{ENTER}`.
+pub fn parse_suffix(text: &str) -> Result<Vec<SuffixKey>, String> {
+    let mut keys = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if keys.len() == MAX_SUFFIX_KEYS {
+            return Err(format!("--suffix: at most {} keys", MAX_SUFFIX_KEYS));
+        }
+        let Some(end) = rest.strip_prefix('{').and_then(|inner| inner.find('}')) else {
+            return Err(format!("--suffix: expected {{TAB}} or {{ENTER}} at '{}'", rest));
+        };
+        let name = &rest[1..end + 1];
+        keys.push(match name.to_ascii_uppercase().as_str() {
+            "TAB" => SuffixKey::Tab,
+            "ENTER" => SuffixKey::Enter,
+            _ => return Err(format!("--suffix: unknown key {{{}}} (use {{TAB}} or {{ENTER}})", name)),
+        });
+        rest = rest[end + 2..].trim_start();
+    }
+    Ok(keys)
+}
+
+/// A connection to the window system that keystrokes are synthesized through.
+#[cfg(feature = "autotype")]
+pub struct Typist(enigo::Enigo);
+
+#[cfg(feature = "autotype")]
+impl Typist {
+    /// Connects to the window system, so a missing display fails before the master is read.
+    pub fn connect() -> Result<Typist, String> {
+        enigo::Enigo::new(&enigo::Settings::default())
+            .map(Typist)
+            .map_err(|e| format!("cannot synthesize keystrokes: {}", e))
+    }
+
+    /// Types `password` one character at a time, `key_delay` apart, then presses the `suffix`
+    /// keys.
+    pub fn type_password(&mut self, password: &str, suffix: &[SuffixKey], key_delay: std::time::Duration) -> Result<(), String> {
+        use enigo::{Direction, Key, Keyboard};
+
+        let mut buf = [0u8; 4];
+        for c in password.chars() {
+            self.0.text(c.encode_utf8(&mut buf)).map_err(|e| format!("typing failed: {}", e))?;
+            std::thread::sleep(key_delay);
+        }
+        zeroize::Zeroize::zeroize(&mut buf);
+        for key in suffix {
+            let key = match key {
+                SuffixKey::Tab => Key::Tab,
+                SuffixKey::Enter => Key::Return,
+            };
+            self.0.key(key, Direction::Click).map_err(|e| format!("typing failed: {}", e))?;
+            std::thread::sleep(key_delay);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "autotype"))]
+pub struct Typist;
+
+#[cfg(not(feature = "autotype"))]
+impl Typist {
+    pub fn connect() -> Result<Typist, String> {
+        Err("pwgen type requires the autotype feature".to_string())
+    }
+
+    pub fn type_password(&mut self, _password: &str, _suffix: &[SuffixKey], _key_delay: std::time::Duration) -> Result<(), String> {
+        Err("pwgen type requires the autotype feature".to_string())
+    }
+}
@@ -0,0 +1,188 @@
+//! Paper backup sheets (`pwgen backup-sheet`): the non-secret state needed to derive every
+//! password again after data loss — the algorithm settings and each site of a profile file
+//! with its username, version and policy — laid out for printing as text or PDF.
+//!
+//! The sheet never holds the master, a pepper or keyfile contents, or a password; profile notes
+//! are left out too, since they are free-form.
+
+use crate::generator::AlgoVersion;
+use crate::kdf::KdfScheme;
+use crate::profiles::Profile;
+use crate::site::SiteMode;
+
+/// Longest line on the sheet; longer values are wrapped.
+pub const WIDTH: usize = 80;
+
+/// Lines per PDF page (A4, 10 pt Courier).
+const PDF_LINES_PER_PAGE: usize = 60;
+
+/// Settings the passwords on the sheet were derived with.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub kdf: KdfScheme,
+    pub algo: AlgoVersion,
+    pub site_mode: SiteMode,
+    pub persona: Option<String>,
+    pub scope: Option<String>,
+    /// Whether a keyfile is required (its contents are not on the sheet)
+    pub keyfile: bool,
+    /// Whether a pepper is required (it is not on the sheet)
+    pub pepper: bool,
+}
+
+/// Lays out the sheet; `header` names the program and date it was made with.
+pub fn render(header: &str, settings: &Settings, profiles: &[Profile]) -> Vec<String> {
+    let mut lines = vec![
+        format!("pwgen backup sheet ({})", header),
+        String::new(),
+        "This sheet holds no master secret and no passwords. With the master, and the".to_string(),
+        "keyfile or pepper if marked required, it is enough to derive every password".to_string(),
+        "again. Keep it with your other paper records.".to_string(),
+        String::new(),
+        "Settings".to_string(),
+    ];
+    let kdf = match &settings.kdf {
+        KdfScheme::Argon2id(params) => match params.profile() {
+            Some(profile) => format!("argon2id, {} profile ({})", profile.name(), params),
+            None => format!("argon2id ({})", params),
+        },
+        KdfScheme::Pbkdf2Sha256 { iterations } => format!("pbkdf2-sha256 ({} iterations)", iterations),
+    };
+    let required = |yes| if yes { "required (not on this sheet)" } else { "none" };
+    let mut field = |name: &str, value: &str| push_field(&mut lines, 2, name, value);
+    field("KDF", &kdf);
+    field("Algorithm version", &settings.algo.number().to_string());
+    field("Site mode", settings.site_mode.name());
+    if let Some(persona) = &settings.persona {
+        field("Persona", persona);
+    }
+    field("Scope", settings.scope.as_deref().unwrap_or("none"));
+    field("Keyfile", required(settings.keyfile));
+    field("Pepper", required(settings.pepper));
+
+    lines.push(String::new());
+    lines.push(format!("Sites ({})", profiles.len()));
+    for (i, profile) in profiles.iter().enumerate() {
+        lines.push(String::new());
+        push_wrapped(&mut lines, &format!("{:>3}. ", i + 1), &profile.site);
+        let mut field = |name: &str, value: &str| push_field(&mut lines, 5, name, value);
+        if !profile.username.is_empty() {
+            field("username", &profile.username);
+        }
+        field("version", &profile.version.unwrap_or(1).to_string());
+        if let Some(length) = profile.length {
+            field("length", &length.to_string());
+        }
+        if let Some(policy) = &profile.policy {
+            field("policy", policy);
+        }
+        if let Some(rules) = &profile.passwordrules {
+            field("passwordrules", rules);
+        }
+        if let Some(pattern) = &profile.pattern {
+            field("pattern", pattern);
+        }
+        if let Some(name) = &profile.name {
+            field("name", name);
+        }
+        if let Some(url) = &profile.url {
+            field("url", url);
+        }
+    }
+    lines
+}
+
+/// `name: value` indented by `indent`, with the values of one block aligned.
+fn push_field(lines: &mut Vec<String>, indent: usize, name: &str, value: &str) {
+    let width = if indent == 2 { 19 } else { 15 };
+    push_wrapped(lines, &format!("{}{:<width$}", " ".repeat(indent), format!("{}:", name)), value);
+}
+
+/// Appends `prefix` and `value`, breaking `value` into lines of at most `WIDTH` characters with
+/// continuation lines aligned under its start.
+fn push_wrapped(lines: &mut Vec<String>, prefix: &str, value: &str) {
+    let indent = prefix.chars().count();
+    let room = WIDTH.saturating_sub(indent).max(20);
+    let chars: Vec<char> = value.chars().collect();
+    let mut chunks = chars.chunks(room);
+    let first: String = chunks.next().unwrap_or_default().iter().collect();
+    lines.push(format!("{}{}", prefix, first).trim_end().to_string());
+    for chunk in chunks {
+        lines.push(format!("{}{}", " ".repeat(indent), chunk.iter().collect::<String>()));
+    }
+}
+
+/// The sheet as a text file.
+pub fn to_text(lines: &[String]) -> String {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// The sheet as a PDF: A4 pages of 10 pt Courier with a page number at the foot. Characters
+/// outside Latin-1 print as `?`, so keep the text sheet for sites in other scripts.
+pub fn to_pdf(lines: &[String]) -> Vec<u8> {
+    let pages: Vec<&[String]> = if lines.is_empty() { vec![&[]] } else { lines.chunks(PDF_LINES_PER_PAGE).collect() };
+
+    // Objects: 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + 2 * i)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec());
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = b"BT /F1 10 Tf 12 TL 56 786 Td\n".to_vec();
+        for line in page.iter() {
+            content.extend_from_slice(&pdf_string(line));
+            content.extend_from_slice(b" Tj T*\n");
+        }
+        content.extend_from_slice(b"ET\nBT /F1 8 Tf 56 40 Td ");
+        content.extend_from_slice(&pdf_string(&format!("pwgen backup sheet - page {} of {}", i + 1, pages.len())));
+        content.extend_from_slice(b" Tj ET\n");
+
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                5 + 2 * i
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend_from_slice(&content);
+        stream.extend_from_slice(b"endstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+    );
+    pdf
+}
+
+/// A PDF literal string in WinAnsiEncoding, which agrees with Latin-1 outside 0x80..=0x9F.
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => out.extend_from_slice(&[b'\\', c as u8]),
+            ' '..='~' => out.push(c as u8),
+            '\u{a0}'..='\u{ff}' => out.push(c as u32 as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out.push(b')');
+    out
+}
@@ -0,0 +1,6 @@
+//! Generates the Kotlin and Swift bindings of `src/pwgen.udl` (the `uniffi` feature):
+//! `cargo run --features uniffi --bin uniffi-bindgen -- generate src/pwgen.udl --language kotlin --out-dir bindings`
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,60 @@
+//! Breach check (`generate --check-breach`): asks the Have I Been Pwned range API whether a
+//! derived password appears in known breaches, without sending the password or its hash.
+//!
+//! k-anonymity: only the first 5 hex digits of the password's SHA-1 are sent; the API answers
+//! with every breached hash suffix under that prefix (`SUFFIX:COUNT` lines, padded with
+//! zero-count decoys so the answer's size reveals nothing), and the match is made locally.
+
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+/// Range API endpoint; the 5-digit prefix is appended.
+pub const RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Hex digits of the SHA-1 sent to the API.
+pub const PREFIX_LEN: usize = 5;
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum BreachError {
+    #[error("request failed: {0}")]
+    Request(String),
+    #[error("unexpected response: {0}")]
+    Response(String),
+}
+
+/// The uppercase hex SHA-1 of `password`, split into the prefix sent and the suffix matched.
+pub fn range_hash(password: &str) -> (String, String) {
+    let digest: String = Sha1::digest(password.as_bytes()).iter().map(|b| format!("{:02X}", b)).collect();
+    let (prefix, suffix) = digest.split_at(PREFIX_LEN);
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// How often `suffix` appears in a range response (0 if it is not listed).
+pub fn count(response: &str, suffix: &str) -> Result<u64, BreachError> {
+    for line in response.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let Some((hash, count)) = line.split_once(':') else {
+            return Err(BreachError::Response(format!("line '{}' is not SUFFIX:COUNT", line)));
+        };
+        if hash.eq_ignore_ascii_case(suffix) {
+            return count.trim().parse().map_err(|_| BreachError::Response(format!("count '{}' is not a number", count)));
+        }
+    }
+    Ok(0)
+}
+
+/// Queries the range API for `password` and returns how often it appears in breaches.
+#[cfg(feature = "network")]
+pub fn check(password: &str) -> Result<u64, BreachError> {
+    let (prefix, suffix) = range_hash(password);
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(10))
+        .user_agent(concat!("pwgen/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let response = agent
+        .get(&format!("{}{}", RANGE_URL, prefix))
+        .set("Add-Padding", "true")
+        .call()
+        .map_err(|e| BreachError::Request(e.to_string()))?;
+    let body = response.into_string().map_err(|e| BreachError::Response(e.to_string()))?;
+    count(&body, &suffix)
+}
@@ -0,0 +1,385 @@
+//! `generate --clip`: puts a derived password on the clipboard instead of printing it, with
+//! the hints that ask clipboard managers and the clipboard history not to keep it, and clears
+//! it again after a while.
+//!
+//! On X11, and on Wayland through XWayland (whose clipboard the compositor shares with
+//! Wayland clients), the password is offered as text along with `x-kde-passwordManagerHint`
+//! set to `secret`, which Klipper, CopyQ and other history managers honour. On Windows it is
+//! set together with `ExcludeClipboardContentFromMonitorProcessing`, and with
+//! `CanIncludeInClipboardHistory` and `CanUploadToCloudClipboard` set to 0, which keep it out
+//! of clipboard monitors, the Win+V history and cloud sync.
+
+use std::time::Duration;
+
+/// The window system the clipboard is reached through.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Platform {
+    X11,
+    /// A Wayland session; the clipboard is set through XWayland
+    Wayland,
+    Windows,
+}
+
+/// A clipboard format offered next to the text, and its contents.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Hint {
+    pub format: &'static str,
+    pub data: &'static [u8],
+}
+
+/// The KDE password-manager hint, also read by clipboard managers outside KDE.
+const KDE_HINT: Hint = Hint { format: "x-kde-passwordManagerHint", data: b"secret" };
+
+/// The Windows formats, each a little-endian DWORD 0 (the first only needs to be present).
+const WINDOWS_HINTS: [Hint; 3] = [
+    Hint { format: "ExcludeClipboardContentFromMonitorProcessing", data: &[0; 4] },
+    Hint { format: "CanIncludeInClipboardHistory", data: &[0; 4] },
+    Hint { format: "CanUploadToCloudClipboard", data: &[0; 4] },
+];
+
+impl Platform {
+    /// The platform of this session (see `from_env`).
+    pub fn detect() -> Option<Platform> {
+        let var = |name| std::env::var(name).ok();
+        Platform::from_env(var("WAYLAND_DISPLAY").as_deref(), var("DISPLAY").as_deref())
+    }
+
+    /// Windows on Windows; elsewhere Wayland if `WAYLAND_DISPLAY` is set, else X11 if
+    /// `DISPLAY` is, else none.
+    pub fn from_env(wayland_display: Option<&str>, display: Option<&str>) -> Option<Platform> {
+        let set = |value: Option<&str>| value.is_some_and(|v| !v.is_empty());
+        if cfg!(windows) {
+            Some(Platform::Windows)
+        } else if set(wayland_display) {
+            Some(Platform::Wayland)
+        } else if set(display) {
+            Some(Platform::X11)
+        } else {
+            None
+        }
+    }
+
+    /// The formats offered next to the password so that clipboard managers skip it.
+    pub fn hints(self) -> &'static [Hint] {
+        match self {
+            Platform::X11 | Platform::Wayland => &[KDE_HINT],
+            Platform::Windows => &WINDOWS_HINTS,
+        }
+    }
+}
+
+/// How a copy ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The clipboard was cleared after the timeout
+    Cleared,
+    /// Another application copied something in the meantime, which was left alone
+    Replaced,
+}
+
+/// A connection to the clipboard, opened before the master is read so that a missing display
+/// fails first.
+pub struct Clipboard {
+    platform: Platform,
+    #[cfg(all(feature = "clipboard", target_os = "linux"))]
+    owner: x11::Owner,
+}
+
+impl Clipboard {
+    pub fn connect() -> Result<Clipboard, String> {
+        let platform = Platform::detect().ok_or("--clip needs a graphical session (DISPLAY or WAYLAND_DISPLAY)")?;
+        Clipboard::open(platform)
+    }
+
+    #[cfg(all(feature = "clipboard", target_os = "linux"))]
+    fn open(platform: Platform) -> Result<Clipboard, String> {
+        if platform == Platform::Wayland && std::env::var_os("DISPLAY").is_none_or(|d| d.is_empty()) {
+            return Err("--clip on Wayland goes through XWayland, but DISPLAY is not set".to_string());
+        }
+        Ok(Clipboard { platform, owner: x11::Owner::connect(platform.hints())? })
+    }
+
+    #[cfg(all(feature = "clipboard", windows))]
+    fn open(platform: Platform) -> Result<Clipboard, String> {
+        Ok(Clipboard { platform })
+    }
+
+    #[cfg(not(all(feature = "clipboard", any(target_os = "linux", windows))))]
+    fn open(_platform: Platform) -> Result<Clipboard, String> {
+        Err("--clip requires the clipboard feature (X11, XWayland or Windows)".to_string())
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Puts `text` on the clipboard with the platform's hints and keeps it there for
+    /// `clear_after`, then clears it unless something else was copied meanwhile.
+    pub fn copy(self, text: &str, clear_after: Duration) -> Result<Outcome, String> {
+        #[cfg(all(feature = "clipboard", target_os = "linux"))]
+        return self.owner.serve(text, clear_after);
+        #[cfg(all(feature = "clipboard", windows))]
+        return windows::copy(text, self.platform.hints(), clear_after);
+        #[cfg(not(all(feature = "clipboard", any(target_os = "linux", windows))))]
+        {
+            let _ = (text, clear_after);
+            Err("--clip requires the clipboard feature".to_string())
+        }
+    }
+}
+
+/// Clipboard ownership through the X11 selection protocol: the password stays in this
+/// process and is handed to each application that pastes it.
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+mod x11 {
+    use std::time::{Duration, Instant};
+
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        self, Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, SelectionNotifyEvent,
+        SelectionRequestEvent, Window, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use zeroize::Zeroizing;
+
+    use super::{Hint, Outcome};
+
+    /// Targets the text is offered as
+    const TEXT_TARGETS: [&str; 4] = ["UTF8_STRING", "text/plain;charset=utf-8", "text/plain", "STRING"];
+
+    pub struct Owner {
+        conn: RustConnection,
+        window: Window,
+        clipboard: Atom,
+        targets: Atom,
+        text: Vec<Atom>,
+        hints: Vec<(Atom, &'static [u8])>,
+    }
+
+    fn error(e: impl std::fmt::Display) -> String {
+        format!("clipboard: {}", e)
+    }
+
+    impl Owner {
+        pub fn connect(hints: &[Hint]) -> Result<Owner, String> {
+            let (conn, screen) = x11rb::connect(None).map_err(|e| format!("cannot open the X display: {}", e))?;
+            let root = conn.setup().roots[screen].root;
+            let window = conn.generate_id().map_err(error)?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .map_err(error)?;
+            let intern = |name: &str| -> Result<Atom, String> {
+                Ok(conn.intern_atom(false, name.as_bytes()).map_err(error)?.reply().map_err(error)?.atom)
+            };
+            let clipboard = intern("CLIPBOARD")?;
+            let targets = intern("TARGETS")?;
+            let text = TEXT_TARGETS.iter().map(|name| intern(name)).collect::<Result<_, _>>()?;
+            let hints = hints.iter().map(|hint| Ok((intern(hint.format)?, hint.data))).collect::<Result<_, String>>()?;
+            Ok(Owner { conn, window, clipboard, targets, text, hints })
+        }
+
+        /// Owns the clipboard and answers paste requests until `clear_after` has passed or
+        /// another application takes the clipboard.
+        pub fn serve(self, text: &str, clear_after: Duration) -> Result<Outcome, String> {
+            let text = Zeroizing::new(text.as_bytes().to_vec());
+            self.conn.set_selection_owner(self.window, self.clipboard, x11rb::CURRENT_TIME).map_err(error)?;
+            let owner = self.conn.get_selection_owner(self.clipboard).map_err(error)?.reply().map_err(error)?.owner;
+            if owner != self.window {
+                return Err("clipboard: another application holds the clipboard".to_string());
+            }
+            let deadline = Instant::now() + clear_after;
+            while Instant::now() < deadline {
+                match self.conn.poll_for_event().map_err(error)? {
+                    Some(Event::SelectionClear(_)) => return Ok(Outcome::Replaced),
+                    Some(Event::SelectionRequest(request)) => self.answer(&request, &text)?,
+                    Some(_) => {}
+                    None => std::thread::sleep(Duration::from_millis(20)),
+                }
+            }
+            self.conn.set_selection_owner(x11rb::NONE, self.clipboard, x11rb::CURRENT_TIME).map_err(error)?;
+            self.conn.flush().map_err(error)?;
+            Ok(Outcome::Cleared)
+        }
+
+        /// Stores the requested target on the requestor's window and notifies it; unknown
+        /// targets are refused.
+        fn answer(&self, request: &SelectionRequestEvent, text: &[u8]) -> Result<(), String> {
+            // Obsolete clients leave the property unset and expect the target to be used
+            let property = if request.property == x11rb::NONE { request.target } else { request.property };
+            let (requestor, target) = (request.requestor, request.target);
+            let stored = if target == self.targets {
+                let mut offered = vec![self.targets];
+                offered.extend(&self.text);
+                offered.extend(self.hints.iter().map(|(atom, _)| *atom));
+                self.conn.change_property32(PropMode::REPLACE, requestor, property, AtomEnum::ATOM, &offered).map_err(error)?;
+                true
+            } else if self.text.contains(&target) {
+                self.conn.change_property8(PropMode::REPLACE, requestor, property, target, text).map_err(error)?;
+                true
+            } else if let Some((_, data)) = self.hints.iter().find(|(atom, _)| *atom == target) {
+                self.conn.change_property8(PropMode::REPLACE, requestor, property, target, data).map_err(error)?;
+                true
+            } else {
+                false
+            };
+            let notify = SelectionNotifyEvent {
+                response_type: xproto::SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor,
+                selection: request.selection,
+                target,
+                property: if stored { property } else { x11rb::NONE },
+            };
+            self.conn.send_event(false, requestor, EventMask::NO_EVENT, notify).map_err(error)?;
+            self.conn.flush().map_err(error)?;
+            Ok(())
+        }
+    }
+}
+
+/// The Win32 clipboard: the password is copied into it, so it is cleared explicitly.
+#[cfg(all(feature = "clipboard", windows))]
+mod windows {
+    use std::ffi::c_void;
+    use std::time::{Duration, Instant};
+
+    use zeroize::Zeroize;
+
+    use super::{Hint, Outcome};
+
+    type Handle = *mut c_void;
+
+    const CF_UNICODETEXT: u32 = 13;
+    const GMEM_MOVEABLE: u32 = 0x0002;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn OpenClipboard(owner: Handle) -> i32;
+        fn CloseClipboard() -> i32;
+        fn EmptyClipboard() -> i32;
+        fn SetClipboardData(format: u32, mem: Handle) -> Handle;
+        fn RegisterClipboardFormatW(name: *const u16) -> u32;
+        fn GetClipboardSequenceNumber() -> u32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GlobalAlloc(flags: u32, bytes: usize) -> Handle;
+        fn GlobalLock(mem: Handle) -> *mut c_void;
+        fn GlobalUnlock(mem: Handle) -> i32;
+        fn GlobalFree(mem: Handle) -> Handle;
+    }
+
+    /// The clipboard while it is open; closed on drop.
+    struct Open;
+
+    impl Open {
+        fn new() -> Result<Open, String> {
+            // Another application may hold it briefly
+            for _ in 0..10 {
+                // SAFETY: no owner window; paired with CloseClipboard in drop
+                if unsafe { OpenClipboard(std::ptr::null_mut()) } != 0 {
+                    return Ok(Open);
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err("clipboard: cannot open the clipboard".to_string())
+        }
+
+        /// Hands a copy of `bytes` to the clipboard as `format`.
+        fn set(&self, format: u32, bytes: &[u8]) -> Result<(), String> {
+            // SAFETY: a fresh movable block of bytes.len() bytes, locked only while copying;
+            // the clipboard owns it once SetClipboardData succeeds, else it is freed here
+            unsafe {
+                let mem = GlobalAlloc(GMEM_MOVEABLE, bytes.len().max(1));
+                if mem.is_null() {
+                    return Err("clipboard: out of memory".to_string());
+                }
+                let ptr = GlobalLock(mem).cast::<u8>();
+                if ptr.is_null() {
+                    GlobalFree(mem);
+                    return Err("clipboard: out of memory".to_string());
+                }
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+                GlobalUnlock(mem);
+                if SetClipboardData(format, mem).is_null() {
+                    GlobalFree(mem);
+                    return Err("clipboard: cannot set the clipboard data".to_string());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Open {
+        fn drop(&mut self) {
+            // SAFETY: opened in `Open::new`
+            unsafe {
+                CloseClipboard();
+            }
+        }
+    }
+
+    fn register(name: &str) -> Result<u32, String> {
+        let wide: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        // SAFETY: a NUL-terminated UTF-16 string
+        match unsafe { RegisterClipboardFormatW(wide.as_ptr()) } {
+            0 => Err(format!("clipboard: cannot register format {}", name)),
+            format => Ok(format),
+        }
+    }
+
+    pub fn copy(text: &str, hints: &[Hint], clear_after: Duration) -> Result<Outcome, String> {
+        let formats = hints.iter().map(|hint| Ok((register(hint.format)?, hint.data))).collect::<Result<Vec<_>, String>>()?;
+        let mut wide: Vec<u8> = text.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        let result = (|| {
+            let open = Open::new()?;
+            // SAFETY: the clipboard is open
+            if unsafe { EmptyClipboard() } == 0 {
+                return Err("clipboard: cannot empty the clipboard".to_string());
+            }
+            // The hints go first, so no monitor sees the text without them
+            for (format, data) in &formats {
+                open.set(*format, data)?;
+            }
+            open.set(CF_UNICODETEXT, &wide)
+        })();
+        wide.zeroize();
+        result?;
+
+        // SAFETY: no preconditions
+        let sequence = unsafe { GetClipboardSequenceNumber() };
+        let deadline = Instant::now() + clear_after;
+        while Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+            // SAFETY: no preconditions
+            if unsafe { GetClipboardSequenceNumber() } != sequence {
+                return Ok(Outcome::Replaced);
+            }
+        }
+        let _open = Open::new()?;
+        // SAFETY: the clipboard is open
+        if unsafe { GetClipboardSequenceNumber() } != sequence {
+            return Ok(Outcome::Replaced);
+        }
+        // SAFETY: the clipboard is open
+        unsafe {
+            EmptyClipboard();
+        }
+        Ok(Outcome::Cleared)
+    }
+}
@@ -0,0 +1,92 @@
+//! `pwgen completions`: shell completion scripts generated from the clap definitions, with
+//! `--site` values completed from the profile file named by `profiles` in the config file.
+//!
+//! clap_complete only writes static scripts, so the `--site` branch of each script is rewritten
+//! to call the hidden `pwgen complete-sites` helper at completion time. That works for bash, zsh
+//! and fish; the PowerShell and elvish scripts complete `--site` without values.
+
+use clap_complete::Shell;
+use pwgen::profiles::Profile;
+
+/// Command the scripts run to list site names; errors are discarded so a missing or broken
+/// profile file only means no suggestions.
+const HELPER: &str = "pwgen complete-sites";
+
+/// The completion script for `shell`, with dynamic `--site` completion where supported.
+pub fn script(shell: Shell, mut cmd: clap::Command) -> String {
+    let mut out = Vec::new();
+    clap_complete::generate(shell, &mut cmd, "pwgen", &mut out);
+    let script = String::from_utf8_lossy(&out).into_owned();
+    match shell {
+        Shell::Bash => bash(&script),
+        Shell::Zsh => zsh(&script),
+        Shell::Fish => fish(&script),
+        _ => script,
+    }
+}
+
+/// Distinct site names of `profiles` starting with `prefix` (ASCII case-insensitively), sorted.
+pub fn site_names<'p>(profiles: &'p [Profile], prefix: &str) -> Vec<&'p str> {
+    let mut names: Vec<&str> = profiles
+        .iter()
+        .map(|profile| profile.site.trim())
+        .filter(|site| site.len() >= prefix.len() && site.is_char_boundary(prefix.len()))
+        .filter(|site| site[..prefix.len()].eq_ignore_ascii_case(prefix))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Replaces the file completion under each `--site)` case with the helper's output.
+fn bash(script: &str) -> String {
+    let mut out = String::with_capacity(script.len());
+    let mut after_site = false;
+    for line in script.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if after_site && trimmed == r#"COMPREPLY=($(compgen -f "${cur}"))"# {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            out.push_str(&format!(
+                "{}COMPREPLY=($(compgen -W \"$({} 2>/dev/null)\" -- \"${{cur}}\"))\n",
+                indent, HELPER
+            ));
+        } else {
+            out.push_str(line);
+        }
+        after_site = trimmed == "--site)";
+    }
+    out
+}
+
+/// Completes `--site=` values with `_pwgen_sites`, defined before the script's entry point.
+fn zsh(script: &str) -> String {
+    let mut out = String::with_capacity(script.len());
+    for line in script.split_inclusive('\n') {
+        if line.starts_with("'--site=[") && line.contains(":STRING:_default'") {
+            out.push_str(&line.replacen(":STRING:_default'", ":SITE:_pwgen_sites'", 1));
+        } else {
+            if line.starts_with("if [ \"$funcstack[1]\" = \"_pwgen\" ]") {
+                out.push_str(&format!(
+                    "(( $+functions[_pwgen_sites] )) ||\n_pwgen_sites() {{\n    local -a sites\n    sites=(${{(f)\"$({} 2>/dev/null)\"}})\n    compadd -a sites\n}}\n\n",
+                    HELPER
+                ));
+            }
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// Adds the helper's output as the arguments of every `-l site` completion.
+fn fish(script: &str) -> String {
+    let mut out = String::with_capacity(script.len());
+    for line in script.split_inclusive('\n') {
+        match line.strip_suffix(" -r\n") {
+            Some(rest) if rest.contains(" -l site ") => {
+                out.push_str(&format!("{} -r -f -a \"({} 2>/dev/null)\"\n", rest, HELPER));
+            }
+            _ => out.push_str(line),
+        }
+    }
+    out
+}
@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::kdf;
+use crate::site;
+
+/// Environment variable naming an explicit config file.
+pub const CONFIG_ENV: &str = "PWGEN_CONFIG";
+
+/// Environment variable holding the pepper; takes precedence over the config file.
+pub const PEPPER_ENV: &str = "PWGEN_PEPPER";
+
+/// OS keychain entry (service, account) holding the pepper.
+pub const KEYCHAIN_PEPPER_ENTRY: (&str, &str) = ("pwgen", "pepper");
+
+/// Settings read from the optional config file (TOML).
+///
+/// Every field is optional; command-line flags take precedence over the file.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct Config {
+    /// Minimum entropy in bits a generated password must reach
+    pub min_bits: Option<u32>,
+    /// Argon2 cost preset used when `--kdf-profile` is not given
+    pub kdf_profile: Option<kdf::KdfProfile>,
+    /// Explicit Argon2 parameters (as written by `pwgen calibrate --save`); take precedence
+    /// over `kdf_profile`
+    pub kdf_params: Option<kdf::KdfParams>,
+    /// Pepper mixed into every derivation (prefer `pepper-keychain` to keeping it here)
+    pub pepper: Option<String>,
+    /// Read the pepper from the OS keychain entry `KEYCHAIN_PEPPER_ENTRY` (account
+    /// `pepper-<name>` for a persona)
+    pub pepper_keychain: bool,
+    /// Keyfile required in addition to the master when `--keyfile` is not given
+    pub keyfile: Option<PathBuf>,
+    /// Scope derived in when `--scope` is not given
+    pub scope: Option<String>,
+    /// How `--site` is reduced when `--site-mode` is not given
+    pub site_mode: Option<site::SiteMode>,
+    /// Show an emoji fingerprint after every master prompt, as with `--emoji-fingerprint`
+    pub emoji_fingerprint: bool,
+    /// Profile file `pwgen list`, `generate --site-from-stdin` and the `--site` completion of the
+    /// shell completion scripts read when `--profiles` is not given
+    pub profiles: Option<PathBuf>,
+    /// Keys `pwgen pinentry` answers with a derived passphrase (`[[pinentry]]` tables)
+    pub pinentry: Vec<PinentryKey>,
+    /// Named masters (`[persona.<name>]` tables), selected with `--persona`
+    pub persona: BTreeMap<String, Persona>,
+    /// Persona applied by `select_persona`
+    #[cfg_attr(feature = "config", serde(skip))]
+    pub selected_persona: Option<String>,
+}
+
+/// Settings of one master, used in place of the top-level ones with `--persona <name>`.
+///
+/// The pepper, keyfile and scope are the persona's own (none if it sets none); the KDF settings
+/// fall back to the top-level ones if it sets neither.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct Persona {
+    pub kdf_profile: Option<kdf::KdfProfile>,
+    pub kdf_params: Option<kdf::KdfParams>,
+    pub pepper: Option<String>,
+    /// Read the pepper from the OS keychain, account `pepper-<name>`
+    pub pepper_keychain: bool,
+    pub keyfile: Option<PathBuf>,
+    pub scope: Option<String>,
+}
+
+impl Config {
+    /// Replaces the top-level settings with those of the persona `name`.
+    pub fn select_persona(mut self, name: &str) -> Result<Config, ConfigError> {
+        let persona = self.persona.get(name).cloned().ok_or_else(|| ConfigError::UnknownPersona(name.to_string()))?;
+        if persona.kdf_profile.is_some() || persona.kdf_params.is_some() {
+            self.kdf_profile = persona.kdf_profile;
+            self.kdf_params = persona.kdf_params;
+        }
+        self.pepper = persona.pepper;
+        self.pepper_keychain = persona.pepper_keychain;
+        self.keyfile = persona.keyfile;
+        self.scope = persona.scope;
+        self.selected_persona = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// OS keychain entry (service, account) of the pepper.
+    pub fn keychain_pepper_entry(&self) -> (&'static str, String) {
+        let (service, account) = KEYCHAIN_PEPPER_ENTRY;
+        match &self.selected_persona {
+            Some(name) => (service, format!("{}-{}", account, name)),
+            None => (service, account.to_string()),
+        }
+    }
+}
+
+/// Whether `name` can name a persona: ASCII letters, digits, `-` and `_`, as it names files
+/// and keychain entries.
+pub fn valid_persona_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// A key whose passphrase `pwgen pinentry` derives instead of asking for it.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct PinentryKey {
+    /// Keygrip of the key, as shown by `gpg --with-keygrip -K` (40 hex digits)
+    pub keygrip: String,
+    /// Site the passphrase is derived for
+    pub site: String,
+    /// Username mixed into the derivation, as with `--username`
+    pub username: String,
+    /// Password version, as with `--version`
+    pub version: Option<u32>,
+    /// Encoded policy (as printed by `generate --json`); the default policy otherwise
+    pub policy: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read config {path}: {message}")]
+    Read { path: String, message: String },
+
+    #[error("invalid config {path}: {message}")]
+    Parse { path: String, message: String },
+
+    #[error("failed to write config {path}: {message}")]
+    Write { path: String, message: String },
+
+    #[error("keychain error: {0}")]
+    Keychain(String),
+
+    #[error("no persona '{0}' in the config file")]
+    UnknownPersona(String),
+}
+
+/// Returns the config file location: `$PWGEN_CONFIG`, else `pwgen/config.toml` under
+/// `$XDG_CONFIG_HOME` (or `~/.config`), or `%APPDATA%` on Windows.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("pwgen").join("config.toml"))
+}
+
+/// Parses config file contents.
+#[cfg(feature = "config")]
+pub fn parse(input: &str) -> Result<Config, ConfigError> {
+    let parse_error = |message: String| ConfigError::Parse { path: "<input>".to_string(), message };
+    let cfg: Config = toml::from_str(input).map_err(|e| parse_error(e.message().to_string()))?;
+    if let Some(params) = &cfg.kdf_params {
+        params.validate().map_err(|e| parse_error(e.to_string()))?;
+    }
+    if cfg.scope.as_ref().is_some_and(|scope| scope.trim().is_empty()) {
+        return Err(parse_error("scope must be nonempty".to_string()));
+    }
+    for (name, persona) in &cfg.persona {
+        if !valid_persona_name(name) {
+            return Err(parse_error(format!("persona name '{}' must be letters, digits, '-' and '_'", name)));
+        }
+        if let Some(params) = &persona.kdf_params {
+            params.validate().map_err(|e| parse_error(format!("persona {}: {}", name, e)))?;
+        }
+        if persona.scope.as_ref().is_some_and(|scope| scope.trim().is_empty()) {
+            return Err(parse_error(format!("persona {}: scope must be nonempty", name)));
+        }
+    }
+    for key in &cfg.pinentry {
+        if key.keygrip.len() != 40 || !key.keygrip.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(parse_error(format!("pinentry keygrip '{}' is not 40 hex digits", key.keygrip)));
+        }
+        if key.site.trim().is_empty() {
+            return Err(parse_error(format!("pinentry key {} has no site", key.keygrip)));
+        }
+    }
+    Ok(cfg)
+}
+
+/// Stores `params` as `kdf-params` in the config file at `path`, creating it if needed.
+///
+/// Other settings are kept (comments are not), and `kdf-profile` is removed since the
+/// explicit parameters replace it.
+#[cfg(feature = "config")]
+pub fn save_kdf_params(path: &Path, params: &kdf::KdfParams) -> Result<(), ConfigError> {
+    let display = path.display().to_string();
+    let write_error = |message: String| ConfigError::Write { path: display.clone(), message };
+
+    let mut table: toml::Table = match std::fs::read_to_string(path) {
+        Ok(input) => input.parse().map_err(|e: toml::de::Error| ConfigError::Parse {
+            path: display.clone(),
+            message: e.message().to_string(),
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(ConfigError::Read { path: display.clone(), message: e.to_string() }),
+    };
+    table.remove("kdf-profile");
+    let value = toml::Value::try_from(params).map_err(|e| write_error(e.to_string()))?;
+    table.insert("kdf-params".to_string(), value);
+
+    let output = toml::to_string(&table).map_err(|e| write_error(e.to_string()))?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| write_error(e.to_string()))?;
+    }
+    std::fs::write(path, output).map_err(|e| write_error(e.to_string()))
+}
+
+/// Resolves the pepper: `$PWGEN_PEPPER`, else the OS keychain if `pepper-keychain` is set,
+/// else `pepper` from the config. Empty values count as unset.
+pub fn resolve_pepper(cfg: &Config) -> Result<Option<Zeroizing<String>>, ConfigError> {
+    if let Some(pepper) = std::env::var_os(PEPPER_ENV).filter(|p| !p.is_empty()) {
+        let pepper = pepper
+            .into_string()
+            .map_err(|_| ConfigError::Parse { path: format!("${}", PEPPER_ENV), message: "not valid UTF-8".to_string() })?;
+        return Ok(Some(Zeroizing::new(pepper)));
+    }
+    if cfg.pepper_keychain {
+        return keychain_pepper(cfg).map(Some);
+    }
+    Ok(cfg.pepper.clone().filter(|p| !p.is_empty()).map(Zeroizing::new))
+}
+
+/// Where `resolve_pepper` would take the pepper from, without reading it (so no keychain
+/// prompt): `env` (`$PWGEN_PEPPER`), `keychain` or `config`; `None` without a pepper.
+pub fn pepper_source(cfg: &Config) -> Option<&'static str> {
+    if std::env::var_os(PEPPER_ENV).is_some_and(|p| !p.is_empty()) {
+        Some("env")
+    } else if cfg.pepper_keychain {
+        Some("keychain")
+    } else if cfg.pepper.as_ref().is_some_and(|p| !p.is_empty()) {
+        Some("config")
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_pepper(cfg: &Config) -> Result<Zeroizing<String>, ConfigError> {
+    let (service, account) = cfg.keychain_pepper_entry();
+    let entry = keyring::Entry::new(service, &account).map_err(|e| ConfigError::Keychain(e.to_string()))?;
+    entry.get_password().map(Zeroizing::new).map_err(|e| ConfigError::Keychain(e.to_string()))
+}
+
+#[cfg(not(feature = "keychain"))]
+fn keychain_pepper(_cfg: &Config) -> Result<Zeroizing<String>, ConfigError> {
+    Err(ConfigError::Keychain("pepper-keychain requires the keychain feature".to_string()))
+}
+
+/// Loads the config from `default_path()`.
+///
+/// A missing file yields the default config, unless it was named by `$PWGEN_CONFIG`.
+#[cfg(feature = "config")]
+pub fn load() -> Result<Config, ConfigError> {
+    let Some(path) = default_path() else {
+        return Ok(Config::default());
+    };
+    let explicit = std::env::var_os(CONFIG_ENV).is_some_and(|p| !p.is_empty());
+    if !explicit && !path.exists() {
+        return Ok(Config::default());
+    }
+    load_from(&path)
+}
+
+/// Loads the config from a specific file.
+#[cfg(feature = "config")]
+pub fn load_from(path: &Path) -> Result<Config, ConfigError> {
+    let display = path.display().to_string();
+    let input = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+        path: display.clone(),
+        message: e.to_string(),
+    })?;
+    parse(&input).map_err(|e| match e {
+        ConfigError::Parse { message, .. } => ConfigError::Parse { path: display, message },
+        other => other,
+    })
+}
@@ -0,0 +1,107 @@
+//! `pwgen serve --dbus`: the `serve` methods as the D-Bus service `org.pwgen.Generator` on the
+//! session bus, for desktop environments, KRunner plugins and GNOME Shell extensions. With
+//! `--agent` the service derives through the running `pwgen agent`.
+//!
+//! ```text
+//! object /org/pwgen/Generator, interface org.pwgen.Generator
+//! Generate(s site, a{sv} options) -> s password
+//! Fingerprint(b emoji)            -> s fingerprint
+//! ```
+//!
+//! `options` takes the params of the JSON-RPC `generate` method besides `site` (`username`,
+//! `version`, `length`, `allow` as an array of strings, ...).
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+use zbus::fdo;
+use zbus::zvariant::{self, OwnedValue};
+
+use super::serve::{self, RpcError};
+use super::{MasterArgs, Session};
+
+/// Well-known name the service owns on the session bus.
+pub const BUS_NAME: &str = "org.pwgen.Generator";
+
+/// Path of the generator object.
+const OBJECT_PATH: &str = "/org/pwgen/Generator";
+
+/// A session-bus connection owning `BUS_NAME`, so a second service fails before the master
+/// is read.
+pub struct Bus(zbus::blocking::Connection);
+
+pub fn connect() -> zbus::Result<Bus> {
+    let conn = zbus::blocking::Connection::session()?;
+    // Fail with `NameTaken` instead of waiting in the queue for the name
+    conn.request_name_with_flags(BUS_NAME, fdo::RequestNameFlags::DoNotQueue.into())?;
+    Ok(Bus(conn))
+}
+
+/// Serves the generator object until the process is stopped.
+pub fn serve(bus: &Bus, session: Session, input: MasterArgs) -> zbus::Result<()> {
+    bus.0.object_server().at(OBJECT_PATH, Generator { session, input })?;
+    loop {
+        std::thread::park();
+    }
+}
+
+struct Generator {
+    session: Session,
+    input: MasterArgs,
+}
+
+#[zbus::interface(name = "org.pwgen.Generator")]
+impl Generator {
+    /// Derives the password for `site` with the generate options in `options`.
+    fn generate(&self, site: &str, options: HashMap<String, OwnedValue>) -> fdo::Result<String> {
+        let mut params = Map::new();
+        for (key, value) in &options {
+            let value = json_value(value).ok_or_else(|| {
+                fdo::Error::InvalidArgs(format!("option '{}' must be a string, number, boolean or array of those", key))
+            })?;
+            params.insert(key.clone(), value);
+        }
+        params.insert("site".into(), Value::String(site.to_string()));
+
+        let result = self.call("generate", params)?;
+        Ok(result["password"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// The master fingerprint (`emoji` for the emoji form).
+    fn fingerprint(&self, emoji: bool) -> fdo::Result<String> {
+        let mut params = Map::new();
+        params.insert("emoji".into(), Value::Bool(emoji));
+        let result = self.call("fingerprint", params)?;
+        Ok(result["fingerprint"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+impl Generator {
+    fn call(&self, method: &str, params: Map<String, Value>) -> fdo::Result<Value> {
+        serve::call(&self.session, &self.input, method, Some(&Value::Object(params))).map_err(|RpcError { code, message }| {
+            if code == serve::INVALID_PARAMS {
+                fdo::Error::InvalidArgs(message)
+            } else {
+                fdo::Error::Failed(message)
+            }
+        })
+    }
+}
+
+/// The JSON form of an option value: strings, numbers, booleans and arrays of those.
+fn json_value(value: &zvariant::Value<'_>) -> Option<Value> {
+    Some(match value {
+        zvariant::Value::Str(s) => Value::String(s.to_string()),
+        zvariant::Value::Bool(b) => Value::Bool(*b),
+        zvariant::Value::U8(n) => Value::from(*n),
+        zvariant::Value::I16(n) => Value::from(*n),
+        zvariant::Value::U16(n) => Value::from(*n),
+        zvariant::Value::I32(n) => Value::from(*n),
+        zvariant::Value::U32(n) => Value::from(*n),
+        zvariant::Value::I64(n) => Value::from(*n),
+        zvariant::Value::U64(n) => Value::from(*n),
+        zvariant::Value::Value(inner) => return json_value(inner),
+        zvariant::Value::Array(items) => Value::Array(items.inner().iter().map(json_value).collect::<Option<_>>()?),
+        _ => return None,
+    })
+}
@@ -0,0 +1,76 @@
+//! Derivation paths (`generate --path`): one string naming a password hierarchically,
+//! `ORG/SITE[/ACCOUNT[/ROTATION]]`, e.g. `work/github.com/alice/2`.
+//!
+//! The segments map onto the fields the context already separates: the site is derived as
+//! `ORG/SITE` (the site normalized as with `--site`, the organization trimmed and lowercased),
+//! the account is the username and the rotation the version. The organization is also bound
+//! into the KDF input as its own tagged digest (see `digest`), so a path never derives the
+//! password of the flat `--site ORG/SITE`, and the same site derives unrelated passwords
+//! under different organizations.
+
+use thiserror::Error;
+
+/// A parsed derivation path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DerivationPath {
+    /// Organization, trimmed and lowercased
+    pub org: String,
+    /// Site, as given (normalized later, like `--site`)
+    pub site: String,
+    /// Account (username); empty if left out
+    pub account: String,
+    /// Rotation (version), if given
+    pub rotation: Option<u32>,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum PathError {
+    #[error("path must be ORG/SITE[/ACCOUNT[/ROTATION]]")]
+    Segments,
+    #[error("path has an empty {0}")]
+    Empty(&'static str),
+    #[error("path rotation '{0}' is not a version number")]
+    Rotation(String),
+}
+
+impl DerivationPath {
+    /// Parses `ORG/SITE[/ACCOUNT[/ROTATION]]`. The account may be empty to give a rotation
+    /// without one (`personal/example.com//3`).
+    pub fn parse(path: &str) -> Result<DerivationPath, PathError> {
+        let segments: Vec<&str> = path.trim().split('/').collect();
+        if !(2..=4).contains(&segments.len()) {
+            return Err(PathError::Segments);
+        }
+        let org = segments[0].trim().to_lowercase();
+        if org.is_empty() {
+            return Err(PathError::Empty("organization"));
+        }
+        let site = segments[1].trim();
+        if site.is_empty() {
+            return Err(PathError::Empty("site"));
+        }
+        let rotation = match segments.get(3).map(|s| s.trim()) {
+            Some(rotation) if rotation.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(rotation.parse().map_err(|_| PathError::Rotation(rotation.to_string()))?)
+            }
+            Some(rotation) => return Err(PathError::Rotation(rotation.to_string())),
+            None => None,
+        };
+        Ok(DerivationPath {
+            org,
+            site: site.to_string(),
+            account: segments.get(2).copied().unwrap_or_default().to_string(),
+            rotation,
+        })
+    }
+
+    /// The digest of the organization for `kdf::MasterInput::with_path`.
+    pub fn digest(&self) -> [u8; crate::kdf::KEYFILE_DIGEST_LEN] {
+        crate::kdf::path_digest(&self.org)
+    }
+
+    /// The site identifier derived from: `ORG/<site_id>`, for the normalized site.
+    pub fn scoped_site(&self, site_id: &str) -> String {
+        format!("{}/{}", self.org, site_id)
+    }
+}
@@ -0,0 +1,235 @@
+//! `pwgen export`: derives the password of every site in a profile file and writes them where
+//! another password manager reads them, for users migrating to or mirroring into it.
+//!
+//! `pass` writes one gpg-encrypted file per site in the layout of pass(1): `<store>/<name>.gpg`,
+//! holding the password on the first line and then `login:`, `url:` and the notes, encrypted to
+//! the recipients in the nearest `.gpg-id` above the entry.
+//!
+//! `csv` writes the CSV files the Bitwarden and 1Password importers read, one login per row.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+use pwgen::profiles::Profile;
+use zeroize::Zeroizing;
+
+use super::{derive_password, MasterArgs, Session};
+
+/// A profile with its derived password.
+pub struct Entry<'p> {
+    pub profile: &'p Profile,
+    pub password: Zeroizing<String>,
+}
+
+/// Derives the passwords of all profiles, failing on the first one that does not derive so a
+/// bad profile file writes nothing.
+pub fn derive_all<'p>(session: &Session, input: &MasterArgs, profiles: &'p [Profile]) -> Result<Vec<Entry<'p>>, String> {
+    profiles
+        .iter()
+        .map(|profile| {
+            let (_, password) = derive_password(session, input, generate_argv(profile))
+                .map_err(|e| format!("{}: {}", profile.entry_name(), e))?;
+            Ok(Entry { profile, password })
+        })
+        .collect()
+}
+
+/// The generate flags a profile stands for.
+fn generate_argv(profile: &Profile) -> Vec<String> {
+    let mut argv = vec![format!("--site={}", profile.site), format!("--username={}", profile.username)];
+    if let Some(version) = profile.version {
+        argv.push(format!("--version={}", version));
+    }
+    if let Some(length) = profile.length {
+        argv.push(format!("--length={}", length));
+    }
+    if let Some(policy) = &profile.policy {
+        argv.push(format!("--policy={}", policy));
+    }
+    if let Some(rules) = &profile.passwordrules {
+        argv.push(format!("--passwordrules={}", rules));
+    }
+    if let Some(pattern) = &profile.pattern {
+        argv.push(format!("--pattern={}", pattern));
+    }
+    argv
+}
+
+/// The default pass store: `$PASSWORD_STORE_DIR`, else `~/.password-store`.
+pub fn default_store() -> Option<PathBuf> {
+    std::env::var_os("PASSWORD_STORE_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".password-store")))
+}
+
+/// Checks that an entry name stays inside the store: relative, `/`-separated, no `.` or `..`.
+pub fn check_entry_name(name: &str) -> Result<(), String> {
+    if name.split('/').any(|part| part.is_empty() || part == "." || part == "..") || name.contains('\\') {
+        return Err(format!("entry name '{}' must be a relative path without '.', '..' or empty parts", name));
+    }
+    Ok(())
+}
+
+/// What `pass` wrote.
+pub struct PassSummary {
+    pub written: usize,
+    pub skipped: usize,
+}
+
+/// Writes the entries into the pass store, skipping existing ones unless `force`.
+pub fn pass(store: &Path, entries: &[Entry<'_>], force: bool) -> Result<PassSummary> {
+    let mut summary = PassSummary { written: 0, skipped: 0 };
+    for entry in entries {
+        let name = entry.profile.entry_name();
+        let path = store.join(format!("{}.gpg", name));
+        if path.exists() && !force {
+            eprintln!("skipped {} (exists; --force to replace)", name);
+            summary.skipped += 1;
+            continue;
+        }
+        let dir = path.parent().unwrap_or(store);
+        std::fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        let recipients = recipients(store, dir)?;
+        encrypt(&pass_contents(entry), &recipients, &path).with_context(|| format!("cannot write {}", name))?;
+        summary.written += 1;
+    }
+    Ok(summary)
+}
+
+/// The decrypted entry: the password, then the extra lines pass and its browser extensions read.
+fn pass_contents(entry: &Entry<'_>) -> Zeroizing<String> {
+    let profile = entry.profile;
+    let mut contents = Zeroizing::new(format!("{}\n", *entry.password));
+    if !profile.username.is_empty() {
+        contents.push_str(&format!("login: {}\n", profile.username));
+    }
+    if let Some(url) = profile.login_url() {
+        contents.push_str(&format!("url: {}\n", url));
+    }
+    if let Some(notes) = &profile.notes {
+        contents.push_str(notes.trim_end());
+        contents.push('\n');
+    }
+    contents
+}
+
+/// GPG recipients of the nearest `.gpg-id` from `dir` up to the store root, as pass finds them.
+fn recipients(store: &Path, dir: &Path) -> Result<Vec<String>> {
+    let mut dir = dir;
+    loop {
+        let path = dir.join(".gpg-id");
+        if path.is_file() {
+            let ids = std::fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+            let ids: Vec<String> = ids
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            if ids.is_empty() {
+                bail!("{} lists no recipients", path.display());
+            }
+            return Ok(ids);
+        }
+        match dir.parent() {
+            Some(parent) if dir != store => dir = parent,
+            _ => bail!("no .gpg-id in {}", store.display()),
+        }
+    }
+}
+
+fn encrypt(contents: &str, recipients: &[String], path: &Path) -> Result<()> {
+    let mut command = Command::new("gpg");
+    command.args(["--batch", "--yes", "--quiet", "--compress-algo=none", "--no-encrypt-to", "--encrypt"]);
+    for id in recipients {
+        command.arg("--recipient").arg(id);
+    }
+    let mut child = command
+        .arg("--output")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("cannot run gpg")?;
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("no pipe to gpg"))?;
+    stdin.write_all(contents.as_bytes()).context("cannot write to gpg")?;
+    drop(stdin);
+    let status = child.wait().context("cannot run gpg")?;
+    if !status.success() {
+        bail!("gpg failed ({})", status);
+    }
+    Ok(())
+}
+
+/// CSV import schemas.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum CsvFormat {
+    /// Bitwarden's "Bitwarden (csv)" import
+    Bitwarden,
+    /// 1Password's CSV import
+    #[value(name = "1password")]
+    OnePassword,
+}
+
+/// Writes the entries as a CSV file with the header row of `format`.
+pub fn csv(out: &mut impl Write, format: CsvFormat, entries: &[Entry<'_>]) -> io::Result<()> {
+    let mut text = Zeroizing::new(String::new());
+    let header: &[&str] = match format {
+        CsvFormat::Bitwarden => &[
+            "folder", "favorite", "type", "name", "notes", "fields", "reprompt", "login_uri", "login_username",
+            "login_password", "login_totp",
+        ],
+        CsvFormat::OnePassword => &["Title", "Website", "Username", "Password", "Notes"],
+    };
+    push_record(&mut text, header);
+    for entry in entries {
+        let profile = entry.profile;
+        let name = profile.entry_name();
+        let url = profile.login_url().unwrap_or_default();
+        let notes = profile.notes.as_deref().unwrap_or_default();
+        match format {
+            CsvFormat::Bitwarden => push_record(
+                &mut text,
+                &["", "", "login", &name, notes, "", "0", &url, &profile.username, &entry.password, ""],
+            ),
+            CsvFormat::OnePassword => push_record(&mut text, &[&name, &url, &profile.username, &entry.password, notes]),
+        }
+    }
+    out.write_all(text.as_bytes())?;
+    out.flush()
+}
+
+/// Appends one CSV record, quoting fields as RFC 4180 requires.
+fn push_record(text: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            text.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) || field.starts_with(' ') || field.ends_with(' ') {
+            text.push('"');
+            text.push_str(&field.replace('"', "\"\""));
+            text.push('"');
+        } else {
+            text.push_str(field);
+        }
+    }
+    text.push('\n');
+}
+
+/// Creates a file only the user can read, refusing to replace one unless `force`.
+pub fn create_private(path: &Path, force: bool) -> io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
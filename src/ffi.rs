@@ -0,0 +1,314 @@
+//! C interface (`ffi` feature): the derivation of the CLI behind `extern "C"` functions, for
+//! embedding in C, Swift, Kotlin/JNI and other languages. `include/pwgen.h` declares it.
+//!
+//! A `PwgenMaster` handle holds the master secret with its KDF and algorithm version; every
+//! derivation takes one and returns a heap string the caller releases with
+//! `pwgen_string_free`, which zeroizes it. Functions return `PWGEN_OK` or a negative error
+//! code, and `pwgen_last_error` describes the most recent failure on the calling thread.
+//! Panics are caught at the boundary and reported as `PWGEN_ERR_INTERNAL`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use zeroize::Zeroize;
+
+use crate::generator::{self, AlgoVersion, GenError};
+use crate::kdf::{KdfParams, KdfProfile, KdfScheme, MasterInput, MasterSecret};
+use crate::policy;
+use crate::site::{self, SiteMode};
+
+pub const PWGEN_OK: c_int = 0;
+/// A required pointer argument was null
+pub const PWGEN_ERR_NULL: c_int = -1;
+/// A string argument was not valid UTF-8
+pub const PWGEN_ERR_UTF8: c_int = -2;
+/// An argument was rejected (empty master, unknown profile, bad policy or pattern)
+pub const PWGEN_ERR_INVALID: c_int = -3;
+/// The KDF or the PRNG failed
+pub const PWGEN_ERR_DERIVE: c_int = -4;
+/// A panic inside the library (a bug); the handle may be left unchanged
+pub const PWGEN_ERR_INTERNAL: c_int = -5;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The master secret, KDF and algorithm version derivations run with.
+pub struct PwgenMaster {
+    master: MasterSecret,
+    scheme: KdfScheme,
+    algo: AlgoVersion,
+}
+
+struct Error(c_int, String);
+
+impl From<GenError> for Error {
+    fn from(e: GenError) -> Self {
+        let code = match e {
+            GenError::Kdf(_) | GenError::Prng(_) => PWGEN_ERR_DERIVE,
+            _ => PWGEN_ERR_INVALID,
+        };
+        Error(code, e.to_string())
+    }
+}
+
+/// Runs `f`, recording its error (or panic) for `pwgen_last_error`.
+fn guard(f: impl FnOnce() -> Result<(), Error>) -> c_int {
+    let Error(code, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return PWGEN_OK,
+        Ok(Err(e)) => e,
+        Err(_) => Error(PWGEN_ERR_INTERNAL, "internal error".to_string()),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    code
+}
+
+/// Borrows a required C string argument.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string that outlives the returned borrow.
+unsafe fn arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error(PWGEN_ERR_NULL, format!("{} is null", name)));
+    }
+    // SAFETY: non-null, and NUL-terminated per the contract above
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| Error(PWGEN_ERR_UTF8, format!("{} is not valid UTF-8", name)))
+}
+
+/// Like `arg`, but null means absent.
+///
+/// # Safety
+///
+/// As for `arg`.
+unsafe fn optional_arg<'a>(s: *const c_char, name: &str) -> Result<Option<&'a str>, Error> {
+    if s.is_null() {
+        Ok(None)
+    } else {
+        // SAFETY: forwarded contract
+        unsafe { arg(s, name) }.map(Some)
+    }
+}
+
+/// Borrows a handle argument.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `pwgen_master_new`.
+unsafe fn borrow_handle<'a>(handle: *const PwgenMaster) -> Result<&'a PwgenMaster, Error> {
+    // SAFETY: null or live per the contract above
+    unsafe { handle.as_ref() }.ok_or_else(|| Error(PWGEN_ERR_NULL, "handle is null".to_string()))
+}
+
+/// Hands `value` to the caller through `out`, zeroizing the Rust copy.
+///
+/// # Safety
+///
+/// `out` must be valid for a pointer write.
+unsafe fn put_string(out: *mut *mut c_char, mut value: String) -> Result<(), Error> {
+    let c = CString::new(value.as_str()).map_err(|_| Error(PWGEN_ERR_INTERNAL, "output contains NUL".to_string()));
+    value.zeroize();
+    // SAFETY: checked non-null by the callers; valid for writes per the contract above
+    unsafe { *out = c?.into_raw() };
+    Ok(())
+}
+
+/// Reduces `site` as `pwgen generate` does with the default `--site-mode exact`, so pasted
+/// URLs and IDNs derive the CLI's password.
+fn normalize_site(site: &str) -> Result<String, Error> {
+    let site = site::normalize(site, SiteMode::Exact).map_err(|e| Error(PWGEN_ERR_INVALID, e.to_string()))?;
+    if site.is_empty() {
+        return Err(Error(PWGEN_ERR_INVALID, "site must be nonempty after trim".to_string()));
+    }
+    Ok(site)
+}
+
+/// Creates a handle for `master` (UTF-8, nonempty) with the balanced Argon2id profile and
+/// algorithm v1, stored in `*out`. Release it with `pwgen_master_free`.
+///
+/// # Safety
+///
+/// `master` must be a NUL-terminated string and `out` valid for a pointer write.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_master_new(master: *const c_char, out: *mut *mut PwgenMaster) -> c_int {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error(PWGEN_ERR_NULL, "out is null".to_string()));
+        }
+        // SAFETY: caller contract
+        let master = MasterSecret::from(unsafe { arg(master, "master") }?);
+        if master.is_empty() {
+            return Err(Error(PWGEN_ERR_INVALID, "master secret must be nonempty".to_string()));
+        }
+        let handle = Box::new(PwgenMaster { master, scheme: KdfScheme::default(), algo: AlgoVersion::default() });
+        // SAFETY: non-null and valid for writes per the caller contract
+        unsafe { *out = Box::into_raw(handle) };
+        Ok(())
+    })
+}
+
+/// Selects a named Argon2id profile (`fast`, `balanced`, `paranoid`), as `--kdf-profile`.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `profile` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_master_set_kdf_profile(handle: *mut PwgenMaster, profile: *const c_char) -> c_int {
+    guard(|| {
+        // SAFETY: caller contract
+        let name = unsafe { arg(profile, "profile") }?;
+        let profile = KdfProfile::ALL
+            .into_iter()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| Error(PWGEN_ERR_INVALID, format!("unknown KDF profile '{}'", name)))?;
+        // SAFETY: null or live per the caller contract
+        let handle = unsafe { handle.as_mut() }.ok_or_else(|| Error(PWGEN_ERR_NULL, "handle is null".to_string()))?;
+        handle.scheme = KdfScheme::Argon2id(profile.params());
+        Ok(())
+    })
+}
+
+/// Selects custom Argon2id costs, as `kdf-params` in the config file.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_master_set_kdf_params(handle: *mut PwgenMaster, mem_kib: u32, t_cost: u32, p_cost: u32) -> c_int {
+    guard(|| {
+        let params = KdfParams { mem_kib, t_cost, p_cost };
+        params.validate().map_err(|e| Error(PWGEN_ERR_INVALID, e.to_string()))?;
+        // SAFETY: null or live per the caller contract
+        let handle = unsafe { handle.as_mut() }.ok_or_else(|| Error(PWGEN_ERR_NULL, "handle is null".to_string()))?;
+        handle.scheme = KdfScheme::Argon2id(params);
+        Ok(())
+    })
+}
+
+/// Selects the algorithm version (1, 2, 3 or 5; 4 with the `blake3` feature), as `--algo-version`.
+///
+/// # Safety
+///
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_master_set_algo_version(handle: *mut PwgenMaster, version: u32) -> c_int {
+    guard(|| {
+        let algo = AlgoVersion::from_number(version)
+            .ok_or_else(|| Error(PWGEN_ERR_INVALID, format!("unknown algorithm version {}", version)))?;
+        // SAFETY: null or live per the caller contract
+        let handle = unsafe { handle.as_mut() }.ok_or_else(|| Error(PWGEN_ERR_NULL, "handle is null".to_string()))?;
+        handle.algo = algo;
+        Ok(())
+    })
+}
+
+/// Zeroizes and frees a handle; null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, which is invalid afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_master_free(handle: *mut PwgenMaster) {
+    if !handle.is_null() {
+        // SAFETY: a live handle from `Box::into_raw`; dropping it zeroizes the master
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Derives the password for `site`, as `pwgen generate`; the site is normalized as with
+/// `--site-mode exact` (a URL counts as its host). `username` may be null (none) and
+/// `policy` null for the default policy, else an encoded policy as in `--json` output.
+/// The password is stored in `*out`; release it with `pwgen_string_free`.
+///
+/// # Safety
+///
+/// `handle` must be a live handle, the strings NUL-terminated (or null where allowed) and
+/// `out` valid for a pointer write.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_generate(
+    handle: *const PwgenMaster,
+    site: *const c_char,
+    username: *const c_char,
+    policy: *const c_char,
+    version: u32,
+    out: *mut *mut c_char,
+) -> c_int {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error(PWGEN_ERR_NULL, "out is null".to_string()));
+        }
+        // SAFETY: caller contract, for all four
+        let (handle, site, username, encoded) = unsafe {
+            (borrow_handle(handle)?, arg(site, "site")?, optional_arg(username, "username")?, optional_arg(policy, "policy")?)
+        };
+        let policy = match encoded {
+            Some(encoded) => policy::decode(encoded).and_then(|p| policy::validate(&p)),
+            None => policy::validate(&policy::default_policy()),
+        }
+        .map_err(|e| Error(PWGEN_ERR_INVALID, e.to_string()))?;
+        let site = normalize_site(site)?;
+        let password = generator::generate_password(master_input(handle), &site, username, &policy, version)?;
+        // SAFETY: checked non-null above
+        unsafe { put_string(out, password) }
+    })
+}
+
+/// Derives a password from a pattern, as `pwgen generate --pattern`, with the site normalized
+/// as for `pwgen_generate`. `username` may be null.
+/// The password is stored in `*out`; release it with `pwgen_string_free`.
+///
+/// # Safety
+///
+/// As for `pwgen_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_generate_pattern(
+    handle: *const PwgenMaster,
+    site: *const c_char,
+    username: *const c_char,
+    pattern: *const c_char,
+    version: u32,
+    out: *mut *mut c_char,
+) -> c_int {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error(PWGEN_ERR_NULL, "out is null".to_string()));
+        }
+        // SAFETY: caller contract, for all four
+        let (handle, site, username, pattern) = unsafe {
+            (borrow_handle(handle)?, arg(site, "site")?, optional_arg(username, "username")?, arg(pattern, "pattern")?)
+        };
+        let site = normalize_site(site)?;
+        let password = generator::generate_from_pattern(master_input(handle), &site, username, pattern, version)?;
+        // SAFETY: checked non-null above
+        unsafe { put_string(out, password) }
+    })
+}
+
+/// Zeroizes and frees a string returned by this library; null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string from this library not yet freed, which is invalid afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pwgen_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: from `CString::into_raw` in `put_string`
+        let mut bytes = unsafe { CString::from_raw(s) }.into_bytes_with_nul();
+        bytes.zeroize();
+    }
+}
+
+/// The message of the last error on this thread, or null if there was none. The pointer is
+/// valid until the next failing call on the same thread; do not free it.
+#[no_mangle]
+pub extern "C" fn pwgen_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+fn master_input(handle: &PwgenMaster) -> MasterInput<'_> {
+    MasterInput::new(handle.master.as_str(), handle.scheme).with_algo(handle.algo)
+}
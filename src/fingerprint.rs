@@ -0,0 +1,60 @@
+use crate::generator::{site_stream, GenError};
+use crate::kdf;
+use crate::recovery::CODE_ALPHABET;
+
+/// Reserved site whose KDF key the fingerprint is drawn from. The `fingerprint` context
+/// label keeps the fingerprint apart from anything a real site of this name derives.
+pub const FINGERPRINT_SITE: &str = "pwgen-fingerprint";
+
+/// Fingerprint shape; `X` is one character of the recovery code alphabet (40 bits in all).
+const FINGERPRINT_SHAPE: &str = "XXXX-XXXX";
+
+/// Emoji drawn for the visual fingerprint: 64 animals, plants, food and objects that are
+/// easy to tell apart and name, each a single code point with emoji presentation.
+const EMOJI: [char; 64] = [
+    '🐶', '🐱', '🐭', '🐹', '🐰', '🦊', '🐻', '🐼', '🐨', '🐯', '🦁', '🐮', '🐷', '🐸', '🐵', '🐔',
+    '🐧', '🐦', '🐤', '🦆', '🦉', '🐴', '🦄', '🐝', '🐛', '🦋', '🐌', '🐞', '🐢', '🐍', '🐙', '🦀',
+    '🐬', '🐳', '🦈', '🐊', '🌵', '🌲', '🌴', '🍀', '🍁', '🍄', '🌻', '🌹', '🌙', '🌞', '🌈', '🔥',
+    '💧', '🍎', '🍋', '🍌', '🍉', '🍇', '🍓', '🍒', '🍍', '🥕', '🌽', '🍕', '🍩', '🎈', '🎁', '🔔',
+];
+
+/// Number of emoji in the visual fingerprint (24 bits).
+pub const EMOJI_COUNT: usize = 4;
+
+/// Derives a short fingerprint of the master input, of the form `XXXX-XXXX`, so the same
+/// master can be confirmed on several devices without showing it.
+///
+/// Every factor, the KDF and the algorithm version feed into it, just as for a password.
+/// Checking a guess costs a full key derivation, and with only 40 bits many masters share
+/// each fingerprint, so it does not help to recover the master.
+pub fn master_fingerprint<'m, K: kdf::Kdf>(master: impl Into<kdf::MasterInput<'m, K>>) -> Result<String, GenError> {
+    let mut rng = site_stream(master.into(), FINGERPRINT_SITE, b"", b"fingerprint", FINGERPRINT_SHAPE.as_bytes(), 1)?;
+
+    let mut fingerprint = String::with_capacity(FINGERPRINT_SHAPE.len());
+    for ch in FINGERPRINT_SHAPE.bytes() {
+        if ch == b'X' {
+            let idx = rng.next_index(CODE_ALPHABET.len())?;
+            fingerprint.push(CODE_ALPHABET[idx] as char);
+        } else {
+            fingerprint.push(ch as char);
+        }
+    }
+    Ok(fingerprint)
+}
+
+/// Derives a visual fingerprint of the master input: `EMOJI_COUNT` emoji separated by
+/// spaces (e.g. `🦊 🌵 🍩 🐢`), to show right after the master is typed so a typo is
+/// noticed before any password is used.
+///
+/// Drawn like `master_fingerprint` but from its own context, so the two do not reveal each
+/// other.
+pub fn emoji_fingerprint<'m, K: kdf::Kdf>(master: impl Into<kdf::MasterInput<'m, K>>) -> Result<String, GenError> {
+    let params = format!("emoji;n={}", EMOJI_COUNT);
+    let mut rng = site_stream(master.into(), FINGERPRINT_SITE, b"", b"fingerprint", params.as_bytes(), 1)?;
+
+    let mut emoji = Vec::with_capacity(EMOJI_COUNT);
+    for _ in 0..EMOJI_COUNT {
+        emoji.push(EMOJI[rng.next_index(EMOJI.len())?].to_string());
+    }
+    Ok(emoji.join(" "))
+}
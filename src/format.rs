@@ -0,0 +1,151 @@
+use alloc::{format, string::String, vec::Vec};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Largest token, in bytes, that can be requested.
+pub const MAX_TOKEN_BYTES: usize = 1024;
+
+/// Binary-to-text encodings for token output (as opposed to charset policies).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Format {
+    /// Lowercase hexadecimal, two characters per byte
+    Hex,
+    /// URL-safe Base64 (RFC 4648 §5) without padding
+    Base64Url,
+    /// Base58 with the Bitcoin alphabet (no 0, O, I, l)
+    Base58,
+}
+
+#[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormatError {
+    #[error("token length must be within [1,1024] bytes")]
+    InvalidLength,
+}
+
+impl Format {
+    /// Name used on the command line and in the derivation context.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Hex => "hex",
+            Format::Base64Url => "base64url",
+            Format::Base58 => "base58",
+        }
+    }
+
+    /// Encodes raw bytes in this format.
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Format::Hex => encode_hex(bytes),
+            Format::Base64Url => encode_base64url(bytes),
+            Format::Base58 => encode_base58(bytes),
+        }
+    }
+}
+
+/// Canonical token parameters bound into the derivation context: `<format>;bytes=<n>`.
+pub fn params(format: Format, bytes: usize) -> String {
+    format!("{};bytes={}", format.name(), bytes)
+}
+
+/// Checks that a requested token length is within bounds.
+pub fn check_length(bytes: usize) -> Result<(), FormatError> {
+    if (1..=MAX_TOKEN_BYTES).contains(&bytes) {
+        Ok(())
+    } else {
+        Err(FormatError::InvalidLength)
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(DIGITS[(b >> 4) as usize] as char);
+        out.push(DIGITS[(b & 0x0f) as usize] as char);
+    }
+    out
+}
+
+fn encode_base64url(bytes: &[u8]) -> String {
+    encode_base64_with(bytes, b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_", false)
+}
+
+/// Standard padded Base64 (RFC 4648 §4), as used for WireGuard keys.
+#[cfg(feature = "std")]
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    encode_base64_with(bytes, b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/", true)
+}
+
+fn encode_base64_with(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.len();
+        let b = [chunk[0], if n > 1 { chunk[1] } else { 0 }, if n > 2 { chunk[2] } else { 0 }];
+        let v = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        // n input bytes produce n + 1 output characters, padded to 4 if requested
+        for k in 0..=n {
+            out.push(alphabet[((v >> (18 - 6 * k)) & 0x3f) as usize] as char);
+        }
+        if pad {
+            for _ in n..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn encode_base58(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    // Each leading zero byte is written as '1'
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+
+    // Repeated division of the big-endian number by 58; digits are little-endian
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &b in &bytes[zeros..] {
+        let mut carry = b as u32;
+        for d in digits.iter_mut() {
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut out = String::with_capacity(zeros + digits.len());
+    out.extend(core::iter::repeat_n('1', zeros));
+    out.extend(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char));
+    digits.zeroize();
+    out
+}
+
+/// RFC 4648 Base32 without padding, as used for TOTP secrets.
+#[cfg(feature = "std")]
+pub(crate) fn encode_base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for &b in bytes {
+        buffer = (buffer << 8) | b as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
@@ -1,6 +1,11 @@
-use crate::{kdf, policy, prng};
+use crate::{format, kdf, pattern, policy, prng};
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use zeroize::Zeroize;
+use zeroize::Zeroizing;
 
 #[derive(Error, Debug)]
 pub enum GenError {
@@ -10,15 +15,224 @@ pub enum GenError {
     Kdf(#[from] kdf::KdfError),
     #[error(transparent)]
     Prng(#[from] prng::PrngError),
+    #[error(transparent)]
+    Pattern(#[from] pattern::PatternError),
+    #[error(transparent)]
+    Format(#[from] format::FormatError),
     #[error("invalid input: {0}")]
     InvalidInput(&'static str),
 }
 
+/// Serializes as the error message, so results can be reported without matching on variants.
+#[cfg(feature = "serde")]
+impl serde::Serialize for GenError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Version of the derivation scheme after the KDF (the PRNG context layout).
+///
+/// Outputs of a released version never change; fixes that would change them go into a new
+/// version instead, selected with `kdf::MasterInput::with_algo` (`--algo-version`).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlgoVersion {
+    /// `pwgen-v1|site=<site_id>|user=<username>|<label>=<params>|version=<decimal>`
+    #[default]
+    V1,
+    /// `pwgen-v2|kdf=<n>:<kdf id>|site=<n>:<site_id>|user=<n>:<username>|<label>=<n>:<params>|version=<decimal>`,
+    /// where `<n>` is the byte length of the value that follows. Length prefixes make the
+    /// context unambiguous whatever the fields contain, and the KDF id (`Kdf::context_id`)
+    /// separates streams derived under different KDF parameters.
+    V2,
+    /// The v2 context with `pwgen-v3` in place of `pwgen-v2`, over a ChaCha20 stream
+    /// (`prng::ChaChaStream`) instead of HKDF-SHA256.
+    V3,
+    /// The v2 context with `pwgen-v4`, over a keyed BLAKE3 stream (`prng::Blake3Stream`) in
+    /// place of both HKDF stages (`blake3` feature).
+    #[cfg(feature = "blake3")]
+    V4,
+    /// The v2 context with `pwgen-v5`, over HKDF-SHA512 (`prng::HkdfSha512Stream`) instead
+    /// of HKDF-SHA256, where SHA-512 primitives are required.
+    V5,
+}
+
+impl AlgoVersion {
+    #[cfg(not(feature = "blake3"))]
+    pub const ALL: [AlgoVersion; 4] = [AlgoVersion::V1, AlgoVersion::V2, AlgoVersion::V3, AlgoVersion::V5];
+    #[cfg(feature = "blake3")]
+    pub const ALL: [AlgoVersion; 5] =
+        [AlgoVersion::V1, AlgoVersion::V2, AlgoVersion::V3, AlgoVersion::V4, AlgoVersion::V5];
+
+    /// Number used on the command line and in `--json` output.
+    pub fn number(self) -> u32 {
+        match self {
+            AlgoVersion::V1 => 1,
+            AlgoVersion::V2 => 2,
+            AlgoVersion::V3 => 3,
+            #[cfg(feature = "blake3")]
+            AlgoVersion::V4 => 4,
+            AlgoVersion::V5 => 5,
+        }
+    }
+
+    /// Tag at the start of the context.
+    fn tag(self) -> &'static [u8] {
+        match self {
+            AlgoVersion::V1 => b"pwgen-v1",
+            AlgoVersion::V2 => b"pwgen-v2",
+            AlgoVersion::V3 => b"pwgen-v3",
+            #[cfg(feature = "blake3")]
+            AlgoVersion::V4 => b"pwgen-v4",
+            AlgoVersion::V5 => b"pwgen-v5",
+        }
+    }
+
+    /// The PRNG of this version over a site key and its context.
+    pub fn stream(self, key: &[u8; 32], info: &[u8]) -> Result<prng::Stream, prng::PrngError> {
+        match self {
+            AlgoVersion::V1 | AlgoVersion::V2 => prng::from_key_and_context(key, info).map(prng::Stream::Hkdf),
+            AlgoVersion::V3 => prng::chacha_from_key_and_context(key, info).map(prng::Stream::ChaCha),
+            #[cfg(feature = "blake3")]
+            AlgoVersion::V4 => Ok(prng::Stream::Blake3(prng::blake3_from_key_and_context(key, info))),
+            AlgoVersion::V5 => prng::sha512_from_key_and_context(key, info).map(prng::Stream::HkdfSha512),
+        }
+    }
+
+    pub fn from_number(n: u32) -> Option<AlgoVersion> {
+        AlgoVersion::ALL.into_iter().find(|a| a.number() == n)
+    }
+}
+
+/// Derives the site key and builds the PRNG over the context of `master.algo` (see
+/// `AlgoVersion`).
+///
+/// `label` names what `params` encodes (e.g. `policy`, `pattern`) so that each output mode
+/// draws from its own stream. The KDF key is zeroized as soon as the PRNG is constructed.
+///
+/// With the `tracing` feature the KDF and the PRNG setup are `kdf` and `prng` spans; they
+/// record the KDF parameters and output label, never the master, site or key.
+pub(crate) fn site_stream<K: kdf::Kdf>(
+    master: kdf::MasterInput<'_, K>,
+    site_id: &str,
+    username_bytes: &[u8],
+    label: &[u8],
+    params: &[u8],
+    version: u32,
+) -> Result<prng::Stream, GenError> {
+    // Derive KDF key (32 bytes)
+    #[cfg(feature = "std")]
+    let start = Instant::now();
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("kdf", kdf = %master.kdf.context_id()).entered();
+    let key = master.kdf.derive_locked(&master.kdf_input(), site_id, &master.extras)?;
+    #[cfg(feature = "std")]
+    let derived = Instant::now();
+
+    #[cfg(feature = "tracing")]
+    let span = {
+        drop(span);
+        tracing::debug_span!("prng", label = %String::from_utf8_lossy(label), algo = master.algo.number()).entered()
+    };
+    let info = context(master.algo, &master.kdf, site_id, username_bytes, label, params, version);
+
+    // Create PRNG
+    let rng = master.algo.stream(&key, &info);
+    // Zeroize key ASAP after rng constructed
+    drop(key);
+    #[cfg(feature = "tracing")]
+    drop(span);
+    #[cfg(feature = "std")]
+    if let Some(timings) = master.timings {
+        timings.record(derived - start, derived.elapsed());
+    }
+    Ok(rng?)
+}
+
+/// Wall-clock time spent in the stages of derivations, collected through
+/// `kdf::MasterInput::with_timings` (for `--verbose`).
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Timings {
+    kdf_nanos: AtomicU64,
+    hkdf_nanos: AtomicU64,
+}
+
+#[cfg(feature = "std")]
+impl Timings {
+    /// Time in the KDF (Argon2id or PBKDF2, or the round trip to the agent holding the master).
+    pub fn kdf(&self) -> Duration {
+        Duration::from_nanos(self.kdf_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Time building the context and setting up the HKDF stream over it.
+    pub fn hkdf(&self) -> Duration {
+        Duration::from_nanos(self.hkdf_nanos.load(Ordering::Relaxed))
+    }
+
+    fn record(&self, kdf: Duration, hkdf: Duration) {
+        let nanos = |d: Duration| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        self.kdf_nanos.fetch_add(nanos(kdf), Ordering::Relaxed);
+        self.hkdf_nanos.fetch_add(nanos(hkdf), Ordering::Relaxed);
+    }
+}
+
+/// Builds the PRNG info context of `algo` (see `AlgoVersion`); `site_id` is the normalized
+/// site and `label` and `params` as for `site_stream`.
+pub fn context<K: kdf::Kdf>(
+    algo: AlgoVersion,
+    kdf: &K,
+    site_id: &str,
+    username_bytes: &[u8],
+    label: &[u8],
+    params: &[u8],
+    version: u32,
+) -> Vec<u8> {
+    let mut info = Vec::with_capacity(64);
+    let version_str = itoa::Buffer::new().format(version).to_string();
+    match algo {
+        AlgoVersion::V1 => {
+            info.extend_from_slice(algo.tag());
+            info.extend_from_slice(b"|site=");
+            info.extend_from_slice(site_id.as_bytes());
+            info.extend_from_slice(b"|user=");
+            info.extend_from_slice(username_bytes);
+            info.push(b'|');
+            info.extend_from_slice(label);
+            info.push(b'=');
+            info.extend_from_slice(params);
+        }
+        // v2 and later
+        _ => {
+            info.extend_from_slice(algo.tag());
+            push_field(&mut info, b"kdf", kdf.context_id().as_bytes());
+            push_field(&mut info, b"site", site_id.as_bytes());
+            push_field(&mut info, b"user", username_bytes);
+            push_field(&mut info, label, params);
+        }
+    }
+    info.extend_from_slice(b"|version=");
+    info.extend_from_slice(version_str.as_bytes());
+    info
+}
+
+/// Appends a length-prefixed v2 context field: `|<name>=<byte length>:<value>`.
+pub(crate) fn push_field(info: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    info.push(b'|');
+    info.extend_from_slice(name);
+    info.push(b'=');
+    info.extend_from_slice(itoa::Buffer::new().format(value.len()).as_bytes());
+    info.push(b':');
+    info.extend_from_slice(value);
+}
+
 /// Generates a deterministic password from the given inputs.
 ///
 /// # Arguments
 ///
-/// * `master` - Master secret
+/// * `master` - Master secret and KDF (a `&kdf::MasterSecret` or `&str` uses the default
+///   Argon2id parameters; see `kdf::MasterInput` and `kdf::Kdf`)
 /// * `site` - Site identifier (will be trimmed and lowercased)
 /// * `username` - Optional username
 /// * `policy_in` - Policy (will be validated; assumes it has been validated via `policy::validate()`)
@@ -28,8 +242,9 @@ pub enum GenError {
 ///
 /// Assumes `policy_in` has been validated via `policy::validate()`. The policy validation
 /// ensures all invariants are satisfied, so this function does not re-check policy bounds.
-pub fn generate_password(
-    master: &str,
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn generate_password<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
     site: &str,
     username: Option<&str>,
     policy_in: &policy::Policy,
@@ -42,38 +257,25 @@ pub fn generate_password(
     // Validate policy - this is the single source of truth for policy validation
     let policy = policy::validate(policy_in)?;
 
-    // Derive KDF key (32 bytes)
-    let mut key = kdf::derive_site_key(master, &site_id)?;
-
-    // Build PRNG info context
-    let mut info = Vec::with_capacity(64);
-    info.extend_from_slice(b"pwgen-v1");
-    info.extend_from_slice(b"|site=");
-    info.extend_from_slice(site_id.as_bytes());
-    info.extend_from_slice(b"|user=");
-    info.extend_from_slice(username_bytes);
-    info.extend_from_slice(b"|policy=");
+    // Derive the PRNG over the policy context
     let enc = policy::encode(&policy);
-    info.extend_from_slice(enc.as_bytes());
-    info.extend_from_slice(b"|version=");
-    let version_str = itoa::Buffer::new().format(version).to_string();
-    info.extend_from_slice(version_str.as_bytes());
-
-    // Create PRNG
-    let mut rng = prng::from_key_and_context(&key, &info)?;
-    // Zeroize key ASAP after rng constructed
-    key.zeroize();
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"policy", enc.as_bytes(), version)?;
 
     // Choose length L
     // Policy has been validated, so we know: 1 ≤ min ≤ max ≤ 128, min ≥ forced_count, allow is nonempty
+    // (forced_count is the sum of min_counts, which validation bounds by min ≤ 128)
     let min = policy.min;
     let max = policy.max;
-    let mut forced_sets = policy::forced_sets(&policy);
-    let forced_count = forced_sets.len() as u8;
+    let classes = policy::allowed_classes(&policy);
+    let letter_positions = u8::from(policy.letter_first) + u8::from(policy.letter_last);
+    let forced_count: u8 = policy.min_counts.iter().sum::<u8>()
+        + policy.force_extended.iter().filter(|&&b| b).count() as u8
+        + classes.iter().filter(|(class, _)| class.force).count() as u8
+        + letter_positions;
 
     // Defense-in-depth: these should never happen after validation, but check in debug builds
-    debug_assert!(min >= 1 && min <= 128, "min should be in [1,128] after validation");
-    debug_assert!(max >= 1 && max <= 128, "max should be in [1,128] after validation");
+    debug_assert!((1..=128).contains(&min), "min should be in [1,128] after validation");
+    debug_assert!((1..=128).contains(&max), "max should be in [1,128] after validation");
     debug_assert!(min <= max, "min should be ≤ max after validation");
     debug_assert!(min >= forced_count, "min should be ≥ forced_count after validation");
 
@@ -89,42 +291,300 @@ pub fn generate_password(
     debug_assert!(length >= min && length <= max, "chosen length should be in [min, max]");
     debug_assert!(forced_count <= length, "forced_count should be ≤ length after validation");
 
-    // Build characters
-    // Policy validation ensures allow is nonempty, so union will be nonempty
-    let union = policy::allowed_alphabet(&policy);
-    debug_assert!(!union.is_empty(), "allowed alphabet should be nonempty after validation");
+    // Build characters; under no_words and no_site the whole draw repeats on the continuing
+    // stream until no dictionary word or site name is left
+    let rejected = |out: &[char]| {
+        (policy.no_words && policy::contains_word(out))
+            || (policy.no_site && !policy::find_site_names(out, &site_id).is_empty())
+    };
+    let mut out = draw_characters(&policy, length, &mut rng)?;
+    let mut attempts = 1;
+    while rejected(&out) {
+        if attempts == MAX_DRAWS {
+            return Err(GenError::InvalidInput("no password without dictionary words or the site name within the draw limit"));
+        }
+        out = draw_characters(&policy, length, &mut rng)?;
+        attempts += 1;
+    }
+
+    debug_assert_eq!(out.len() as u8, length);
 
-    let mut out = Vec::<u8>::with_capacity(length as usize);
+    // Convert to String, return
+    let s: String = out.into_iter().collect();
+// Cleanup notes:
+// - The derived KDF key is zeroized immediately after constructing the PRNG.
+// - The generated password bytes are moved into the returned String; we cannot
+//   zeroize them here because the caller must receive/hold the password.
+// - Context/policy metadata (info, allowed alphabets, site_id, etc.) are not
+//   cryptographic secrets; they are dropped normally (see below for optional
+//   zeroization if we want to treat them as sensitive metadata).
+    Ok(s)
+}
 
-    // Forced picks: fixed order lower -> upper -> digit -> symbol
-    for (_set, alphabet) in forced_sets.drain(..) {
+/// Draws of a whole password under `no_words` and `no_site` before giving up; a policy this
+/// rarely avoids them is better served by a pattern or passphrase.
+const MAX_DRAWS: u32 = 64;
+
+/// Draws the characters of a password of the given length: letters for the ends, forced
+/// picks, the fill and the shuffle, then the run and sequence repair.
+fn draw_characters(policy: &policy::Policy, length: u8, rng: &mut prng::Stream) -> Result<Vec<char>, GenError> {
+    let mut forced_sets = policy::forced_sets(policy);
+    let extended = policy::allowed_extended(policy);
+    let classes = policy::allowed_classes(policy);
+    let letter_positions = u8::from(policy.letter_first) + u8::from(policy.letter_last);
+
+    // Build characters (ASCII sets are widened to char, so their outputs are unchanged)
+    let mut out = Vec::<char>::with_capacity(length as usize);
+    let sets = policy::allowed_sets(policy);
+    let fixed: Vec<u8> = sets.iter().flat_map(|(_, alphabet)| alphabet.iter().copied()).collect();
+    let extras = policy::class_extras(policy, &fixed);
+    let mut counts = policy.min_counts;
+
+    // Letter picks for the first and last position, from the letters of sets still below
+    // their max count once the forced picks are made; they stay out of the shuffle.
+    // Policy validation ensures there are such letters
+    let mut ends: [Option<char>; 2] = [None, None];
+    for (end, wanted) in ends.iter_mut().zip([policy.letter_first, policy.letter_last]) {
+        if !wanted {
+            continue;
+        }
+        let letters: Vec<char> = open_alphabet(&sets, &extended, &policy.max_counts, &counts)
+            .into_iter()
+            .chain(extras.iter().map(|&b| b as char))
+            .filter(|c| c.is_alphabetic())
+            .collect();
+        debug_assert!(!letters.is_empty(), "letter positions need letters after validation");
+        let c = letters[alphabet_index(rng, letters.len())?];
+        if let Some(set) = u8::try_from(c).ok().and_then(policy::charset_of) {
+            counts[set.index()] += 1;
+        }
+        *end = Some(c);
+    }
+
+    // Forced picks: fixed order lower -> upper -> digit -> symbol, min_counts[set] from each
+    for (set, alphabet) in forced_sets.drain(..) {
+        for _ in 0..policy.min_counts[set.index()] {
+            let idx = rng.next_index(alphabet.len())?;
+            out.push(alphabet[idx] as char);
+        }
+    }
+    // Then one from each forced extended set, in fixed order
+    for (_, alphabet) in extended.iter().filter(|(set, _)| policy.force_extended[set.index()]) {
         let idx = rng.next_index(alphabet.len())?;
         out.push(alphabet[idx]);
     }
+    // Then one from each forced named class, in name order
+    for (_, alphabet) in classes.iter().filter(|(class, _)| class.force) {
+        let idx = rng.next_index(alphabet.len())?;
+        out.push(alphabet[idx] as char);
+    }
 
-    // Fill remaining with union
-    let remaining = length as usize - out.len();
+    // Fill remaining from the union of sets still below their max count, followed by the
+    // extended sets and the characters named classes add. Without caps this is the whole
+    // allowed alphabet; it is only rebuilt when a set reaches its cap (validation keeps caps
+    // and classes apart).
+    // Policy validation ensures the caps leave room for max length, so union stays nonempty
+    let mut union = open_alphabet(&sets, &extended, &policy.max_counts, &counts);
+    union.extend(extras.iter().map(|&b| b as char));
+    let remaining = length as usize - letter_positions as usize - out.len();
     for _ in 0..remaining {
-        let idx = rng.next_index(union.len())?;
-        out.push(union[idx]);
+        debug_assert!(!union.is_empty(), "open alphabet should be nonempty after validation");
+        let idx = alphabet_index(rng, union.len())?;
+        let c = union[idx];
+        out.push(c);
+        if let Some(set) = u8::try_from(c).ok().and_then(policy::charset_of) {
+            let i = set.index();
+            counts[i] += 1;
+            if policy.max_counts[i] == Some(counts[i]) {
+                union = open_alphabet(&sets, &extended, &policy.max_counts, &counts);
+            }
+        }
     }
 
-    // Deterministic Fisher–Yates shuffle
+    // Deterministic Fisher–Yates shuffle, then the letters go to their ends
     for i in (1..out.len()).rev() {
         let j = rng.next_index(i + 1)?;
         out.swap(i, j);
     }
+    if let Some(c) = ends[0] {
+        out.insert(0, c);
+    }
+    if let Some(c) = ends[1] {
+        out.push(c);
+    }
 
-    debug_assert_eq!(out.len() as u8, length);
+    // Repair runs longer than max_consecutive and sequences longer than max_sequential, left
+    // to right: redraw the offending character from its own set (keeping per-set counts),
+    // extended set, or the first named class holding it, so that it differs from its
+    // predecessor (and, under max_sequential, does not continue a sequence from it, and stays
+    // a letter at the last position under letter_last).
+    // Policy validation ensures every allowed set and class has an alternative character
+    if policy.max_consecutive.is_some() || policy.max_sequential.is_some() {
+        let run_limit = policy.max_consecutive.map_or(usize::MAX, usize::from);
+        let sequence_limit = policy.max_sequential.map_or(usize::MAX, usize::from);
+        let mut run = 1;
+        let mut sequence = (0, 1); // (step, length)
+        for i in 1..out.len() {
+            run = if out[i] == out[i - 1] { run + 1 } else { 1 };
+            sequence = match policy::sequence_step(out[i - 1], out[i]) {
+                Some(step) if step == sequence.0 => (step, sequence.1 + 1),
+                Some(step) => (step, 2),
+                None => (0, 1),
+            };
+            if run <= run_limit && sequence.1 <= sequence_limit {
+                continue;
+            }
+            let ascii = sets
+                .iter()
+                .map(|(_, a)| a)
+                .chain(classes.iter().map(|(_, a)| a))
+                .find(|a| u8::try_from(out[i]).is_ok_and(|b| a.contains(&b)))
+                .map(|a| a.iter().map(|&b| b as char).collect::<Vec<char>>());
+            let letter_only = policy.letter_last && i == out.len() - 1;
+            let prev = out[i - 1];
+            let allowed = |c: char| {
+                c != prev
+                    && (policy.max_sequential.is_none() || policy::sequence_step(prev, c).is_none())
+                    && (!letter_only || c.is_alphabetic())
+            };
+            let alphabet: Vec<char> = ascii
+                .or_else(|| extended.iter().find(|(_, a)| a.contains(&out[i])).map(|(_, a)| a.to_vec()))
+                .map(|a| a.into_iter().filter(|&c| allowed(c)).collect())
+                .unwrap_or_default();
+            debug_assert!(!alphabet.is_empty(), "run repair needs an alternative character");
+            let idx = alphabet_index(rng, alphabet.len())?;
+            out[i] = alphabet[idx];
+            run = 1;
+            sequence = (0, 1);
+        }
+    }
 
-    // Convert to String (ASCII), return
-    let s = String::from_utf8(out).expect("output must be valid ASCII");
-// Cleanup notes:
-// - The derived KDF key is zeroized immediately after constructing the PRNG.
-// - The generated password bytes are moved into the returned String; we cannot
-//   zeroize them here because the caller must receive/hold the password.
-// - Context/policy metadata (info, allowed alphabets, site_id, etc.) are not
-//   cryptographic secrets; they are dropped normally (see below for optional
-//   zeroization if we want to treat them as sensitive metadata).
-    Ok(s)
+    Ok(out)
+}
+
+/// Draws an index into an alphabet of `n` characters: one byte per draw up to 256 (every
+/// ASCII policy, so their outputs are unchanged), four bytes per draw beyond, where extended
+/// sets take the alphabet past one byte.
+fn alphabet_index(rng: &mut prng::Stream, n: usize) -> Result<usize, prng::PrngError> {
+    match u32::try_from(n) {
+        Ok(n) if n > 256 => Ok(rng.next_index_u32(n)? as usize),
+        _ => rng.next_index(n),
+    }
+}
+
+/// Concatenates the alphabets of sets whose count is still below their max count (fixed set
+/// order), then the extended sets, which are never capped.
+fn open_alphabet(
+    sets: &[(policy::Charset, Vec<u8>)],
+    extended: &[(policy::Extended, &[char])],
+    max_counts: &[Option<u8>; 4],
+    counts: &[u8; 4],
+) -> Vec<char> {
+    let mut out = Vec::new();
+    for (set, alphabet) in sets {
+        let i = set.index();
+        if max_counts[i].is_none_or(|cap| counts[i] < cap) {
+            out.extend(alphabet.iter().map(|&b| b as char));
+        }
+    }
+    for (_, alphabet) in extended {
+        out.extend_from_slice(alphabet);
+    }
+    out
+}
+
+/// Generates a deterministic password from a template pattern (see `pattern::parse`).
+///
+/// Each placeholder draws one character from its class; literals are copied as-is. The
+/// pattern string itself is bound into the derivation context in place of the policy.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn generate_from_pattern<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    pattern_in: &str,
+    version: u32,
+) -> Result<String, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    // Parse before running the KDF so bad patterns fail fast
+    let tokens = pattern::parse(pattern_in)?;
+
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"pattern", pattern_in.as_bytes(), version)?;
+
+    draw_tokens(&mut rng, &tokens)
+}
+
+/// Draws one character per placeholder token, in order, and copies literals.
+pub(crate) fn draw_tokens(rng: &mut prng::Stream, tokens: &[pattern::Token]) -> Result<String, GenError> {
+    let mut out = Vec::<u8>::with_capacity(tokens.len());
+    for token in tokens {
+        match *token {
+            pattern::Token::Class(alphabet) => {
+                let idx = rng.next_index(alphabet.len())?;
+                out.push(alphabet[idx]);
+            }
+            pattern::Token::Literal(b) => out.push(b),
+        }
+    }
+
+    Ok(String::from_utf8(out).expect("output must be valid ASCII"))
+}
+
+/// Generates a deterministic token of `bytes` random bytes, encoded in `format`.
+///
+/// Tokens ignore charset policies; the format and byte length are bound into the derivation
+/// context (`format=<format>;bytes=<n>`), so each format yields an independent value.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn generate_token<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    format_in: format::Format,
+    bytes: usize,
+    version: u32,
+) -> Result<String, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    format::check_length(bytes)?;
+
+    let params = format::params(format_in, bytes);
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"format", params.as_bytes(), version)?;
+
+    let mut raw = Zeroizing::new(vec![0u8; bytes]);
+    rng.fill(&mut raw)?;
+    Ok(format_in.encode(&raw))
+}
+
+/// Generates `len` bytes of deterministic key material (encryption keys, HMAC keys).
+///
+/// The bytes are not encoded; the length is bound into the derivation context
+/// (`bytes=<len>`), so different lengths yield independent values. The result is
+/// zeroized on drop.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub fn generate_bytes<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    len: usize,
+    version: u32,
+) -> Result<Zeroizing<Vec<u8>>, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    if !(1..=format::MAX_TOKEN_BYTES).contains(&len) {
+        return Err(GenError::InvalidInput("length must be within [1,1024] bytes"));
+    }
+
+    let len_str = itoa::Buffer::new().format(len).to_string();
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"bytes", len_str.as_bytes(), version)?;
+
+    let mut out = Zeroizing::new(vec![0u8; len]);
+    rng.fill(&mut out)?;
+    Ok(out)
 }
@@ -1,23 +1,732 @@
-use argon2::{Algorithm, Argon2, Params, Version};
+use alloc::{format, string::{String, ToString}};
+#[cfg(feature = "kdf")]
+use argon2::{Algorithm, Argon2, AssociatedData, Params, ParamsBuilder, Version};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 use zeroize::Zeroize;
+#[cfg(feature = "std")]
+use zeroize::Zeroizing;
+
+use crate::generator::AlgoVersion;
+#[cfg(feature = "std")]
+use crate::generator::Timings;
+use crate::memlock::Locked;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 pub const KDF_OUT_LEN: usize = 32;
 
+/// PBKDF2-HMAC-SHA256 iteration count (OWASP's recommendation for SHA-256).
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Length of a keyfile or pepper digest (SHA-256).
+pub const KEYFILE_DIGEST_LEN: usize = 32;
+
+// Tags before each factor digest in the KDF input of v2 and later (see `MasterInput::kdf_input`)
+const KEYFILE_TAG: u8 = 0x01;
+const PEPPER_TAG: u8 = 0x02;
+const YUBIKEY_TAG: u8 = 0x03;
+const SCOPE_TAG: u8 = 0x04;
+const PATH_TAG: u8 = 0x05;
+
+/// Largest Argon2 working memory, in KiB, kept for reuse between derivations (the paranoid
+/// profile's); larger buffers are freed after each derivation.
+pub const MAX_RETAINED_MEM_KIB: u32 = 262_144;
+
+/// Most Argon2 memory, in KiB, that long-running processes (the agent, `pwgen serve`) accept
+/// for the derivations they run on request: 4 GiB.
+pub const MAX_SERVED_MEM_KIB: u32 = 4 * 1024 * 1024;
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// Argon2 working memory of this thread's derivations, allocated at the largest `mem_kib`
+    /// used so far (up to `MAX_RETAINED_MEM_KIB`) and reused, so batch modes (export, serve,
+    /// the agent) do not allocate and fault in the whole block array for every site. It is
+    /// wiped after each derivation, and locked into memory with the `mlock` feature.
+    static ARGON2_MEMORY: std::cell::RefCell<Vec<argon2::Block>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
 /// Errors that can occur during key derivation
 #[derive(Error, Debug)]
 pub enum KdfError {
     #[error("invalid KDF parameters: {0}")]
     InvalidParams(String),
 
+    #[cfg(feature = "kdf")]
     #[error("argon2 error: {0:?}")]
     Argon2(argon2::Error),
+
+    /// Failure reported by a custom `Kdf` implementation
+    #[error("kdf backend error: {0}")]
+    Backend(String),
+}
+
+/// Stretches the master secret into a site key.
+///
+/// `KdfScheme` and `KdfParams` (Argon2id) implement this; library users can implement it
+/// to derive keys elsewhere (an HSM, a remote service) or to stub the KDF in tests, and
+/// pass it to the generators through `MasterInput`.
+pub trait Kdf {
+    /// Derives the 32-byte key for `site` (already trimmed and lowercased by the generators).
+    ///
+    /// `master` is the master password's UTF-8 bytes, followed by any second-factor input
+    /// (see `MasterInput::kdf_input`).
+    fn derive(&self, master: &[u8], site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError>;
+
+    /// Like `derive`, also passing Argon2's secret and associated data.
+    ///
+    /// The default implementation is for KDFs without such inputs: it rejects nonempty
+    /// extras and otherwise calls `derive`.
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        if !extras.is_empty() {
+            return Err(unsupported_extras());
+        }
+        self.derive(master, site)
+    }
+
+    /// Like `derive_with_extras`, returning the key in a locked buffer (see `memlock`).
+    ///
+    /// The default implementation moves the key returned by `derive_with_extras` there and
+    /// wipes the original; the built-in KDFs derive into the locked buffer directly.
+    fn derive_locked(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<Locked<[u8; KDF_OUT_LEN]>, KdfError> {
+        let mut key = self.derive_with_extras(master, site, extras)?;
+        let locked = Locked::new(alloc::boxed::Box::new(key));
+        key.zeroize();
+        Ok(locked)
+    }
+
+    /// Canonical name and parameters, bound into the algorithm v2 context so that
+    /// different KDFs never share a PRNG stream. Custom implementations should override it.
+    fn context_id(&self) -> String {
+        "custom".to_string()
+    }
+}
+
+impl<K: Kdf + ?Sized> Kdf for &K {
+    fn derive(&self, master: &[u8], site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        (**self).derive(master, site)
+    }
+
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        (**self).derive_with_extras(master, site, extras)
+    }
+
+    fn derive_locked(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<Locked<[u8; KDF_OUT_LEN]>, KdfError> {
+        (**self).derive_locked(master, site, extras)
+    }
+
+    fn context_id(&self) -> String {
+        (**self).context_id()
+    }
+}
+
+/// Serializes as the error message; `argon2::Error` has no serde support of its own.
+#[cfg(feature = "serde")]
+impl serde::Serialize for KdfError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Largest Argon2 associated data, in bytes.
+#[cfg(feature = "kdf")]
+pub const MAX_AD_LEN: usize = Params::MAX_DATA_LEN;
+
+/// Optional Argon2 inputs besides the password and salt; both are empty in the v1 scheme.
+///
+/// `secret` is Argon2's key input K, a pepper kept out of the password buffer; `ad` is its
+/// associated data X, binding keys to a context string (at most `MAX_AD_LEN` bytes).
+/// Only Argon2id accepts them.
+#[derive(Copy, Clone, Default)]
+pub struct KdfExtras<'a> {
+    pub secret: Option<&'a [u8]>,
+    pub ad: Option<&'a [u8]>,
+}
+
+impl KdfExtras<'_> {
+    /// True if neither input is set (or both are empty).
+    pub fn is_empty(&self) -> bool {
+        self.secret.is_none_or(|s| s.is_empty()) && self.ad.is_none_or(|a| a.is_empty())
+    }
+}
+
+fn unsupported_extras() -> KdfError {
+    KdfError::InvalidParams("only Argon2id takes a secret or associated data".to_string())
+}
+
+/// Argon2id cost parameters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    any(feature = "serde", feature = "config"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub mem_kib: u32,
+    /// Iterations
+    pub t_cost: u32,
+    /// Parallelism (lanes)
+    pub p_cost: u32,
+}
+
+/// Named, vetted Argon2id parameter sets.
+///
+/// Every profile derives different keys, so the same profile must be used on every device.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    any(feature = "serde", feature = "config"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum KdfProfile {
+    /// 19 MiB, 2 iterations (OWASP minimum), for slow or memory-constrained machines
+    Fast,
+    /// 64 MiB, 3 iterations (the v1 scheme)
+    #[default]
+    Balanced,
+    /// 256 MiB, 4 iterations
+    Paranoid,
+}
+
+impl KdfProfile {
+    pub const ALL: [KdfProfile; 3] = [KdfProfile::Fast, KdfProfile::Balanced, KdfProfile::Paranoid];
+
+    /// Name used on the command line, in the config file and in `--json` output.
+    pub fn name(self) -> &'static str {
+        match self {
+            KdfProfile::Fast => "fast",
+            KdfProfile::Balanced => "balanced",
+            KdfProfile::Paranoid => "paranoid",
+        }
+    }
+
+    pub fn params(self) -> KdfParams {
+        match self {
+            KdfProfile::Fast => KdfParams { mem_kib: 19_456, t_cost: 2, p_cost: 1 },
+            KdfProfile::Balanced => KdfParams { mem_kib: 65_536, t_cost: 3, p_cost: 1 },
+            KdfProfile::Paranoid => KdfParams { mem_kib: 262_144, t_cost: 4, p_cost: 1 },
+        }
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfProfile::Balanced.params()
+    }
+}
+
+impl KdfParams {
+    /// Returns the named profile with exactly these parameters, if any.
+    pub fn profile(&self) -> Option<KdfProfile> {
+        KdfProfile::ALL.into_iter().find(|p| p.params() == *self)
+    }
+
+    /// Checks the memory cost against `MAX_SERVED_MEM_KIB`, for parameters that arrive in
+    /// requests to a long-running process.
+    pub fn check_served(&self) -> Result<(), KdfError> {
+        if self.mem_kib > MAX_SERVED_MEM_KIB {
+            return Err(KdfError::InvalidParams(format!(
+                "memory cost {} KiB exceeds the {} KiB served limit",
+                self.mem_kib, MAX_SERVED_MEM_KIB
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks the parameters against Argon2's limits.
+    #[cfg(feature = "kdf")]
+    pub fn validate(&self) -> Result<(), KdfError> {
+        Params::new(self.mem_kib, self.t_cost, self.p_cost, Some(KDF_OUT_LEN))
+            .map(|_| ())
+            .map_err(|e| KdfError::InvalidParams(e.to_string()))
+    }
+}
+
+impl core::fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "m={},t={},p={}", self.mem_kib, self.t_cost, self.p_cost)
+    }
+}
+
+/// Smallest memory cost `calibrate` will choose (the `fast` profile's).
+#[cfg(feature = "std")]
+pub const CALIBRATE_MIN_MEM_KIB: u32 = 19_456;
+
+/// Finds the Argon2id parameters whose derivation time on this machine is closest to
+/// `target`, using 3 iterations and 1 lane and scaling memory (up to `max_mem_kib`).
+///
+/// Returns the parameters and the measured time of one derivation with them.
+#[cfg(feature = "std")]
+pub fn calibrate(target: Duration, max_mem_kib: u32) -> Result<(KdfParams, Duration), KdfError> {
+    const T_COST: u32 = 3;
+    let max_mem_kib = max_mem_kib.max(CALIBRATE_MIN_MEM_KIB);
+
+    // Double memory until a derivation takes at least half the target (or hits the cap)
+    let mut mem_kib = CALIBRATE_MIN_MEM_KIB;
+    let mut elapsed = time_derivation(&KdfParams { mem_kib, t_cost: T_COST, p_cost: 1 })?;
+    while elapsed * 2 < target && mem_kib < max_mem_kib {
+        mem_kib = mem_kib.saturating_mul(2).min(max_mem_kib);
+        elapsed = time_derivation(&KdfParams { mem_kib, t_cost: T_COST, p_cost: 1 })?;
+    }
+
+    // Argon2 time is roughly linear in memory: scale to the target, rounded down to whole MiB
+    let scaled = (mem_kib as f64 * target.as_secs_f64() / elapsed.as_secs_f64().max(1e-6)) as u64;
+    let mem_kib = ((scaled / 1024 * 1024) as u32).clamp(CALIBRATE_MIN_MEM_KIB, max_mem_kib);
+    let params = KdfParams { mem_kib, t_cost: T_COST, p_cost: 1 };
+    let elapsed = time_derivation(&params)?;
+    Ok((params, elapsed))
+}
+
+#[cfg(feature = "std")]
+fn time_derivation(params: &KdfParams) -> Result<Duration, KdfError> {
+    let start = Instant::now();
+    let mut key = derive_site_key_with("pwgen-calibrate", "calibrate.invalid", params)?;
+    let elapsed = start.elapsed();
+    key.zeroize();
+    Ok(elapsed)
+}
+
+/// Function used to stretch the master secret into a site key.
+///
+/// The schemes use separate salt domains, so they can never yield the same key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KdfScheme {
+    /// Argon2id with the given costs (the default)
+    Argon2id(KdfParams),
+    /// PBKDF2-HMAC-SHA256, for devices that cannot spare Argon2's memory. Much weaker
+    /// against GPU guessing; only use it where Argon2 is infeasible.
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl Default for KdfScheme {
+    fn default() -> Self {
+        KdfScheme::Argon2id(KdfParams::default())
+    }
+}
+
+impl From<KdfParams> for KdfScheme {
+    fn from(params: KdfParams) -> Self {
+        KdfScheme::Argon2id(params)
+    }
+}
+
+impl KdfScheme {
+    /// PBKDF2-HMAC-SHA256 with `PBKDF2_ITERATIONS`.
+    pub fn pbkdf2() -> Self {
+        KdfScheme::Pbkdf2Sha256 { iterations: PBKDF2_ITERATIONS }
+    }
+
+    /// Name recorded in `--json` output: the Argon2 profile name (or `m=..,t=..,p=..` for
+    /// custom costs), or `pbkdf2-sha256-v1;i=<iterations>`.
+    pub fn label(&self) -> String {
+        match self {
+            KdfScheme::Argon2id(params) => match params.profile() {
+                Some(profile) => profile.name().to_string(),
+                None => params.to_string(),
+            },
+            KdfScheme::Pbkdf2Sha256 { iterations } => format!("pbkdf2-sha256-v1;i={}", iterations),
+        }
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl Kdf for KdfScheme {
+    fn derive(&self, master: &[u8], site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        self.derive_with_extras(master, site, &KdfExtras::default())
+    }
+
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        match self {
+            KdfScheme::Argon2id(params) => derive_site_key_extras(master, site, params, extras),
+            KdfScheme::Pbkdf2Sha256 { .. } if !extras.is_empty() => Err(unsupported_extras()),
+            KdfScheme::Pbkdf2Sha256 { iterations } => derive_site_key_pbkdf2(master, site, *iterations),
+        }
+    }
+
+    fn derive_locked(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<Locked<[u8; KDF_OUT_LEN]>, KdfError> {
+        match self {
+            KdfScheme::Argon2id(params) => derive_site_key_locked(master, site, params, extras),
+            KdfScheme::Pbkdf2Sha256 { .. } if !extras.is_empty() => Err(unsupported_extras()),
+            KdfScheme::Pbkdf2Sha256 { iterations } => {
+                let mut key: Locked<[u8; KDF_OUT_LEN]> = Locked::new(Default::default());
+                derive_pbkdf2_into(master, site, *iterations, &mut key)?;
+                Ok(key)
+            }
+        }
+    }
+
+    /// `argon2id;m=<kib>,t=<t>,p=<p>` or `pbkdf2-sha256;i=<iterations>`.
+    fn context_id(&self) -> String {
+        match self {
+            KdfScheme::Argon2id(params) => params.context_id(),
+            KdfScheme::Pbkdf2Sha256 { iterations } => format!("pbkdf2-sha256;i={}", iterations),
+        }
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl Kdf for KdfParams {
+    fn derive(&self, master: &[u8], site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        derive_site_key_with(master, site, self)
+    }
+
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        derive_site_key_extras(master, site, self, extras)
+    }
+
+    fn derive_locked(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &KdfExtras<'_>,
+    ) -> Result<Locked<[u8; KDF_OUT_LEN]>, KdfError> {
+        derive_site_key_locked(master, site, self, extras)
+    }
+
+    fn context_id(&self) -> String {
+        format!("argon2id;{}", self)
+    }
+}
+
+/// Master secret together with the KDF it is stretched with and the algorithm version.
+///
+/// Generators accept anything convertible into this; a plain `&str` or `&MasterSecret` uses
+/// Argon2id with the default (balanced) parameters and algorithm v1.
+#[derive(Copy, Clone)]
+pub struct MasterInput<'a, K = KdfScheme> {
+    pub password: &'a str,
+    /// Digest of a keyfile used as a second factor (see `keyfile_digest`)
+    pub keyfile_digest: Option<[u8; KEYFILE_DIGEST_LEN]>,
+    /// Digest of a machine- or vault-stored pepper (see `pepper_digest`)
+    pub pepper_digest: Option<[u8; KEYFILE_DIGEST_LEN]>,
+    /// Digest of the site's YubiKey response (see `yubikey::response_digest`)
+    pub yubikey_digest: Option<[u8; KEYFILE_DIGEST_LEN]>,
+    /// Digest of the scope derived in (see `scope_digest`)
+    pub scope_digest: Option<[u8; KEYFILE_DIGEST_LEN]>,
+    /// Digest of a derivation path's organization (see `path_digest`)
+    pub path_digest: Option<[u8; KEYFILE_DIGEST_LEN]>,
+    /// Argon2 secret and associated data
+    pub extras: KdfExtras<'a>,
+    pub kdf: K,
+    pub algo: AlgoVersion,
+    /// Where the time spent in each stage is recorded, if anywhere
+    #[cfg(feature = "std")]
+    pub timings: Option<&'a Timings>,
+}
+
+impl<'a, K: Kdf> MasterInput<'a, K> {
+    /// `kdf` may be a `KdfScheme`, Argon2id `KdfParams`, or any other `Kdf` (by value or
+    /// reference).
+    pub fn new(password: &'a str, kdf: K) -> Self {
+        MasterInput {
+            password,
+            keyfile_digest: None,
+            pepper_digest: None,
+            yubikey_digest: None,
+            scope_digest: None,
+            path_digest: None,
+            extras: KdfExtras::default(),
+            kdf,
+            algo: AlgoVersion::default(),
+            #[cfg(feature = "std")]
+            timings: None,
+        }
+    }
+
+    /// Requires a keyfile as well as the password; `digest` comes from `keyfile_digest`.
+    pub fn with_keyfile(mut self, digest: [u8; KEYFILE_DIGEST_LEN]) -> Self {
+        self.keyfile_digest = Some(digest);
+        self
+    }
+
+    /// Binds derivations to a pepper as well as the password; `digest` comes from
+    /// `pepper_digest`.
+    pub fn with_pepper(mut self, digest: [u8; KEYFILE_DIGEST_LEN]) -> Self {
+        self.pepper_digest = Some(digest);
+        self
+    }
+
+    /// Binds derivations to a YubiKey; `digest` is the `yubikey::response_digest` of the
+    /// key's response to the site's `yubikey::challenge`.
+    ///
+    /// The response is specific to one site, so this input must not be reused across sites.
+    pub fn with_yubikey(mut self, digest: [u8; KEYFILE_DIGEST_LEN]) -> Self {
+        self.yubikey_digest = Some(digest);
+        self
+    }
+
+    /// Derives in a scope (`personal`, `work`), whose outputs are independent of every other
+    /// scope's and of unscoped ones; `digest` comes from `scope_digest`.
+    pub fn with_scope(mut self, digest: [u8; KEYFILE_DIGEST_LEN]) -> Self {
+        self.scope_digest = Some(digest);
+        self
+    }
+
+    /// Derives under a derivation path (`generate --path`), so its outputs differ from those
+    /// of the flat site, username and version; `digest` comes from `path_digest`.
+    pub fn with_path(mut self, digest: [u8; KEYFILE_DIGEST_LEN]) -> Self {
+        self.path_digest = Some(digest);
+        self
+    }
+
+    /// Passes `secret` to Argon2 as its key input (K).
+    pub fn with_argon2_secret(mut self, secret: &'a [u8]) -> Self {
+        self.extras.secret = Some(secret);
+        self
+    }
+
+    /// Passes `ad` to Argon2 as associated data (X); at most `MAX_AD_LEN` bytes.
+    pub fn with_argon2_ad(mut self, ad: &'a [u8]) -> Self {
+        self.extras.ad = Some(ad);
+        self
+    }
+
+    /// Bytes passed to the KDF: the password, followed by the keyfile, pepper, YubiKey, scope
+    /// and path digests, each if present.
+    ///
+    /// Layout: `password || [tag] keyfile || [tag] pepper || [tag] yubikey || [tag] scope ||
+    /// [tag] path`, where v2 and later put a one-byte tag before each digest (keyfile `0x01`,
+    /// pepper `0x02`, YubiKey `0x03`, scope `0x04`, path `0x05`), so which factor a digest
+    /// stands for does not depend on its offset. v1 appends the bare digests and is unchanged.
+    /// Without any factor this is just the password in every version.
+    ///
+    /// The bytes are assembled in a locked buffer (see `memlock`), so no unlocked copy of the
+    /// password is made.
+    pub fn kdf_input(&self) -> Locked<[u8]> {
+        let tagged = self.algo != AlgoVersion::V1;
+        let digests = [
+            (KEYFILE_TAG, &self.keyfile_digest),
+            (PEPPER_TAG, &self.pepper_digest),
+            (YUBIKEY_TAG, &self.yubikey_digest),
+            (SCOPE_TAG, &self.scope_digest),
+            (PATH_TAG, &self.path_digest),
+        ];
+        let factors = digests.iter().filter(|(_, digest)| digest.is_some()).count();
+        let len = self.password.len() + factors * (KEYFILE_DIGEST_LEN + usize::from(tagged));
+        let mut input = Locked::new(alloc::vec![0u8; len].into_boxed_slice());
+        let mut at = 0;
+        let mut put = |bytes: &[u8]| {
+            input[at..at + bytes.len()].copy_from_slice(bytes);
+            at += bytes.len();
+        };
+        put(self.password.as_bytes());
+        for (tag, digest) in digests {
+            let Some(digest) = digest else { continue };
+            if tagged {
+                put(&[tag]);
+            }
+            put(digest);
+        }
+        input
+    }
+
+    /// Selects the algorithm version (v1 unless set).
+    pub fn with_algo(mut self, algo: AlgoVersion) -> Self {
+        self.algo = algo;
+        self
+    }
+
+    /// Adds the time spent in the KDF and HKDF setup of each derivation to `timings`.
+    #[cfg(feature = "std")]
+    pub fn with_timings(mut self, timings: &'a Timings) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl<'a> From<&'a str> for MasterInput<'a> {
+    fn from(password: &'a str) -> Self {
+        MasterInput::new(password, KdfScheme::default())
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl<'a> From<&'a String> for MasterInput<'a> {
+    fn from(password: &'a String) -> Self {
+        MasterInput::new(password, KdfScheme::default())
+    }
+}
+
+#[cfg(feature = "kdf")]
+impl<'a> From<&'a MasterSecret> for MasterInput<'a> {
+    fn from(master: &'a MasterSecret) -> Self {
+        MasterInput::new(master.as_str(), KdfScheme::default())
+    }
+}
+
+/// The master password, zeroized when dropped and redacted from `Debug` output, so it is
+/// wiped on every path out of a function and cannot end up in a log.
+#[derive(Clone, Default)]
+pub struct MasterSecret(String);
+
+impl MasterSecret {
+    /// Takes ownership of `password` as typed (e.g. at a prompt), without trimming it.
+    pub fn new(password: String) -> Self {
+        MasterSecret(password)
+    }
+
+    /// Reads a file, stdin or descriptor to EOF as the master. Trailing line endings
+    /// (including CRLF) are dropped, the secret is otherwise kept as provided; the raw bytes
+    /// are zeroized even when they are not valid UTF-8.
+    #[cfg(feature = "std")]
+    pub fn from_reader(mut reader: impl std::io::Read) -> std::io::Result<Self> {
+        let mut buf = Zeroizing::new(Vec::new());
+        reader.read_to_end(&mut buf)?;
+        let text = core::str::from_utf8(&buf)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "master secret is not valid UTF-8"))?;
+        let end = if text.ends_with('\n') { text.trim_end_matches(['\n', '\r']).len() } else { text.len() };
+        Ok(MasterSecret(text[..end].to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for MasterSecret {
+    fn from(password: String) -> Self {
+        MasterSecret::new(password)
+    }
+}
+
+impl From<&str> for MasterSecret {
+    fn from(password: &str) -> Self {
+        MasterSecret::new(password.to_string())
+    }
+}
+
+impl core::fmt::Debug for MasterSecret {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MasterSecret(<redacted>)")
+    }
+}
+
+impl Drop for MasterSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Hashes keyfile contents for `MasterInput::with_keyfile`:
+/// `SHA256(b"pwgen-keyfile-v1:" || contents)`.
+///
+/// Any file works (random bytes, a photo); it must be kept byte-for-byte identical.
+pub fn keyfile_digest(contents: &[u8]) -> [u8; KEYFILE_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-keyfile-v1:");
+    hasher.update(contents);
+    hasher.finalize().into()
+}
+
+/// Hashes a pepper for `MasterInput::with_pepper`: `SHA256(b"pwgen-pepper-v1:" || pepper)`.
+pub fn pepper_digest(pepper: &[u8]) -> [u8; KEYFILE_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-pepper-v1:");
+    hasher.update(pepper);
+    hasher.finalize().into()
+}
+
+/// Hashes a scope name for `MasterInput::with_scope`, trimmed and lowercased:
+/// `SHA256(b"pwgen-scope-v1:" || scope)`.
+pub fn scope_digest(scope: &str) -> [u8; KEYFILE_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-scope-v1:");
+    hasher.update(scope.trim().to_lowercase().as_bytes());
+    hasher.finalize().into()
+}
+
+/// Hashes a derivation path's organization for `MasterInput::with_path`, trimmed and
+/// lowercased: `SHA256(b"pwgen-path-v1:" || org)`.
+pub fn path_digest(org: &str) -> [u8; KEYFILE_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-path-v1:");
+    hasher.update(org.trim().to_lowercase().as_bytes());
+    hasher.finalize().into()
 }
 
 /// Lowercases + trims site before salt.
 /// Returns 32-byte key. Zeroizes internals where possible.
+#[cfg(feature = "kdf")]
 pub fn derive_site_key(master: &str, site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+    derive_site_key_with(master, site, &KdfParams::default())
+}
+
+/// Like `derive_site_key`, with explicit Argon2id parameters (see `KdfProfile`).
+#[cfg(feature = "kdf")]
+pub fn derive_site_key_with(
+    master: impl AsRef<[u8]>,
+    site: &str,
+    kdf_params: &KdfParams,
+) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+    derive_site_key_extras(master, site, kdf_params, &KdfExtras::default())
+}
+
+/// Like `derive_site_key_with`, also passing Argon2's secret and associated data.
+///
+/// With empty extras this is exactly `derive_site_key_with`.
+#[cfg(feature = "kdf")]
+pub fn derive_site_key_extras(
+    master: impl AsRef<[u8]>,
+    site: &str,
+    kdf_params: &KdfParams,
+    extras: &KdfExtras<'_>,
+) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+    derive_site_key_locked(master, site, kdf_params, extras).map(|key| *key)
+}
+
+/// Like `derive_site_key_extras`, deriving into a locked buffer (see `memlock`).
+#[cfg(feature = "kdf")]
+pub fn derive_site_key_locked(
+    master: impl AsRef<[u8]>,
+    site: &str,
+    kdf_params: &KdfParams,
+    extras: &KdfExtras<'_>,
+) -> Result<Locked<[u8; KDF_OUT_LEN]>, KdfError> {
     // Normalize site per v0.1
     let site_id = site.trim().to_ascii_lowercase();
 
@@ -30,26 +739,88 @@ pub fn derive_site_key(master: &str, site: &str) -> Result<[u8; KDF_OUT_LEN], Kd
     salt16.copy_from_slice(&digest[..16]);
 
     // Argon2id parameters
-    const MEM_KIB: u32 = 65_536; // 64 MiB
-    const T_COST: u32 = 3;       // iterations
-    const P_COST: u32 = 1;       // parallelism
+    let mut builder = ParamsBuilder::new();
+    builder
+        .m_cost(kdf_params.mem_kib)
+        .t_cost(kdf_params.t_cost)
+        .p_cost(kdf_params.p_cost)
+        .output_len(KDF_OUT_LEN);
+    if let Some(ad) = extras.ad.filter(|a| !a.is_empty()) {
+        let data = AssociatedData::new(ad)
+            .map_err(|_| KdfError::InvalidParams(format!("associated data exceeds {} bytes", MAX_AD_LEN)))?;
+        builder.data(data);
+    }
+    let params = builder.build().map_err(|e| KdfError::InvalidParams(e.to_string()))?;
+    let argon2 = match extras.secret.filter(|s| !s.is_empty()) {
+        Some(secret) => Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, params)
+            .map_err(|e| KdfError::InvalidParams(e.to_string()))?,
+        None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+    };
 
-    let params = Params::new(MEM_KIB, T_COST, P_COST, Some(KDF_OUT_LEN))
-        .map_err(|e| KdfError::InvalidParams(e.to_string()))?;
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    // Copy master into a locked buffer, zeroized when dropped
+    let master_bytes = Locked::from_slice(master.as_ref());
 
-    // Copy master into an owned buffer we can zeroize after use
-    let mut master_bytes = master.as_bytes().to_vec();
-
-    // Derive key
-    let mut out = [0u8; KDF_OUT_LEN];
-    argon2
-        .hash_password_into(&master_bytes, &salt16, &mut out)
-        .map_err(KdfError::Argon2)?;
+    // Derive key in the thread's reused working memory
+    let mut out: Locked<[u8; KDF_OUT_LEN]> = Locked::new(Default::default());
+    #[cfg(feature = "std")]
+    let result = ARGON2_MEMORY.with(|memory| {
+        let mut memory = memory.borrow_mut();
+        let blocks = argon2.params().block_count();
+        if memory.len() < blocks {
+            // Replace rather than grow, so the old contents are not copied
+            crate::memlock::unlock(&memory);
+            *memory = alloc::vec![argon2::Block::default(); blocks];
+            crate::memlock::lock(&memory);
+        }
+        let result = argon2.hash_password_into_with_memory(&master_bytes, &salt16, &mut *out, &mut memory[..blocks]);
+        memory[..blocks].iter_mut().for_each(Zeroize::zeroize);
+        if memory.len() > MAX_RETAINED_MEM_KIB as usize {
+            // One Argon2 block is 1 KiB; do not keep an oversized buffer for the thread's lifetime
+            crate::memlock::unlock(&memory);
+            *memory = Vec::new();
+        }
+        result
+    });
+    // Without std there are no thread-locals; Argon2 allocates (and wipes) its own memory
+    #[cfg(not(feature = "std"))]
+    let result = argon2.hash_password_into(&master_bytes, &salt16, &mut *out);
+    drop(master_bytes);
+    result.map_err(KdfError::Argon2)?;
 
     // Zeroize sensitive intermediates
-    master_bytes.zeroize();
     salt16.zeroize();
 
     Ok(out)
 }
+
+/// PBKDF2-HMAC-SHA256 site key.
+///
+/// Salt: `SHA256(b"pwgen-pbkdf2-salt-v1:" || site_id)[0..16]`, a different domain from
+/// the Argon2id salt so the two schemes cannot be confused.
+#[cfg(feature = "kdf")]
+pub fn derive_site_key_pbkdf2(master: impl AsRef<[u8]>, site: &str, iterations: u32) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+    let mut out = [0u8; KDF_OUT_LEN];
+    derive_pbkdf2_into(master.as_ref(), site, iterations, &mut out)?;
+    Ok(out)
+}
+
+/// Derives the PBKDF2 site key into `out`, so `derive_locked` can pass a locked buffer.
+#[cfg(feature = "kdf")]
+fn derive_pbkdf2_into(master: &[u8], site: &str, iterations: u32, out: &mut [u8; KDF_OUT_LEN]) -> Result<(), KdfError> {
+    if iterations == 0 {
+        return Err(KdfError::InvalidParams("PBKDF2 iterations must be at least 1".to_string()));
+    }
+    let site_id = site.trim().to_ascii_lowercase();
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-pbkdf2-salt-v1:");
+    hasher.update(site_id.as_bytes());
+    let digest = hasher.finalize();
+    let mut salt16 = [0u8; 16];
+    salt16.copy_from_slice(&digest[..16]);
+
+    pbkdf2::pbkdf2_hmac::<Sha256>(master, &salt16, iterations, out);
+    salt16.zeroize();
+
+    Ok(())
+}
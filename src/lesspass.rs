@@ -0,0 +1,117 @@
+//! LessPass compatibility (`generate --compat lesspass`): the passwords LessPass 2 derives, so
+//! its users can move to this CLI without rotating every password.
+//!
+//! Entropy: `PBKDF2-HMAC-SHA256(master, site || login || hex(counter), 100000 iterations, 32
+//! bytes)`, read as a big-endian integer. The password is drawn from it by repeated division:
+//! `length - k` characters from the union of the k enabled sets, then one character of each
+//! set, each inserted at a position drawn the same way. Site and login are used exactly as
+//! typed into LessPass.
+
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// PBKDF2 iterations of LessPass 2.
+pub const ITERATIONS: u32 = 100_000;
+
+/// Shortest password LessPass generates.
+pub const MIN_LENGTH: usize = 5;
+
+/// Longest password LessPass generates.
+pub const MAX_LENGTH: usize = 35;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// LessPass password profile; the default is LessPass's own.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Profile {
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub length: usize,
+    pub counter: u32,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile { lowercase: true, uppercase: true, digits: true, symbols: true, length: 16, counter: 1 }
+    }
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum LessPassError {
+    #[error("LessPass passwords are 5 to 35 characters long")]
+    Length,
+    #[error("LessPass needs at least one character set")]
+    NoCharacterSets,
+}
+
+impl Profile {
+    /// The enabled character sets, in LessPass's order.
+    fn sets(&self) -> Vec<&'static str> {
+        [(self.lowercase, LOWERCASE), (self.uppercase, UPPERCASE), (self.digits, DIGITS), (self.symbols, SYMBOLS)]
+            .into_iter()
+            .filter_map(|(enabled, set)| enabled.then_some(set))
+            .collect()
+    }
+
+    fn check(&self) -> Result<Vec<&'static str>, LessPassError> {
+        let sets = self.sets();
+        if sets.is_empty() {
+            return Err(LessPassError::NoCharacterSets);
+        }
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(LessPassError::Length);
+        }
+        Ok(sets)
+    }
+
+    /// Entropy of the password in bits, counting the characters drawn from all sets and the
+    /// one drawn from each set (not their positions).
+    pub fn entropy_bits(&self) -> Result<f64, LessPassError> {
+        let sets = self.check()?;
+        let all: usize = sets.iter().map(|set| set.len()).sum();
+        let per_set: f64 = sets.iter().map(|set| (set.len() as f64).log2()).sum();
+        Ok((self.length - sets.len()) as f64 * (all as f64).log2() + per_set)
+    }
+}
+
+/// Generates the LessPass password for `site` and `login`.
+pub fn generate(master: &str, site: &str, login: &str, profile: &Profile) -> Result<String, LessPassError> {
+    let sets = profile.check()?;
+
+    let salt = format!("{}{}{:x}", site, login, profile.counter);
+    let mut entropy = Zeroizing::new([0u8; 32]);
+    pbkdf2_hmac::<Sha256>(master.as_bytes(), salt.as_bytes(), ITERATIONS, &mut *entropy);
+
+    let all = sets.concat().into_bytes();
+    let mut password = Zeroizing::new(Vec::with_capacity(profile.length));
+    for _ in 0..profile.length - sets.len() {
+        password.push(all[divmod(&mut *entropy, all.len())]);
+    }
+    let mut one_per_set = Zeroizing::new(Vec::with_capacity(sets.len()));
+    for set in &sets {
+        one_per_set.push(set.as_bytes()[divmod(&mut *entropy, set.len())]);
+    }
+    for &c in one_per_set.iter() {
+        let at = divmod(&mut *entropy, password.len());
+        password.insert(at, c);
+    }
+    Ok(password.iter().map(|&b| char::from(b)).collect())
+}
+
+/// Divides the big-endian integer `n` by `divisor` in place and returns the remainder.
+fn divmod(n: &mut [u8], divisor: usize) -> usize {
+    let mut remainder = 0;
+    for byte in n.iter_mut() {
+        let current = (remainder << 8) | usize::from(*byte);
+        *byte = (current / divisor) as u8;
+        remainder = current % divisor;
+    }
+    remainder
+}
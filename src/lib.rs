@@ -1,4 +1,79 @@
+//! Deterministic password derivation.
+//!
+//! The derivation core (`policy`, `prng`, `generator`, `pattern`, `format`, `kdf` and
+//! `memlock`) is `no_std` with `alloc` when the `std` feature is off, for password devices and
+//! other embedded targets; Argon2id and PBKDF2 are behind the `kdf` feature there. Every other
+//! module needs `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// The UniFFI scaffolding included below has a blank line after a doc comment
+#![cfg_attr(feature = "uniffi", allow(clippy::empty_line_after_doc_comments))]
+
+extern crate alloc;
+
 pub mod policy;
+pub mod memlock;
 pub mod kdf;
 pub mod prng;
 pub mod generator;
+pub mod pattern;
+pub mod format;
+#[cfg(feature = "std")]
+pub mod recovery;
+#[cfg(feature = "std")]
+pub mod wireguard;
+#[cfg(feature = "std")]
+pub mod totp;
+#[cfg(feature = "std")]
+pub mod answer;
+#[cfg(feature = "std")]
+pub mod username;
+#[cfg(feature = "std")]
+pub mod fingerprint;
+#[cfg(feature = "std")]
+pub mod verifier;
+#[cfg(feature = "strength")]
+pub mod strength;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod yubikey;
+#[cfg(feature = "std")]
+pub mod site;
+#[cfg(feature = "std")]
+pub mod profiles;
+#[cfg(feature = "std")]
+pub mod lesspass;
+#[cfg(feature = "std")]
+pub mod spectre;
+#[cfg(feature = "std")]
+pub mod sgp;
+#[cfg(feature = "std")]
+pub mod vectors;
+#[cfg(feature = "std")]
+pub mod selftest;
+#[cfg(feature = "std")]
+pub mod period;
+#[cfg(feature = "std")]
+pub mod derivation_path;
+#[cfg(feature = "std")]
+pub mod shamir;
+#[cfg(feature = "std")]
+pub mod team;
+#[cfg(feature = "std")]
+pub mod backup_sheet;
+#[cfg(feature = "std")]
+pub mod breach;
+#[cfg(feature = "std")]
+pub mod autotype;
+#[cfg(feature = "std")]
+pub mod clipboard;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+
+#[cfg(feature = "uniffi")]
+use mobile::{KdfCosts, Master, PwgenError};
+#[cfg(feature = "uniffi")]
+uniffi::include_scaffolding!("pwgen");
@@ -1,11 +1,30 @@
-use std::io::{self, Read};
+use std::io;
 use std::process;
 
-use anyhow::{anyhow, Context, Result};
-use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
-use zeroize::Zeroize;
+use anyhow::{Context, Result};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use pwgen::generator::{self, GenError};
-use pwgen::policy;
+use pwgen::{answer, autotype, backup_sheet, clipboard, config, derivation_path, fingerprint, format, kdf, pattern, lesspass, period, policy, profiles, recovery, sgp, shamir, site, spectre, team, totp, username, verifier, wireguard, yubikey};
+#[cfg(feature = "strength")]
+use pwgen::strength;
+#[cfg(feature = "network")]
+use pwgen::breach;
+
+mod agent;
+#[cfg(feature = "tty")]
+mod prompt;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+mod dbus;
+#[cfg(feature = "serve")]
+mod native_host;
+mod pinentry;
+mod export;
+mod output;
+mod completions;
+#[cfg(feature = "vectors")]
+mod vector_file;
 
 /// CLI for deterministic password generator.
 #[derive(Debug, Parser)]
@@ -20,13 +39,86 @@ use pwgen::policy;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Log diagnostics at this level and above to stderr (needs the tracing feature)
+    #[arg(long = "log-level", global = true, value_enum, value_name = "LEVEL")]
+    log_level: Option<CliLogLevel>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Generate a password
     #[command(disable_help_flag = true)]
-    Generate(GenerateArgs),
+    Generate(Box<GenerateArgs>),
+    /// Derive recovery codes
+    #[command(disable_help_flag = true)]
+    Recovery(RecoveryArgs),
+    /// Derive a WireGuard keypair
+    #[command(disable_help_flag = true)]
+    Wireguard(WireguardArgs),
+    /// Derive a TOTP secret or compute its current code
+    #[command(disable_help_flag = true)]
+    Totp(TotpArgs),
+    /// Derive an answer to a security question
+    #[command(disable_help_flag = true)]
+    Answer(AnswerArgs),
+    /// Derive a pseudonymous username or email alias
+    #[command(disable_help_flag = true)]
+    Username(UsernameArgs),
+    /// Check whether an existing password (read from stdin) satisfies a policy
+    #[command(disable_help_flag = true)]
+    Check(CheckArgs),
+    /// Benchmark Argon2 and pick parameters for a target latency
+    #[command(disable_help_flag = true)]
+    Calibrate(CalibrateArgs),
+    /// Print a short fingerprint of the master secret to compare across devices
+    #[command(disable_help_flag = true)]
+    Fingerprint(FingerprintArgs),
+    /// Store or check a verifier that detects mistyped masters
+    #[command(disable_help_flag = true, subcommand)]
+    Verifier(VerifierCommand),
+    /// Split the master into shares for a paper backup, or recover it from them
+    #[command(disable_help_flag = true, subcommand)]
+    Share(ShareCommand),
+    /// Hold the master secret and derive site keys for `pwgen client` (Unix only)
+    #[command(disable_help_flag = true)]
+    Agent(AgentArgs),
+    /// Run a command with site keys from the running agent instead of a master secret
+    #[command(disable_help_flag = true)]
+    Client(ClientArgs),
+    /// Answer JSON-RPC requests on stdin, localhost HTTP or D-Bus (generate, encode-policy, fingerprint)
+    #[command(disable_help_flag = true)]
+    Serve(ServeArgs),
+    /// Native messaging host for the browser extension; derives through the agent
+    #[command(disable_help_flag = true)]
+    NativeHost(NativeHostArgs),
+    /// Pinentry for gpg-agent that derives the passphrases of configured keys through the agent
+    #[command(disable_help_flag = true)]
+    Pinentry(PinentryArgs),
+    /// Derive the passwords of a profile file into another password manager
+    #[command(disable_help_flag = true, subcommand)]
+    Export(ExportCommand),
+    /// Bump a site's version in a profile file and print its current and new password
+    #[command(disable_help_flag = true)]
+    Rotate(RotateArgs),
+    /// Print the non-secret state of a profile file (sites, versions, settings) for a paper backup
+    #[command(disable_help_flag = true)]
+    BackupSheet(BackupSheetArgs),
+    /// List the sites of a profile file, e.g. for a launcher such as fzf, rofi or dmenu
+    #[command(disable_help_flag = true)]
+    List(ListArgs),
+    /// Type a password into the focused window instead of printing it
+    #[command(disable_help_flag = true)]
+    Type(TypeArgs),
+    /// Test vectors for checking other implementations
+    #[command(disable_help_flag = true, subcommand)]
+    Vectors(VectorsCommand),
+    /// Check that this build derives the recorded outputs of the built-in vectors
+    #[command(disable_help_flag = true)]
+    SelfTest,
+    /// Print a shell completion script
+    #[command(disable_help_flag = true)]
+    Completions(CompletionsArgs),
     /// Show detailed help information
     Help,
 }
@@ -39,16 +131,52 @@ enum CliCharset {
     Symbol,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliCompat {
+    Lesspass,
+    Spectre,
+    Sgp,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliSpectreType {
+    Maximum,
+    Long,
+    Medium,
+    Short,
+    Basic,
+    Pin,
+    Name,
+    Phrase,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliSgpHash {
+    Md5,
+    Sha512,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliVersionPolicy {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliFormat {
+    Hex,
+    Base64url,
+    Base58,
+}
+
+// How the master secret is supplied (shared by every subcommand)
 #[derive(Debug, Args)]
 #[command(group(
     ArgGroup::new("master_input")
-        .args(["master", "master_prompt", "master_stdin"]) 
+        .args(["master", "master_prompt", "master_stdin", "master_file", "master_fd", "master_env", "team"])
 ))]
-struct GenerateArgs {
-    /// Site identifier
-    #[arg(long, value_name = "STRING")]
-    site: String,
-
+struct MasterArgs {
     /// Master secret provided directly (risky, not recommended)
     #[arg(long, value_name = "STRING")]
     master: Option<String>,
@@ -61,6 +189,180 @@ struct GenerateArgs {
     #[arg(long = "master-stdin")]
     master_stdin: bool,
 
+    /// Read the master secret from a file (trailing newlines are ignored)
+    #[arg(long = "master-file", value_name = "PATH")]
+    master_file: Option<std::path::PathBuf>,
+
+    /// Read the master secret from an inherited file descriptor until EOF (Unix only)
+    #[arg(long = "master-fd", value_name = "N")]
+    master_fd: Option<i32>,
+
+    /// Read the master secret from the environment variable VAR (visible to the process owner; prints a warning)
+    #[arg(long = "master-env", value_name = "VAR")]
+    master_env: Option<String>,
+
+    /// Use the master combined from a team's contributed secrets, one per line in FILE
+    #[arg(long, value_name = "FILE")]
+    team: Option<std::path::PathBuf>,
+
+    /// Abort the master prompt after SECS seconds without a keystroke
+    #[arg(long = "prompt-timeout", value_name = "SECS",
+          value_parser = clap::value_parser!(u64).range(1..=3600))]
+    prompt_timeout: Option<u64>,
+
+    /// Echo an asterisk per keystroke at the master prompt
+    #[arg(long = "prompt-mask")]
+    prompt_mask: bool,
+
+    /// Argon2 cost preset; use the same one on every device (overrides the config file)
+    #[arg(long = "kdf-profile", value_enum, value_name = "PROFILE")]
+    kdf_profile: Option<CliKdfProfile>,
+
+    /// Key-stretching function; pbkdf2 is only for devices that cannot run Argon2
+    #[arg(long, value_enum, value_name = "KDF", default_value = "argon2id")]
+    kdf: CliKdf,
+
+    /// File whose contents are required in addition to the master secret (second factor)
+    #[arg(long, value_name = "PATH")]
+    keyfile: Option<std::path::PathBuf>,
+
+    /// Mix in a YubiKey's HMAC-SHA1 response to a site challenge (slot 1 or 2; default 2)
+    #[arg(long, value_name = "SLOT", num_args = 0..=1, default_missing_value = "2",
+          value_parser = clap::value_parser!(u8).range(1..=2))]
+    yubikey: Option<u8>,
+
+    /// File whose contents are passed to Argon2 as its secret key input (a pepper)
+    #[arg(long = "argon2-secret-file", value_name = "PATH")]
+    argon2_secret_file: Option<std::path::PathBuf>,
+
+    /// Associated data passed to Argon2 to bind keys to a context (at most 32 bytes)
+    #[arg(long = "argon2-ad", value_name = "STRING")]
+    argon2_ad: Option<String>,
+
+    /// Derive in a scope (e.g. personal, work) whose outputs are independent of every other scope's
+    #[arg(long, value_name = "NAME")]
+    scope: Option<String>,
+
+    /// Use the master settings of a `[persona.NAME]` table in the config file (keyfile, pepper,
+    /// KDF, scope; the verifier is kept per persona)
+    #[arg(long, value_name = "NAME")]
+    persona: Option<String>,
+
+    /// Derivation scheme version (1 = original, 2 = unambiguous context bound to the KDF,
+    /// 3 = v2 over a ChaCha20 stream, 4 = v2 over BLAKE3 with the `blake3` feature,
+    /// 5 = v2 over HKDF-SHA512)
+    #[arg(long = "algo-version", value_enum, value_name = "N", default_value = "1")]
+    algo_version: CliAlgoVersion,
+
+    /// Refuse a guessable master secret instead of only warning after the prompt
+    #[arg(long = "enforce-strength")]
+    enforce_strength: bool,
+
+    /// After the master prompt, show an emoji fingerprint of the master to catch typos (one extra KDF run)
+    #[arg(long = "emoji-fingerprint")]
+    emoji_fingerprint: bool,
+
+    /// How --site is reduced: exact, or the registrable domain of its host (overrides the config file)
+    #[arg(long = "site-mode", value_enum, value_name = "MODE")]
+    site_mode: Option<CliSiteMode>,
+
+    /// Set by `pwgen client`: derive site keys in the agent listening on this socket
+    #[arg(skip)]
+    agent: Option<std::path::PathBuf>,
+}
+
+impl MasterArgs {
+    /// Whether the master secret will be typed at the TTY prompt.
+    fn prompts(&self) -> bool {
+        self.master.is_none() && !self.master_stdin && self.master_file.is_none() && self.master_fd.is_none()
+            && self.master_env.is_none() && self.team.is_none()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliSiteMode {
+    Exact,
+    Registrable,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliAlgoVersion {
+    #[value(name = "1")]
+    V1,
+    #[value(name = "2")]
+    V2,
+    #[value(name = "3")]
+    V3,
+    #[cfg(feature = "blake3")]
+    #[value(name = "4")]
+    V4,
+    #[value(name = "5")]
+    V5,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliLogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliKdf {
+    Argon2id,
+    Pbkdf2,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliKdfProfile {
+    Fast,
+    Balanced,
+    Paranoid,
+}
+
+#[derive(Debug, Args)]
+struct GenerateArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(
+        long,
+        value_name = "STRING",
+        required = false,
+        required_unless_present_any = ["path", "site_from_stdin", "requests"],
+        default_value = "",
+        hide_default_value = true
+    )]
+    site: String,
+
+    /// Read the site (and username) from a line on stdin, as chosen from pwgen list --format picker
+    #[arg(long = "site-from-stdin", conflicts_with_all = ["site", "path", "username", "master_stdin"])]
+    site_from_stdin: bool,
+
+    /// Profile file whose settings apply to the site read with --site-from-stdin (default: profiles in the config file)
+    #[arg(long, value_name = "PATH", requires = "site_from_stdin")]
+    profiles: Option<std::path::PathBuf>,
+
+    /// ORG/SITE[/ACCOUNT[/ROTATION]] in place of --site, --username and --version
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["site", "username", "version", "version_policy", "compat"])]
+    path: Option<String>,
+
+    /// Answer JSON requests ({"site", "username", "version", <policy>}), one per line, from this file or - for stdin
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "site", "path", "site_from_stdin", "username", "version", "version_policy", "compat", "length", "min", "max",
+            "allow_sets", "force_sets", "classes", "min_counts", "max_counts", "no_lower", "no_upper", "no_digit", "no_symbol",
+            "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site", "policy_encoded", "passwordrules", "pattern", "format", "json", "output", "field",
+            "dry_run", "print0", "no_newline", "check_breach", "clip",
+        ]
+    )]
+    requests: Option<String>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
     /// Optional username to include in context
     #[arg(long, value_name = "STRING", default_value = "")]
     username: String,
@@ -69,244 +371,3859 @@ struct GenerateArgs {
     #[arg(long, value_name = "INT")]
     length: Option<u32>,
 
-    /// Minimum length
-    #[arg(long, value_name = "INT", default_value_t = 12)]
-    min: u32,
+    /// Minimum length
+    #[arg(long, value_name = "INT", default_value_t = 12)]
+    min: u32,
+
+    /// Maximum length
+    #[arg(long, value_name = "INT", default_value_t = 16)]
+    max: u32,
+
+    /// Allowed character sets (comma-separated: lower, upper, digit, symbol, an extended set
+    /// latin1, greek, cyrillic or emoji-v1, a named class hexlower, hexupper or base32, or one
+    /// defined with --class)
+    #[arg(long = "allow", value_name = "SET", value_delimiter = ',')]
+    allow_sets: Vec<String>,
+
+    /// Forced character sets to include (comma-separated; subset of allow)
+    #[arg(long = "force", value_name = "SET", value_delimiter = ',')]
+    force_sets: Vec<String>,
+
+    /// Define a named character class for --allow and --force (e.g. vowels=aeiou; repeatable)
+    #[arg(long = "class", value_name = "NAME=CHARS", value_parser = parse_class)]
+    classes: Vec<(String, String)>,
+
+    /// Minimum characters per set (comma-separated SET:N, e.g. digit:2,symbol:2; implies --force)
+    #[arg(long = "min-count", value_name = "SET:N", value_delimiter = ',', value_parser = parse_set_count)]
+    min_counts: Vec<(CliCharset, u8)>,
+
+    /// Maximum characters per set (comma-separated SET:N, e.g. symbol:3)
+    #[arg(long = "max-count", value_name = "SET:N", value_delimiter = ',', value_parser = parse_set_count)]
+    max_counts: Vec<(CliCharset, u8)>,
+
+    /// Disallow lowercase letters
+    #[arg(long = "no-lower")]
+    no_lower: bool,
+
+    /// Disallow uppercase letters
+    #[arg(long = "no-upper")]
+    no_upper: bool,
+
+    /// Disallow digits
+    #[arg(long = "no-digit")]
+    no_digit: bool,
+
+    /// Disallow symbols
+    #[arg(long = "no-symbol")]
+    no_symbol: bool,
+
+    /// Characters to remove from every allowed set (e.g. "0O1lI")
+    #[arg(long, value_name = "CHARS")]
+    exclude: Option<String>,
+
+    /// Remove visually ambiguous characters (0/O, 1/l/I, |, quotes)
+    #[arg(long = "no-ambiguous")]
+    no_ambiguous: bool,
+
+    /// Keep only characters typed the same on US, UK, German and French keyboards (symbols * + - /, no a, m, q, w, y, z)
+    #[arg(long = "layout-safe")]
+    layout_safe: bool,
+
+    /// Start the password with a letter
+    #[arg(long = "letter-first")]
+    letter_first: bool,
+
+    /// End the password with a letter
+    #[arg(long = "letter-last")]
+    letter_last: bool,
+
+    /// Longest run of one repeated character (e.g. 2 allows "aa" but not "aaa")
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    max_consecutive: Option<u8>,
+
+    /// Never repeat a character twice in a row (same as --max-consecutive 1)
+    #[arg(long = "no-repeat", conflicts_with = "max_consecutive")]
+    no_repeat: bool,
+
+    /// Longest ascending or descending run such as "abc" or "321" (letters and digits; e.g. 2 allows "ab" but not "abc")
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    max_sequential: Option<u8>,
+
+    /// Redraw passwords whose letters spell a common English word of 4 or more letters (any case)
+    #[arg(long = "no-words")]
+    no_words: bool,
+
+    /// Redraw passwords containing the site or one of its labels (e.g. "google" for accounts.google.com, any case)
+    #[arg(long = "no-site")]
+    no_site: bool,
+
+    /// Encoded policy (as printed by --json/--verbose), instead of the policy flags
+    #[arg(
+        long = "policy",
+        value_name = "ENCODED",
+        conflicts_with_all = [
+            "length", "min", "max", "allow_sets", "force_sets", "classes", "min_counts", "max_counts",
+            "no_lower", "no_upper", "no_digit", "no_symbol", "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site",
+            "passwordrules", "pattern",
+        ]
+    )]
+    policy_encoded: Option<String>,
+
+    /// Apple passwordrules string published by the site, instead of the policy flags
+    #[arg(
+        long,
+        value_name = "RULES",
+        conflicts_with_all = [
+            "length", "min", "max", "allow_sets", "force_sets", "classes", "min_counts", "max_counts",
+            "no_lower", "no_upper", "no_digit", "no_symbol", "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site", "pattern",
+        ]
+    )]
+    passwordrules: Option<String>,
+
+    /// Template pattern instead of a policy (C/c consonant, V/v vowel, A upper, a letter, n digit, o symbol, x any)
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all = [
+            "length", "min", "max", "allow_sets", "force_sets", "classes", "min_counts", "max_counts",
+            "no_lower", "no_upper", "no_digit", "no_symbol", "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site",
+        ]
+    )]
+    pattern: Option<String>,
+
+    /// Emit an encoded random token instead of a password (not subject to charset policies)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = [
+            "length", "min", "max", "allow_sets", "force_sets", "classes", "min_counts", "max_counts",
+            "no_lower", "no_upper", "no_digit", "no_symbol", "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site",
+            "policy_encoded", "passwordrules", "pattern",
+        ]
+    )]
+    format: Option<CliFormat>,
+
+    /// Token length in bytes for --format
+    #[arg(long, value_name = "INT", default_value_t = 32, requires = "format")]
+    bytes: usize,
+
+    /// Derive another generator's password from the master alone (--username is its login or user name, --version its counter)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SCHEME",
+        conflicts_with_all = [
+            "min", "max", "allow_sets", "force_sets", "classes", "min_counts", "max_counts", "exclude", "no_ambiguous", "layout_safe", "letter_first", "letter_last", "max_consecutive", "no_repeat", "max_sequential", "no_words", "no_site",
+            "policy_encoded", "passwordrules", "pattern", "format", "kdf_profile", "kdf", "keyfile", "yubikey", "scope", "persona",
+            "argon2_secret_file", "argon2_ad", "algo_version", "emoji_fingerprint", "site_mode",
+        ]
+    )]
+    compat: Option<CliCompat>,
+
+    /// Spectre password type for --compat spectre [default: long]
+    #[arg(long = "spectre-type", value_enum, value_name = "TYPE", requires = "compat")]
+    spectre_type: Option<CliSpectreType>,
+
+    /// SuperGenPass hash for --compat sgp [default: md5]
+    #[arg(long = "sgp-hash", value_enum, value_name = "HASH", requires = "compat")]
+    sgp_hash: Option<CliSgpHash>,
+
+    /// Fail unless the password reaches this many bits of entropy (overrides the config file)
+    #[arg(long = "min-bits", value_name = "BITS")]
+    min_bits: Option<u32>,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Derive the version from the current calendar period: YYYYMM, YYYYQ or YYYY (UTC)
+    #[arg(long = "version-policy", value_enum, value_name = "PERIOD", conflicts_with = "version")]
+    version_policy: Option<CliVersionPolicy>,
+
+    /// Date whose period --version-policy uses instead of today, to rederive a past password
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "version_policy")]
+    at: Option<String>,
+
+    /// Print a JSON object with details instead of plain password
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print the normalized derivation context without reading the master or deriving a password
+    #[arg(long = "dry-run", conflicts_with_all = ["compat", "check_breach", "print0", "no_newline"])]
+    dry_run: bool,
+
+    /// End the plain password with a NUL byte instead of a newline (for xargs -0)
+    #[arg(long, conflicts_with_all = ["json", "output", "field", "no_newline"])]
+    print0: bool,
+
+    /// Print the plain password without a trailing newline
+    #[arg(short = 'n', long = "no-newline", conflicts_with_all = ["json", "output", "field"])]
+    no_newline: bool,
+
+    /// Copy the password to the clipboard instead of printing it, marked for clipboard managers to skip
+    #[arg(long, conflicts_with_all = ["json", "output", "field", "dry_run", "print0", "no_newline"])]
+    clip: bool,
+
+    /// Seconds the password stays on the clipboard before it is cleared
+    #[arg(long = "clip-seconds", value_name = "SECS", default_value_t = 30, requires = "clip",
+          value_parser = clap::value_parser!(u64).range(1..=3600))]
+    clip_seconds: u64,
+
+    /// Warn if the password appears in known breaches (Have I Been Pwned, sends 5 hex digits of its SHA-1)
+    #[arg(long = "check-breach")]
+    check_breach: bool,
+
+    /// Print extra info (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct RecoveryArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(long, value_name = "STRING")]
+    site: String,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Optional username to include in context
+    #[arg(long, value_name = "STRING", default_value = "")]
+    username: String,
+
+    /// Number of codes (more codes extend the same list)
+    #[arg(long, value_name = "INT", default_value_t = 10)]
+    count: usize,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Print a JSON object with details instead of one code per line
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print extra info (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct WireguardArgs {
+    /// Site identifier (e.g. the VPN or peer name)
+    #[arg(long, value_name = "STRING")]
+    site: String,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Optional username to include in context
+    #[arg(long, value_name = "STRING", default_value = "")]
+    username: String,
+
+    /// Print the public key instead of the private key
+    #[arg(long)]
+    public: bool,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Print a JSON object with both keys instead of a single key
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print extra info, including the public key (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct TotpArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(long, value_name = "STRING")]
+    site: String,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Optional username to include in context
+    #[arg(long, value_name = "STRING", default_value = "")]
+    username: String,
+
+    /// Print the current 6-digit code instead of the base32 secret
+    #[arg(long)]
+    code: bool,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Print a JSON object with the secret and current code
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print extra info (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+#[allow(clippy::enum_variant_names)] // variant names are the CLI values
+enum CliUsernameStyle {
+    WordWordDigits,
+    WordDigits,
+    WordWord,
+}
+
+#[derive(Debug, Args)]
+struct UsernameArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(long, value_name = "STRING")]
+    site: String,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Username shape
+    #[arg(long, value_enum, value_name = "STYLE", default_value = "word-word-digits")]
+    style: CliUsernameStyle,
+
+    /// Print an email alias at this catch-all domain instead of a bare username
+    #[arg(long, value_name = "DOMAIN")]
+    domain: Option<String>,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Print a JSON object with details instead of plain username
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print extra info (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct AnswerArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(long, value_name = "STRING")]
+    site: String,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Optional username to include in context
+    #[arg(long, value_name = "STRING", default_value = "")]
+    username: String,
+
+    /// Security question (case, spacing and trailing '?' are ignored)
+    #[arg(long, value_name = "STRING")]
+    question: String,
+
+    /// Rotation/version number
+    #[arg(long, value_name = "UINT", default_value_t = 1)]
+    version: u32,
+
+    /// Print a JSON object with details instead of plain answer
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+
+    /// Print extra info (to stderr)
+    #[arg(long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct CalibrateArgs {
+    /// Desired time for one key derivation, in milliseconds
+    #[arg(long = "target-ms", value_name = "MS", default_value_t = 500,
+          value_parser = clap::value_parser!(u64).range(1..=60_000))]
+    target_ms: u64,
+
+    /// Upper bound on the memory cost, in MiB
+    #[arg(long = "max-mem-mib", value_name = "MIB", default_value_t = 1024,
+          value_parser = clap::value_parser!(u32).range(19..=4_194_303))]
+    max_mem_mib: u32,
+
+    /// Store the parameters as kdf-params in the config file
+    #[arg(long)]
+    save: bool,
+
+    /// Print a JSON object with details instead of plain parameters
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct FingerprintArgs {
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Print the emoji fingerprint instead of the XXXX-XXXX one
+    #[arg(long)]
+    emoji: bool,
+
+    /// Print a JSON object with details instead of plain fingerprint
+    #[arg(long)]
+    json: bool,
+
+    /// Print the details in this format instead (--json is --output json)
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "json")]
+    output: Option<output::Format>,
+
+    /// Print only this field of the details, e.g. password, length or policy
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["json", "output"])]
+    field: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum VerifierCommand {
+    /// Hash the master into a verifier file next to the config file
+    #[command(disable_help_flag = true)]
+    Init(VerifierInitArgs),
+    /// Check the master against the stored verifier
+    #[command(disable_help_flag = true)]
+    Check(VerifierCheckArgs),
+}
+
+#[derive(Debug, Args)]
+struct VerifierInitArgs {
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Replace an existing verifier
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct VerifierCheckArgs {
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum ShareCommand {
+    /// Split the master into shares, any THRESHOLD of which recover it
+    #[command(disable_help_flag = true)]
+    Split(Box<ShareSplitArgs>),
+    /// Recover the master from shares read from stdin, one per line
+    #[command(disable_help_flag = true)]
+    Combine,
+}
+
+#[derive(Debug, Args)]
+struct ShareSplitArgs {
+    /// Shares needed to recover the master (at least 2)
+    #[arg(long, value_name = "K")]
+    threshold: u8,
+
+    /// Shares to create (at most 255)
+    #[arg(long, value_name = "N")]
+    shares: u8,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct AgentArgs {
+    #[command(subcommand)]
+    action: Option<AgentAction>,
+
+    /// Socket to listen on (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+
+    /// Lock after SECS seconds without a request (wipes the master and cached keys)
+    #[arg(long = "idle-timeout", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    idle_timeout: Option<u64>,
+
+    /// Lock SECS seconds after the master was given, however busy the agent is
+    #[arg(long = "max-ttl", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    max_ttl: Option<u64>,
+
+    /// Start without a master (e.g. under systemd); give it with `pwgen agent unlock`
+    #[arg(long, conflicts_with = "master_input")]
+    locked: bool,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum AgentAction {
+    /// Wipe the running agent's master and cached keys until it is unlocked
+    #[command(disable_help_flag = true)]
+    Lock(AgentLockArgs),
+    /// Give the running agent the master secret again
+    #[command(disable_help_flag = true)]
+    Unlock(Box<AgentUnlockArgs>),
+}
+
+#[derive(Debug, Args)]
+struct AgentLockArgs {
+    /// Agent socket (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct AgentUnlockArgs {
+    /// Agent socket (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+struct ClientArgs {
+    /// Agent socket (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: ClientCommand,
+}
+
+/// Commands `pwgen client` runs against the agent; they take the same options as on their own.
+#[derive(Debug, Subcommand)]
+enum ClientCommand {
+    #[command(disable_help_flag = true)]
+    Generate(Box<GenerateArgs>),
+    #[command(disable_help_flag = true)]
+    Recovery(RecoveryArgs),
+    #[command(disable_help_flag = true)]
+    Wireguard(WireguardArgs),
+    #[command(disable_help_flag = true)]
+    Totp(TotpArgs),
+    #[command(disable_help_flag = true)]
+    Answer(AnswerArgs),
+    #[command(disable_help_flag = true)]
+    Username(UsernameArgs),
+    #[command(disable_help_flag = true)]
+    Fingerprint(FingerprintArgs),
+    #[command(disable_help_flag = true)]
+    Type(TypeArgs),
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("transport").args(["stdio", "http", "dbus"]).required(true)))]
+struct ServeArgs {
+    /// Read JSON-RPC requests from stdin and answer on stdout, one per line
+    #[arg(long, conflicts_with = "master_stdin")]
+    stdio: bool,
+
+    /// Serve the methods over HTTP on a loopback address, e.g. 127.0.0.1:7878 (prints an auth token)
+    #[arg(long, value_name = "ADDR")]
+    http: Option<std::net::SocketAddr>,
+
+    /// Own org.pwgen.Generator on the D-Bus session bus and serve Generate and Fingerprint (Linux)
+    #[arg(long)]
+    dbus: bool,
+
+    /// Derive through the running agent instead of reading a master secret
+    #[arg(long, conflicts_with = "master_input")]
+    agent: bool,
+
+    /// Agent socket for --agent (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH", requires = "agent")]
+    socket: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+struct NativeHostArgs {
+    /// Extension allowed to call the host: a Chrome extension ID or origin, or a Firefox add-on ID (repeatable; default: whatever the host manifest allows)
+    #[arg(long = "allow-extension", value_name = "ID")]
+    allow_extension: Vec<String>,
+
+    /// Agent socket (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Arguments the browser passes: the caller's origin (Chrome) or manifest path and add-on ID (Firefox)
+    #[arg(value_name = "CALLER", trailing_var_arg = true, allow_hyphen_values = true)]
+    caller: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct PinentryArgs {
+    /// Real pinentry for other keys, refused passphrases, confirmations and messages
+    #[arg(long, value_name = "PROGRAM", default_value = "pinentry")]
+    fallback: std::path::PathBuf,
+
+    /// Agent socket (default: $PWGEN_AGENT_SOCK, else $XDG_RUNTIME_DIR/pwgen/agent.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportCommand {
+    /// Write gpg-encrypted entries into a pass(1) password store
+    #[command(disable_help_flag = true)]
+    Pass(ExportPassArgs),
+    /// Write a CSV file for the Bitwarden or 1Password importer
+    #[command(disable_help_flag = true)]
+    Csv(ExportCsvArgs),
+}
+
+#[derive(Debug, Args)]
+struct ExportPassArgs {
+    /// Profile file listing the sites to export as [[site]] tables
+    #[arg(long, value_name = "PATH")]
+    profiles: std::path::PathBuf,
+
+    /// Password store (default: $PASSWORD_STORE_DIR, else ~/.password-store)
+    #[arg(long, value_name = "DIR")]
+    store: Option<std::path::PathBuf>,
+
+    /// Replace existing entries
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+struct ExportCsvArgs {
+    /// Profile file listing the sites to export as [[site]] tables
+    #[arg(long, value_name = "PATH")]
+    profiles: std::path::PathBuf,
+
+    /// Import schema to write
+    #[arg(long, value_enum)]
+    format: export::CsvFormat,
+
+    /// File to write, readable only by you (default: standard output)
+    #[arg(long, short = 'o', value_name = "PATH")]
+    output: Option<std::path::PathBuf>,
+
+    /// Replace an existing output file
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+struct RotateArgs {
+    /// Profile file keeping the site's version (created if missing)
+    #[arg(long, value_name = "PATH")]
+    profiles: std::path::PathBuf,
+
+    /// Site to rotate; a profile is added for it if the file has none
+    #[arg(long)]
+    site: String,
+
+    /// Username of the profile, if the file has several for the site
+    #[arg(long)]
+    username: Option<String>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("rules").args(["policy_encoded", "passwordrules"]).required(true)))]
+struct CheckArgs {
+    /// Encoded policy to check against, as printed by generate --json
+    #[arg(long = "policy", value_name = "ENCODED")]
+    policy_encoded: Option<String>,
+
+    /// Apple passwordrules string published by the site to check against
+    #[arg(long, value_name = "RULES")]
+    passwordrules: Option<String>,
+
+    /// Site the password is for, checked against policies with no-site
+    #[arg(long, value_name = "SITE")]
+    site: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct BackupSheetArgs {
+    /// Profile file whose sites are listed
+    #[arg(long, value_name = "PATH")]
+    profiles: std::path::PathBuf,
+
+    /// File to write, as PDF if it ends in .pdf, else as text (default: text on standard output)
+    #[arg(long, value_name = "PATH")]
+    out: Option<std::path::PathBuf>,
+
+    /// Persona of the config file the passwords are derived with
+    #[arg(long, value_name = "NAME")]
+    persona: Option<String>,
+
+    /// Argon2 cost preset the passwords are derived with (default: from the config file)
+    #[arg(long = "kdf-profile", value_enum, value_name = "PROFILE")]
+    kdf_profile: Option<CliKdfProfile>,
+
+    /// Key-stretching function the passwords are derived with
+    #[arg(long, value_enum, value_name = "KDF", default_value = "argon2id")]
+    kdf: CliKdf,
+
+    /// Derivation scheme version the passwords are derived with
+    #[arg(long = "algo-version", value_enum, value_name = "N", default_value = "1")]
+    algo_version: CliAlgoVersion,
+
+    /// Site mode the passwords are derived with (default: from the config file)
+    #[arg(long = "site-mode", value_enum, value_name = "MODE")]
+    site_mode: Option<CliSiteMode>,
+
+    /// Scope the passwords are derived in (default: from the config file)
+    #[arg(long, value_name = "NAME")]
+    scope: Option<String>,
+
+    /// Mark a keyfile as required even if the config file names none
+    #[arg(long = "with-keyfile")]
+    with_keyfile: bool,
+}
+
+#[derive(Debug, Args)]
+struct ListArgs {
+    /// Profile file to list (default: profiles in the config file)
+    #[arg(long, value_name = "PATH")]
+    profiles: Option<std::path::PathBuf>,
+
+    /// text: a table of sites, usernames and versions; picker: one site per line for --site-from-stdin
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "text")]
+    format: CliListFormat,
+}
+
+#[derive(Debug, Args)]
+struct TypeArgs {
+    /// Site identifier or URL (only the host of a URL is used)
+    #[arg(long, value_name = "STRING", required_unless_present = "site_from_stdin")]
+    site: Option<String>,
+
+    /// Read the site (and username) from a line on stdin, as chosen from pwgen list --format picker
+    #[arg(long = "site-from-stdin", conflicts_with_all = ["site", "username", "master_stdin"])]
+    site_from_stdin: bool,
+
+    /// Profile file whose settings apply to the site read with --site-from-stdin (default: profiles in the config file)
+    #[arg(long, value_name = "PATH", requires = "site_from_stdin")]
+    profiles: Option<std::path::PathBuf>,
+
+    /// Optional username to include in context
+    #[arg(long, value_name = "STRING", default_value = "")]
+    username: String,
+
+    /// Rotation/version number [default: 1]
+    #[arg(long, value_name = "UINT")]
+    version: Option<u32>,
+
+    /// Fixed length
+    #[arg(long, value_name = "INT", conflicts_with_all = ["policy", "passwordrules", "pattern"])]
+    length: Option<u32>,
+
+    /// Encoded policy (as printed by generate --json)
+    #[arg(long, value_name = "ENCODED", conflicts_with_all = ["passwordrules", "pattern"])]
+    policy: Option<String>,
+
+    /// Apple passwordrules string published by the site
+    #[arg(long, value_name = "RULES", conflicts_with = "pattern")]
+    passwordrules: Option<String>,
+
+    /// Template pattern instead of a policy
+    #[arg(long, value_name = "PATTERN")]
+    pattern: Option<String>,
+
+    #[command(flatten)]
+    master_input: MasterArgs,
+
+    /// Seconds to wait before typing, to focus the target field
+    #[arg(long, value_name = "SECS", default_value_t = 3)]
+    countdown: u64,
+
+    /// Milliseconds between keystrokes
+    #[arg(long = "key-delay", value_name = "MS", default_value_t = 12)]
+    key_delay: u64,
+
+    /// Keys to press after the password: {TAB} and {ENTER}, e.g. "{TAB}{ENTER}"
+    #[arg(long, value_name = "KEYS")]
+    suffix: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum CliListFormat {
+    Text,
+    Picker,
+}
+
+#[derive(Debug, Subcommand)]
+enum VectorsCommand {
+    /// Write the built-in vector matrix with this build's outputs as JSON
+    #[command(disable_help_flag = true)]
+    Export(VectorsExportArgs),
+    /// Check a vector file from another implementation against this build
+    #[command(disable_help_flag = true)]
+    Verify(VectorsVerifyArgs),
+}
+
+#[derive(Debug, Args)]
+struct VectorsExportArgs {
+    /// File to write (default: standard output)
+    #[arg(long, value_name = "PATH")]
+    out: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct VectorsVerifyArgs {
+    /// Vector file (schema pwgen-vectors-v1)
+    #[arg(value_name = "FILE")]
+    file: std::path::PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct CompletionsArgs {
+    /// Shell to complete for
+    #[arg(value_enum, value_name = "SHELL")]
+    shell: clap_complete::Shell,
+}
+
+/// `pwgen complete-sites`, run by the completion scripts. It is parsed apart from `Cli` so the
+/// scripts do not offer it as a subcommand.
+#[derive(Debug, Parser)]
+#[command(name = "pwgen complete-sites", bin_name = "pwgen complete-sites", disable_help_flag = true)]
+struct CompleteSitesArgs {
+    /// Only list sites starting with this
+    #[arg(value_name = "PREFIX", default_value = "")]
+    prefix: String,
+
+    /// Profile file to list (default: `profiles` in the config file)
+    #[arg(long, value_name = "PATH")]
+    profiles: Option<std::path::PathBuf>,
+}
+
+fn main() {
+    // Handle version flags before clap parsing
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "-v" {
+        println!("{}", env!("CARGO_PKG_VERSION"));
+        process::exit(0);
+    }
+    if args.len() > 1 && args[1] == "complete-sites" {
+        let exit_code = match handle_complete_sites(CompleteSitesArgs::parse_from(&args[1..])) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                4
+            }
+        };
+        process::exit(exit_code);
+    }
+    
+    let cli = Cli::parse();
+    if let Some(level) = cli.log_level {
+        if !init_logging(level) {
+            process::exit(2);
+        }
+    }
+    let exit_code = match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            4
+        }
+    };
+    process::exit(exit_code);
+}
+
+/// Logs spans and events at `level` and above to stderr, each span when it closes with the
+/// time spent in it.
+#[cfg(feature = "tracing")]
+fn init_logging(level: CliLogLevel) -> bool {
+    let level = match level {
+        CliLogLevel::Error => tracing::Level::ERROR,
+        CliLogLevel::Warn => tracing::Level::WARN,
+        CliLogLevel::Info => tracing::Level::INFO,
+        CliLogLevel::Debug => tracing::Level::DEBUG,
+        CliLogLevel::Trace => tracing::Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(level)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .init();
+    true
+}
+
+#[cfg(not(feature = "tracing"))]
+fn init_logging(_level: CliLogLevel) -> bool {
+    eprintln!("invalid input: --log-level requires the tracing feature");
+    false
+}
+
+fn run(cli: Cli) -> Result<i32> {
+    match cli.command {
+        Some(Commands::Generate(args)) => handle_generate(*args),
+        Some(Commands::Recovery(args)) => handle_recovery(args),
+        Some(Commands::Wireguard(args)) => handle_wireguard(args),
+        Some(Commands::Totp(args)) => handle_totp(args),
+        Some(Commands::Answer(args)) => handle_answer(args),
+        Some(Commands::Username(args)) => handle_username(args),
+        Some(Commands::Calibrate(args)) => handle_calibrate(args),
+        Some(Commands::Fingerprint(args)) => handle_fingerprint(args),
+        Some(Commands::Verifier(VerifierCommand::Init(args))) => handle_verifier_init(args),
+        Some(Commands::Verifier(VerifierCommand::Check(args))) => handle_verifier_check(args),
+        Some(Commands::Share(ShareCommand::Split(args))) => handle_share_split(*args),
+        Some(Commands::Share(ShareCommand::Combine)) => handle_share_combine(),
+        Some(Commands::Agent(AgentArgs { action: Some(AgentAction::Lock(args)), .. })) => handle_agent_lock(args),
+        Some(Commands::Agent(AgentArgs { action: Some(AgentAction::Unlock(args)), .. })) => handle_agent_unlock(*args),
+        Some(Commands::Agent(args)) => handle_agent(args),
+        Some(Commands::Client(args)) => handle_client(args),
+        Some(Commands::Serve(args)) => handle_serve(args),
+        Some(Commands::NativeHost(args)) => handle_native_host(args),
+        Some(Commands::Pinentry(args)) => handle_pinentry(args),
+        Some(Commands::Export(ExportCommand::Pass(args))) => handle_export_pass(args),
+        Some(Commands::Export(ExportCommand::Csv(args))) => handle_export_csv(args),
+        Some(Commands::Rotate(args)) => handle_rotate(args),
+        Some(Commands::BackupSheet(args)) => handle_backup_sheet(args),
+        Some(Commands::List(args)) => handle_list(args),
+        Some(Commands::Type(args)) => handle_type(args),
+        Some(Commands::Check(args)) => handle_check(args),
+        Some(Commands::Vectors(VectorsCommand::Export(args))) => handle_vectors_export(args),
+        Some(Commands::Vectors(VectorsCommand::Verify(args))) => handle_vectors_verify(args),
+        Some(Commands::SelfTest) => handle_self_test(),
+        Some(Commands::Completions(args)) => handle_completions(args),
+        Some(Commands::Help) => {
+            print_long_help();
+            Ok(0)
+        }
+        None => {
+            print_short_help();
+            Ok(0)
+        }
+    }
+}
+
+fn print_short_help() {
+    println!("pwgen - Deterministic password generator using Argon2id and HKDF");
+    println!();
+    println!("Usage:");
+    println!("  pwgen                    Show this help message");
+    println!("  pwgen help               Show detailed help");
+    println!("  pwgen -v                 Show the pwgen version");
+    println!("  pwgen generate --site X  Generate a password (prompts for master secret)");
+    println!("  pwgen recovery --site X  Derive recovery codes for a site");
+    println!("  pwgen wireguard --site X Derive a WireGuard private key (--public for the public key)");
+    println!("  pwgen totp --site X      Derive a TOTP secret (--code for the current code)");
+    println!("  pwgen answer --site X --question Q  Derive an answer to a security question");
+    println!("  pwgen username --site X  Derive a pseudonymous username (--domain for an email alias)");
+    println!("  pwgen check --policy P   Check whether a password read from stdin satisfies a policy");
+    println!("  pwgen calibrate          Pick Argon2 costs for this machine (--save to keep them)");
+    println!("  pwgen fingerprint        Show a short fingerprint of the master secret");
+    println!("  pwgen verifier init      Store a verifier that warns about mistyped masters (check to test)");
+    println!("  pwgen share split --threshold 3 --shares 5  Split the master into shares (share combine recovers it)");
+    println!("  pwgen agent              Hold the master secret for pwgen client (like ssh-agent)");
+    println!("  pwgen agent lock         Wipe the agent's master and cached keys (unlock to restore)");
+    println!("  pwgen client generate --site X  Run a command with site keys from the agent");
+    println!("  pwgen serve --stdio      Answer JSON-RPC requests on stdin (--http ADDR, --dbus)");
+    println!("  pwgen native-host        Native messaging host for the browser extension (derives through the agent)");
+    println!("  pwgen pinentry           Pinentry for gpg-agent with derived passphrases for configured keys");
+    println!("  pwgen export pass --profiles F  Write the passwords of a profile file into a pass store");
+    println!("  pwgen export csv --profiles F --format bitwarden  CSV for the Bitwarden or 1Password importer");
+    println!("  pwgen rotate --profiles F --site X  Bump the site's version and print its current and new password");
+    println!("  pwgen backup-sheet --profiles F  Print sites, versions and settings (never secrets) for a paper backup");
+    println!("  pwgen list --format picker  List the sites of the profile file for fzf/rofi/dmenu (generate --site-from-stdin)");
+    println!("  pwgen type --site X      Type the password into the focused window after a countdown");
+    println!("  pwgen vectors export     Write test vectors for other implementations as JSON (--out FILE)");
+    println!("  pwgen vectors verify F   Check a vector file from another implementation against this build");
+    println!("  pwgen self-test          Check that this build derives the recorded test vector outputs");
+    println!("  pwgen completions SHELL  Print a completion script for bash, zsh, fish, powershell or elvish");
+    println!();
+    println!("For detailed help, run: pwgen help");
+}
+
+fn print_long_help() {
+    println!("pwgen - Deterministic password generator using Argon2id and HKDF");
+    println!();
+    println!("Usage:");
+    println!("  pwgen help               Show this detailed help");
+    println!("  pwgen -v                 Show the pwgen version");
+    println!("  pwgen generate --site X  Generate a password (prompts for master secret)");
+    println!("  pwgen recovery --site X  Derive recovery codes for a site");
+    println!("  pwgen wireguard --site X Derive a WireGuard private key (--public for the public key)");
+    println!("  pwgen totp --site X      Derive a TOTP secret (--code for the current code)");
+    println!("  pwgen answer --site X --question Q  Derive an answer to a security question");
+    println!("  pwgen username --site X  Derive a pseudonymous username (--domain for an email alias)");
+    println!("  pwgen check --policy P   Check whether a password read from stdin satisfies a policy");
+    println!("  pwgen calibrate          Pick Argon2 costs for this machine (--save to keep them)");
+    println!("  pwgen fingerprint        Show a short fingerprint of the master secret");
+    println!("  pwgen verifier init      Store a verifier that warns about mistyped masters (check to test)");
+    println!("  pwgen share split --threshold 3 --shares 5  Split the master into shares (share combine recovers it)");
+    println!("  pwgen agent              Hold the master secret for pwgen client (like ssh-agent)");
+    println!("  pwgen agent lock         Wipe the agent's master and cached keys (unlock to restore)");
+    println!("  pwgen client generate --site X  Run a command with site keys from the agent");
+    println!("  pwgen serve --stdio      Answer JSON-RPC requests on stdin (--http ADDR, --dbus)");
+    println!("  pwgen native-host        Native messaging host for the browser extension (derives through the agent)");
+    println!("  pwgen pinentry           Pinentry for gpg-agent with derived passphrases for configured keys");
+    println!("  pwgen export pass --profiles F  Write the passwords of a profile file into a pass store");
+    println!("  pwgen export csv --profiles F --format bitwarden  CSV for the Bitwarden or 1Password importer");
+    println!("  pwgen rotate --profiles F --site X  Bump the site's version and print its current and new password");
+    println!("  pwgen backup-sheet --profiles F  Print sites, versions and settings (never secrets) for a paper backup");
+    println!("  pwgen list --format picker  List the sites of the profile file for fzf/rofi/dmenu (generate --site-from-stdin)");
+    println!("  pwgen type --site X      Type the password into the focused window after a countdown");
+    println!("  pwgen vectors export     Write test vectors for other implementations as JSON (--out FILE)");
+    println!("  pwgen vectors verify F   Check a vector file from another implementation against this build");
+    println!("  pwgen self-test          Check that this build derives the recorded test vector outputs");
+    println!("  pwgen completions SHELL  Print a completion script for bash, zsh, fish, powershell or elvish");
+    println!();
+    println!("Generate options:");
+
+    // Build the generate command with all its arguments to show detailed help
+    let mut generate_cmd = {
+        let cmd = clap::Command::new("generate")
+            .about("Generate a password")
+            .disable_help_flag(true);
+        GenerateArgs::augment_args(cmd)
+    };
+    
+    // Print the help for the generate subcommand
+    generate_cmd.print_help().unwrap_or_else(|_| {
+        // Fallback if printing fails
+        eprintln!("pwgen generate - Generate a password");
+        eprintln!();
+        eprintln!("Usage: pwgen generate --site <STRING> [OPTIONS]");
+        eprintln!();
+        eprintln!("For full documentation, see the README.md file.");
+    });
+
+    println!();
+    println!("Recovery options:");
+    let mut recovery_cmd = {
+        let cmd = clap::Command::new("recovery")
+            .about("Derive recovery codes")
+            .disable_help_flag(true);
+        RecoveryArgs::augment_args(cmd)
+    };
+    recovery_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen recovery --site <STRING> [OPTIONS]");
+    });
+
+    println!();
+    println!("WireGuard options:");
+    let mut wireguard_cmd = {
+        let cmd = clap::Command::new("wireguard")
+            .about("Derive a WireGuard keypair")
+            .disable_help_flag(true);
+        WireguardArgs::augment_args(cmd)
+    };
+    wireguard_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen wireguard --site <STRING> [OPTIONS]");
+    });
+
+    println!();
+    println!("TOTP options:");
+    let mut totp_cmd = {
+        let cmd = clap::Command::new("totp")
+            .about("Derive a TOTP secret or compute its current code")
+            .disable_help_flag(true);
+        TotpArgs::augment_args(cmd)
+    };
+    totp_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen totp --site <STRING> [OPTIONS]");
+    });
+
+    println!();
+    println!("Answer options:");
+    let mut answer_cmd = {
+        let cmd = clap::Command::new("answer")
+            .about("Derive an answer to a security question")
+            .disable_help_flag(true);
+        AnswerArgs::augment_args(cmd)
+    };
+    answer_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen answer --site <STRING> --question <STRING> [OPTIONS]");
+    });
+
+    println!();
+    println!("Username options:");
+    let mut username_cmd = {
+        let cmd = clap::Command::new("username")
+            .about("Derive a pseudonymous username or email alias")
+            .disable_help_flag(true);
+        UsernameArgs::augment_args(cmd)
+    };
+    username_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen username --site <STRING> [OPTIONS]");
+    });
+
+    println!();
+    println!("Check options:");
+    let mut check_cmd = {
+        let cmd = clap::Command::new("check")
+            .about("Check whether an existing password (read from stdin) satisfies a policy")
+            .disable_help_flag(true);
+        CheckArgs::augment_args(cmd)
+    };
+    check_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen check --policy <ENCODED> | --passwordrules <RULES>");
+    });
+
+    println!();
+    println!("Calibrate options:");
+    let mut calibrate_cmd = {
+        let cmd = clap::Command::new("calibrate")
+            .about("Benchmark Argon2 and pick parameters for a target latency")
+            .disable_help_flag(true);
+        CalibrateArgs::augment_args(cmd)
+    };
+    calibrate_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen calibrate [--target-ms <MS>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Fingerprint options:");
+    let mut fingerprint_cmd = {
+        let cmd = clap::Command::new("fingerprint")
+            .about("Print a short fingerprint of the master secret to compare across devices")
+            .disable_help_flag(true);
+        FingerprintArgs::augment_args(cmd)
+    };
+    fingerprint_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen fingerprint [OPTIONS]");
+    });
+
+    println!();
+    println!("Verifier init options:");
+    let mut verifier_cmd = {
+        let cmd = clap::Command::new("verifier init")
+            .about("Hash the master into a verifier file next to the config file")
+            .disable_help_flag(true);
+        VerifierInitArgs::augment_args(cmd)
+    };
+    verifier_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen verifier init [--force] [OPTIONS]");
+    });
+
+    println!();
+    println!("Share split options:");
+    let mut share_split_cmd = {
+        let cmd = clap::Command::new("share split")
+            .about("Split the master into shares, any THRESHOLD of which recover it")
+            .disable_help_flag(true);
+        ShareSplitArgs::augment_args(cmd)
+    };
+    share_split_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen share split --threshold <K> --shares <N> [OPTIONS]");
+    });
+    println!("  pwgen share combine reads the shares from stdin, one per line, and prints the master.");
+
+    println!();
+    println!("Agent options:");
+    let mut agent_cmd = {
+        let cmd = clap::Command::new("agent")
+            .about("Hold the master secret and derive site keys for `pwgen client` (Unix only)")
+            .disable_help_flag(true);
+        AgentArgs::augment_args(cmd)
+    };
+    agent_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen agent [--socket <PATH>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Agent unlock options:");
+    let mut unlock_cmd = {
+        let cmd = clap::Command::new("agent unlock")
+            .about("Give the running agent the master secret again")
+            .disable_help_flag(true);
+        AgentUnlockArgs::augment_args(cmd)
+    };
+    unlock_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen agent unlock [--socket <PATH>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Client usage:");
+    println!("  pwgen client [--socket <PATH>] <COMMAND> [OPTIONS]");
+    println!("  COMMAND is generate, recovery, wireguard, totp, answer, username or fingerprint, with the");
+    println!("  options above minus the master secret, --keyfile and --argon2-secret-file, which the agent holds.");
+
+    println!();
+    println!("Serve options:");
+    let mut serve_cmd = {
+        let cmd = clap::Command::new("serve")
+            .about("Answer JSON-RPC requests on stdin, localhost HTTP or D-Bus (generate, encode-policy, fingerprint)")
+            .disable_help_flag(true);
+        ServeArgs::augment_args(cmd)
+    };
+    serve_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen serve --stdio [OPTIONS]");
+    });
+
+    println!();
+    println!("Native host options:");
+    let mut native_host_cmd = {
+        let cmd = clap::Command::new("native-host")
+            .about("Native messaging host for the browser extension; derives through the agent")
+            .disable_help_flag(true);
+        NativeHostArgs::augment_args(cmd)
+    };
+    native_host_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen native-host [--allow-extension <ID>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Pinentry options:");
+    let mut pinentry_cmd = {
+        let cmd = clap::Command::new("pinentry")
+            .about("Pinentry for gpg-agent that derives the passphrases of configured keys through the agent")
+            .disable_help_flag(true);
+        PinentryArgs::augment_args(cmd)
+    };
+    pinentry_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen pinentry [--fallback <PROGRAM>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Export pass options:");
+    let mut export_pass_cmd = {
+        let cmd = clap::Command::new("export pass")
+            .about("Write gpg-encrypted entries into a pass(1) password store")
+            .disable_help_flag(true);
+        ExportPassArgs::augment_args(cmd)
+    };
+    export_pass_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen export pass --profiles <PATH> [--store <DIR>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Export csv options:");
+    let mut export_csv_cmd = {
+        let cmd = clap::Command::new("export csv")
+            .about("Write a CSV file for the Bitwarden or 1Password importer")
+            .disable_help_flag(true);
+        ExportCsvArgs::augment_args(cmd)
+    };
+    export_csv_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen export csv --profiles <PATH> --format <FORMAT> [--output <PATH>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Rotate options:");
+    let mut rotate_cmd = {
+        let cmd = clap::Command::new("rotate")
+            .about("Bump a site's version in a profile file and print its current and new password")
+            .disable_help_flag(true);
+        RotateArgs::augment_args(cmd)
+    };
+    rotate_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen rotate --profiles <PATH> --site <SITE> [--username <USERNAME>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Backup sheet options:");
+    let mut backup_sheet_cmd = {
+        let cmd = clap::Command::new("backup-sheet")
+            .about("Print the non-secret state of a profile file (sites, versions, settings) for a paper backup")
+            .disable_help_flag(true);
+        BackupSheetArgs::augment_args(cmd)
+    };
+    backup_sheet_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen backup-sheet --profiles <PATH> [--out <PATH>] [OPTIONS]");
+    });
+
+    println!();
+    println!("List options:");
+    let mut list_cmd = {
+        let cmd = clap::Command::new("list")
+            .about("List the sites of a profile file, e.g. for a launcher such as fzf, rofi or dmenu")
+            .disable_help_flag(true);
+        ListArgs::augment_args(cmd)
+    };
+    list_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen list [--profiles <PATH>] [--format <FORMAT>]");
+    });
+
+    println!();
+    println!("Type options:");
+    let mut type_cmd = {
+        let cmd = clap::Command::new("type")
+            .about("Type a password into the focused window instead of printing it")
+            .disable_help_flag(true);
+        TypeArgs::augment_args(cmd)
+    };
+    type_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen type --site <SITE> [--suffix <KEYS>] [OPTIONS]");
+    });
+
+    println!();
+    println!("Vectors export options:");
+    let mut vectors_export_cmd = {
+        let cmd = clap::Command::new("vectors export")
+            .about("Write the built-in vector matrix with this build's outputs as JSON")
+            .disable_help_flag(true);
+        VectorsExportArgs::augment_args(cmd)
+    };
+    vectors_export_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen vectors export [--out <PATH>]");
+    });
+
+    println!();
+    println!("Vectors verify options:");
+    let mut vectors_verify_cmd = {
+        let cmd = clap::Command::new("vectors verify")
+            .about("Check a vector file from another implementation against this build")
+            .disable_help_flag(true);
+        VectorsVerifyArgs::augment_args(cmd)
+    };
+    vectors_verify_cmd.print_help().unwrap_or_else(|_| {
+        eprintln!("Usage: pwgen vectors verify <FILE>");
+    });
+}
+
+fn handle_generate(mut args: GenerateArgs) -> Result<i32> {
+    let check_breach = std::mem::take(&mut args.check_breach);
+    let dry_run = std::mem::take(&mut args.dry_run);
+    if check_breach && !cfg!(feature = "network") {
+        eprintln!("invalid input: --check-breach requires the network feature");
+        return Ok(2);
+    }
+    if let Some(policy) = args.version_policy.take() {
+        match period_version(policy, args.at.as_deref())? {
+            Ok((version, period)) => {
+                if args.verbose {
+                    eprintln!("Version {} for the {} period {}", version, period::VersionPolicy::from(policy).name(), period);
+                }
+                args.version = version;
+            }
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                return Ok(2);
+            }
+        }
+    }
+    if let Some(source) = args.requests.take() {
+        return handle_requests(args, &source);
+    }
+    if std::mem::take(&mut args.site_from_stdin) {
+        let profile = match read_picked_profile(args.profiles.take()) {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                return Ok(2);
+            }
+        };
+        args.site = profile.site;
+        args.username = profile.username;
+        if let Some(version) = profile.version {
+            args.version = version;
+        }
+        if profile.length.is_some() {
+            args.length = profile.length;
+        }
+        if profile.policy.is_some() {
+            args.policy_encoded = profile.policy;
+        }
+        if profile.passwordrules.is_some() {
+            args.passwordrules = profile.passwordrules;
+        }
+        if profile.pattern.is_some() {
+            args.pattern = profile.pattern;
+        }
+    }
+    if let Some(compat) = args.compat {
+        return handle_generate_compat(args, compat, check_breach);
+    }
+    let path = match args.path.take().map(|path| derivation_path::DerivationPath::parse(&path)).transpose() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+    if let Some(path) = &path {
+        // clap already rejects --site, --username, --version and --version-policy next to
+        // --path; a value that reached them any other way must not be overwritten silently
+        if !args.site.is_empty() || !args.username.is_empty() || args.version != 1 {
+            eprintln!("invalid input: --path cannot be combined with --site, --username, --version or --version-policy");
+            return Ok(2);
+        }
+        args.site = path.site.clone();
+        args.username = path.account.clone();
+        args.version = path.rotation.unwrap_or(1);
+    }
+    if dry_run {
+        return Ok(handle_dry_run(&args, path.as_ref()));
+    }
+    // Connect before the master is read, so a missing display fails first
+    let clipboard = match args.clip.then(clipboard::Clipboard::connect).transpose() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return Ok(4);
+        }
+    };
+    let Some(Resolved { site, master, scheme, algo, factors, cfg }) =
+        resolve_scoped_inputs(&args.site, path.as_ref(), &mut args.master_input)?
+    else {
+        return Ok(2);
+    };
+
+    let GenPlan { mode, params_key, params, entropy } = match plan_generation(&args, &cfg) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+
+    let username_opt = if args.username.is_empty() {
+        None
+    } else {
+        Some(args.username.as_str())
+    };
+
+    if args.verbose {
+        eprintln!(
+            "Generating password...\n  site: {}\n  username: {}\n  version: {}\n  {}: {}\n  entropy: {:.1} bits\n  kdf profile: {}",
+            site,
+            username_opt.unwrap_or("<empty>"),
+            args.version,
+            params_key,
+            params,
+            entropy,
+            scheme.label()
+        );
+    }
+
+    let timings = generator::Timings::default();
+    let master_in = master_input(&master, scheme, algo, &factors).with_timings(&timings);
+    let start = std::time::Instant::now();
+    let result = mode.generate(master_in, &site, username_opt, args.version);
+    let elapsed = start.elapsed();
+
+    // Zeroize master ASAP after generation call returns
+    drop(master);
+
+    if args.verbose {
+        eprintln!(
+            "Timing:\n  kdf: {:.1?}\n  hkdf setup: {:.1?}\n  character selection: {:.1?}\n  total: {:.1?}",
+            timings.kdf(),
+            timings.hkdf(),
+            elapsed.saturating_sub(timings.kdf() + timings.hkdf()),
+            elapsed
+        );
+    }
+
+    match result {
+        Ok(password) => {
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Generate {
+                    password: &password,
+                    length: password.chars().count(),
+                    site: &site,
+                    username: &args.username,
+                    version: args.version,
+                    params: output::Params::new(params_key, &params),
+                    entropy_bits: output::round_bits(entropy),
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else if clipboard.is_none() {
+                print_password(&password, &args);
+            }
+            if check_breach {
+                warn_if_breached(&password);
+            }
+            Ok(clipboard.map_or(0, |clipboard| clip_password(clipboard, &password, args.clip_seconds)))
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+/// Prints the plain password, ended as `--print0` or `--no-newline` ask.
+fn print_password(password: &str, args: &GenerateArgs) {
+    let end = if args.print0 {
+        "\0"
+    } else if args.no_newline {
+        ""
+    } else {
+        "\n"
+    };
+    print!("{}{}", password, end);
+    let _ = io::Write::flush(&mut io::stdout());
+}
+
+/// `--clip`: copies the password and waits until the clipboard is cleared or another copy
+/// replaces it.
+fn clip_password(clipboard: clipboard::Clipboard, password: &str, seconds: u64) -> i32 {
+    eprintln!("Copied to the clipboard; it is cleared in {} seconds", seconds);
+    match clipboard.copy(password, std::time::Duration::from_secs(seconds)) {
+        Ok(clipboard::Outcome::Cleared) => {
+            eprintln!("Clipboard cleared");
+            0
+        }
+        Ok(clipboard::Outcome::Replaced) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            4
+        }
+    }
+}
+
+/// `generate --dry-run`: everything the password would be derived from except the master and
+/// the secret factors, which are only reported as used or not. Nothing is read from the
+/// master inputs and the KDF does not run.
+fn handle_dry_run(args: &GenerateArgs, path: Option<&derivation_path::DerivationPath>) -> i32 {
+    if args.site.trim().is_empty() {
+        eprintln!("invalid input: --site must be nonempty after trim");
+        return 2;
+    }
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return 2;
+        }
+    };
+    let input = &args.master_input;
+    let site = match normalize_site(&args.site, input.site_mode, &cfg) {
+        Ok(site) => match path {
+            Some(path) => path.scoped_site(&site),
+            None => site,
+        },
+        Err(e) => {
+            eprintln!("invalid input: --site: {}", e);
+            return 2;
+        }
+    };
+    let Some((cfg, scheme, algo)) = resolve_settings(input, cfg) else {
+        return 2;
+    };
+    let GenPlan { params_key, params, entropy, .. } = match plan_generation(args, &cfg) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return 2;
+        }
+    };
+    // Only where the pepper would come from: reading it could prompt for the keychain
+    let pepper_source = config::pepper_source(&cfg);
+    let context = generator::context(
+        algo,
+        &scheme,
+        &site,
+        args.username.as_bytes(),
+        params_key.as_bytes(),
+        params.as_bytes(),
+        args.version,
+    );
+    let context = String::from_utf8_lossy(&context);
+    let keyfile = input.keyfile.as_ref().or(cfg.keyfile.as_ref()).map(|path| path.display().to_string());
+    let argon2_secret_file = input.argon2_secret_file.as_ref().map(|path| path.display().to_string());
+    let dry_run = output::DryRun {
+        site: &site,
+        username: &args.username,
+        version: args.version,
+        params: output::Params::new(params_key, &params),
+        entropy_bits: output::round_bits(entropy),
+        derivation: output::Derivation::new(scheme, algo),
+        persona: cfg.selected_persona.as_deref(),
+        scope: input.scope.as_deref().or(cfg.scope.as_deref()),
+        path_org: path.map(|path| path.org.as_str()),
+        keyfile: keyfile.as_deref(),
+        pepper: pepper_source.is_some(),
+        pepper_source,
+        yubikey_slot: input.yubikey,
+        argon2_secret_file: argon2_secret_file.as_deref(),
+        argon2_ad: input.argon2_ad.as_deref(),
+        context: &context,
+    };
+    if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+        if let Err(e) = output::print(selection, &dry_run) {
+            eprintln!("invalid input: {}", e);
+            return 2;
+        }
+        return 0;
+    }
+
+    let or_none = |value: Option<&str>| value.unwrap_or("none").to_string();
+    println!("Derivation context (dry run: no master read, no password derived)");
+    println!("  site: {}", site);
+    println!("  username: {}", if args.username.is_empty() { "<empty>" } else { &args.username });
+    println!("  version: {}", args.version);
+    println!("  {}: {}", params_key, params);
+    println!("  entropy: {:.1} bits", entropy);
+    println!("  kdf: {} (profile {})", kdf::Kdf::context_id(&scheme), scheme.label());
+    println!("  algorithm version: {}", algo.number());
+    if let Some(persona) = dry_run.persona {
+        println!("  persona: {}", persona);
+    }
+    println!("  scope: {}", or_none(dry_run.scope));
+    if let Some(org) = dry_run.path_org {
+        println!("  path organization: {}", org);
+    }
+    println!("  keyfile: {}", or_none(dry_run.keyfile));
+    println!("  pepper: {}", pepper_source.map_or("none".to_string(), |source| format!("from {}", source)));
+    println!("  yubikey: {}", input.yubikey.map_or("none".to_string(), |slot| format!("slot {}", slot)));
+    println!("  argon2 secret file: {}", or_none(dry_run.argon2_secret_file));
+    println!("  argon2 associated data: {}", or_none(dry_run.argon2_ad));
+    println!("  context: {}", context);
+    0
+}
+
+/// Warns on stderr if `password` appears in known breaches; a failed query is only a warning.
+#[cfg(feature = "network")]
+fn warn_if_breached(password: &str) {
+    match breach::check(password) {
+        Ok(0) => {}
+        Ok(count) => eprintln!(
+            "warning: this password appears {} times in known breaches (Have I Been Pwned); derive another with a new version",
+            count
+        ),
+        Err(e) => eprintln!("warning: breach check failed: {}", e),
+    }
+}
+
+#[cfg(not(feature = "network"))]
+fn warn_if_breached(_password: &str) {}
+
+impl From<CliVersionPolicy> for period::VersionPolicy {
+    fn from(policy: CliVersionPolicy) -> Self {
+        match policy {
+            CliVersionPolicy::Monthly => period::VersionPolicy::Monthly,
+            CliVersionPolicy::Quarterly => period::VersionPolicy::Quarterly,
+            CliVersionPolicy::Yearly => period::VersionPolicy::Yearly,
+        }
+    }
+}
+
+/// The version `--version-policy` stands for at `--at` (default: today) and its period; the
+/// inner error is invalid input.
+fn period_version(policy: CliVersionPolicy, at: Option<&str>) -> Result<std::result::Result<(u32, String), period::PeriodError>> {
+    let date = match at {
+        Some(at) => period::Date::parse(at),
+        None => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .context("system clock is before the Unix epoch")?
+                .as_secs();
+            period::Date::from_unix(now)
+        }
+    };
+    let policy = period::VersionPolicy::from(policy);
+    Ok(date.map(|date| (policy.version(date), policy.period(date))))
+}
+
+/// `generate --compat`: the password another generator derives from the master alone, without
+/// the KDF, pepper or other factors of pwgen's own scheme.
+fn handle_generate_compat(mut args: GenerateArgs, compat: CliCompat, check_breach: bool) -> Result<i32> {
+    if args.site.trim().is_empty() {
+        eprintln!("invalid input: --site must be nonempty after trim");
+        return Ok(2);
+    }
+    if args.master_input.agent.is_some() {
+        eprintln!("invalid input: --compat derives from the master secret itself, which the agent does not hand out");
+        return Ok(2);
+    }
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+
+    let planned = CompatMode::from_args(&args, compat).and_then(|mode| {
+        let entropy = mode.entropy_bits()?;
+        check_entropy_floor(mode.label(), entropy, args.min_bits, &cfg)?;
+        Ok((mode, entropy))
+    });
+    let (mode, entropy) = match planned {
+        Ok(planned) => planned,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+
+    // Connect before the master is read, so a missing display fails first
+    let clipboard = match args.clip.then(clipboard::Clipboard::connect).transpose() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return Ok(4);
+        }
+    };
+
+    let prompted = args.master_input.prompts();
+    let master = read_master(&mut args.master_input)?;
+    if master.is_empty() {
+        eprintln!("invalid input: master secret must be nonempty");
+        return Ok(2);
+    }
+    if !check_strength(&master, prompted, args.master_input.enforce_strength) {
+        return Ok(2);
+    }
+
+    if args.verbose {
+        eprintln!(
+            "Generating password...\n  site: {}\n  username: {}\n  version: {}\n  compat: {}\n  entropy: {:.1} bits",
+            args.site,
+            if args.username.is_empty() { "<empty>" } else { &args.username },
+            args.version,
+            mode.label(),
+            entropy
+        );
+    }
+
+    let result = mode.generate(master.as_str(), &args.site, &args.username, args.version);
+    drop(master);
+
+    let password = match result {
+        Ok(password) => password,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+    if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+        if let Err(e) = output::print(selection, &output::Compat {
+            password: &password,
+            length: password.chars().count(),
+            site: &args.site,
+            username: &args.username,
+            version: args.version,
+            compat: mode.label(),
+            entropy_bits: output::round_bits(entropy),
+        }) {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    } else if clipboard.is_none() {
+        print_password(&password, &args);
+    }
+    if check_breach {
+        warn_if_breached(&password);
+    }
+    Ok(clipboard.map_or(0, |clipboard| clip_password(clipboard, &password, args.clip_seconds)))
+}
+
+/// What `generate --compat` derives: another generator's scheme with its settings.
+enum CompatMode {
+    LessPass(lesspass::Profile),
+    Spectre(spectre::PasswordType),
+    Sgp { length: usize, hash: sgp::HashAlgorithm },
+}
+
+impl CompatMode {
+    /// Takes the scheme's settings from the generate flags, refusing those it has no use for.
+    fn from_args(args: &GenerateArgs, compat: CliCompat) -> std::result::Result<Self, String> {
+        if args.spectre_type.is_some() && compat != CliCompat::Spectre {
+            return Err("--spectre-type only applies to --compat spectre".to_string());
+        }
+        if args.sgp_hash.is_some() && compat != CliCompat::Sgp {
+            return Err("--sgp-hash only applies to --compat sgp".to_string());
+        }
+        match compat {
+            CliCompat::Lesspass => {
+                // --length, the --no-* sets and --version as the counter
+                let defaults = lesspass::Profile::default();
+                Ok(CompatMode::LessPass(lesspass::Profile {
+                    lowercase: !args.no_lower,
+                    uppercase: !args.no_upper,
+                    digits: !args.no_digit,
+                    symbols: !args.no_symbol,
+                    length: args.length.map_or(defaults.length, |length| length as usize),
+                    counter: args.version,
+                }))
+            }
+            CliCompat::Spectre => {
+                if args.length.is_some() || args.no_lower || args.no_upper || args.no_digit || args.no_symbol {
+                    return Err("--compat spectre takes --spectre-type instead of --length and --no-*".to_string());
+                }
+                if args.username.is_empty() {
+                    return Err("--compat spectre needs --username, the full name Spectre knows you by".to_string());
+                }
+                Ok(CompatMode::Spectre(match args.spectre_type.unwrap_or(CliSpectreType::Long) {
+                    CliSpectreType::Maximum => spectre::PasswordType::Maximum,
+                    CliSpectreType::Long => spectre::PasswordType::Long,
+                    CliSpectreType::Medium => spectre::PasswordType::Medium,
+                    CliSpectreType::Short => spectre::PasswordType::Short,
+                    CliSpectreType::Basic => spectre::PasswordType::Basic,
+                    CliSpectreType::Pin => spectre::PasswordType::Pin,
+                    CliSpectreType::Name => spectre::PasswordType::Name,
+                    CliSpectreType::Phrase => spectre::PasswordType::Phrase,
+                }))
+            }
+            CliCompat::Sgp => {
+                if args.no_lower || args.no_upper || args.no_digit || args.no_symbol {
+                    return Err("--compat sgp takes no character sets (--no-*)".to_string());
+                }
+                if !args.username.is_empty() || args.version != 1 {
+                    return Err("--compat sgp takes no --username or --version (SuperGenPass has neither)".to_string());
+                }
+                let hash = match args.sgp_hash.unwrap_or(CliSgpHash::Md5) {
+                    CliSgpHash::Md5 => sgp::HashAlgorithm::Md5,
+                    CliSgpHash::Sha512 => sgp::HashAlgorithm::Sha512,
+                };
+                Ok(CompatMode::Sgp { length: args.length.map_or(sgp::DEFAULT_LENGTH, |length| length as usize), hash })
+            }
+        }
+    }
+
+    /// Name of the scheme in verbose and JSON output.
+    fn label(&self) -> &'static str {
+        match self {
+            CompatMode::LessPass(_) => "lesspass",
+            CompatMode::Spectre(_) => "spectre",
+            CompatMode::Sgp { .. } => "sgp",
+        }
+    }
+
+    fn entropy_bits(&self) -> std::result::Result<f64, String> {
+        match self {
+            CompatMode::LessPass(profile) => profile.entropy_bits().map_err(|e| e.to_string()),
+            CompatMode::Spectre(password_type) => Ok(password_type.entropy_bits()),
+            CompatMode::Sgp { length, .. } => sgp::entropy_bits(*length).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Derives the password; `username` is the LessPass login or the Spectre full name.
+    fn generate(&self, master: &str, site: &str, username: &str, version: u32) -> std::result::Result<String, String> {
+        match self {
+            CompatMode::LessPass(profile) => lesspass::generate(master, site, username, profile).map_err(|e| e.to_string()),
+            CompatMode::Spectre(password_type) => {
+                spectre::generate(master, username, site, version, *password_type).map_err(|e| e.to_string())
+            }
+            CompatMode::Sgp { length, hash } => sgp::generate(master, site, *length, *hash).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+fn handle_recovery(mut args: RecoveryArgs) -> Result<i32> {
+    let Some(Resolved { site, master, scheme, algo, factors, .. }) = resolve_inputs(&args.site, &mut args.master_input)? else {
+        return Ok(2);
+    };
+
+    let username_opt = if args.username.is_empty() {
+        None
+    } else {
+        Some(args.username.as_str())
+    };
+
+    if args.verbose {
+        eprintln!(
+            "Deriving recovery codes...\n  site: {}\n  username: {}\n  version: {}\n  count: {}\n  kdf profile: {}",
+            site,
+            username_opt.unwrap_or("<empty>"),
+            args.version,
+            args.count,
+            scheme.label()
+        );
+    }
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = recovery::generate_codes(master_in, &site, username_opt, args.count, args.version);
+    drop(master);
+
+    match result {
+        Ok(codes) => {
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Recovery {
+                    codes: &codes,
+                    site: &site,
+                    username: username_opt.unwrap_or(""),
+                    version: args.version,
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else {
+                for code in &codes {
+                    println!("{}", code);
+                }
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_wireguard(mut args: WireguardArgs) -> Result<i32> {
+    let Some(Resolved { site, master, scheme, algo, factors, .. }) = resolve_inputs(&args.site, &mut args.master_input)? else {
+        return Ok(2);
+    };
+
+    let username_opt = if args.username.is_empty() {
+        None
+    } else {
+        Some(args.username.as_str())
+    };
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = wireguard::derive_keypair(master_in, &site, username_opt, args.version);
+    drop(master);
+
+    match result {
+        Ok(keypair) => {
+            let public_key = keypair.public_key_base64();
+            if args.verbose {
+                eprintln!(
+                    "Deriving WireGuard keypair...\n  site: {}\n  username: {}\n  version: {}\n  public key: {}\n  kdf profile: {}",
+                    site,
+                    username_opt.unwrap_or("<empty>"),
+                    args.version,
+                    public_key,
+            scheme.label()
+        );
+            }
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Wireguard {
+                    private_key: &keypair.private_key_base64(),
+                    public_key: &public_key,
+                    site: &site,
+                    username: username_opt.unwrap_or(""),
+                    version: args.version,
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else if args.public {
+                println!("{}", public_key);
+            } else {
+                println!("{}", keypair.private_key_base64().as_str());
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_totp(mut args: TotpArgs) -> Result<i32> {
+    let Some(Resolved { site, master, scheme, algo, factors, .. }) = resolve_inputs(&args.site, &mut args.master_input)? else {
+        return Ok(2);
+    };
+
+    let username_opt = if args.username.is_empty() {
+        None
+    } else {
+        Some(args.username.as_str())
+    };
+
+    if args.verbose {
+        eprintln!(
+            "Deriving TOTP secret...\n  site: {}\n  username: {}\n  version: {}\n  algorithm: SHA1, {} digits, {}s period\n  kdf profile: {}",
+            site,
+            username_opt.unwrap_or("<empty>"),
+            args.version,
+            totp::DIGITS,
+            totp::PERIOD,
+            scheme.label()
+        );
+    }
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = totp::derive_secret(master_in, &site, username_opt, args.version);
+    drop(master);
+
+    match result {
+        Ok(secret) => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .context("system clock is before the Unix epoch")?
+                .as_secs();
+            let code = totp::format_code(totp::code_at(&secret, now, totp::PERIOD, totp::DIGITS), totp::DIGITS);
+            let remaining = totp::PERIOD - now % totp::PERIOD;
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Totp {
+                    secret: &totp::secret_base32(&secret),
+                    code: &code,
+                    valid_for: remaining,
+                    site: &site,
+                    username: username_opt.unwrap_or(""),
+                    version: args.version,
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else if args.code {
+                if args.verbose {
+                    eprintln!("  valid for: {}s", remaining);
+                }
+                println!("{}", code);
+            } else {
+                println!("{}", totp::secret_base32(&secret).as_str());
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_answer(mut args: AnswerArgs) -> Result<i32> {
+    let Some(Resolved { site, master, scheme, algo, factors, .. }) = resolve_inputs(&args.site, &mut args.master_input)? else {
+        return Ok(2);
+    };
+
+    let username_opt = if args.username.is_empty() {
+        None
+    } else {
+        Some(args.username.as_str())
+    };
+    let question = answer::normalize_question(&args.question);
+
+    if args.verbose {
+        eprintln!(
+            "Deriving answer...\n  site: {}\n  username: {}\n  version: {}\n  question: {}\n  kdf profile: {}",
+            site,
+            username_opt.unwrap_or("<empty>"),
+            args.version,
+            question,
+            scheme.label()
+        );
+    }
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = answer::generate_answer(master_in, &site, username_opt, &question, args.version);
+    drop(master);
+
+    match result {
+        Ok(answer) => {
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Answer {
+                    answer: &answer,
+                    question: &question,
+                    site: &site,
+                    username: username_opt.unwrap_or(""),
+                    version: args.version,
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else {
+                println!("{}", answer);
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_username(mut args: UsernameArgs) -> Result<i32> {
+    // Check the domain before prompting, so a typo doesn't cost a KDF run
+    if let Some(Err(e)) = args.domain.as_deref().map(username::normalize_domain) {
+        return Ok(report_gen_error(e));
+    }
+
+    let Some(Resolved { site, master, scheme, algo, factors, .. }) = resolve_inputs(&args.site, &mut args.master_input)? else {
+        return Ok(2);
+    };
+
+    let style = match args.style {
+        CliUsernameStyle::WordWordDigits => username::Style::WordWordDigits,
+        CliUsernameStyle::WordDigits => username::Style::WordDigits,
+        CliUsernameStyle::WordWord => username::Style::WordWord,
+    };
+
+    if args.verbose {
+        eprintln!(
+            "Deriving username...\n  site: {}\n  version: {}\n  style: {}\n  kdf profile: {}",
+            site,
+            args.version,
+            style.name(),
+            scheme.label()
+        );
+    }
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = username::generate_username(master_in, &site, style, args.version).and_then(|name| {
+        match &args.domain {
+            Some(domain) => username::email_alias(&name, domain),
+            None => Ok(name),
+        }
+    });
+    drop(master);
+
+    match result {
+        Ok(name) => {
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Username {
+                    username: &name,
+                    style: style.name(),
+                    site: &site,
+                    version: args.version,
+                    derivation: output::Derivation::new(scheme, algo),
+                }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else {
+                println!("{}", name);
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_fingerprint(mut args: FingerprintArgs) -> Result<i32> {
+    let Some(Resolved { master, scheme, algo, factors, .. }) =
+        resolve_inputs(fingerprint::FINGERPRINT_SITE, &mut args.master_input)?
+    else {
+        return Ok(2);
+    };
+
+    let master_in = master_input(&master, scheme, algo, &factors);
+    let result = if args.emoji {
+        fingerprint::emoji_fingerprint(master_in)
+    } else {
+        fingerprint::master_fingerprint(master_in)
+    };
+    drop(master);
+
+    match result {
+        Ok(fp) => {
+            if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+                if let Err(e) = output::print(selection, &output::Fingerprint { fingerprint: &fp, derivation: output::Derivation::new(scheme, algo) }) {
+                    eprintln!("invalid input: {}", e);
+                    return Ok(2);
+                }
+            } else {
+                println!("{}", fp);
+            }
+            Ok(0)
+        }
+        Err(e) => Ok(report_gen_error(e)),
+    }
+}
+
+fn handle_verifier_init(mut args: VerifierInitArgs) -> Result<i32> {
+    if !check_persona_name(args.master_input.persona.as_deref()) {
+        return Ok(2);
+    }
+    let Some(path) = verifier::path_for(args.master_input.persona.as_deref()) else {
+        eprintln!("invalid input: cannot locate the config directory (set PWGEN_CONFIG)");
+        return Ok(2);
+    };
+    if !args.force && path.exists() {
+        eprintln!("invalid input: {}", verifier::VerifierError::Exists(path.display().to_string()));
+        return Ok(2);
+    }
+
+    let master = read_master(&mut args.master_input)?;
+    if master.is_empty() {
+        eprintln!("invalid input: master secret must be nonempty");
+        return Ok(2);
+    }
+    let result = verifier::create(master.as_str());
+    drop(master);
+
+    match result.and_then(|v| verifier::save(&path, &v, args.force)) {
+        Ok(()) => {
+            eprintln!("saved to {}", path.display());
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("verifier error: {}", e);
+            Ok(4)
+        }
+    }
+}
+
+fn handle_share_split(mut args: ShareSplitArgs) -> Result<i32> {
+    if args.threshold < 2 || args.threshold > args.shares {
+        eprintln!("invalid input: {}", shamir::ShareError::Counts);
+        return Ok(2);
+    }
+    let master = read_master(&mut args.master_input)?;
+    if master.is_empty() {
+        eprintln!("invalid input: master secret must be nonempty");
+        return Ok(2);
+    }
+    let result = shamir::split(master.as_bytes(), args.threshold, args.shares, &mut rand_core::OsRng);
+    drop(master);
+
+    match result {
+        Ok(shares) => {
+            for share in &shares {
+                println!("{}", zeroize::Zeroizing::new(share.encode()).as_str());
+            }
+            eprintln!(
+                "any {} of these {} shares recover the master (pwgen share combine); give each to a different person",
+                args.threshold, args.shares
+            );
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            Ok(2)
+        }
+    }
+}
+
+fn handle_share_combine() -> Result<i32> {
+    use std::io::{BufRead, IsTerminal};
+
+    if io::stdin().is_terminal() {
+        eprintln!("Enter the shares, one per line, then an empty line:");
+    }
+    let mut shares = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = zeroize::Zeroizing::new(line.context("failed to read from stdin")?);
+        if line.trim().is_empty() {
+            if io::stdin().is_terminal() {
+                break;
+            }
+            continue;
+        }
+        match shamir::Share::parse(&line) {
+            Ok(share) => shares.push(share),
+            Err(e) => {
+                eprintln!("invalid input: share {}: {}", shares.len() + 1, e);
+                return Ok(2);
+            }
+        }
+    }
+
+    let secret = match shamir::combine(&shares) {
+        Ok(secret) => secret,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+    match std::str::from_utf8(&secret) {
+        Ok(master) => {
+            println!("{}", master);
+            Ok(0)
+        }
+        Err(_) => {
+            eprintln!("invalid input: the shares do not combine into a master; were they split together?");
+            Ok(2)
+        }
+    }
+}
+
+/// Reports a `--persona` that cannot name a verifier file (exit code 2).
+fn check_persona_name(persona: Option<&str>) -> bool {
+    match persona {
+        Some(name) if !config::valid_persona_name(name) => {
+            eprintln!("invalid input: --persona must be letters, digits, '-' and '_'");
+            false
+        }
+        _ => true,
+    }
+}
+
+fn handle_verifier_check(mut args: VerifierCheckArgs) -> Result<i32> {
+    if !check_persona_name(args.master_input.persona.as_deref()) {
+        return Ok(2);
+    }
+    let stored = match verifier::path_for(args.master_input.persona.as_deref()).map(|path| verifier::load(&path).map(|v| (path, v))) {
+        Some(Ok((_, Some(stored)))) => stored,
+        Some(Ok((path, None))) => {
+            eprintln!("invalid input: no verifier at {} (run pwgen verifier init)", path.display());
+            return Ok(2);
+        }
+        Some(Err(e)) => {
+            eprintln!("verifier error: {}", e);
+            return Ok(2);
+        }
+        None => {
+            eprintln!("invalid input: cannot locate the config directory (set PWGEN_CONFIG)");
+            return Ok(2);
+        }
+    };
+
+    let master = read_master(&mut args.master_input)?;
+    let result = verifier::verify(&stored, master.as_str());
+    drop(master);
+
+    match result {
+        Ok(true) => {
+            println!("master matches the verifier");
+            Ok(0)
+        }
+        Ok(false) => {
+            println!("master does NOT match the verifier");
+            Ok(2)
+        }
+        Err(e) => {
+            eprintln!("verifier error: {}", e);
+            Ok(2)
+        }
+    }
+}
+
+fn handle_agent(mut args: AgentArgs) -> Result<i32> {
+    let limits = agent::Limits {
+        idle_timeout: args.idle_timeout.map(std::time::Duration::from_secs),
+        max_ttl: args.max_ttl.map(std::time::Duration::from_secs),
+    };
+    // Take the socket from systemd, or bind before prompting, so a second agent fails before
+    // the master is typed
+    let listener = match agent::activated().context("socket activation failed")? {
+        Some(listener) => listener,
+        None => {
+            let Some(socket) = args.socket.take().or_else(agent::default_socket) else {
+                eprintln!("invalid input: cannot choose an agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+                return Ok(2);
+            };
+            agent::bind(&socket).with_context(|| format!("cannot listen on {}", socket.display()))?
+        }
+    };
+    let secrets = if args.locked {
+        None
+    } else {
+        let Some(secrets) = agent_secrets(&mut args.master_input)? else {
+            return Ok(2);
+        };
+        Some(secrets)
+    };
+
+    agent::serve(&listener, secrets, limits).context("agent failed")?;
+    Ok(0)
+}
+
+fn handle_agent_lock(args: AgentLockArgs) -> Result<i32> {
+    let Some(socket) = args.socket.or_else(agent::default_socket) else {
+        eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+        return Ok(2);
+    };
+    match agent::lock(&socket) {
+        Ok(()) => {
+            println!("agent locked");
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("agent error: {}", e);
+            Ok(2)
+        }
+    }
+}
+
+fn handle_agent_unlock(mut args: AgentUnlockArgs) -> Result<i32> {
+    let Some(socket) = args.socket.take().or_else(agent::default_socket) else {
+        eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+        return Ok(2);
+    };
+    let Some(secrets) = agent_secrets(&mut args.master_input)? else {
+        return Ok(2);
+    };
+    match agent::unlock(&socket, &secrets) {
+        Ok(()) => {
+            println!("agent unlocked");
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("agent error: {}", e);
+            Ok(2)
+        }
+    }
+}
+
+/// Reads what the agent derives with: the master, the keyfile and pepper digests and the
+/// Argon2 secret. The KDF itself is chosen per request by the client.
+///
+/// Returns `None` after reporting invalid input (exit code 2).
+fn agent_secrets(input: &mut MasterArgs) -> Result<Option<agent::Secrets>> {
+    if input.yubikey.is_some() || input.argon2_ad.is_some() || input.scope.is_some() {
+        eprintln!("invalid input: --yubikey, --argon2-ad and --scope depend on the request; pass them to pwgen client");
+        return Ok(None);
+    }
+    let Some(Resolved { master, scheme, algo, mut factors, .. }) = resolve_inputs(agent::AGENT_SITE, input)? else {
+        return Ok(None);
+    };
+    // A persona's scope is applied by the client, with the rest of the request
+    factors.scope = None;
+    let secrets = agent::Secrets::new(
+        &master_input(&master, scheme, algo, &factors).kdf_input(),
+        factors.argon2_secret.as_ref().map(|s| s.as_slice()),
+    );
+    drop(master);
+    Ok(Some(secrets))
+}
+
+fn handle_client(args: ClientArgs) -> Result<i32> {
+    let Some(socket) = args.socket.or_else(agent::default_socket) else {
+        eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+        return Ok(2);
+    };
+    let agent = Some(socket);
+    match args.command {
+        ClientCommand::Generate(mut args) => {
+            args.master_input.agent = agent;
+            handle_generate(*args)
+        }
+        ClientCommand::Recovery(mut args) => {
+            args.master_input.agent = agent;
+            handle_recovery(args)
+        }
+        ClientCommand::Wireguard(mut args) => {
+            args.master_input.agent = agent;
+            handle_wireguard(args)
+        }
+        ClientCommand::Totp(mut args) => {
+            args.master_input.agent = agent;
+            handle_totp(args)
+        }
+        ClientCommand::Answer(mut args) => {
+            args.master_input.agent = agent;
+            handle_answer(args)
+        }
+        ClientCommand::Username(mut args) => {
+            args.master_input.agent = agent;
+            handle_username(args)
+        }
+        ClientCommand::Fingerprint(mut args) => {
+            args.master_input.agent = agent;
+            handle_fingerprint(args)
+        }
+        ClientCommand::Type(mut args) => {
+            args.master_input.agent = agent;
+            handle_type(args)
+        }
+    }
+}
+
+#[cfg(feature = "serve")]
+fn handle_serve(mut args: ServeArgs) -> Result<i32> {
+    if let Some(addr) = args.http {
+        if !cfg!(feature = "http") {
+            eprintln!("invalid input: --http requires the http feature");
+            return Ok(2);
+        }
+        // Plain HTTP: off the loopback interface the token and passwords cross the network in the clear
+        if !addr.ip().is_loopback() {
+            eprintln!("invalid input: --http only listens on loopback addresses (127.0.0.1 or ::1)");
+            return Ok(2);
+        }
+    }
+    if args.dbus && !cfg!(all(feature = "dbus", target_os = "linux")) {
+        eprintln!("invalid input: --dbus requires the dbus feature (Linux only)");
+        return Ok(2);
+    }
+    if args.agent {
+        let Some(socket) = args.socket.take().or_else(agent::default_socket) else {
+            eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+            return Ok(2);
+        };
+        args.master_input.agent = Some(socket);
+    }
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    let bus = if args.dbus {
+        Some(dbus::connect().with_context(|| format!("cannot own {} on the session bus", dbus::BUS_NAME))?)
+    } else {
+        None
+    };
+    // Bind before prompting, so a taken port fails before the master is typed
+    #[cfg(feature = "http")]
+    let listener = match args.http {
+        Some(addr) => Some(serve::bind_http(addr).with_context(|| format!("cannot listen on {}", addr))?),
+        None => None,
+    };
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+    // The master is read once; sites and the YubiKey are resolved per request
+    let Some(session) = resolve_session(&mut args.master_input, cfg)? else {
+        return Ok(2);
+    };
+    if !check_served_kdf(&session.scheme) {
+        return Ok(2);
+    }
+
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    if let Some(bus) = bus {
+        dbus::serve(&bus, session, args.master_input).context("D-Bus service failed")?;
+        return Ok(0);
+    }
+    #[cfg(feature = "http")]
+    if let Some(listener) = listener {
+        serve::http(&listener, session, &args.master_input).context("serve failed")?;
+        return Ok(0);
+    }
+    serve::stdio(session, &args.master_input).context("serve failed")?;
+    Ok(0)
+}
+
+/// `generate --requests`: reads the master once and answers a stream of JSON requests.
+#[cfg(feature = "serve")]
+fn handle_requests(mut args: GenerateArgs, source: &str) -> Result<i32> {
+    if source == "-" && args.master_input.master_stdin {
+        eprintln!("invalid input: --requests - and --master-stdin both read stdin");
+        return Ok(2);
+    }
+    // Open the file before the master is read, so a wrong path fails first
+    let reader: Box<dyn io::BufRead> = if source == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match std::fs::File::open(source) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("invalid input: --requests {}: {}", source, e);
+                return Ok(2);
+            }
+        }
+    };
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+    let Some(session) = resolve_session(&mut args.master_input, cfg)? else {
+        return Ok(2);
+    };
+    if !check_served_kdf(&session.scheme) {
+        return Ok(2);
+    }
+    serve::requests(session, &args.master_input, reader, io::stdout().lock()).context("--requests failed")?;
+    Ok(0)
+}
+
+/// Rejects Argon2 costs above `kdf::MAX_SERVED_MEM_KIB` for modes that derive on request.
+#[cfg(feature = "serve")]
+fn check_served_kdf(scheme: &kdf::KdfScheme) -> bool {
+    match scheme {
+        kdf::KdfScheme::Argon2id(params) => match params.check_served() {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                false
+            }
+        },
+        kdf::KdfScheme::Pbkdf2Sha256 { .. } => true,
+    }
+}
+
+#[cfg(not(feature = "serve"))]
+fn handle_requests(_args: GenerateArgs, _source: &str) -> Result<i32> {
+    eprintln!("invalid input: generate --requests requires the serve feature");
+    Ok(2)
+}
+
+#[cfg(not(feature = "serve"))]
+fn handle_serve(_args: ServeArgs) -> Result<i32> {
+    eprintln!("invalid input: pwgen serve requires the serve feature");
+    Ok(2)
+}
+
+#[cfg(feature = "serve")]
+fn handle_native_host(mut args: NativeHostArgs) -> Result<i32> {
+    // The host manifest already limits which extensions may start the host; this also holds
+    // when several extensions share one manifest
+    let caller = native_host::caller(&args.caller);
+    if !args.allow_extension.is_empty()
+        && !caller.is_some_and(|c| args.allow_extension.iter().any(|a| native_host::same_extension(a, c)))
+    {
+        eprintln!("invalid input: extension {} is not allowed (see --allow-extension)", caller.unwrap_or("<unknown>"));
+        return Ok(2);
+    }
+    let Some(socket) = args.socket.take().or_else(agent::default_socket) else {
+        eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+        return Ok(2);
+    };
+    args.master_input.agent = Some(socket);
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+    let Some(session) = resolve_session(&mut args.master_input, cfg)? else {
+        return Ok(2);
+    };
+
+    native_host::run(session, &args.master_input).context("native messaging failed")?;
+    Ok(0)
+}
+
+#[cfg(not(feature = "serve"))]
+fn handle_native_host(_args: NativeHostArgs) -> Result<i32> {
+    eprintln!("invalid input: pwgen native-host requires the serve feature");
+    Ok(2)
+}
+
+fn handle_pinentry(mut args: PinentryArgs) -> Result<i32> {
+    let Some(socket) = args.socket.take().or_else(agent::default_socket) else {
+        eprintln!("invalid input: cannot locate the agent socket (set {} or pass --socket)", agent::SOCKET_ENV);
+        return Ok(2);
+    };
+    args.master_input.agent = Some(socket);
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+    let Some(session) = resolve_session(&mut args.master_input, cfg)? else {
+        return Ok(2);
+    };
+
+    pinentry::run(session, &args.master_input, args.fallback.as_os_str()).context("pinentry failed")?;
+    Ok(0)
+}
+
+fn handle_export_pass(mut args: ExportPassArgs) -> Result<i32> {
+    let Some(store) = args.store.take().or_else(export::default_store) else {
+        eprintln!("invalid input: cannot locate the password store (set PASSWORD_STORE_DIR or pass --store)");
+        return Ok(2);
+    };
+    if !store.join(".gpg-id").is_file() {
+        eprintln!("invalid input: {} is not a pass store (no .gpg-id; run pass init)", store.display());
+        return Ok(2);
+    }
+    let Some(profiles) = load_export_profiles(&args.profiles, true) else {
+        return Ok(2);
+    };
+    let Some(entries) = derive_exports(&mut args.master_input, &profiles)? else {
+        return Ok(2);
+    };
+
+    let summary = export::pass(&store, &entries, args.force)?;
+    eprintln!("exported {} entries to {} ({} skipped)", summary.written, store.display(), summary.skipped);
+    Ok(0)
+}
+
+fn handle_export_csv(mut args: ExportCsvArgs) -> Result<i32> {
+    if let Some(path) = args.output.as_ref().filter(|path| !args.force && path.exists()) {
+        eprintln!("invalid input: {} exists (--force to replace)", path.display());
+        return Ok(2);
+    }
+    let Some(profiles) = load_export_profiles(&args.profiles, false) else {
+        return Ok(2);
+    };
+    let Some(entries) = derive_exports(&mut args.master_input, &profiles)? else {
+        return Ok(2);
+    };
+
+    match &args.output {
+        Some(path) => {
+            let mut file = export::create_private(path, args.force).with_context(|| format!("cannot create {}", path.display()))?;
+            export::csv(&mut file, args.format, &entries).with_context(|| format!("cannot write {}", path.display()))?;
+            eprintln!("exported {} entries to {}", entries.len(), path.display());
+        }
+        None => export::csv(&mut io::stdout().lock(), args.format, &entries).context("cannot write the CSV")?,
+    }
+    Ok(0)
+}
+
+/// Loads a profile file for export, reporting errors; `paths` checks that entry names are
+/// relative paths, as they name files.
+fn load_export_profiles(path: &std::path::Path, paths: bool) -> Option<Vec<profiles::Profile>> {
+    let profiles = match load_profiles(path) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return None;
+        }
+    };
+    if paths {
+        if let Err(e) = profiles.iter().try_for_each(|profile| export::check_entry_name(&profile.entry_name())) {
+            eprintln!("invalid input: {}", e);
+            return None;
+        }
+    }
+    Some(profiles)
+}
+
+/// Reads the master once and derives the passwords of all profiles, reporting errors.
+fn derive_exports<'p>(input: &mut MasterArgs, profiles: &'p [profiles::Profile]) -> Result<Option<Vec<export::Entry<'p>>>> {
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(None);
+        }
+    };
+    let Some(session) = resolve_session(input, cfg)? else {
+        return Ok(None);
+    };
+
+    let entries = export::derive_all(&session, input, profiles);
+    drop(session);
+    match entries {
+        Ok(entries) => Ok(Some(entries)),
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+fn handle_rotate(mut args: RotateArgs) -> Result<i32> {
+    if args.site.trim().is_empty() {
+        eprintln!("invalid input: site must be nonempty");
+        return Ok(2);
+    }
+    let mut profiles = if args.profiles.exists() {
+        match load_profiles(&args.profiles) {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                return Ok(2);
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let index = match profiles::find(&profiles, &args.site, args.username.as_deref()) {
+        Ok(Some(index)) => index,
+        Ok(None) => {
+            profiles.push(profiles::Profile {
+                site: args.site.trim().to_string(),
+                username: args.username.clone().unwrap_or_default(),
+                ..Default::default()
+            });
+            profiles.len() - 1
+        }
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+
+    let current = profiles[index].clone();
+    let version = current.version.unwrap_or(1);
+    let Some(next) = version.checked_add(1) else {
+        eprintln!("invalid input: {} is at the highest version ({})", current.entry_name(), version);
+        return Ok(2);
+    };
+    let rotated = profiles::Profile { version: Some(next), ..current.clone() };
+    let pair = [current, rotated.clone()];
+    let Some(entries) = derive_exports(&mut args.master_input, &pair)? else {
+        return Ok(2);
+    };
+
+    profiles[index] = rotated;
+    save_profiles(&args.profiles, &profiles)?;
+    println!("current (version {}): {}", version, *entries[0].password);
+    println!("new (version {}): {}", next, *entries[1].password);
+    eprintln!("{} is now at version {} in {}", pair[1].entry_name(), next, args.profiles.display());
+    Ok(0)
+}
+
+fn handle_check(args: CheckArgs) -> Result<i32> {
+    // clap's ArgGroup requires one of them
+    let policy = match &args.policy_encoded {
+        Some(encoded) => policy::decode(encoded),
+        None => policy::from_passwordrules(args.passwordrules.as_deref().unwrap_or_default()),
+    };
+    let policy = match policy.and_then(|pol| policy::validate(&pol)) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+
+    // A pasted URL counts as its host, as for generate
+    let site = match (&args.site, policy.no_site) {
+        (Some(site), _) => match site::normalize(site, site::SiteMode::Exact) {
+            Ok(site) => site,
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                return Ok(2);
+            }
+        },
+        (None, false) => String::new(),
+        (None, true) => {
+            eprintln!("invalid input: the policy forbids the site name; give the site with --site");
+            return Ok(2);
+        }
+    };
+
+    let password = read_master_stdin().context("failed to read the password")?;
+    let violations = policy::check_for_site(&policy, password.as_str(), &site);
+    drop(password);
+
+    if violations.is_empty() {
+        println!("password satisfies the policy");
+        return Ok(0);
+    }
+    println!("password does NOT satisfy the policy:");
+    for violation in &violations {
+        println!("  {}", violation);
+    }
+    Ok(2)
+}
+
+fn handle_list(args: ListArgs) -> Result<i32> {
+    let Some(profiles) = load_default_profiles(args.profiles) else {
+        return Ok(2);
+    };
+    match args.format {
+        CliListFormat::Picker => {
+            for profile in &profiles {
+                println!("{}", profiles::picker_line(profile));
+            }
+        }
+        CliListFormat::Text => {
+            let rows: Vec<[String; 3]> = profiles
+                .iter()
+                .map(|p| [p.site.trim().to_string(), p.username.clone(), p.version.unwrap_or(1).to_string()])
+                .collect();
+            let width = |i: usize, header: &str| rows.iter().map(|row| row[i].chars().count()).fold(header.len(), usize::max);
+            let (site_width, username_width) = (width(0, "SITE"), width(1, "USERNAME"));
+            println!("{:<site_width$}  {:<username_width$}  VERSION", "SITE", "USERNAME");
+            for [site, username, version] in &rows {
+                println!("{:<site_width$}  {:<username_width$}  {}", site, username, version);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Loads the profile file named by `path`, else by `profiles` in the config file, reporting
+/// errors.
+fn load_default_profiles(path: Option<std::path::PathBuf>) -> Option<Vec<profiles::Profile>> {
+    let path = match path {
+        Some(path) => path,
+        None => match load_config() {
+            Ok(config::Config { profiles: Some(path), .. }) => path,
+            Ok(_) => {
+                eprintln!("invalid input: no profile file (set profiles in the config file or pass --profiles)");
+                return None;
+            }
+            Err(e) => {
+                eprintln!("config error: {}", e);
+                return None;
+            }
+        },
+    };
+    match load_profiles(&path) {
+        Ok(profiles) => Some(profiles),
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            None
+        }
+    }
+}
+
+/// Reads the site and username from the first line of stdin, a line of
+/// `pwgen list --format picker` (`--site-from-stdin`). Returns the profile for them in the
+/// profile file (`path`, else `profiles` in the config file) if it has one, else a profile with
+/// just the site and username.
+fn read_picked_profile(path: Option<std::path::PathBuf>) -> std::result::Result<profiles::Profile, String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).map_err(|e| format!("--site-from-stdin: {}", e))?;
+    let (site, username) = profiles::parse_picker_line(&line);
+    if site.is_empty() {
+        return Err("--site-from-stdin read no site".to_string());
+    }
+    let picked = profiles::Profile { site: site.to_string(), username: username.to_string(), ..Default::default() };
+
+    let path = match path {
+        Some(path) => path,
+        None => match load_config().map_err(|e| e.to_string())?.profiles {
+            Some(path) => path,
+            None => return Ok(picked),
+        },
+    };
+    let mut list = load_profiles(&path).map_err(|e| e.to_string())?;
+    match profiles::find(&list, &picked.site, Some(&picked.username)).map_err(|e| e.to_string())? {
+        Some(index) => Ok(list.swap_remove(index)),
+        None => Ok(picked),
+    }
+}
+
+fn handle_type(mut args: TypeArgs) -> Result<i32> {
+    if !cfg!(feature = "autotype") {
+        eprintln!("invalid input: pwgen type requires the autotype feature");
+        return Ok(2);
+    }
+    let suffix = match args.suffix.as_deref().map(autotype::parse_suffix).transpose() {
+        Ok(suffix) => suffix.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+    let mut profile = match args.site.take() {
+        Some(site) => profiles::Profile { site, username: std::mem::take(&mut args.username), ..Default::default() },
+        None => match read_picked_profile(args.profiles.take()) {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                return Ok(2);
+            }
+        },
+    };
+    if profile.site.trim().is_empty() {
+        eprintln!("invalid input: site must be nonempty");
+        return Ok(2);
+    }
+    profile.version = args.version.or(profile.version);
+    if args.length.is_some() || args.policy.is_some() || args.passwordrules.is_some() || args.pattern.is_some() {
+        profile.length = args.length;
+        profile.policy = args.policy.take();
+        profile.passwordrules = args.passwordrules.take();
+        profile.pattern = args.pattern.take();
+    }
+
+    let mut typist = match autotype::Typist::connect() {
+        Ok(typist) => typist,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return Ok(4);
+        }
+    };
+    let Some(entries) = derive_exports(&mut args.master_input, std::slice::from_ref(&profile))? else {
+        return Ok(2);
+    };
+    for remaining in (1..=args.countdown).rev() {
+        eprint!("\rTyping into the focused window in {}s... ", remaining);
+        let _ = io::Write::flush(&mut io::stderr());
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    if args.countdown > 0 {
+        eprintln!();
+    }
+    if let Err(e) = typist.type_password(&entries[0].password, &suffix, std::time::Duration::from_millis(args.key_delay)) {
+        eprintln!("error: {}", e);
+        return Ok(4);
+    }
+    Ok(0)
+}
+
+fn handle_backup_sheet(args: BackupSheetArgs) -> Result<i32> {
+    let profiles = match load_profiles(&args.profiles) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    };
+    let mut cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(2);
+        }
+    };
+    if let Some(name) = &args.persona {
+        cfg = match cfg.select_persona(name) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("config error: {}", e);
+                return Ok(2);
+            }
+        };
+    }
+    let kdf = match args.kdf {
+        CliKdf::Argon2id => kdf::KdfScheme::Argon2id(kdf_params(args.kdf_profile, &cfg)),
+        CliKdf::Pbkdf2 if args.kdf_profile.is_some() => {
+            eprintln!("invalid input: --kdf-profile only applies to --kdf argon2id");
+            return Ok(2);
+        }
+        CliKdf::Pbkdf2 => kdf::KdfScheme::pbkdf2(),
+    };
+    let settings = backup_sheet::Settings {
+        kdf,
+        algo: match args.algo_version {
+            CliAlgoVersion::V1 => generator::AlgoVersion::V1,
+            CliAlgoVersion::V2 => generator::AlgoVersion::V2,
+            CliAlgoVersion::V3 => generator::AlgoVersion::V3,
+            #[cfg(feature = "blake3")]
+            CliAlgoVersion::V4 => generator::AlgoVersion::V4,
+            CliAlgoVersion::V5 => generator::AlgoVersion::V5,
+        },
+        site_mode: match args.site_mode {
+            Some(CliSiteMode::Exact) => site::SiteMode::Exact,
+            Some(CliSiteMode::Registrable) => site::SiteMode::Registrable,
+            None => cfg.site_mode.unwrap_or_default(),
+        },
+        persona: cfg.selected_persona.clone(),
+        scope: args.scope.or(cfg.scope).map(|scope| scope.trim().to_lowercase()).filter(|scope| !scope.is_empty()),
+        keyfile: args.with_keyfile || cfg.keyfile.is_some(),
+        pepper: cfg.pepper.is_some()
+            || cfg.pepper_keychain
+            || std::env::var_os(config::PEPPER_ENV).is_some_and(|pepper| !pepper.is_empty()),
+    };
+
+    let today = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .and_then(|now| period::Date::from_unix(now.as_secs()).ok())
+        .map(|date| format!(", {:04}-{:02}-{:02}", date.year, date.month, date.day))
+        .unwrap_or_default();
+    let header = format!("pwgen {}{}", env!("CARGO_PKG_VERSION"), today);
+    let lines = backup_sheet::render(&header, &settings, &profiles);
+
+    match &args.out {
+        Some(path) => {
+            let pdf = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+            let contents = if pdf { backup_sheet::to_pdf(&lines) } else { backup_sheet::to_text(&lines).into_bytes() };
+            std::fs::write(path, contents).with_context(|| format!("cannot write {}", path.display()))?;
+            eprintln!("wrote the backup sheet for {} sites to {}", profiles.len(), path.display());
+        }
+        None => print!("{}", backup_sheet::to_text(&lines)),
+    }
+    Ok(0)
+}
+
+#[cfg(feature = "vectors")]
+fn handle_vectors_export(args: VectorsExportArgs) -> Result<i32> {
+    let vectors = match pwgen::vectors::export() {
+        Ok(vectors) => vectors,
+        Err(e) => return Ok(report_gen_error(e)),
+    };
+    let text = vector_file::render(&vectors);
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("cannot write {}", path.display()))?;
+            eprintln!("wrote {} vectors to {}", vectors.len(), path.display());
+        }
+        None => print!("{}", text),
+    }
+    Ok(0)
+}
+
+#[cfg(not(feature = "vectors"))]
+fn handle_vectors_export(_args: VectorsExportArgs) -> Result<i32> {
+    eprintln!("invalid input: pwgen vectors requires the vectors feature");
+    Ok(2)
+}
+
+#[cfg(feature = "vectors")]
+fn handle_vectors_verify(args: VectorsVerifyArgs) -> Result<i32> {
+    let text = match std::fs::read_to_string(&args.file) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("invalid input: cannot read {}: {}", args.file.display(), e);
+            return Ok(2);
+        }
+    };
+    let vectors = match vector_file::parse(&text) {
+        Ok(vectors) => vectors,
+        Err(e) => {
+            eprintln!("invalid input: {}: {}", args.file.display(), e);
+            return Ok(2);
+        }
+    };
+    let mut failed = 0;
+    for vector in &vectors {
+        let differences = match pwgen::vectors::compute(&vector.inputs) {
+            Ok(actual) => vector_file::diff(&vector.expected, &actual)
+                .into_iter()
+                .map(|(step, expected, actual)| format!("{}: expected {:?}, got {:?}", step, expected, actual))
+                .collect(),
+            Err(e) => vec![format!("cannot derive: {}", e)],
+        };
+        if differences.is_empty() {
+            println!("ok    {}", vector.name);
+        } else {
+            failed += 1;
+            println!("FAIL  {}", vector.name);
+            for difference in differences {
+                println!("        {}", difference);
+            }
+        }
+    }
+    if failed > 0 {
+        println!("{} of {} vectors differ", failed, vectors.len());
+        return Ok(4);
+    }
+    println!("all {} vectors match", vectors.len());
+    Ok(0)
+}
+
+#[cfg(not(feature = "vectors"))]
+fn handle_vectors_verify(_args: VectorsVerifyArgs) -> Result<i32> {
+    eprintln!("invalid input: pwgen vectors requires the vectors feature");
+    Ok(2)
+}
+
+/// Writes the completion script for `args.shell` to stdout. It is generated from the clap
+/// definitions, so subcommands, flags and enum values (charsets, KDF profiles, formats) stay
+/// in step with the build.
+fn handle_completions(args: CompletionsArgs) -> Result<i32> {
+    let script = completions::script(args.shell, Cli::command());
+    io::Write::write_all(&mut io::stdout(), script.as_bytes()).context("failed to write the completion script")?;
+    Ok(0)
+}
+
+/// Prints the site names of the profile file starting with `args.prefix`, one per line.
+fn handle_complete_sites(args: CompleteSitesArgs) -> Result<i32> {
+    let Some(profiles) = load_default_profiles(args.profiles) else {
+        return Ok(2);
+    };
+    for site in completions::site_names(&profiles, &args.prefix) {
+        println!("{}", site);
+    }
+    Ok(0)
+}
+
+fn handle_self_test() -> Result<i32> {
+    let checks = pwgen::selftest::run();
+    let mut failed = 0;
+    for check in &checks {
+        match &check.error {
+            Some(e) => println!("FAIL  {}: {}", check.name, e),
+            None if !check.failed.is_empty() => println!("FAIL  {}: {} differ", check.name, check.failed.join(", ")),
+            None => println!("ok    {}", check.name),
+        }
+        if !check.passed() {
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        println!("self-test failed: {} of {} vectors differ", failed, checks.len());
+        return Ok(4);
+    }
+    println!("self-test passed: {} vectors", checks.len());
+    Ok(0)
+}
+
+fn handle_calibrate(args: CalibrateArgs) -> Result<i32> {
+    let target = std::time::Duration::from_millis(args.target_ms);
+    let (params, elapsed) = match kdf::calibrate(target, args.max_mem_mib * 1024) {
+        Ok(found) => found,
+        Err(e) => {
+            eprintln!("kdf error: {}", e);
+            return Ok(4);
+        }
+    };
+
+    let saved_to = if args.save {
+        match save_kdf_params(&params) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("config error: {}", e);
+                return Ok(2);
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(selection) = output::selection(args.json, args.output, args.field.as_deref()) {
+        if let Err(e) = output::print(selection, &output::Calibrate {
+            mem_kib: params.mem_kib,
+            t_cost: params.t_cost,
+            p_cost: params.p_cost,
+            elapsed_ms: elapsed.as_millis(),
+            target_ms: args.target_ms,
+            kdf_profile: kdf::KdfScheme::from(params).label(),
+            saved: saved_to.is_some(),
+        }) {
+            eprintln!("invalid input: {}", e);
+            return Ok(2);
+        }
+    } else {
+        println!("{} ({} ms, target {} ms)", params, elapsed.as_millis(), args.target_ms);
+        if let Some(path) = &saved_to {
+            eprintln!("saved to {}", path.display());
+        }
+    }
+    Ok(0)
+}
+
+/// Inputs every subcommand needs before deriving anything.
+struct Resolved {
+    site: String,
+    master: kdf::MasterSecret,
+    /// PBKDF2 if `--kdf pbkdf2`, else Argon2id with `--kdf-profile`, else the config file's
+    /// `kdf-params` or `kdf-profile`, else balanced
+    scheme: kdf::KdfScheme,
+    algo: generator::AlgoVersion,
+    factors: Factors,
+    cfg: config::Config,
+}
+
+/// `Resolved` without the site: what a long-running `pwgen serve` keeps between requests.
+/// The YubiKey factor is per site, so it is left unset.
+struct Session {
+    master: kdf::MasterSecret,
+    scheme: kdf::KdfScheme,
+    algo: generator::AlgoVersion,
+    factors: Factors,
+    cfg: config::Config,
+}
+
+/// Optional inputs mixed into the KDF alongside the master secret.
+#[derive(Clone, Default)]
+struct Factors {
+    /// `--keyfile`
+    keyfile: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    /// `$PWGEN_PEPPER`, the keychain or the config file
+    pepper: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    /// The YubiKey's response to the site challenge (`--yubikey`)
+    yubikey: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    /// `--scope`
+    scope: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    /// The organization of `--path`
+    path: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    /// Contents of `--argon2-secret-file`
+    argon2_secret: Option<zeroize::Zeroizing<Vec<u8>>>,
+    /// `--argon2-ad`
+    argon2_ad: Option<String>,
+    /// Socket of the agent holding the master and the factors above (`pwgen client`)
+    agent: Option<std::path::PathBuf>,
+}
+
+/// Normalizes `--site`, loads the config file and reads a nonempty master secret.
+///
+/// Returns `None` after reporting invalid input or a bad config file (exit code 2).
+fn resolve_inputs(site: &str, input: &mut MasterArgs) -> Result<Option<Resolved>> {
+    resolve_scoped_inputs(site, None, input)
+}
+
+/// Like `resolve_inputs`, deriving from the site scoped by a `--path` organization.
+fn resolve_scoped_inputs(site: &str, path: Option<&derivation_path::DerivationPath>, input: &mut MasterArgs) -> Result<Option<Resolved>> {
+    if site.trim().is_empty() {
+        eprintln!("invalid input: --site must be nonempty after trim");
+        return Ok(None);
+    }
+
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(None);
+        }
+    };
+
+    let site = match normalize_site(site, input.site_mode, &cfg) {
+        Ok(site) => match path {
+            Some(path) => path.scoped_site(&site),
+            None => site,
+        },
+        Err(e) => {
+            eprintln!("invalid input: --site: {}", e);
+            return Ok(None);
+        }
+    };
+    let Some(Session { master, scheme, algo, mut factors, cfg }) = resolve_session(input, cfg)? else {
+        return Ok(None);
+    };
+    factors.path = path.map(derivation_path::DerivationPath::digest);
+
+    // Ask the YubiKey last, so a touch prompt follows the master prompt
+    factors.yubikey = match query_yubikey(input.yubikey, &site) {
+        Ok(digest) => digest,
+        Err(()) => {
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(Resolved { site, master, scheme, algo, factors, cfg }))
+}
+
+/// Normalizes a site per `--site-mode`, else the config file, and warns about homographs.
+fn normalize_site(site: &str, mode: Option<CliSiteMode>, cfg: &config::Config) -> std::result::Result<String, site::SiteError> {
+    let site_mode = match mode {
+        Some(CliSiteMode::Exact) => site::SiteMode::Exact,
+        Some(CliSiteMode::Registrable) => site::SiteMode::Registrable,
+        None => cfg.site_mode.unwrap_or_default(),
+    };
+    let site = site::normalize(site, site_mode)?;
+    if let Some(label) = site::homograph_label(&site) {
+        eprintln!("warning: site label '{}' mixes scripts or imitates ASCII letters; check it is the site you mean", label);
+    }
+    Ok(site)
+}
+
+/// Resolves the KDF and reads the factors and a nonempty master secret: all of
+/// `resolve_inputs` but the site and the YubiKey.
+///
+/// Returns `None` after reporting invalid input or a bad config file (exit code 2).
+/// Argon2 costs: `--kdf-profile`, else the config file's `kdf-params`, else its `kdf-profile`.
+fn kdf_params(profile: Option<CliKdfProfile>, cfg: &config::Config) -> kdf::KdfParams {
+    match (profile, cfg.kdf_params) {
+        (Some(profile), _) => cli_kdf_profile(profile).params(),
+        (None, Some(params)) => params,
+        (None, None) => cfg.kdf_profile.unwrap_or_default().params(),
+    }
+}
+
+fn resolve_session(input: &mut MasterArgs, cfg: config::Config) -> Result<Option<Session>> {
+    let Some((cfg, scheme, algo)) = resolve_settings(input, cfg) else {
+        return Ok(None);
+    };
+    let scope = input.scope.as_deref().or(cfg.scope.as_deref()).map(kdf::scope_digest);
+    if let Some(socket) = input.agent.take() {
+        return Ok(resolve_client(input, scheme, algo, scope, cfg, socket));
+    }
+
+    let keyfile = match input.keyfile.as_ref().or(cfg.keyfile.as_ref()) {
+        Some(path) => match std::fs::read(path) {
+            Ok(contents) if contents.is_empty() => {
+                eprintln!("invalid input: keyfile {} is empty", path.display());
+                return Ok(None);
+            }
+            Ok(contents) => {
+                let contents = zeroize::Zeroizing::new(contents);
+                Some(kdf::keyfile_digest(&contents))
+            }
+            Err(e) => {
+                eprintln!("invalid input: cannot read keyfile {}: {}", path.display(), e);
+                return Ok(None);
+            }
+        },
+        None => None,
+    };
+
+    let argon2_secret = match &input.argon2_secret_file {
+        Some(path) => match std::fs::read(path) {
+            Ok(contents) if contents.is_empty() => {
+                eprintln!("invalid input: Argon2 secret file {} is empty", path.display());
+                return Ok(None);
+            }
+            Ok(contents) => Some(zeroize::Zeroizing::new(contents)),
+            Err(e) => {
+                eprintln!("invalid input: cannot read Argon2 secret file {}: {}", path.display(), e);
+                return Ok(None);
+            }
+        },
+        None => None,
+    };
+
+    let pepper = match config::resolve_pepper(&cfg) {
+        Ok(pepper) => pepper.map(|p| kdf::pepper_digest(p.as_bytes())),
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            return Ok(None);
+        }
+    };
+
+    let prompted = input.prompts();
+    let master = read_master(input)?;
+    if master.is_empty() {
+        eprintln!("invalid input: master secret must be nonempty");
+        return Ok(None);
+    }
+
+    if !check_strength(&master, prompted, input.enforce_strength) {
+        return Ok(None);
+    }
+    // The verifier belongs to a personal master, not to a team's
+    if input.team.is_none() {
+        check_verifier(&master, cfg.selected_persona.as_deref());
+    }
+
+    let factors = Factors {
+        keyfile,
+        pepper,
+        scope,
+        argon2_secret,
+        argon2_ad: input.argon2_ad.take(),
+        ..Default::default()
+    };
+
+    // Show the emoji fingerprint before the YubiKey is asked, so a typo is seen right away
+    if prompted && (input.emoji_fingerprint || cfg.emoji_fingerprint) {
+        match fingerprint::emoji_fingerprint(master_input(&master, scheme, algo, &factors)) {
+            Ok(emoji) => eprintln!("Fingerprint: {}", emoji),
+            Err(e) => {
+                return Err(anyhow::anyhow!("emoji fingerprint failed: {}", e));
+            }
+        }
+    }
+
+    Ok(Some(Session { master, scheme, algo, factors, cfg }))
+}
+
+/// Selects the persona and resolves the KDF and algorithm version from the flags and config
+/// file, without reading the master.
+///
+/// Returns `None` after reporting invalid input or an unknown persona (exit code 2).
+fn resolve_settings(input: &MasterArgs, cfg: config::Config) -> Option<(config::Config, kdf::KdfScheme, generator::AlgoVersion)> {
+    let cfg = match &input.persona {
+        Some(name) => match cfg.select_persona(name) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("config error: {}", e);
+                return None;
+            }
+        },
+        None => cfg,
+    };
+    let params = kdf_params(input.kdf_profile, &cfg);
+    let scheme = match input.kdf {
+        CliKdf::Argon2id => kdf::KdfScheme::Argon2id(params),
+        CliKdf::Pbkdf2 if input.kdf_profile.is_some() => {
+            eprintln!("invalid input: --kdf-profile only applies to --kdf argon2id");
+            return None;
+        }
+        CliKdf::Pbkdf2 if input.argon2_secret_file.is_some() || input.argon2_ad.is_some() => {
+            eprintln!("invalid input: --argon2-secret-file and --argon2-ad only apply to --kdf argon2id");
+            return None;
+        }
+        CliKdf::Pbkdf2 => kdf::KdfScheme::pbkdf2(),
+    };
+    if input.argon2_ad.as_ref().is_some_and(|ad| ad.len() > kdf::MAX_AD_LEN) {
+        eprintln!("invalid input: --argon2-ad must be at most {} bytes", kdf::MAX_AD_LEN);
+        return None;
+    }
+    let algo = match input.algo_version {
+        CliAlgoVersion::V1 => generator::AlgoVersion::V1,
+        CliAlgoVersion::V2 => generator::AlgoVersion::V2,
+        CliAlgoVersion::V3 => generator::AlgoVersion::V3,
+        #[cfg(feature = "blake3")]
+        CliAlgoVersion::V4 => generator::AlgoVersion::V4,
+        CliAlgoVersion::V5 => generator::AlgoVersion::V5,
+    };
+    if input.scope.as_ref().is_some_and(|scope| scope.trim().is_empty()) {
+        eprintln!("invalid input: --scope must be nonempty after trim");
+        return None;
+    }
+    Some((cfg, scheme, algo))
+}
+
+/// The rest of `resolve_session` for `pwgen client`: the master secret, keyfile, pepper and
+/// Argon2 secret stay with the agent, so only the per-request factors are resolved here.
+fn resolve_client(
+    input: &mut MasterArgs,
+    scheme: kdf::KdfScheme,
+    algo: generator::AlgoVersion,
+    scope: Option<[u8; kdf::KEYFILE_DIGEST_LEN]>,
+    cfg: config::Config,
+    socket: std::path::PathBuf,
+) -> Option<Session> {
+    if !input.prompts() || input.master_prompt || input.keyfile.is_some() || input.argon2_secret_file.is_some() {
+        eprintln!("invalid input: with the agent, the master secret, --keyfile and --argon2-secret-file are given to pwgen agent");
+        return None;
+    }
+    let factors = Factors {
+        scope,
+        argon2_ad: input.argon2_ad.take(),
+        agent: Some(socket),
+        ..Default::default()
+    };
+    // The agent prepends the master to the client's KDF input, so the local part is empty
+    Some(Session { master: kdf::MasterSecret::default(), scheme, algo, factors, cfg })
+}
+
+/// Digest of the YubiKey's response to the site challenge, if `--yubikey` was given.
+/// `Err` after reporting the failure.
+fn query_yubikey(slot: Option<u8>, site: &str) -> std::result::Result<Option<[u8; kdf::KEYFILE_DIGEST_LEN]>, ()> {
+    let Some(slot) = slot else {
+        return Ok(None);
+    };
+    match yubikey::challenge_response(slot, &yubikey::challenge(site)) {
+        Ok(response) => Ok(Some(yubikey::response_digest(&response))),
+        Err(e) => {
+            eprintln!("yubikey error: {}", e);
+            Err(())
+        }
+    }
+}
+
+/// Warns when the master typed at the prompt is guessable; with `--enforce-strength`, refuses
+/// a guessable master however it was given. Returns `false` to refuse.
+#[cfg(feature = "strength")]
+fn check_strength(master: &kdf::MasterSecret, prompted: bool, enforce: bool) -> bool {
+    if !prompted && !enforce {
+        return true;
+    }
+    let estimate = strength::estimate(master.as_str());
+    if estimate.score >= strength::MIN_SCORE {
+        return true;
+    }
+    let detail = format!(
+        "score {}/4, about 10^{:.0} guesses{}",
+        estimate.score,
+        estimate.guesses_log10,
+        estimate.warning.map(|w| format!("; {}", w)).unwrap_or_default()
+    );
+    if enforce {
+        eprintln!("invalid input: master secret is too guessable ({})", detail);
+        false
+    } else {
+        eprintln!("warning: master secret is guessable ({})", detail);
+        true
+    }
+}
+
+#[cfg(not(feature = "strength"))]
+fn check_strength(_master: &kdf::MasterSecret, _prompted: bool, enforce: bool) -> bool {
+    if enforce {
+        eprintln!("invalid input: --enforce-strength requires the strength feature");
+    }
+    !enforce
+}
 
-    /// Maximum length
-    #[arg(long, value_name = "INT", default_value_t = 16)]
-    max: u32,
+/// Warns when a verifier is stored (see `pwgen verifier init`) and `master` does not match it.
+fn check_verifier(master: &kdf::MasterSecret, persona: Option<&str>) {
+    let Some(path) = verifier::path_for(persona) else {
+        return;
+    };
+    match verifier::load(&path).and_then(|stored| stored.map(|v| verifier::verify(&v, master.as_str())).transpose()) {
+        Ok(Some(false)) => eprintln!("warning: master secret does not match the stored verifier; check for a typo"),
+        Ok(_) => {}
+        Err(e) => eprintln!("warning: verifier not checked: {}", e),
+    }
+}
 
-    /// Allowed character sets (comma-separated)
-    #[arg(long = "allow", value_delimiter = ',', value_enum)]
-    allow_sets: Vec<CliCharset>,
+/// Where site keys are derived: in this process, or in the agent (`pwgen client`).
+#[derive(Copy, Clone)]
+enum Stretch<'a> {
+    Local(kdf::KdfScheme),
+    Agent(agent::Remote<'a>),
+}
 
-    /// Forced character sets to include (comma-separated; subset of allow)
-    #[arg(long = "force", value_delimiter = ',', value_enum)]
-    force_sets: Vec<CliCharset>,
+impl kdf::Kdf for Stretch<'_> {
+    fn derive(&self, master: &[u8], site: &str) -> std::result::Result<[u8; kdf::KDF_OUT_LEN], kdf::KdfError> {
+        match self {
+            Stretch::Local(scheme) => scheme.derive(master, site),
+            Stretch::Agent(remote) => remote.derive(master, site),
+        }
+    }
 
-    /// Disallow lowercase letters
-    #[arg(long = "no-lower")]
-    no_lower: bool,
+    fn derive_with_extras(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &kdf::KdfExtras<'_>,
+    ) -> std::result::Result<[u8; kdf::KDF_OUT_LEN], kdf::KdfError> {
+        match self {
+            Stretch::Local(scheme) => scheme.derive_with_extras(master, site, extras),
+            Stretch::Agent(remote) => remote.derive_with_extras(master, site, extras),
+        }
+    }
 
-    /// Disallow uppercase letters
-    #[arg(long = "no-upper")]
-    no_upper: bool,
+    fn derive_locked(
+        &self,
+        master: &[u8],
+        site: &str,
+        extras: &kdf::KdfExtras<'_>,
+    ) -> std::result::Result<pwgen::memlock::Locked<[u8; kdf::KDF_OUT_LEN]>, kdf::KdfError> {
+        match self {
+            Stretch::Local(scheme) => scheme.derive_locked(master, site, extras),
+            Stretch::Agent(remote) => remote.derive_locked(master, site, extras),
+        }
+    }
 
-    /// Disallow digits
-    #[arg(long = "no-digit")]
-    no_digit: bool,
+    fn context_id(&self) -> String {
+        match self {
+            Stretch::Local(scheme) => scheme.context_id(),
+            Stretch::Agent(remote) => remote.context_id(),
+        }
+    }
+}
 
-    /// Disallow symbols
-    #[arg(long = "no-symbol")]
-    no_symbol: bool,
+/// Combines the master secret with the resolved KDF, algorithm version and extra factors.
+fn master_input<'a>(
+    master: &'a kdf::MasterSecret,
+    scheme: kdf::KdfScheme,
+    algo: generator::AlgoVersion,
+    factors: &'a Factors,
+) -> kdf::MasterInput<'a, Stretch<'a>> {
+    let stretch = match &factors.agent {
+        Some(socket) => Stretch::Agent(agent::Remote { socket, scheme }),
+        None => Stretch::Local(scheme),
+    };
+    let mut input = kdf::MasterInput::new(master.as_str(), stretch).with_algo(algo);
+    if let Some(digest) = factors.keyfile {
+        input = input.with_keyfile(digest);
+    }
+    if let Some(digest) = factors.pepper {
+        input = input.with_pepper(digest);
+    }
+    if let Some(digest) = factors.yubikey {
+        input = input.with_yubikey(digest);
+    }
+    if let Some(digest) = factors.scope {
+        input = input.with_scope(digest);
+    }
+    if let Some(digest) = factors.path {
+        input = input.with_path(digest);
+    }
+    if let Some(secret) = &factors.argon2_secret {
+        input = input.with_argon2_secret(secret);
+    }
+    if let Some(ad) = &factors.argon2_ad {
+        input = input.with_argon2_ad(ad.as_bytes());
+    }
+    input
+}
 
-    /// Rotation/version number
-    #[arg(long, value_name = "UINT", default_value_t = 1)]
-    version: u32,
+/// Prints a generation error and returns its exit code.
+fn report_gen_error(err: GenError) -> i32 {
+    match err {
+        GenError::Policy(e) => { eprintln!("policy error: {}", e); 2 }
+        GenError::Kdf(e) => { eprintln!("kdf error: {}", e); 4 }
+        GenError::Prng(e) => { eprintln!("prng error: {}", e); 4 }
+        GenError::Pattern(e) => { eprintln!("invalid input: {}", e); 2 }
+        GenError::Format(e) => { eprintln!("invalid input: {}", e); 2 }
+        GenError::InvalidInput(msg) => { eprintln!("invalid input: {}", msg); 2 }
+    }
+}
 
-    /// Print a JSON object with details instead of plain password
-    #[arg(long)]
-    json: bool,
+/// What `generate` derives: a charset policy, a template pattern or an encoded token.
+enum GenMode {
+    Policy(policy::Policy),
+    Pattern(String),
+    Token(format::Format, usize),
+}
 
-    /// Print extra info (to stderr)
-    #[arg(long)]
-    verbose: bool,
+/// The mode of a `generate` request with its description (see `GenMode::describe`) and
+/// estimated entropy.
+struct GenPlan {
+    mode: GenMode,
+    params_key: &'static str,
+    params: String,
+    entropy: f64,
 }
 
-fn main() {
-    // Handle version flags before clap parsing
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        match args[1].as_str() {
-            "-v" => {
-                println!("{}", env!("CARGO_PKG_VERSION"));
-                process::exit(0);
-            }
-            _ => {}
-        }
+/// Picks the mode from the generate flags and checks it against the entropy floor
+/// (`--min-bits`, then the config file).
+///
+/// Errors are user-facing messages; the caller reports them as invalid input.
+fn plan_generation(args: &GenerateArgs, cfg: &config::Config) -> std::result::Result<GenPlan, String> {
+    if args.compat.is_some() {
+        return Err("--compat is only available to pwgen generate".to_string());
     }
-    
-    let cli = Cli::parse();
-    let exit_code = match run(cli) {
-        Ok(code) => code,
-        Err(err) => {
-            eprintln!("error: {:#}", err);
-            4
-        }
+    if args.version_policy.is_some() {
+        return Err("--version-policy is only available to pwgen generate".to_string());
+    }
+    if args.path.is_some() {
+        return Err("--path is only available to pwgen generate".to_string());
+    }
+    if args.check_breach {
+        return Err("--check-breach is only available to pwgen generate".to_string());
+    }
+    if args.dry_run {
+        return Err("--dry-run is only available to pwgen generate".to_string());
+    }
+    if args.requests.is_some() {
+        return Err("--requests is only available to pwgen generate".to_string());
+    }
+    if args.site_from_stdin {
+        return Err("--site-from-stdin is only available to pwgen generate".to_string());
+    }
+    // Pattern and token modes replace the policy entirely; otherwise build one from the flags
+    let mode = match (&args.pattern, args.format) {
+        (Some(pattern), _) => GenMode::Pattern(pattern.clone()),
+        (None, Some(fmt)) => GenMode::Token(cli_format(fmt), args.bytes),
+        (None, None) => GenMode::Policy(build_policy(args)?),
     };
-    process::exit(exit_code);
+    let (params_key, params) = mode.describe();
+    let entropy = mode.entropy_bits().map_err(|e| e.to_string())?;
+
+    check_entropy_floor(params_key, entropy, args.min_bits, cfg)?;
+    Ok(GenPlan { mode, params_key, params, entropy })
 }
 
-fn run(cli: Cli) -> Result<i32> {
-    match cli.command {
-        Some(Commands::Generate(args)) => handle_generate(args),
-        Some(Commands::Help) => {
-            print_long_help();
-            Ok(0)
+/// Refuses to generate below the requested entropy floor (`--min-bits`, then the config file).
+fn check_entropy_floor(what: &str, entropy: f64, min_bits: Option<u32>, cfg: &config::Config) -> std::result::Result<(), String> {
+    match min_bits.or(cfg.min_bits) {
+        Some(floor) if entropy < f64::from(floor) => Err(format!(
+            "{} yields {:.1} bits of entropy, below the required {} (raise the length or allow more characters)",
+            what, entropy, floor
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl GenMode {
+    /// Derives the password (or token) for `site`.
+    fn generate<'m, K: kdf::Kdf>(
+        &self,
+        master: kdf::MasterInput<'m, K>,
+        site: &str,
+        username: Option<&str>,
+        version: u32,
+    ) -> std::result::Result<String, GenError> {
+        match self {
+            GenMode::Policy(pol) => generator::generate_password(master, site, username, pol, version),
+            GenMode::Pattern(pattern) => generator::generate_from_pattern(master, site, username, pattern, version),
+            GenMode::Token(fmt, bytes) => generator::generate_token(master, site, username, *fmt, *bytes, version),
         }
-        None => {
-            print_short_help();
-            Ok(0)
+    }
+
+    /// Returns the JSON/verbose key and canonical value describing the mode.
+    fn describe(&self) -> (&'static str, String) {
+        match self {
+            GenMode::Policy(pol) => ("policy", policy::encode(pol)),
+            GenMode::Pattern(pattern) => ("pattern", pattern.clone()),
+            GenMode::Token(fmt, bytes) => ("format", format::params(*fmt, *bytes)),
         }
     }
-}
 
-fn print_short_help() {
-    println!("pwgen - Deterministic password generator using Argon2id and HKDF");
-    println!();
-    println!("Usage:");
-    println!("  pwgen                    Show this help message");
-    println!("  pwgen help               Show detailed help");
-    println!("  pwgen -v                 Show the pwgen version");
-    println!("  pwgen generate --site X  Generate a password (prompts for master secret)");
-    println!();
-    println!("For detailed help, run: pwgen help");
+    /// Returns the estimated entropy of the output (see `policy::entropy_bits`).
+    fn entropy_bits(&self) -> std::result::Result<f64, pattern::PatternError> {
+        match self {
+            GenMode::Policy(pol) => Ok(policy::entropy_bits(pol)),
+            GenMode::Pattern(p) => pattern::parse(p).map(|tokens| pattern::entropy_bits(&tokens)),
+            GenMode::Token(_, bytes) => Ok(*bytes as f64 * 8.0),
+        }
+    }
 }
 
-fn print_long_help() {
-    println!("pwgen - Deterministic password generator using Argon2id and HKDF");
-    println!();
-    println!("Usage:");
-    println!("  pwgen help               Show this detailed help");
-    println!("  pwgen -v                 Show the pwgen version");
-    println!("  pwgen generate --site X  Generate a password (prompts for master secret)");
-    println!();
-    println!("Generate options:");
+/// Parses generate flags (without the subcommand name) given other than on the command line.
+///
+/// Errors are the first line of clap's message, without the `error: ` prefix or usage hint.
+fn parse_generate_args<I, T>(argv: I) -> std::result::Result<GenerateArgs, String>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    use clap::FromArgMatches;
 
-    // Build the generate command with all its arguments to show detailed help
-    let mut generate_cmd = {
-        let cmd = clap::Command::new("generate")
-            .about("Generate a password")
-            .disable_help_flag(true);
-        GenerateArgs::augment_args(cmd)
-    };
-    
-    // Print the help for the generate subcommand
-    generate_cmd.print_help().unwrap_or_else(|_| {
-        // Fallback if printing fails
-        eprintln!("pwgen generate - Generate a password");
-        eprintln!();
-        eprintln!("Usage: pwgen generate --site <STRING> [OPTIONS]");
-        eprintln!();
-        eprintln!("For full documentation, see the README.md file.");
-    });
+    let command = GenerateArgs::augment_args(clap::Command::new("generate").no_binary_name(true).disable_help_flag(true));
+    command.try_get_matches_from(argv).and_then(|matches| GenerateArgs::from_arg_matches(&matches)).map_err(|e| {
+        let rendered = e.render().to_string();
+        let line = rendered.lines().next().unwrap_or_default();
+        line.strip_prefix("error: ").unwrap_or(line).to_string()
+    })
 }
 
-fn handle_generate(args: GenerateArgs) -> Result<i32> {
-    // Normalize and validate site
-    let site = args.site.trim().to_lowercase();
-    if site.is_empty() {
-        eprintln!("invalid input: --site must be nonempty after trim");
-        return Ok(2);
-    }
+/// Derives the password for generate flags given other than on the command line (pinentry
+/// keys, profile files), normalizing the site and asking the YubiKey like `resolve_inputs`.
+///
+/// Returns the normalized site and the password; errors are user-facing messages.
+fn derive_password(
+    session: &Session,
+    input: &MasterArgs,
+    argv: Vec<String>,
+) -> std::result::Result<(String, zeroize::Zeroizing<String>), String> {
+    let args = parse_generate_args(argv)?;
+    let GenPlan { mode, .. } = plan_generation(&args, &session.cfg)?;
+    let site = normalize_site(&args.site, input.site_mode, &session.cfg).map_err(|e| e.to_string())?;
+    let yubikey = query_yubikey(input.yubikey, &site).map_err(|()| "YubiKey challenge-response failed".to_string())?;
 
-    // Resolve master secret: default to prompt if no method specified
-    let mut master = match (args.master, args.master_prompt, args.master_stdin) {
-        (Some(m), false, false) => m,
-        (None, true, false) => read_master_prompt()?,
-        (None, false, true) => read_master_stdin()?,
-        (None, false, false) => read_master_prompt()?, // Default: prompt when no method specified
-        _ => unreachable!("clap ArgGroup enforces at most one method"),
-    };
+    let factors = Factors { yubikey, ..session.factors.clone() };
+    let username = Some(args.username.as_str()).filter(|u| !u.is_empty());
+    let master = master_input(&session.master, session.scheme, session.algo, &factors);
+    let password = mode.generate(master, &site, username, args.version).map_err(|e| e.to_string())?;
+    Ok((site, zeroize::Zeroizing::new(password)))
+}
 
-    if master.is_empty() {
-        master.zeroize();
-        eprintln!("invalid input: master secret must be nonempty");
-        return Ok(2);
+/// Builds and validates the policy from the generate flags.
+///
+/// Errors are user-facing messages; the caller reports them as invalid input.
+fn build_policy(args: &GenerateArgs) -> std::result::Result<policy::Policy, String> {
+    // An encoded policy or site-published rules replace the individual policy flags
+    if let Some(encoded) = &args.policy_encoded {
+        let pol = policy::decode(encoded).map_err(|e| e.to_string())?;
+        return policy::validate(&pol).map_err(|e| e.to_string());
+    }
+    if let Some(rules) = &args.passwordrules {
+        let pol = policy::from_passwordrules(rules).map_err(|e| e.to_string())?;
+        return policy::validate(&pol).map_err(|e| e.to_string());
     }
 
     // Determine length constraints (CLI input shape validation only)
-    let (_length, min, max) = normalize_length(args.length, args.min, args.max).map_err(|e| {
-        eprintln!("invalid input: {}", e);
-        anyhow!(e)
-    })?;
+    let (_length, min, max) = normalize_length(args.length, args.min, args.max)?;
 
     // Determine allowed and forced sets (CLI input shape validation only)
-    let (allowed, forced) = normalize_policy_sets(
+    let sets = normalize_policy_sets(
         &args.allow_sets,
         &args.force_sets,
+        &args.classes,
         args.no_lower,
         args.no_upper,
         args.no_digit,
         args.no_symbol,
-    )
-    .map_err(|e| {
-        eprintln!("invalid input: {}", e);
-        anyhow!(e)
-    })?;
+    )?;
+
+    // Excluded characters must be printable ASCII (CLI input shape validation only)
+    let exclude = normalize_exclude(args.exclude.as_deref())?;
 
     // Convert CLI inputs to Policy, handling u32 -> u8 conversion safely
     // All policy invariant validation will be done by policy::validate()
-    let pol = match cli_to_policy(min, max, allowed, forced) {
-        Ok(p) => p,
-        Err(e) => {
-            master.zeroize();
-            eprintln!("invalid input: {}", e);
-            return Ok(2);
-        }
+    let pol = policy::Policy {
+        exclude,
+        no_ambiguous: args.no_ambiguous,
+        layout_safe: args.layout_safe,
+        letter_first: args.letter_first,
+        letter_last: args.letter_last,
+        max_consecutive: if args.no_repeat { Some(1) } else { args.max_consecutive },
+        max_sequential: args.max_sequential,
+        no_words: args.no_words,
+        no_site: args.no_site,
+        min_counts: set_counts_to_array(&args.min_counts),
+        max_counts: set_caps_to_array(&args.max_counts),
+        classes: sets.classes,
+        allow_extended: sets.allowed_extended,
+        force_extended: sets.forced_extended,
+        ..cli_to_policy(min, max, sets.allowed, sets.forced)?
     };
 
     // Validate policy - this is the single source of truth for policy invariants
-    let pol = match policy::validate(&pol) {
-        Ok(p) => p,
-        Err(e) => {
-            master.zeroize();
-            eprintln!("invalid input: {}", e);
-            return Ok(2);
-        }
-    };
-
-    let username_opt = if args.username.is_empty() {
-        None
-    } else {
-        Some(args.username.as_str())
-    };
-
-    if args.verbose {
-        let pol_enc = policy::encode(&pol);
-        eprintln!(
-            "Generating password...\n  site: {}\n  username: {}\n  version: {}\n  policy: {}",
-            site,
-            username_opt.unwrap_or("<empty>"),
-            args.version,
-            pol_enc
-        );
-    }
-
-    let result = generator::generate_password(&master, &site, username_opt, &pol, args.version);
-
-    // Zeroize master ASAP after generation call returns
-    master.zeroize();
-
-    match result {
-        Ok(password) => {
-            if args.json {
-                // Manually compose a single-line JSON
-                let length_out = password.chars().count();
-                let username_json = username_opt.unwrap_or("");
-                let policy_str = policy::encode(&pol);
-                let algo_version = 1; // placeholder for algorithm versioning
-                println!(
-                    "{{\"password\":\"{}\",\"length\":{},\"site\":\"{}\",\"username\":\"{}\",\"version\":{},\"policy\":\"{}\",\"algo_version\":{}}}",
-                    escape_json_string(&password),
-                    length_out,
-                    escape_json_string(&site),
-                    escape_json_string(username_json),
-                    args.version,
-                    escape_json_string(&policy_str),
-                    algo_version
-                );
-            } else {
-                println!("{}", password);
-            }
-            Ok(0)
-        }
-        Err(GenError::Policy(e)) => { eprintln!("policy error: {}", e); Ok(2) }
-        Err(GenError::Kdf(e)) => { eprintln!("kdf error: {}", e); Ok(4) }
-        Err(GenError::Prng(e)) => { eprintln!("prng error: {}", e); Ok(4) }
-        Err(GenError::InvalidInput(msg)) => { eprintln!("invalid input: {}", msg); Ok(2) }
-    }
+    policy::validate(&pol).map_err(|e| e.to_string())
 }
 
 /// Converts CLI length inputs to normalized form.
@@ -334,29 +4251,53 @@ fn normalize_length(length: Option<u32>, min: u32, max: u32) -> std::result::Res
     Ok((None, min, max))
 }
 
-/// Converts CLI charset inputs to normalized boolean arrays.
+/// Allowed and forced fixed and extended sets, and the named classes of a policy.
+struct PolicySets {
+    allowed: [bool; 4],
+    forced: [bool; 4],
+    allowed_extended: [bool; 4],
+    forced_extended: [bool; 4],
+    classes: Vec<policy::Class>,
+}
+
+/// Converts CLI charset inputs to normalized boolean arrays and the named classes they list.
 /// 
 /// This function performs basic CLI input shape validation (early UX feedback).
 /// Actual policy invariant validation (allow nonempty, force ⊆ allow) is done by `policy::validate()`.
 fn normalize_policy_sets(
-    allow_list: &[CliCharset],
-    force_list: &[CliCharset],
+    allow_list: &[String],
+    force_list: &[String],
+    defined: &[(String, String)],
     no_lower: bool,
     no_upper: bool,
     no_digit: bool,
     no_symbol: bool,
-) -> std::result::Result<([bool;4], [bool;4]), String> {
+) -> std::result::Result<PolicySets, String> {
+    // Split the lists into the fixed sets, extended sets and named classes
+    let split = |list: &[String]| -> ([bool; 4], [bool; 4], Vec<String>) {
+        let mut flags = [false; 4];
+        let mut extended = [false; 4];
+        let mut names = Vec::new();
+        for name in list.iter().map(|name| name.trim()) {
+            if let Some(set) = policy::Charset::ALL.iter().find(|set| set.name() == name) {
+                flags[set.index()] = true;
+            } else if let Some(set) = policy::extended_from_name(name) {
+                extended[set.index()] = true;
+            } else {
+                names.push(name.to_string());
+            }
+        }
+        (flags, extended, names)
+    };
+    let (allow_flags, allowed_extended, allow_names) = split(allow_list);
+    let (forced, forced_extended, force_names) = split(force_list);
+
     // Start with defaults = all allowed
     let mut allowed = [true, true, true, true];
 
     // Apply explicit allow list if provided
     if !allow_list.is_empty() {
-        allowed = [
-            allow_list.contains(&CliCharset::Lower),
-            allow_list.contains(&CliCharset::Upper),
-            allow_list.contains(&CliCharset::Digit),
-            allow_list.contains(&CliCharset::Symbol),
-        ];
+        allowed = allow_flags;
     }
 
     // Apply shorthand toggles to allowed
@@ -374,61 +4315,297 @@ fn normalize_policy_sets(
     }
 
     // Early UX feedback - full validation in policy::validate()
-    if !allowed.iter().any(|&b| b) {
+    if !allowed.iter().any(|&b| b) && !allowed_extended.iter().any(|&b| b) && allow_names.is_empty() {
         return Err("allowed sets cannot be empty".to_string());
     }
 
-    let forced = [
-        force_list.contains(&CliCharset::Lower),
-        force_list.contains(&CliCharset::Upper),
-        force_list.contains(&CliCharset::Digit),
-        force_list.contains(&CliCharset::Symbol),
-    ];
-
     // Early UX feedback - full validation in policy::validate()
-    if (forced[0] && !allowed[0]) || (forced[1] && !allowed[1]) || (forced[2] && !allowed[2]) || (forced[3] && !allowed[3]) {
+    if (forced[0] && !allowed[0]) || (forced[1] && !allowed[1]) || (forced[2] && !allowed[2]) || (forced[3] && !allowed[3])
+        || (0..4).any(|i| forced_extended[i] && !allowed_extended[i])
+        || force_names.iter().any(|name| !allow_names.contains(name))
+    {
         return Err("forced sets must be subset of allowed".to_string());
     }
 
-    Ok((allowed, forced))
+    // Classes defined with --class take precedence over the built-in named classes
+    let mut classes: Vec<policy::Class> = Vec::new();
+    for name in &allow_names {
+        if classes.iter().any(|class| class.name == *name) {
+            continue;
+        }
+        let force = force_names.contains(name);
+        let class = match defined.iter().rev().find(|(defined, _)| defined == name) {
+            Some((_, chars)) => policy::Class { name: name.clone(), chars: chars.as_bytes().to_vec(), force },
+            None => match policy::named_class(name) {
+                Some(named) => policy::Class::from_provider(&named, force),
+                None => {
+                    return Err(format!(
+                        "unknown character set '{}' (expected lower, upper, digit, symbol, {}, {} or a --class name)",
+                        name,
+                        policy::Extended::ALL.iter().map(|set| set.name()).collect::<Vec<_>>().join(", "),
+                        policy::NAMED_CLASSES.iter().map(policy::CharsetProvider::name).collect::<Vec<_>>().join(", ")
+                    ))
+                }
+            },
+        };
+        classes.push(class);
+    }
+
+    Ok(PolicySets { allowed, forced, allowed_extended, forced_extended, classes })
+}
+
+/// Parses a `NAME=CHARS` class definition such as `vowels=aeiou`.
+fn parse_class(input: &str) -> std::result::Result<(String, String), String> {
+    let (name, chars) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=CHARS, got '{}'", input))?;
+    if chars.is_empty() || !chars.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(format!("class characters must be printable ASCII, got '{}'", chars));
+    }
+    Ok((name.trim().to_string(), chars.to_string()))
+}
+
+/// Parses a `SET:N` pair such as `digit:2`.
+fn parse_set_count(input: &str) -> std::result::Result<(CliCharset, u8), String> {
+    let (name, count) = input
+        .split_once(':')
+        .ok_or_else(|| format!("expected SET:N, got '{}'", input))?;
+    let set = CliCharset::from_str(name.trim(), true)?;
+    let count = count
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|&n| n <= 128)
+        .ok_or_else(|| format!("count must be within [0,128], got '{}'", count))?;
+    Ok((set, count))
+}
+
+/// Collects `SET:N` pairs into the fixed lower, upper, digit, symbol order (last one wins).
+fn set_counts_to_array(pairs: &[(CliCharset, u8)]) -> [u8; 4] {
+    let mut counts = [0u8; 4];
+    for &(set, n) in pairs {
+        counts[cli_charset_index(set)] = n;
+    }
+    counts
+}
+
+/// Like `set_counts_to_array`, but sets without a pair stay unlimited.
+fn set_caps_to_array(pairs: &[(CliCharset, u8)]) -> [Option<u8>; 4] {
+    let mut caps = [None; 4];
+    for &(set, n) in pairs {
+        caps[cli_charset_index(set)] = Some(n);
+    }
+    caps
+}
+
+fn cli_kdf_profile(profile: CliKdfProfile) -> kdf::KdfProfile {
+    match profile {
+        CliKdfProfile::Fast => kdf::KdfProfile::Fast,
+        CliKdfProfile::Balanced => kdf::KdfProfile::Balanced,
+        CliKdfProfile::Paranoid => kdf::KdfProfile::Paranoid,
+    }
+}
+
+fn cli_format(fmt: CliFormat) -> format::Format {
+    match fmt {
+        CliFormat::Hex => format::Format::Hex,
+        CliFormat::Base64url => format::Format::Base64Url,
+        CliFormat::Base58 => format::Format::Base58,
+    }
+}
+
+fn cli_charset_index(set: CliCharset) -> usize {
+    match set {
+        CliCharset::Lower => 0,
+        CliCharset::Upper => 1,
+        CliCharset::Digit => 2,
+        CliCharset::Symbol => 3,
+    }
+}
+
+/// Converts the `--exclude` string to raw bytes.
+///
+/// Only printable ASCII is accepted; characters outside the fixed sets are dropped
+/// later by `policy::validate()`.
+fn normalize_exclude(exclude: Option<&str>) -> std::result::Result<Vec<u8>, String> {
+    let chars = exclude.unwrap_or("");
+    if !chars.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err("--exclude must contain only printable ASCII characters".to_string());
+    }
+    Ok(chars.as_bytes().to_vec())
+}
+
+#[cfg(feature = "config")]
+fn load_config() -> std::result::Result<config::Config, config::ConfigError> {
+    config::load()
+}
+
+#[cfg(feature = "config")]
+fn save_kdf_params(params: &kdf::KdfParams) -> std::result::Result<std::path::PathBuf, config::ConfigError> {
+    let path = config::default_path().ok_or_else(|| config::ConfigError::Write {
+        path: "<none>".to_string(),
+        message: "no config location (set PWGEN_CONFIG)".to_string(),
+    })?;
+    config::save_kdf_params(&path, params)?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "config"))]
+fn save_kdf_params(_params: &kdf::KdfParams) -> std::result::Result<std::path::PathBuf, config::ConfigError> {
+    Err(config::ConfigError::Write {
+        path: "<none>".to_string(),
+        message: "built without the config feature".to_string(),
+    })
+}
+
+#[cfg(not(feature = "config"))]
+fn load_config() -> std::result::Result<config::Config, config::ConfigError> {
+    Ok(config::Config::default())
 }
 
-fn read_master_prompt() -> Result<String> {
+#[cfg(feature = "config")]
+fn load_profiles(path: &std::path::Path) -> std::result::Result<Vec<profiles::Profile>, profiles::ProfilesError> {
+    profiles::load(path)
+}
+
+#[cfg(feature = "config")]
+fn save_profiles(path: &std::path::Path, list: &[profiles::Profile]) -> std::result::Result<(), profiles::ProfilesError> {
+    profiles::save(path, list)
+}
+
+#[cfg(not(feature = "config"))]
+fn save_profiles(path: &std::path::Path, _list: &[profiles::Profile]) -> std::result::Result<(), profiles::ProfilesError> {
+    Err(profiles::ProfilesError::Write {
+        path: path.display().to_string(),
+        message: "profile files require the config feature".to_string(),
+    })
+}
+
+#[cfg(not(feature = "config"))]
+fn load_profiles(path: &std::path::Path) -> std::result::Result<Vec<profiles::Profile>, profiles::ProfilesError> {
+    Err(profiles::ProfilesError::Read {
+        path: path.display().to_string(),
+        message: "profile files require the config feature".to_string(),
+    })
+}
+
+/// Resolves the master secret from the chosen input method, prompting by default.
+fn read_master(input: &mut MasterArgs) -> Result<kdf::MasterSecret> {
+    // clap's ArgGroup enforces at most one method
+    if let Some(m) = input.master.take() {
+        Ok(kdf::MasterSecret::new(m))
+    } else if input.master_stdin {
+        read_master_stdin()
+    } else if let Some(path) = &input.master_file {
+        read_master_file(path)
+    } else if let Some(fd) = input.master_fd {
+        read_master_fd(fd)
+    } else if let Some(var) = &input.master_env {
+        read_master_env(var)
+    } else if let Some(path) = &input.team {
+        read_team_master(path)
+    } else {
+        read_master_prompt(input) // Default: prompt when no method specified
+    }
+}
+
+fn read_master_prompt(input: &MasterArgs) -> Result<kdf::MasterSecret> {
     #[cfg(feature = "tty")]
     {
         let prompt = "Master: ";
+        let options = prompt::PromptOptions {
+            timeout: input.prompt_timeout.map(std::time::Duration::from_secs),
+            mask: input.prompt_mask,
+        };
+        if !options.is_plain() {
+            // Raw-mode reader on the TTY: per-keystroke timeout and optional masking
+            return match prompt::read_secret(prompt, options) {
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    Err(anyhow::anyhow!("master prompt timed out after {}s", input.prompt_timeout.unwrap_or_default()))
+                }
+                result => result.map(kdf::MasterSecret::new).context("failed to read TTY password"),
+            };
+        }
         // prompt_password() reads from TTY and hides input, even when stdin is redirected
         let master = rpassword::prompt_password(prompt)
             .context("failed to read TTY password")?;
-        Ok(master)
+        Ok(kdf::MasterSecret::new(master))
     }
 
     #[cfg(not(feature = "tty"))]
     {
-        Err(anyhow!(
+        let _ = input;
+        Err(anyhow::anyhow!(
             "TTY prompting is not available in this build (built with --no-default-features). Use --master-stdin or rebuild with default features."
         ))
     }
 }
 
-fn read_master_stdin() -> Result<String> {
-    let mut buf = String::new();
-    io::stdin()
-        .read_to_string(&mut buf)
-        .context("failed to read from stdin")?;
-    // Keep as provided (no trim), but normalize Windows CRLF
-    if buf.ends_with('\n') {
-        while buf.ends_with('\n') || buf.ends_with('\r') {
-            buf.pop();
-        }
+/// Reads the whole file as the master secret, zeroizing the raw buffer.
+fn read_master_file(path: &std::path::Path) -> Result<kdf::MasterSecret> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open master file {}", path.display()))?;
+    kdf::MasterSecret::from_reader(file).with_context(|| format!("failed to read master file {}", path.display()))
+}
+
+/// Combines the contributions in a team file (see `team`) into the team master.
+fn read_team_master(path: &std::path::Path) -> Result<kdf::MasterSecret> {
+    let text = zeroize::Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("failed to read team file {}", path.display()))?,
+    );
+    let master = team::team_master(&team::parse(&text)).with_context(|| format!("team file {}", path.display()))?;
+    Ok(kdf::MasterSecret::from(master.as_str()))
+}
+
+/// Reads the master secret from an inherited descriptor (`pwgen ... --master-fd 3 3<secret`),
+/// which is closed afterwards.
+#[cfg(unix)]
+fn read_master_fd(fd: i32) -> Result<kdf::MasterSecret> {
+    use std::os::fd::FromRawFd;
+    if fd < 0 {
+        anyhow::bail!("--master-fd must be a nonnegative descriptor");
     }
-    Ok(buf)
+    // Taking ownership of a descriptor that is not open would abort on close
+    // SAFETY: F_GETFD only queries the descriptor's flags
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        anyhow::bail!("--master-fd {}: {}", fd, io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor is open, was inherited for this purpose, and nothing else in the
+    // process owns it
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    kdf::MasterSecret::from_reader(file).with_context(|| format!("failed to read master from descriptor {}", fd))
+}
+
+#[cfg(not(unix))]
+fn read_master_fd(_fd: i32) -> Result<kdf::MasterSecret> {
+    anyhow::bail!("--master-fd is only supported on Unix")
+}
+
+/// Reads the master secret from an environment variable and removes it from this process's
+/// environment, so it is not inherited by the YubiKey helper.
+fn read_master_env(var: &str) -> Result<kdf::MasterSecret> {
+    let master = std::env::var(var).map_err(|e| match e {
+        std::env::VarError::NotPresent => anyhow::anyhow!("environment variable {} is not set", var),
+        std::env::VarError::NotUnicode(_) => anyhow::anyhow!("environment variable {} is not valid UTF-8", var),
+    })?;
+    // Single-threaded at this point, so nothing else reads the environment concurrently
+    std::env::remove_var(var);
+    eprintln!(
+        "warning: master secret read from ${}; the environment is readable by the same user (/proc/PID/environ) and inherited by child processes",
+        var
+    );
+    Ok(kdf::MasterSecret::new(master))
+}
+
+fn read_master_stdin() -> Result<kdf::MasterSecret> {
+    kdf::MasterSecret::from_reader(io::stdin()).context("failed to read from stdin")
 }
 
 /// Safely converts CLI inputs (u32) to Policy (u8), ensuring no lossy casts.
 /// 
 /// This helper ensures that min/max values are within valid range [1, 128] before
-/// casting from u32 to u8. The returned Policy is not yet validated - call
+/// casting from u32 to u8. Remaining policy fields take their defaults and are filled
+/// in by the caller. The returned Policy is not yet validated - call
 /// `policy::validate()` to enforce all invariants.
 fn cli_to_policy(
     min: u32,
@@ -452,21 +4629,6 @@ fn cli_to_policy(
         max: max as u8,
         allow,
         force,
+        ..policy::default_policy()
     })
 }
-
-fn escape_json_string(input: &str) -> String {
-    let mut out = String::with_capacity(input.len() + 8);
-    for ch in input.chars() {
-        match ch {
-            '"' => out.push_str("\\\""),
-            '\\' => out.push_str("\\\\"),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            '\t' => out.push_str("\\t"),
-            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
-            c => out.push(c),
-        }
-    }
-    out
-}
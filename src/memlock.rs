@@ -0,0 +1,101 @@
+//! Page-locked storage for the secrets the derivation pipeline holds: the copy of the master
+//! in `kdf` and the site key it derives, Argon2's working memory and the HKDF PRK in `prng`.
+//!
+//! With the `mlock` feature (Unix) these live in allocations locked into RAM with `mlock`, so
+//! they are never written to swap; without it, or when locking fails (e.g. `RLIMIT_MEMLOCK`
+//! is exhausted), they are ordinary heap allocations. Either way they are zeroized before
+//! being freed, which is what the generators relied on before.
+//!
+//! Locks do not nest: unlocking one allocation unlocks every page it touches, including pages
+//! shared with another locked allocation, so locking is best effort.
+
+use alloc::{boxed::Box, vec};
+use zeroize::Zeroize;
+
+/// A boxed secret locked into memory while the `mlock` feature is on, zeroized on drop.
+pub struct Locked<T: ?Sized + Zeroize> {
+    value: Box<T>,
+    locked: bool,
+}
+
+impl<T: ?Sized + Zeroize> Locked<T> {
+    /// Locks the pages of `value`; it is kept (unlocked) if they cannot be locked.
+    pub fn new(value: Box<T>) -> Self {
+        let locked = lock_region(&*value);
+        Locked { value, locked }
+    }
+
+    /// Whether the value is locked into memory.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Locked<[u8]> {
+    /// A locked copy of `bytes`.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut locked = Locked::new(vec![0u8; bytes.len()].into_boxed_slice());
+        locked.copy_from_slice(bytes);
+        locked
+    }
+}
+
+impl<T: ?Sized + Zeroize> core::ops::Deref for Locked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: ?Sized + Zeroize> core::ops::DerefMut for Locked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: ?Sized + Zeroize> Drop for Locked<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+        if self.locked {
+            unlock_region(&*self.value);
+        }
+    }
+}
+
+/// Locks the pages holding `items` into memory. Returns false, leaving them unlocked, if the
+/// `mlock` feature is off or the system refuses.
+pub fn lock<T>(items: &[T]) -> bool {
+    lock_region(items)
+}
+
+/// Unlocks pages locked with `lock`, before they are freed.
+pub fn unlock<T>(items: &[T]) {
+    unlock_region(items)
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn lock_region<T: ?Sized>(value: &T) -> bool {
+    let len = core::mem::size_of_val(value);
+    // SAFETY: locks pages of a live allocation; mlock does not access the memory
+    len == 0 || unsafe { libc::mlock((value as *const T).cast(), len) } == 0
+}
+
+#[cfg(all(feature = "mlock", unix))]
+fn unlock_region<T: ?Sized>(value: &T) {
+    let len = core::mem::size_of_val(value);
+    if len != 0 {
+        // SAFETY: unlocks pages locked in `lock_region`
+        unsafe {
+            libc::munlock((value as *const T).cast(), len);
+        }
+    }
+}
+
+#[cfg(not(all(feature = "mlock", unix)))]
+fn lock_region<T: ?Sized>(_value: &T) -> bool {
+    false
+}
+
+#[cfg(not(all(feature = "mlock", unix)))]
+fn unlock_region<T: ?Sized>(_value: &T) {}
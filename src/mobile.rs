@@ -0,0 +1,110 @@
+//! UniFFI bindings (`uniffi` feature): the `Master` object and errors declared in
+//! `src/pwgen.udl`, from which `uniffi-bindgen` generates Kotlin and Swift. The scaffolding
+//! is included at the crate root, where UniFFI expects it.
+//!
+//! Mobile companion apps derive through the same generators as the CLI, so their outputs
+//! are byte-compatible by construction.
+
+use thiserror::Error;
+
+use crate::generator::{self, AlgoVersion, GenError};
+use crate::kdf::{KdfParams, KdfProfile, KdfScheme, MasterInput, MasterSecret};
+use crate::policy;
+use crate::site::{self, SiteMode};
+
+#[derive(Error, Debug)]
+pub enum PwgenError {
+    #[error("invalid input: {message}")]
+    InvalidInput { message: String },
+    #[error("derivation failed: {message}")]
+    Derivation { message: String },
+}
+
+impl PwgenError {
+    fn invalid(message: impl ToString) -> Self {
+        PwgenError::InvalidInput { message: message.to_string() }
+    }
+}
+
+impl From<GenError> for PwgenError {
+    fn from(e: GenError) -> Self {
+        match e {
+            GenError::Kdf(_) | GenError::Prng(_) => PwgenError::Derivation { message: e.to_string() },
+            _ => PwgenError::invalid(e),
+        }
+    }
+}
+
+/// Argon2id costs, as `kdf-params` in the config file.
+pub struct KdfCosts {
+    pub mem_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+/// The master secret, KDF and algorithm version derivations run with.
+pub struct Master {
+    master: MasterSecret,
+    scheme: KdfScheme,
+    algo: AlgoVersion,
+}
+
+impl Master {
+    /// With a named Argon2id profile (`fast`, `balanced`, `paranoid`).
+    pub fn new(master: String, kdf_profile: String, algo_version: u32) -> Result<Self, PwgenError> {
+        let master = MasterSecret::new(master);
+        let profile = KdfProfile::ALL
+            .into_iter()
+            .find(|p| p.name() == kdf_profile)
+            .ok_or_else(|| PwgenError::invalid(format!("unknown KDF profile '{}'", kdf_profile)))?;
+        Master::with_scheme(master, KdfScheme::Argon2id(profile.params()), algo_version)
+    }
+
+    /// With custom Argon2id costs.
+    pub fn with_kdf_costs(master: String, costs: KdfCosts, algo_version: u32) -> Result<Self, PwgenError> {
+        let master = MasterSecret::new(master);
+        let params = KdfParams { mem_kib: costs.mem_kib, t_cost: costs.t_cost, p_cost: costs.p_cost };
+        params.validate().map_err(PwgenError::invalid)?;
+        Master::with_scheme(master, KdfScheme::Argon2id(params), algo_version)
+    }
+
+    fn with_scheme(master: MasterSecret, scheme: KdfScheme, algo_version: u32) -> Result<Self, PwgenError> {
+        if master.is_empty() {
+            return Err(PwgenError::invalid("master secret must be nonempty"));
+        }
+        let algo = AlgoVersion::from_number(algo_version)
+            .ok_or_else(|| PwgenError::invalid(format!("unknown algorithm version {}", algo_version)))?;
+        Ok(Master { master, scheme, algo })
+    }
+
+    /// As `pwgen generate`, with an encoded policy or the default one.
+    pub fn generate(&self, site: String, username: Option<String>, policy: Option<String>, version: u32) -> Result<String, PwgenError> {
+        let policy = match policy {
+            Some(encoded) => policy::decode(&encoded).and_then(|p| policy::validate(&p)),
+            None => policy::validate(&policy::default_policy()),
+        }
+        .map_err(PwgenError::invalid)?;
+        let site = normalize_site(&site)?;
+        Ok(generator::generate_password(self.input(), &site, username.as_deref(), &policy, version)?)
+    }
+
+    /// As `pwgen generate --pattern`.
+    pub fn generate_pattern(&self, site: String, username: Option<String>, pattern: String, version: u32) -> Result<String, PwgenError> {
+        let site = normalize_site(&site)?;
+        Ok(generator::generate_from_pattern(self.input(), &site, username.as_deref(), &pattern, version)?)
+    }
+
+    fn input(&self) -> MasterInput<'_> {
+        MasterInput::new(self.master.as_str(), self.scheme).with_algo(self.algo)
+    }
+}
+
+/// Reduces the site as `pwgen generate` does with the default `--site-mode exact` (URLs to
+/// their host, IDNs to Unicode), so pasted URLs derive the CLI's password.
+fn normalize_site(site: &str) -> Result<String, PwgenError> {
+    let site = site::normalize(site, SiteMode::Exact).map_err(PwgenError::invalid)?;
+    if site.is_empty() {
+        return Err(PwgenError::invalid("site must be nonempty after trim"));
+    }
+    Ok(site)
+}
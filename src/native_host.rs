@@ -0,0 +1,129 @@
+//! `pwgen native-host`: a native messaging host for a companion Chrome or Firefox extension.
+//!
+//! The browser starts the host and exchanges messages on stdin and stdout, each a JSON
+//! document preceded by its length as a 32-bit integer in native byte order. Messages are the
+//! JSON-RPC requests of `pwgen serve --stdio` with one more member, `origin`: the origin of the
+//! page the extension fills (`https://login.example.com`). `generate` only derives for that
+//! origin's host or a parent domain of it, so a compromised page cannot ask for another site's
+//! password; without a `site` param the origin itself is the site.
+//!
+//! The browser gives the host no terminal, so it derives through the running `pwgen agent`.
+
+use std::io::{self, Read, Write};
+
+use pwgen::site;
+use serde_json::{Map, Value};
+
+use super::serve::{self, RpcError};
+use super::{normalize_site, MasterArgs, Session};
+
+/// Longest message the host reads; requests are a few hundred bytes.
+const MAX_MESSAGE: u32 = 64 * 1024;
+
+/// Longest message the browser accepts from a host.
+const MAX_REPLY: usize = 1024 * 1024;
+
+/// The calling extension, from the arguments the browser starts the host with: Chrome passes
+/// the extension's origin (`chrome-extension://<id>/`), Firefox the path of the host manifest
+/// and then the extension's ID.
+pub fn caller(args: &[String]) -> Option<&str> {
+    match args {
+        [origin, ..] if origin.starts_with("chrome-extension://") => Some(origin),
+        [_, id, ..] => Some(id),
+        _ => None,
+    }
+}
+
+/// Whether `allowed` (an extension ID or a `chrome-extension://` origin) names `caller`.
+pub fn same_extension(allowed: &str, caller: &str) -> bool {
+    let id = |s: &str| s.trim_start_matches("chrome-extension://").trim_end_matches('/').to_string();
+    id(allowed) == id(caller)
+}
+
+/// Answers messages from the browser until it closes the port, then wipes the master.
+pub fn run(session: Session, input: &MasterArgs) -> io::Result<()> {
+    let mut reader = io::stdin().lock();
+    let mut writer = io::stdout().lock();
+    let result = loop {
+        let message = match read_message(&mut reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        };
+        let response = serve::respond(&message, |method, request| {
+            let mut params = match request.get("params") {
+                None | Some(Value::Null) => Map::new(),
+                Some(Value::Object(params)) => params.clone(),
+                Some(_) => return Err(RpcError::new(serve::INVALID_PARAMS, "params must be an object")),
+            };
+            if method == "generate" {
+                let origin = request.get("origin").and_then(Value::as_str).unwrap_or_default();
+                check_origin(&session, input, origin, &mut params)?;
+            }
+            serve::call(&session, input, method, Some(&Value::Object(params)))
+        });
+        if let Some(response) = response {
+            if let Err(e) = write_message(&mut writer, &response) {
+                break Err(e);
+            }
+        }
+    };
+    drop(session);
+    result
+}
+
+/// Checks that the requested site is the page origin's host or a parent domain of it down to
+/// its registrable domain (after `--site-mode`), and fills in the origin as the site when none
+/// was given.
+fn check_origin(session: &Session, input: &MasterArgs, origin: &str, params: &mut Map<String, Value>) -> Result<(), RpcError> {
+    let invalid = |message: String| RpcError::new(serve::INVALID_PARAMS, message);
+    if !(origin.starts_with("https://") || origin.starts_with("http://")) {
+        return Err(invalid(format!("origin '{}' is not an http or https origin", origin)));
+    }
+    let host = site::normalize(origin, site::SiteMode::Exact).map_err(|e| invalid(format!("origin: {}", e)))?;
+
+    let requested = match params.get("site") {
+        None | Some(Value::Null) => {
+            params.insert("site".into(), Value::String(origin.to_string()));
+            return Ok(());
+        }
+        Some(Value::String(requested)) => requested,
+        Some(_) => return Err(invalid("site must be a string".into())),
+    };
+    let requested = normalize_site(requested, input.site_mode, &session.cfg).map_err(|e| invalid(format!("site: {}", e)))?;
+    // Without the Public Suffix List, only the host itself is known not to be a public suffix
+    let floor = site::registrable_domain(&host).unwrap_or_else(|_| host.clone());
+    let within = |domain: &str, parent: &str| domain == parent || domain.ends_with(&format!(".{}", parent));
+    if within(&host, &requested) && within(&requested, &floor) {
+        Ok(())
+    } else {
+        Err(invalid(format!("site '{}' does not match the page origin {}", requested, origin)))
+    }
+}
+
+/// Reads one length-prefixed message; `None` once the browser closes stdin.
+fn read_message(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes is over {}", len, MAX_MESSAGE)));
+    }
+    let mut message = vec![0u8; len as usize];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let bytes = zeroize::Zeroizing::new(serde_json::to_vec(message).map_err(io::Error::other)?);
+    if bytes.len() > MAX_REPLY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "reply is over the browser's 1 MiB limit"));
+    }
+    writer.write_all(&(bytes.len() as u32).to_ne_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
@@ -0,0 +1,422 @@
+//! Structured output (`--json`, `--output`, `--field`): one typed struct per command,
+//! serialized with a versioned schema so downstream tooling can rely on the field names and
+//! types.
+//!
+//! Every object starts with `"schema": SCHEMA`. Within a schema version fields are only ever
+//! added; renaming, retyping or removing one bumps it. YAML, TOML and env output are rendered
+//! from the same structs, so they carry the same fields in the same order.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use zeroize::{Zeroize, Zeroizing};
+
+use pwgen::generator::AlgoVersion;
+use pwgen::kdf::KdfScheme;
+
+/// Version of the `--json` schema.
+pub const SCHEMA: u32 = 1;
+
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    schema: u32,
+    #[serde(flatten)]
+    output: &'a T,
+}
+
+/// Formats of `--output`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One line of JSON, as with --json
+    Json,
+    /// A YAML mapping, e.g. for Ansible vars
+    Yaml,
+    /// A TOML document; nested objects become tables
+    Toml,
+    /// NAME='value' lines for `eval` or `source`, nested names joined with `_`
+    Env,
+}
+
+/// What to print in place of the plain output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Selection<'a> {
+    /// All fields, in a format (`--json`, `--output`)
+    Format(Format),
+    /// One field, by name (`--field`); `a.b` names field `b` of object `a`
+    Field(&'a str),
+}
+
+/// The selection made with `--json`, `--output` or `--field`, if any.
+pub fn selection(json: bool, output: Option<Format>, field: Option<&str>) -> Option<Selection<'_>> {
+    match (field, output.or(json.then_some(Format::Json))) {
+        (Some(field), _) => Some(Selection::Field(field)),
+        (None, format) => format.map(Selection::Format),
+    }
+}
+
+/// Prints the selection of `output`; formats are preceded by the schema version. Fails if a
+/// selected field does not exist, without printing anything.
+pub fn print(selection: Selection, output: &impl Serialize) -> Result<(), String> {
+    let versioned = Versioned { schema: SCHEMA, output };
+    if selection == Selection::Format(Format::Json) {
+        let json = Zeroizing::new(serde_json::to_string(&versioned).expect("output structs serialize to JSON"));
+        println!("{}", json.as_str());
+        return Ok(());
+    }
+    let mut value = serde_json::to_value(&versioned).expect("output structs serialize to JSON");
+    let text = match (selection, &value) {
+        (Selection::Field(name), _) => field(&value, name),
+        (Selection::Format(Format::Yaml), Value::Object(object)) => Ok(yaml(object)),
+        (Selection::Format(Format::Toml), Value::Object(object)) => Ok(toml(object)),
+        (Selection::Format(_), Value::Object(object)) => Ok(env(object)),
+        _ => Ok(String::new()),
+    }
+    .map(Zeroizing::new);
+    wipe(&mut value);
+    print!("{}", text?.as_str());
+    Ok(())
+}
+
+/// One field on its own: strings as they are, arrays one item per line, objects as JSON.
+fn field(value: &Value, name: &str) -> Result<String, String> {
+    let field = name
+        .split('.')
+        .try_fold(value, |value, key| value.get(key))
+        .ok_or_else(|| match value {
+            Value::Object(object) => format!(
+                "no field '{}' in the output (fields: {})",
+                name,
+                object.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+            ),
+            _ => format!("no field '{}' in the output", name),
+        })?;
+    let mut out = String::new();
+    match field {
+        Value::Array(items) => {
+            for item in items {
+                let _ = match item {
+                    Value::String(s) => writeln!(out, "{}", s),
+                    _ => writeln!(out, "{}", item),
+                };
+            }
+        }
+        Value::String(s) => {
+            let _ = writeln!(out, "{}", s);
+        }
+        _ => {
+            let _ = writeln!(out, "{}", field);
+        }
+    }
+    Ok(out)
+}
+
+fn yaml(object: &Map<String, Value>) -> String {
+    let mut out = String::new();
+    for (key, value) in object {
+        match value {
+            Value::Object(nested) => {
+                let _ = writeln!(out, "{}:", key);
+                for (key, value) in nested {
+                    let _ = writeln!(out, "  {}: {}", key, *scalar(value));
+                }
+            }
+            Value::Array(items) if !items.is_empty() => {
+                let _ = writeln!(out, "{}:", key);
+                for item in items {
+                    let _ = writeln!(out, "  - {}", *scalar(item));
+                }
+            }
+            _ => {
+                let _ = writeln!(out, "{}: {}", key, *scalar(value));
+            }
+        }
+    }
+    out
+}
+
+fn toml(object: &Map<String, Value>) -> String {
+    // Tables must come after the top-level keys
+    let mut out = String::new();
+    for (key, value) in object.iter().filter(|(_, value)| !value.is_object()) {
+        let _ = writeln!(out, "{} = {}", key, *scalar(value));
+    }
+    for (key, value) in object {
+        if let Value::Object(nested) = value {
+            let _ = write!(out, "\n[{}]\n", key);
+            for (key, value) in nested {
+                let _ = writeln!(out, "{} = {}", key, *scalar(value));
+            }
+        }
+    }
+    out
+}
+
+fn env(object: &Map<String, Value>) -> String {
+    let mut out = String::new();
+    for (key, value) in object {
+        push_env(&mut out, &key.to_uppercase(), value);
+    }
+    out
+}
+
+/// `NAME=value` lines; arrays are numbered from 1 (`CODES_1`, `CODES_2`, ...).
+fn push_env(out: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Object(nested) => {
+            for (key, value) in nested {
+                push_env(out, &format!("{}_{}", name, key.to_uppercase()), value);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                push_env(out, &format!("{}_{}", name, i + 1), item);
+            }
+        }
+        Value::String(s) => {
+            let _ = writeln!(out, "{}='{}'", name, *Zeroizing::new(s.replace('\'', "'\\''")));
+        }
+        _ => {
+            let _ = writeln!(out, "{}={}", name, value);
+        }
+    }
+}
+
+/// A value in flow style, valid in YAML and TOML alike: strings double-quoted with JSON
+/// escapes (plus `\uXXXX` for the control characters JSON leaves as they are), arrays in
+/// brackets.
+fn scalar(value: &Value) -> Zeroizing<String> {
+    let mut out = Zeroizing::new(String::new());
+    match value {
+        Value::String(s) => {
+            let json = Zeroizing::new(serde_json::to_string(s).expect("strings serialize to JSON"));
+            for c in json.chars() {
+                if c.is_control() {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                } else {
+                    out.push(c);
+                }
+            }
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(if i == 0 { "" } else { ", " });
+                out.push_str(&scalar(item));
+            }
+            out.push(']');
+        }
+        _ => out.push_str(&value.to_string()),
+    }
+    out
+}
+
+/// Overwrites the strings of a value, which may hold the password.
+pub fn wipe(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Array(items) => items.iter_mut().for_each(wipe),
+        Value::Object(object) => object.values_mut().for_each(wipe),
+        _ => {}
+    }
+}
+
+/// Entropy rounded to one decimal, as `--json` prints it.
+pub fn round_bits(bits: f64) -> f64 {
+    (bits * 10.0).round() / 10.0
+}
+
+/// How a master-derived output was derived.
+#[derive(Serialize)]
+pub struct Derivation {
+    /// Profile name or custom parameters, as accepted by `--kdf-profile` and `kdf-params`
+    pub kdf_profile: String,
+    pub kdf: Kdf,
+    pub algo_version: u32,
+}
+
+impl Derivation {
+    pub fn new(scheme: KdfScheme, algo: AlgoVersion) -> Derivation {
+        let kdf = match scheme {
+            KdfScheme::Argon2id(params) => {
+                Kdf::Argon2id { mem_kib: params.mem_kib, t_cost: params.t_cost, p_cost: params.p_cost }
+            }
+            KdfScheme::Pbkdf2Sha256 { iterations } => Kdf::Pbkdf2Sha256 { iterations },
+        };
+        Derivation { kdf_profile: scheme.label(), kdf, algo_version: algo.number() }
+    }
+}
+
+/// The key-stretching function and its parameters.
+#[derive(Serialize)]
+#[serde(tag = "name")]
+pub enum Kdf {
+    #[serde(rename = "argon2id")]
+    Argon2id { mem_kib: u32, t_cost: u32, p_cost: u32 },
+    #[serde(rename = "pbkdf2-sha256")]
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+/// `pwgen generate`.
+#[derive(Serialize)]
+pub struct Generate<'a> {
+    pub password: &'a str,
+    pub length: usize,
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub params: Params<'a>,
+    pub entropy_bits: f64,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// What the password was generated from: an encoded policy, a pattern or a format.
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Params<'a> {
+    Policy(&'a str),
+    Pattern(&'a str),
+    Format(&'a str),
+}
+
+impl<'a> Params<'a> {
+    /// `params` under the key the generation plan names.
+    pub fn new(key: &str, params: &'a str) -> Params<'a> {
+        match key {
+            "pattern" => Params::Pattern(params),
+            "format" => Params::Format(params),
+            _ => Params::Policy(params),
+        }
+    }
+}
+
+/// `pwgen generate --dry-run`: the inputs of a derivation, without the master or secret factors.
+#[derive(Serialize)]
+pub struct DryRun<'a> {
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub params: Params<'a>,
+    pub entropy_bits: f64,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persona: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<&'a str>,
+    /// Organization of `--path`, bound into the KDF input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_org: Option<&'a str>,
+    /// Path of the keyfile, not its contents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<&'a str>,
+    /// Whether a pepper is mixed in (its value is not shown)
+    pub pepper: bool,
+    /// Where the pepper comes from (`env`, `keychain` or `config`); it is not read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pepper_source: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yubikey_slot: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub argon2_secret_file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub argon2_ad: Option<&'a str>,
+    /// PRNG info context
+    pub context: &'a str,
+}
+
+/// `pwgen generate --compat`: third-party algorithms take no KDF or algorithm version.
+#[derive(Serialize)]
+pub struct Compat<'a> {
+    pub password: &'a str,
+    pub length: usize,
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    pub compat: &'a str,
+    pub entropy_bits: f64,
+}
+
+/// `pwgen recovery`.
+#[derive(Serialize)]
+pub struct Recovery<'a> {
+    pub codes: &'a [String],
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen wireguard`.
+#[derive(Serialize)]
+pub struct Wireguard<'a> {
+    pub private_key: &'a str,
+    pub public_key: &'a str,
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen totp`.
+#[derive(Serialize)]
+pub struct Totp<'a> {
+    pub secret: &'a str,
+    pub code: &'a str,
+    /// Seconds the code stays valid
+    pub valid_for: u64,
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen answer`.
+#[derive(Serialize)]
+pub struct Answer<'a> {
+    pub answer: &'a str,
+    pub question: &'a str,
+    pub site: &'a str,
+    pub username: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen username`.
+#[derive(Serialize)]
+pub struct Username<'a> {
+    pub username: &'a str,
+    pub style: &'a str,
+    pub site: &'a str,
+    pub version: u32,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen fingerprint`.
+#[derive(Serialize)]
+pub struct Fingerprint<'a> {
+    pub fingerprint: &'a str,
+    #[serde(flatten)]
+    pub derivation: Derivation,
+}
+
+/// `pwgen calibrate`.
+#[derive(Serialize)]
+pub struct Calibrate {
+    pub mem_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub elapsed_ms: u128,
+    pub target_ms: u64,
+    pub kdf_profile: String,
+    pub saved: bool,
+}
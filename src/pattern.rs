@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+use thiserror::Error;
+
+use crate::policy::{DIGIT_BYTES, SYMBOL_BYTES, UPPER_BYTES};
+
+// Placeholder alphabets (fixed order; changing them changes pattern outputs)
+const UPPER_VOWELS: &[u8] = b"AEIOU";
+const UPPER_CONSONANTS: &[u8] = b"BCDFGHJKLMNPQRSTVWXYZ";
+const LOWER_VOWELS: &[u8] = b"aeiou";
+const LOWER_CONSONANTS: &[u8] = b"bcdfghjklmnpqrstvwxyz";
+const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const ANY: &[u8] =
+    b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!\"#$%&'()*+,-./:;<=>?@[\\]^_{|}~";
+
+/// Longest pattern output, matching the policy length bound.
+pub const MAX_PATTERN_LEN: usize = 128;
+
+/// One position of a parsed pattern.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    /// Draw one character from this alphabet
+    Class(&'static [u8]),
+    /// Emit this character unchanged
+    Literal(u8),
+}
+
+#[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatternError {
+    #[error("pattern must be nonempty")]
+    Empty,
+
+    #[error("pattern must produce at most 128 characters")]
+    TooLong,
+
+    #[error("pattern must contain only printable ASCII characters")]
+    NonAscii,
+
+    #[error("pattern must not end with an unescaped '\\'")]
+    TrailingEscape,
+}
+
+/// Returns the alphabet for a placeholder character, if it is one.
+///
+/// - `C`/`c`: upper/lower consonant
+/// - `V`/`v`: upper/lower vowel
+/// - `A`: upper letter, `a`: any letter
+/// - `n`: digit
+/// - `o`: symbol
+/// - `x`: any of the above
+pub fn placeholder(ch: u8) -> Option<&'static [u8]> {
+    match ch {
+        b'C' => Some(UPPER_CONSONANTS),
+        b'c' => Some(LOWER_CONSONANTS),
+        b'V' => Some(UPPER_VOWELS),
+        b'v' => Some(LOWER_VOWELS),
+        b'A' => Some(UPPER_BYTES),
+        b'a' => Some(LETTERS),
+        b'n' => Some(DIGIT_BYTES),
+        b'o' => Some(SYMBOL_BYTES),
+        b'x' => Some(ANY),
+        _ => None,
+    }
+}
+
+/// Parses a template pattern such as `Cvcc-nnnn-Cvcc`.
+///
+/// Placeholders (see `placeholder`) become classes; every other printable ASCII character
+/// (including space) is a literal. A backslash makes the next character literal, so `\n`
+/// emits the letter `n` and `\\` emits a backslash.
+pub fn parse(pattern: &str) -> Result<Vec<Token>, PatternError> {
+    if pattern.is_empty() {
+        return Err(PatternError::Empty);
+    }
+    if !pattern.bytes().all(|b| b == b' ' || b.is_ascii_graphic()) {
+        return Err(PatternError::NonAscii);
+    }
+
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut bytes = pattern.bytes();
+    while let Some(b) = bytes.next() {
+        let token = if b == b'\\' {
+            Token::Literal(bytes.next().ok_or(PatternError::TrailingEscape)?)
+        } else {
+            placeholder(b).map_or(Token::Literal(b), Token::Class)
+        };
+        tokens.push(token);
+    }
+
+    if tokens.len() > MAX_PATTERN_LEN {
+        return Err(PatternError::TooLong);
+    }
+    Ok(tokens)
+}
+
+/// Entropy, in bits, of a password generated from parsed pattern tokens.
+///
+/// Each placeholder is an independent uniform draw; literals add nothing.
+#[cfg(feature = "std")]
+pub fn entropy_bits(tokens: &[Token]) -> f64 {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Class(alphabet) => (alphabet.len() as f64).log2(),
+            Token::Literal(_) => 0.0,
+        })
+        .sum()
+}
@@ -0,0 +1,118 @@
+//! Date-based versions (`generate --version-policy`): the version is the calendar period a
+//! date falls in, so passwords rotate on schedule without a stored counter.
+//!
+//! Versions read as the period: `YYYY` (yearly), `YYYYQ` (quarterly, Q in 1..=4) and `YYYYMM`
+//! (monthly). Dates are in UTC.
+
+use thiserror::Error;
+
+/// Calendar period that makes up one version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VersionPolicy {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+/// A calendar date (proleptic Gregorian, years 1 to 9999).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum PeriodError {
+    #[error("date must be YYYY-MM-DD")]
+    Syntax,
+    #[error("no such date: {0}")]
+    Invalid(String),
+    #[error("date is outside the years 1 to 9999")]
+    OutOfRange,
+}
+
+impl VersionPolicy {
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            VersionPolicy::Monthly => "monthly",
+            VersionPolicy::Quarterly => "quarterly",
+            VersionPolicy::Yearly => "yearly",
+        }
+    }
+
+    /// Version of the period `date` falls in.
+    pub fn version(self, date: Date) -> u32 {
+        match self {
+            VersionPolicy::Monthly => date.year * 100 + date.month,
+            VersionPolicy::Quarterly => date.year * 10 + date.month.div_ceil(3),
+            VersionPolicy::Yearly => date.year,
+        }
+    }
+
+    /// The period `date` falls in, as shown to the user: `2026-10`, `2026-Q4` or `2026`.
+    pub fn period(self, date: Date) -> String {
+        match self {
+            VersionPolicy::Monthly => format!("{:04}-{:02}", date.year, date.month),
+            VersionPolicy::Quarterly => format!("{:04}-Q{}", date.year, date.month.div_ceil(3)),
+            VersionPolicy::Yearly => format!("{:04}", date.year),
+        }
+    }
+}
+
+impl Date {
+    /// Parses `YYYY-MM-DD`.
+    pub fn parse(input: &str) -> Result<Date, PeriodError> {
+        let parts: Vec<&str> = input.trim().split('-').collect();
+        let [year, month, day] = parts.as_slice() else {
+            return Err(PeriodError::Syntax);
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(PeriodError::Syntax);
+        }
+        let number = |s: &str| {
+            if s.bytes().all(|b| b.is_ascii_digit()) {
+                s.parse::<u32>().map_err(|_| PeriodError::Syntax)
+            } else {
+                Err(PeriodError::Syntax)
+            }
+        };
+        let date = Date { year: number(year)?, month: number(month)?, day: number(day)? };
+        if date.year == 0 {
+            return Err(PeriodError::OutOfRange);
+        }
+        if !(1..=12).contains(&date.month) || date.day == 0 || date.day > days_in_month(date.year, date.month) {
+            return Err(PeriodError::Invalid(input.trim().to_string()));
+        }
+        Ok(date)
+    }
+
+    /// The UTC date of a Unix time in seconds.
+    pub fn from_unix(secs: u64) -> Result<Date, PeriodError> {
+        // Howard Hinnant's civil_from_days, for days since 1970-01-01
+        let days = i64::try_from(secs / 86_400).map_err(|_| PeriodError::OutOfRange)?;
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        if !(1..=9999).contains(&year) {
+            return Err(PeriodError::OutOfRange);
+        }
+        Ok(Date { year: year as u32, month: month as u32, day: day as u32 })
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
@@ -0,0 +1,307 @@
+//! `pwgen pinentry`: a pinentry, the passphrase dialog gpg-agent (and other tools) start, that
+//! answers for the keys listed as `[[pinentry]]` in the config file with a passphrase derived
+//! through the running agent, and hands every other dialog to a real pinentry.
+//!
+//! Speaks the Assuan protocol on stdin and stdout: one command per line, answered by `D`
+//! (data) and `S` (status) lines and a final `OK` or `ERR <code> <description>`. Commands that
+//! set up the dialog (`OPTION`, `SETDESC`, `SETKEYINFO`, ...) are recorded and replayed to the
+//! real pinentry when it is first needed: for a `GETPIN` of another key, after a derived
+//! passphrase was refused (`SETERROR`) or when the agent cannot derive, and for `CONFIRM` and
+//! `MESSAGE`. From then on every command goes to it as well.
+
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use pwgen::config::PinentryKey;
+use zeroize::Zeroizing;
+
+use super::{derive_password, MasterArgs, Session};
+
+/// `GPG_ERR_CANCELED` from the pinentry error source.
+const ERR_CANCELED: u32 = 83886179;
+/// `GPG_ERR_ASS_UNKNOWN_CMD` from the pinentry error source.
+const ERR_UNKNOWN_COMMAND: u32 = 83886355;
+/// `GPG_ERR_ASS_PARAMETER` from the pinentry error source.
+const ERR_PARAMETER: u32 = 83886360;
+
+struct Pinentry<'a> {
+    session: &'a Session,
+    input: &'a MasterArgs,
+    fallback_program: &'a OsStr,
+    fallback: Option<Fallback>,
+    /// Commands that set up the dialog since the last `RESET`, for the fallback
+    setup: Vec<String>,
+    /// Keygrip from `SETKEYINFO`
+    keygrip: Option<String>,
+    /// `SETERROR` was given since the last `GETPIN`: the previous passphrase was refused
+    retry: bool,
+    /// `SETREPEAT` was given: the passphrase is new and asked twice
+    repeat: bool,
+}
+
+/// The real pinentry, once started.
+struct Fallback {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// Answers Assuan commands on stdin until `BYE` or end of input, then wipes the master.
+pub fn run(session: Session, input: &MasterArgs, fallback: &OsStr) -> io::Result<()> {
+    let mut reader = io::stdin().lock();
+    let mut out = io::stdout().lock();
+    let result = Pinentry {
+        session: &session,
+        input,
+        fallback_program: fallback,
+        fallback: None,
+        setup: Vec::new(),
+        keygrip: None,
+        retry: false,
+        repeat: false,
+    }
+    .serve(&mut reader, &mut out);
+    drop(session);
+    result
+}
+
+impl Pinentry<'_> {
+    fn serve(mut self, reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "OK Pleased to meet you")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let command = line.trim_end_matches(['\r', '\n']);
+            let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+            match name.to_ascii_uppercase().as_str() {
+                "" => continue,
+                "BYE" => {
+                    writeln!(out, "OK closing connection")?;
+                    out.flush()?;
+                    break;
+                }
+                "GETPIN" => self.getpin(command, reader, out)?,
+                "CONFIRM" | "MESSAGE" => self.delegate(command, reader, out)?,
+                "GETINFO" => self.getinfo(arg, out)?,
+                "RESET" => {
+                    self.setup.clear();
+                    self.keygrip = None;
+                    self.retry = false;
+                    self.repeat = false;
+                    self.setup_command(command, reader, out)?;
+                    self.setup.clear();
+                }
+                "SETKEYINFO" => {
+                    // `n/<keygrip>`, `s/<keygrip>` or `--clear`
+                    self.keygrip = arg.split_once('/').map(|(_, grip)| grip.to_ascii_uppercase());
+                    self.setup_command(command, reader, out)?;
+                }
+                "SETERROR" => {
+                    self.retry = true;
+                    self.setup_command(command, reader, out)?;
+                }
+                "SETREPEAT" => {
+                    self.repeat = true;
+                    self.setup_command(command, reader, out)?;
+                }
+                "NOP" | "OPTION" | "CLEARPASSPHRASE" => self.setup_command(command, reader, out)?,
+                other if other.starts_with("SET") => self.setup_command(command, reader, out)?,
+                _ => {
+                    writeln!(out, "ERR {} Unknown IPC command", ERR_UNKNOWN_COMMAND)?;
+                    out.flush()?;
+                }
+            }
+        }
+
+        if let Some(mut fallback) = self.fallback.take() {
+            let _ = writeln!(fallback.stdin, "BYE");
+            drop(fallback.stdin);
+            let _ = fallback.child.wait();
+        }
+        Ok(())
+    }
+
+    /// Records a command that sets up the dialog and answers `OK`, or passes it on if the
+    /// fallback is running.
+    fn setup_command(&mut self, command: &str, reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        self.setup.push(command.to_string());
+        if self.fallback.is_some() {
+            return self.forward(command, reader, out);
+        }
+        writeln!(out, "OK")?;
+        out.flush()
+    }
+
+    fn getpin(&mut self, command: &str, reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        let retry = std::mem::take(&mut self.retry);
+        let key = self
+            .keygrip
+            .as_deref()
+            .and_then(|grip| self.session.cfg.pinentry.iter().find(|key| key.keygrip.eq_ignore_ascii_case(grip)));
+        let derived = match key {
+            // A refused passphrase is asked for by hand, e.g. the old one when first switching a
+            // key to its derived passphrase
+            Some(key) if !retry => match self.derive(key) {
+                Ok(passphrase) => Some(passphrase),
+                Err(e) => {
+                    eprintln!("pwgen pinentry: cannot derive the passphrase for {}: {}", key.keygrip, e);
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let result = match derived {
+            Some(passphrase) => {
+                if self.repeat {
+                    writeln!(out, "S PIN_REPEATED")?;
+                }
+                let line = Zeroizing::new(format!("D {}\nOK\n", *escape(&passphrase)));
+                out.write_all(line.as_bytes())?;
+                out.flush()
+            }
+            None => self.delegate(command, reader, out),
+        };
+        // Like pinentry, show an error only with the next passphrase dialog
+        self.setup.retain(|c| !c.to_ascii_uppercase().starts_with("SETERROR"));
+        result
+    }
+
+    fn derive(&self, key: &PinentryKey) -> Result<Zeroizing<String>, String> {
+        let mut argv = vec![format!("--site={}", key.site), format!("--username={}", key.username)];
+        if let Some(version) = key.version {
+            argv.push(format!("--version={}", version));
+        }
+        if let Some(policy) = &key.policy {
+            argv.push(format!("--policy={}", policy));
+        }
+        derive_password(self.session, self.input, argv).map(|(_, passphrase)| passphrase)
+    }
+
+    fn getinfo(&mut self, arg: &str, out: &mut impl Write) -> io::Result<()> {
+        let value = match arg.trim() {
+            "flavor" => "pwgen".to_string(),
+            "version" => env!("CARGO_PKG_VERSION").to_string(),
+            "pid" => std::process::id().to_string(),
+            _ => {
+                writeln!(out, "ERR {} IPC parameter error", ERR_PARAMETER)?;
+                return out.flush();
+            }
+        };
+        writeln!(out, "D {}\nOK", *escape(&value))?;
+        out.flush()
+    }
+
+    /// Hands a command to the fallback, starting it first if needed.
+    fn delegate(&mut self, command: &str, reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        if self.fallback.is_none() {
+            match self.start_fallback() {
+                Ok(fallback) => self.fallback = Some(fallback),
+                Err(e) => {
+                    writeln!(
+                        out,
+                        "ERR {} Operation cancelled <cannot start {}: {}>",
+                        ERR_CANCELED,
+                        self.fallback_program.to_string_lossy(),
+                        e
+                    )?;
+                    return out.flush();
+                }
+            }
+        }
+        self.forward(command, reader, out)
+    }
+
+    fn start_fallback(&self) -> io::Result<Fallback> {
+        let mut child = Command::new(self.fallback_program).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("no pipes to the pinentry"));
+        };
+        let mut fallback = Fallback { child, stdin, stdout: BufReader::new(stdout) };
+
+        let greeting = fallback.read_line()?;
+        if !greeting.starts_with("OK") {
+            return Err(io::Error::other(format!("unexpected greeting '{}'", greeting.trim_end())));
+        }
+        for command in &self.setup {
+            writeln!(fallback.stdin, "{}", command)?;
+            fallback.stdin.flush()?;
+            while !is_final(&fallback.read_line()?) {}
+        }
+        Ok(fallback)
+    }
+
+    /// Sends a command to the fallback and relays its answer, including the inquiries of its
+    /// quality bar (`INQUIRE QUALITY`), which the caller answers.
+    fn forward(&mut self, command: &str, reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        let Some(fallback) = self.fallback.as_mut() else {
+            return Ok(());
+        };
+        let relayed = (|| -> io::Result<()> {
+            writeln!(fallback.stdin, "{}", command)?;
+            fallback.stdin.flush()?;
+            loop {
+                let line = Zeroizing::new(fallback.read_line()?);
+                out.write_all(line.as_bytes())?;
+                out.flush()?;
+                if is_final(&line) {
+                    return Ok(());
+                }
+                if line.starts_with("INQUIRE") {
+                    loop {
+                        let mut answer = Zeroizing::new(String::new());
+                        if reader.read_line(&mut answer)? == 0 {
+                            return Err(io::ErrorKind::UnexpectedEof.into());
+                        }
+                        fallback.stdin.write_all(answer.as_bytes())?;
+                        fallback.stdin.flush()?;
+                        if answer.starts_with("END") || answer.starts_with("CAN") {
+                            break;
+                        }
+                    }
+                }
+            }
+        })();
+        if let Err(e) = relayed {
+            // The pinentry went away; start a new one for the next dialog
+            self.fallback = None;
+            writeln!(out, "ERR {} Operation cancelled <pinentry failed: {}>", ERR_CANCELED, e)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Fallback {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the pinentry exited"));
+        }
+        Ok(line)
+    }
+}
+
+/// Whether an Assuan response line ends the answer to a command.
+fn is_final(line: &str) -> bool {
+    let word = line.split([' ', '\r', '\n']).next().unwrap_or_default();
+    word == "OK" || word == "ERR"
+}
+
+/// Percent-escapes a data line as Assuan requires.
+fn escape(data: &str) -> Zeroizing<String> {
+    let mut escaped = Zeroizing::new(String::with_capacity(data.len()));
+    for c in data.chars() {
+        match c {
+            '%' | '\r' | '\n' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
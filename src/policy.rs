@@ -1,12 +1,155 @@
+use alloc::{format, string::{String, ToString}, vec::Vec};
+use core::str::FromStr;
+
 use thiserror::Error;
 
 // Fixed, ordered ASCII character sets
 const LOWER_BYTES: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
-const UPPER_BYTES: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-const DIGIT_BYTES: &[u8] = b"0123456789";
-const SYMBOL_BYTES: &[u8] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_{|}~";
+pub(crate) const UPPER_BYTES: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub(crate) const DIGIT_BYTES: &[u8] = b"0123456789";
+pub(crate) const SYMBOL_BYTES: &[u8] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_{|}~";
+// Characters that are easily confused when read aloud or copied from paper
+const AMBIGUOUS_BYTES: &[u8] = b"01IOl|'`";
+// Symbols on the numeric keypad, the only ones at the same key (and shift state) on US, UK,
+// German and French layouts; the main-block symbol keys all differ between QWERTY, QWERTZ
+// and AZERTY
+const LAYOUT_SAFE_SYMBOLS: &[u8] = b"*+-/";
+// Letters that QWERTZ (y/z) or AZERTY (a/q, w/z, m) move to another key
+const LAYOUT_MOVED_LETTERS: &[u8] = b"AMQWYZamqwyz";
+// Shortest site name `no_site` keeps out of passwords; shorter labels are not recognizable
+const MIN_SITE_NAME: usize = 3;
+// Words of 4 to 8 letters that `no_words` keeps out of passwords, sorted and lowercase.
+// Frozen: changing the list changes outputs of no-words policies
+const WORDS: &[&str] = &[
+    "able", "access", "acid", "admin", "aged", "also", "angel", "apple", "area", "army", "away",
+    "baby", "back", "ball", "banana", "band", "bank", "base", "baseball", "bath", "batman", "beach",
+    "bear", "beat", "beer", "bell", "best", "bird", "black", "blue", "boat", "body", "bomb", "bone",
+    "book", "boot", "born", "boss", "both", "bowl", "bread", "brown", "burn", "busy", "cake",
+    "call", "calm", "came", "camp", "candy", "card", "care", "case", "cash", "cast", "cell",
+    "chair", "chat", "cheese", "chef", "cherry", "chicken", "china", "city", "club", "coal", "coat",
+    "code", "coffee", "cold", "come", "computer", "cook", "cookie", "cool", "copy", "core", "cost",
+    "crew", "dance", "dark", "data", "date", "dead", "deal", "dear", "deep", "desk", "diet",
+    "dolphin", "door", "down", "dragon", "draw", "dream", "drop", "drug", "duck", "dust", "duty",
+    "each", "eagle", "earn", "earth", "east", "easy", "edge", "else", "empty", "enter", "even",
+    "ever", "evil", "exit", "face", "fact", "fail", "fair", "fall", "fame", "family", "fancy",
+    "farm", "fast", "fear", "feel", "field", "file", "fill", "film", "find", "fine", "fire", "firm",
+    "fish", "five", "flag", "flat", "flow", "flower", "food", "foot", "football", "forest", "form",
+    "four", "free", "freedom", "friday", "friend", "frog", "from", "fuck", "full", "fund", "funny",
+    "gain", "game", "gate", "ghost", "gift", "girl", "give", "glad", "glass", "goal", "gold",
+    "golf", "good", "grass", "gray", "green", "grow", "hair", "half", "hall", "hand", "hang",
+    "happy", "hard", "hate", "have", "head", "hear", "heart", "heat", "heaven", "hell", "hello",
+    "help", "here", "hero", "hide", "high", "hill", "hockey", "hold", "hole", "holy", "home",
+    "honey", "hope", "horn", "horse", "host", "hour", "house", "huge", "hunt", "hunter", "idea",
+    "internet", "iron", "item", "jazz", "jesus", "join", "joke", "jump", "jury", "just", "keep",
+    "kick", "kill", "killer", "kind", "king", "kiss", "knee", "knife", "know", "lady", "lake",
+    "land", "last", "late", "laugh", "lazy", "lead", "leaf", "left", "lemon", "less", "letmein",
+    "life", "lift", "light", "like", "line", "link", "lion", "list", "live", "load", "loan", "lock",
+    "long", "look", "lord", "lose", "loss", "lost", "love", "lover", "luck", "lucky", "made",
+    "magic", "mail", "main", "make", "male", "many", "mark", "mass", "master", "meal", "meat",
+    "meet", "menu", "milk", "mind", "mine", "miss", "mode", "monday", "money", "monkey", "moon",
+    "more", "most", "mother", "move", "much", "music", "must", "name", "navy", "near", "neck",
+    "need", "news", "next", "nice", "night", "nine", "none", "nose", "note", "ocean", "okay",
+    "once", "only", "open", "orange", "pack", "page", "pain", "pair", "palm", "paper", "park",
+    "part", "party", "pass", "password", "past", "path", "peace", "peak", "phone", "piano", "pick",
+    "pink", "pipe", "pizza", "plan", "plant", "play", "plus", "poem", "poet", "pool", "poor",
+    "port", "post", "power", "princess", "pull", "pure", "purple", "push", "queen", "quick",
+    "qwerty", "race", "radio", "rain", "rank", "rare", "rate", "read", "real", "rent", "rest",
+    "rice", "rich", "ride", "ring", "rise", "risk", "river", "road", "robot", "rock", "role",
+    "roof", "room", "root", "rose", "round", "rule", "safe", "sale", "salt", "same", "sand", "save",
+    "seat", "secret", "seed", "seek", "self", "sell", "send", "shadow", "sheep", "ship", "shoe",
+    "shop", "shot", "show", "shut", "sick", "side", "sign", "silk", "silver", "sing", "site",
+    "size", "skin", "sleep", "slow", "smile", "snake", "snow", "soccer", "soft", "soil", "sold",
+    "some", "song", "soon", "sort", "soul", "spot", "spring", "star", "stay", "step", "stop",
+    "storm", "such", "sugar", "suit", "summer", "sunshine", "superman", "sure", "sweet", "swim",
+    "table", "tail", "take", "tale", "talk", "tall", "tank", "tape", "task", "team", "tear", "tell",
+    "tent", "term", "test", "text", "than", "that", "them", "then", "they", "thin", "this", "tiger",
+    "time", "tiny", "tone", "tool", "tour", "town", "train", "tree", "trip", "true", "tune", "turn",
+    "type", "unit", "upon", "user", "vast", "very", "view", "vote", "wait", "wake", "walk", "wall",
+    "want", "warm", "wash", "water", "wave", "weak", "wear", "week", "welcome", "well", "west",
+    "what", "when", "white", "wide", "wife", "wild", "will", "wind", "window", "wine", "wing",
+    "winter", "wire", "wise", "wish", "wolf", "woman", "wood", "word", "work", "world", "yard",
+    "year", "yellow", "your", "zero", "zone",
+];
+
+/// Longest name of a named class.
+pub const MAX_CLASS_NAME: usize = 32;
+
+/// A named character class that a policy can allow and force.
+///
+/// The four fixed sets (`Charset`) and the built-in named classes (`NAMED_CLASSES`) are
+/// providers; `Class::from_provider` turns any provider, including one defined by a library
+/// user, into a class of `Policy::classes`.
+pub trait CharsetProvider {
+    /// Name in `allow`/`force` and the policy encoding: lowercase ASCII letters, digits and
+    /// `-`, at most `MAX_CLASS_NAME` bytes.
+    fn name(&self) -> &str;
+
+    /// Characters of the class; each must belong to one of the fixed sets.
+    fn chars(&self) -> &[u8];
+}
+
+/// A built-in named class (see `NAMED_CLASSES`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NamedClass {
+    name: &'static str,
+    chars: &'static [u8],
+}
+
+impl CharsetProvider for NamedClass {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn chars(&self) -> &[u8] {
+        self.chars
+    }
+}
+
+/// Named classes available by name on the command line besides the fixed sets.
+pub const NAMED_CLASSES: [NamedClass; 3] = [
+    NamedClass { name: "hexlower", chars: b"0123456789abcdef" },
+    NamedClass { name: "hexupper", chars: b"0123456789ABCDEF" },
+    // RFC 4648
+    NamedClass { name: "base32", chars: b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567" },
+];
+
+/// Looks up a built-in named class.
+pub fn named_class(name: &str) -> Option<NamedClass> {
+    NAMED_CLASSES.iter().copied().find(|class| class.name == name)
+}
+
+/// A named class allowed by a policy in addition to the fixed sets, with its characters
+/// bound into the policy encoding (`class-<name>=<chars>`), so the policy derives the same
+/// passwords wherever it is decoded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Class {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "ascii_string"))]
+    pub chars: Vec<u8>, // sorted + deduped by validate
+    /// At least one character of the class is required
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub force: bool,
+}
+
+impl Class {
+    pub fn from_provider(provider: &dyn CharsetProvider, force: bool) -> Self {
+        Class { name: provider.name().to_string(), chars: provider.chars().to_vec(), force }
+    }
+}
+
+impl CharsetProvider for Class {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn chars(&self) -> &[u8] {
+        &self.chars
+    }
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum Charset {
     Lower,
     Upper,
@@ -14,15 +157,155 @@ pub enum Charset {
     Symbol,
 }
 
+impl Charset {
+    /// All sets in the fixed order used by `allow`/`force` and the policy encoding.
+    pub const ALL: [Charset; 4] = [Charset::Lower, Charset::Upper, Charset::Digit, Charset::Symbol];
+
+    /// Position of this set in the `allow`/`force` arrays.
+    pub fn index(self) -> usize {
+        match self {
+            Charset::Lower => 0,
+            Charset::Upper => 1,
+            Charset::Digit => 2,
+            Charset::Symbol => 3,
+        }
+    }
+
+    /// Name used in the policy encoding and on the CLI.
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Lower => "lower",
+            Charset::Upper => "upper",
+            Charset::Digit => "digit",
+            Charset::Symbol => "symbol",
+        }
+    }
+}
+
+/// Optional non-ASCII alphabets, allowed and forced like the fixed sets.
+///
+/// The tables are fixed: changing one would change every password derived with it, so a
+/// revised table is added under a new name (hence the version in `emoji-v1`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum Extended {
+    Latin1,
+    Greek,
+    Cyrillic,
+    #[cfg_attr(feature = "serde", serde(rename = "emoji-v1"))]
+    EmojiV1,
+}
+
+impl Extended {
+    /// All extended sets in the order used by `allow_extended`/`force_extended` and the policy encoding.
+    pub const ALL: [Extended; 4] = [Extended::Latin1, Extended::Greek, Extended::Cyrillic, Extended::EmojiV1];
+
+    /// Position of this set in the `allow_extended`/`force_extended` arrays.
+    pub fn index(self) -> usize {
+        match self {
+            Extended::Latin1 => 0,
+            Extended::Greek => 1,
+            Extended::Cyrillic => 2,
+            Extended::EmojiV1 => 3,
+        }
+    }
+
+    /// Name used in the policy encoding and on the CLI.
+    pub fn name(self) -> &'static str {
+        match self {
+            Extended::Latin1 => "latin1",
+            Extended::Greek => "greek",
+            Extended::Cyrillic => "cyrillic",
+            Extended::EmojiV1 => "emoji-v1",
+        }
+    }
+
+    /// Characters of the set, in code point order.
+    pub fn chars(self) -> &'static [char] {
+        match self {
+            Extended::Latin1 => LATIN1_CHARS,
+            Extended::Greek => GREEK_CHARS,
+            Extended::Cyrillic => CYRILLIC_CHARS,
+            Extended::EmojiV1 => EMOJI_V1_CHARS,
+        }
+    }
+}
+
+// Latin-1 Supplement letters: U+00C0..=U+00FF without × and ÷
+const LATIN1_CHARS: &[char] = &[
+    'À', 'Á', 'Â', 'Ã', 'Ä', 'Å', 'Æ', 'Ç', 'È', 'É', 'Ê', 'Ë', 'Ì', 'Í', 'Î', 'Ï',
+    'Ð', 'Ñ', 'Ò', 'Ó', 'Ô', 'Õ', 'Ö', 'Ø', 'Ù', 'Ú', 'Û', 'Ü', 'Ý', 'Þ', 'ß',
+    'à', 'á', 'â', 'ã', 'ä', 'å', 'æ', 'ç', 'è', 'é', 'ê', 'ë', 'ì', 'í', 'î', 'ï',
+    'ð', 'ñ', 'ò', 'ó', 'ô', 'õ', 'ö', 'ø', 'ù', 'ú', 'û', 'ü', 'ý', 'þ', 'ÿ',
+];
+// Modern Greek letters without tonos: U+0391..=U+03A9 and U+03B1..=U+03C9, without final sigma
+const GREEK_CHARS: &[char] = &[
+    'Α', 'Β', 'Γ', 'Δ', 'Ε', 'Ζ', 'Η', 'Θ', 'Ι', 'Κ', 'Λ', 'Μ', 'Ν', 'Ξ', 'Ο', 'Π', 'Ρ', 'Σ', 'Τ', 'Υ', 'Φ', 'Χ', 'Ψ', 'Ω',
+    'α', 'β', 'γ', 'δ', 'ε', 'ζ', 'η', 'θ', 'ι', 'κ', 'λ', 'μ', 'ν', 'ξ', 'ο', 'π', 'ρ', 'σ', 'τ', 'υ', 'φ', 'χ', 'ψ', 'ω',
+];
+// Russian alphabet: U+0401, U+0410..=U+044F, U+0451
+const CYRILLIC_CHARS: &[char] = &[
+    'Ё', 'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я', 'ё',
+];
+// 128 emoji (7 bits each) that are easy to tell apart and name: animals, plants, food,
+// faces and objects, each a single code point with emoji presentation (Unicode 11 or older)
+const EMOJI_V1_CHARS: &[char] = &[
+    '🌈', '🌙', '🌞', '🌲', '🌴', '🌵', '🌹', '🌻', '🌽', '🍀', '🍁', '🍄', '🍅', '🍇', '🍉', '🍊',
+    '🍋', '🍌', '🍍', '🍎', '🍐', '🍑', '🍒', '🍓', '🍔', '🍕', '🍟', '🍦', '🍩', '🍪', '🍭', '🍰',
+    '🍿', '🎁', '🎈', '🎩', '🎯', '🎲', '🎸', '🎹', '🎺', '🎻', '🎾', '🏀', '🏈', '🏠', '🏰', '🐊',
+    '🐌', '🐍', '🐔', '🐙', '🐛', '🐝', '🐞', '🐢', '🐤', '🐦', '🐧', '🐨', '🐬', '🐭', '🐮', '🐯',
+    '🐰', '🐱', '🐳', '🐴', '🐵', '🐶', '🐷', '🐸', '🐹', '🐻', '🐼', '👑', '👓', '👟', '👻', '👽',
+    '💀', '💎', '💡', '💧', '📌', '📚', '📷', '🔑', '🔒', '🔔', '🔥', '🔨', '🗿', '😀', '😂', '😅',
+    '😇', '😉', '😍', '😎', '😜', '😡', '😭', '😱', '😴', '🚀', '🚁', '🚗', '🚢', '🚲', '🤔', '🤖',
+    '🥁', '🥐', '🥑', '🥕', '🥥', '🦀', '🦁', '🦄', '🦆', '🦈', '🦉', '🦊', '🦋', '🧀', '🧦', '🧲',
+];
+
+impl CharsetProvider for Charset {
+    fn name(&self) -> &str {
+        Charset::name(*self)
+    }
+
+    fn chars(&self) -> &[u8] {
+        match self {
+            Charset::Lower => LOWER_BYTES,
+            Charset::Upper => UPPER_BYTES,
+            Charset::Digit => DIGIT_BYTES,
+            Charset::Symbol => SYMBOL_BYTES,
+        }
+    }
+}
+
+/// Missing fields take their `default_policy()` values when deserializing, and `exclude`
+/// is (de)serialized as a string.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
 pub struct Policy {
     pub min: u8,
     pub max: u8,
     pub allow: [bool; 4], // order: lower, upper, digit, symbol
     pub force: [bool; 4], // subset of allow
+    #[cfg_attr(feature = "serde", serde(with = "ascii_string"))]
+    pub exclude: Vec<u8>, // characters removed from every set (sorted + deduped by validate)
+    pub no_ambiguous: bool, // also remove AMBIGUOUS_BYTES from every set
+    pub layout_safe: bool, // keep only LAYOUT_SAFE_SYMBOLS and letters outside LAYOUT_MOVED_LETTERS
+    pub min_counts: [u8; 4], // minimum characters per set; nonzero implies force
+    pub max_counts: [Option<u8>; 4], // maximum characters per set; None = unlimited
+    pub max_consecutive: Option<u8>, // longest run of one repeated character; None = unlimited
+    pub max_sequential: Option<u8>, // longest ascending/descending run such as "abc" or "321"; None = unlimited
+    pub letter_first: bool, // the first character is a letter
+    pub letter_last: bool, // the last character is a letter
+    pub no_words: bool, // no ASCII run of letters spells a word from WORDS (any case)
+    pub no_site: bool, // the site and its labels (see site_names) do not appear (any case)
+    pub classes: Vec<Class>, // named classes allowed besides the fixed sets (sorted by name by validate)
+    pub allow_extended: [bool; 4], // order: latin1, greek, cyrillic, emoji-v1
+    pub force_extended: [bool; 4], // subset of allow_extended
 }
 
 #[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PolicyError {
     #[error("invalid length bounds (require 1 ≤ min ≤ max ≤ 128)")]
     InvalidBounds,
@@ -33,8 +316,47 @@ pub enum PolicyError {
     #[error("forced sets must be subset of allowed sets")]
     ForceNotSubset,
 
-    #[error("min length must be at least the number of forced sets")]
+    #[error("min length must be at least the number of required characters")]
     MinLessThanForcedCount,
+
+    #[error("forced sets must keep at least one character after exclusions")]
+    ForcedSetExcluded,
+
+    #[error("per-set maximum count must be at least the per-set minimum")]
+    MaxCountBelowMin,
+
+    #[error("per-set maximum counts leave too few characters for max length")]
+    MaxCountsTooSmall,
+
+    #[error("max consecutive must be at least 1")]
+    MaxConsecutiveZero,
+
+    #[error("max consecutive needs at least two characters in every allowed set")]
+    MaxConsecutiveUnsatisfiable,
+
+    #[error("max sequential must be at least 1")]
+    MaxSequentialZero,
+
+    #[error("max sequential needs a character outside each sequence in every allowed set")]
+    MaxSequentialUnsatisfiable,
+
+    #[error("invalid class: {0}")]
+    InvalidClass(String),
+
+    #[error("per-set maximum counts cannot be combined with named classes")]
+    MaxCountWithClasses,
+
+    #[error("layout-safe policies cannot allow extended sets")]
+    LayoutSafeExtended,
+
+    #[error("letter-first/letter-last need allowed letters beyond the per-set minimums")]
+    LetterUnavailable,
+
+    #[error("invalid passwordrules: {0}")]
+    InvalidPasswordRules(String),
+
+    #[error("invalid policy encoding: {0}")]
+    InvalidEncoding(String),
 }
 
 pub fn default_policy() -> Policy {
@@ -43,6 +365,45 @@ pub fn default_policy() -> Policy {
         max: 16,
         allow: [true, true, true, true],
         force: [false, false, false, false],
+        exclude: Vec::new(),
+        no_ambiguous: false,
+        layout_safe: false,
+        min_counts: [0, 0, 0, 0],
+        max_counts: [None, None, None, None],
+        max_consecutive: None,
+        max_sequential: None,
+        letter_first: false,
+        letter_last: false,
+        no_words: false,
+        no_site: false,
+        classes: Vec::new(),
+        allow_extended: [false; 4],
+        force_extended: [false; 4],
+    }
+}
+
+/// Serde helper storing ASCII byte lists (`Policy::exclude`) as strings.
+#[cfg(feature = "serde")]
+mod ascii_string {
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&String::from_utf8_lossy(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if !s.is_ascii() {
+            return Err(serde::de::Error::custom("exclude must be ASCII"));
+        }
+        Ok(s.into_bytes())
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        default_policy()
     }
 }
 
@@ -53,9 +414,26 @@ pub fn default_policy() -> Policy {
 /// satisfies:
 ///
 /// - `1 ≤ min ≤ max ≤ 128`
-/// - `allow` is not empty
-/// - `force ⊆ allow`
-/// - `min ≥ forced_count` (where forced_count is the number of forced sets)
+/// - `allow`, `allow_extended` and `classes` are not all empty
+/// - `force ⊆ allow` and `force_extended ⊆ allow_extended`
+/// - `min_counts[i] ≥ 1` exactly when `force[i]` (a forced set requires at least one character)
+/// - `min ≥ forced_count` (where forced_count is the sum of `min_counts` plus one per forced
+///   extended set, forced class and letter position)
+/// - `max_counts[i] ≥ min_counts[i]`; caps are only kept for allowed sets where they
+///   can bind (`cap < max`), and a cap of zero is folded into `allow`
+/// - when every nonempty allowed set is capped, the caps add up to at least `max`
+/// - `max_consecutive` is only kept when it can bind (`1 ≤ limit < max`), and then every
+///   nonempty allowed set has at least two characters so runs can be repaired
+/// - `max_sequential` likewise, and then every character of an allowed set has another one
+///   in its set that neither equals it nor follows it in a sequence
+/// - `exclude` is sorted, deduplicated, and only holds characters from the fixed sets
+/// - `layout_safe` policies allow no extended sets
+/// - `letter_first`/`letter_last` have letters to draw from: a letter set or class without a
+///   cap, or capped letter sets with room for them beyond their minimums
+/// - `classes` have valid names, distinct from each other and from the fixed and extended sets, are sorted by name,
+///   and hold sorted, deduplicated characters from the fixed sets; they exclude `max_counts`
+/// - the allowed alphabet, every forced set and every forced class are nonempty after
+///   exclusions
 ///
 /// After validation, the generator should not need to re-check any policy-related invariants.
 pub fn validate(policy: &Policy) -> Result<Policy, PolicyError> {
@@ -68,11 +446,48 @@ pub fn validate(policy: &Policy) -> Result<Policy, PolicyError> {
         return Err(PolicyError::InvalidBounds);
     }
 
-    let allow = policy.allow;
-    let force = policy.force;
+    // Per-set minimums imply force, and force implies a minimum of one
+    let mut force = policy.force;
+    let mut min_counts = policy.min_counts;
+    for i in 0..4 {
+        force[i] |= min_counts[i] > 0;
+        if force[i] {
+            min_counts[i] = min_counts[i].max(1);
+        }
+    }
+
+    // Per-set maximums: a cap of zero disallows the set, caps that can never bind are dropped
+    let mut allow = policy.allow;
+    let mut max_counts = policy.max_counts;
+    for i in 0..4 {
+        match max_counts[i] {
+            Some(cap) if cap < min_counts[i] => return Err(PolicyError::MaxCountBelowMin),
+            Some(0) => {
+                allow[i] = false;
+                max_counts[i] = None;
+            }
+            Some(cap) if !allow[i] || cap >= max => max_counts[i] = None,
+            _ => {}
+        }
+    }
+
+    // Normalize exclusions so equivalent policies share one encoding
+    let mut exclude: Vec<u8> = policy
+        .exclude
+        .iter()
+        .copied()
+        .filter(|&b| charset_of(b).is_some())
+        .collect();
+    exclude.sort_unstable();
+    exclude.dedup();
+
+    let classes = normalize_classes(&policy.classes)?;
+    if !classes.is_empty() && max_counts.iter().any(Option::is_some) {
+        return Err(PolicyError::MaxCountWithClasses);
+    }
 
     // Allowed union must be nonempty
-    if !allow.iter().any(|&b| b) {
+    if !allow.iter().any(|&b| b) && !policy.allow_extended.iter().any(|&b| b) && classes.is_empty() {
         return Err(PolicyError::EmptyAllowed);
     }
 
@@ -82,26 +497,430 @@ pub fn validate(policy: &Policy) -> Result<Policy, PolicyError> {
             return Err(PolicyError::ForceNotSubset);
         }
     }
+    for i in 0..4 {
+        if policy.force_extended[i] && !policy.allow_extended[i] {
+            return Err(PolicyError::ForceNotSubset);
+        }
+    }
+    if policy.layout_safe && policy.allow_extended.iter().any(|&b| b) {
+        return Err(PolicyError::LayoutSafeExtended);
+    }
 
-    // Enforce min ≥ forced_count (where forced_count is the number of forced sets)
-    let forced_count = force.iter().filter(|&&b| b).count() as u8;
-    if min < forced_count {
+    // Enforce min ≥ forced_count (where forced_count is the number of required characters)
+    let forced_count: u32 = min_counts.iter().map(|&c| c as u32).sum::<u32>()
+        + policy.force_extended.iter().filter(|&&b| b).count() as u32
+        + classes.iter().filter(|class| class.force).count() as u32
+        + u32::from(policy.letter_first)
+        + u32::from(policy.letter_last);
+    if (min as u32) < forced_count {
         return Err(PolicyError::MinLessThanForcedCount);
     }
 
-    Ok(Policy { min, max, allow, force })
+    let normalized = Policy {
+        min,
+        max,
+        allow,
+        force,
+        exclude,
+        no_ambiguous: policy.no_ambiguous,
+        layout_safe: policy.layout_safe,
+        min_counts,
+        max_counts,
+        max_consecutive: match policy.max_consecutive {
+            Some(0) => return Err(PolicyError::MaxConsecutiveZero),
+            Some(limit) if limit < max => Some(limit),
+            _ => None,
+        },
+        max_sequential: match policy.max_sequential {
+            Some(0) => return Err(PolicyError::MaxSequentialZero),
+            Some(limit) if limit < max => Some(limit),
+            _ => None,
+        },
+        letter_first: policy.letter_first,
+        letter_last: policy.letter_last,
+        no_words: policy.no_words,
+        no_site: policy.no_site,
+        classes,
+        allow_extended: policy.allow_extended,
+        force_extended: policy.force_extended,
+    };
+
+    // Exclusions must not empty the union or any forced set
+    if allowed_alphabet(&normalized).is_empty() && allowed_extended(&normalized).is_empty() {
+        return Err(PolicyError::EmptyAllowed);
+    }
+    if forced_sets(&normalized).iter().any(|(_, alphabet)| alphabet.is_empty())
+        || allowed_classes(&normalized).iter().any(|(class, alphabet)| class.force && alphabet.is_empty())
+    {
+        return Err(PolicyError::ForcedSetExcluded);
+    }
+
+    // Capped sets must leave room for the longest password (None = some set is unlimited;
+    // extended sets are, and so are named classes, which exclude caps)
+    let capacity: Option<u32> = allowed_sets(&normalized)
+        .iter()
+        .filter(|(_, alphabet)| !alphabet.is_empty())
+        .map(|(set, _)| normalized.max_counts[set.index()].map(u32::from))
+        .sum();
+    if normalized.classes.is_empty()
+        && allowed_extended(&normalized).is_empty()
+        && capacity.is_some_and(|c| c < max as u32)
+    {
+        return Err(PolicyError::MaxCountsTooSmall);
+    }
+
+    // Run repair redraws within the same set, so each set needs an alternative character
+    // (and an alternative letter, where a letter position may draw from it)
+    let letter_positions = u32::from(normalized.letter_first) + u32::from(normalized.letter_last);
+    let letter_count = |alphabet: &[u8]| alphabet.iter().filter(|b| b.is_ascii_alphabetic()).count();
+    if normalized.max_consecutive.is_some()
+        && (allowed_sets(&normalized).iter().any(|(_, alphabet)| alphabet.len() == 1)
+            || allowed_classes(&normalized).iter().any(|(_, alphabet)| alphabet.len() == 1)
+            || (letter_positions > 0
+                && allowed_sets(&normalized)
+                    .iter()
+                    .map(|(_, alphabet)| alphabet)
+                    .chain(allowed_classes(&normalized).iter().map(|(_, alphabet)| alphabet))
+                    .any(|alphabet| letter_count(alphabet) == 1)))
+    {
+        return Err(PolicyError::MaxConsecutiveUnsatisfiable);
+    }
+
+    // Sequence repair also skips the neighbours of the previous character
+    if normalized.max_sequential.is_some() {
+        let ascii = |alphabet: &[u8]| alphabet.iter().map(|&b| char::from(b)).collect::<Vec<char>>();
+        let mut alphabets: Vec<Vec<char>> = allowed_sets(&normalized)
+            .iter()
+            .map(|(_, alphabet)| ascii(alphabet))
+            .chain(allowed_classes(&normalized).iter().map(|(_, alphabet)| ascii(alphabet)))
+            .chain(allowed_extended(&normalized).iter().map(|(_, chars)| chars.to_vec()))
+            .collect();
+        if normalized.letter_last {
+            let letters: Vec<Vec<char>> =
+                alphabets.iter().map(|alphabet| alphabet.iter().copied().filter(|c| c.is_alphabetic()).collect()).collect();
+            alphabets.extend(letters);
+        }
+        let repairable = |alphabet: &Vec<char>| {
+            alphabet.iter().all(|&p| alphabet.iter().any(|&c| c != p && sequence_step(p, c).is_none()))
+        };
+        if !alphabets.iter().all(repairable) {
+            return Err(PolicyError::MaxSequentialUnsatisfiable);
+        }
+    }
+
+    // Letter positions draw from the letters of sets below their cap after their minimums
+    // (None = some letter set, extended set or class is unlimited)
+    if letter_positions > 0 {
+        let capped_room: Option<u32> = allowed_sets(&normalized)
+            .iter()
+            .filter(|(_, alphabet)| letter_count(alphabet) > 0)
+            .map(|(set, _)| {
+                let i = set.index();
+                normalized.max_counts[i].map(|cap| u32::from(cap - normalized.min_counts[i]))
+            })
+            .chain(letters_outside_fixed_sets(&normalized).then_some(None))
+            .sum();
+        if letters(&normalized).is_empty() || capped_room.is_some_and(|room| room < letter_positions) {
+            return Err(PolicyError::LetterUnavailable);
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Whether an allowed extended set or named class adds letters the fixed sets lack.
+fn letters_outside_fixed_sets(policy: &Policy) -> bool {
+    let fixed: Vec<u8> = allowed_sets(policy).into_iter().flat_map(|(_, alphabet)| alphabet).collect();
+    class_extras(policy, &fixed).iter().any(u8::is_ascii_alphabetic)
+        || allowed_extended(policy).iter().any(|(_, chars)| chars.iter().any(|c| c.is_alphabetic()))
+}
+
+/// Returns the allowed letters of a policy (ASCII first, then extended sets), minus exclusions.
+pub fn letters(policy: &Policy) -> Vec<char> {
+    allowed_alphabet(policy)
+        .into_iter()
+        .filter(u8::is_ascii_alphabetic)
+        .map(char::from)
+        .chain(allowed_extended(policy).into_iter().flat_map(|(_, chars)| chars.iter().copied()).filter(|c| c.is_alphabetic()))
+        .collect()
+}
+
+/// Checks the names and characters of named classes, and sorts them (and their characters)
+/// so equivalent policies share one encoding.
+fn normalize_classes(classes: &[Class]) -> Result<Vec<Class>, PolicyError> {
+    let err = |msg: String| PolicyError::InvalidClass(msg);
+    let mut out: Vec<Class> = Vec::with_capacity(classes.len());
+    for class in classes {
+        let name = class.name.as_str();
+        if name.is_empty()
+            || name.len() > MAX_CLASS_NAME
+            || !name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        {
+            return Err(err(format!(
+                "name '{}' must be 1 to {} lowercase letters, digits or '-'",
+                name, MAX_CLASS_NAME
+            )));
+        }
+        if charset_from_name(name).is_some() || extended_from_name(name).is_some() || out.iter().any(|c| c.name == name)
+        {
+            return Err(err(format!("'{}' is defined twice", name)));
+        }
+        if let Some(&b) = class.chars.iter().find(|&&b| charset_of(b).is_none()) {
+            return Err(err(format!("'{}' holds '{}', which is not in the fixed sets", name, b.escape_ascii())));
+        }
+        if class.chars.is_empty() {
+            return Err(err(format!("'{}' has no characters", name)));
+        }
+        let mut chars = class.chars.clone();
+        chars.sort_unstable();
+        chars.dedup();
+        out.push(Class { name: class.name.clone(), chars, force: class.force });
+    }
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(out)
 }
 
 /// Canonical, deterministic encoding used in PRNG context
 /// Format: b"min=" <u8> b";max=" <u8> b";allow=" <csv> b";force=" <csv>
-/// csv order: lower,upper,digit,symbol; empty union encodes as empty string
+///         [b";exclude=" <chars>] [b";no-ambiguous=1"] [b";layout-safe=1"] [b";min-count=" <set:n csv>]
+///         [b";max-count=" <set:n csv>] [b";max-consecutive=" <u8>] [b";max-sequential=" <u8>]
+///         [b";letter-first=1"] [b";letter-last=1"] [b";no-words=1"] [b";no-site=1"]
+///         [b";class-" <name> b"=" <chars>]...
+/// csv order: lower,upper,digit,symbol, then latin1,greek,cyrillic,emoji-v1, then named classes; empty union
+/// encodes as empty string
+/// Optional fields are omitted when unset so existing policies keep their encoding;
+/// min-count only lists sets requiring two or more characters (one is implied by force);
+/// `%`, `;` and `=` inside exclude and class characters are written as `%XX` (uppercase hex).
 pub fn encode(policy: &Policy) -> String {
-    let allow_csv = csv_from_flags(policy.allow);
-    let force_csv = csv_from_flags(policy.force);
-    format!(
+    let with_names = |flags: [bool; 4], extended: [bool; 4], forced_only: bool| {
+        let extended_names = Extended::ALL.iter().filter(|set| extended[set.index()]).map(|set| set.name());
+        let class_names = policy.classes.iter().filter(|class| class.force || !forced_only).map(|class| class.name.as_str());
+        let mut csv = csv_from_flags(flags);
+        for name in extended_names.chain(class_names) {
+            if !csv.is_empty() {
+                csv.push(',');
+            }
+            csv.push_str(name);
+        }
+        csv
+    };
+    let allow_csv = with_names(policy.allow, policy.allow_extended, false);
+    let force_csv = with_names(policy.force, policy.force_extended, true);
+    let mut out = format!(
         "min={};max={};allow={};force={}",
         policy.min, policy.max, allow_csv, force_csv
-    )
+    );
+    if !policy.exclude.is_empty() {
+        out.push_str(";exclude=");
+        out.push_str(&escape_value(&policy.exclude));
+    }
+    if policy.no_ambiguous {
+        out.push_str(";no-ambiguous=1");
+    }
+    if policy.layout_safe {
+        out.push_str(";layout-safe=1");
+    }
+    let min_count_csv = Charset::ALL
+        .iter()
+        .filter(|set| policy.min_counts[set.index()] > 1)
+        .map(|set| format!("{}:{}", set.name(), policy.min_counts[set.index()]))
+        .collect::<Vec<_>>()
+        .join(",");
+    if !min_count_csv.is_empty() {
+        out.push_str(";min-count=");
+        out.push_str(&min_count_csv);
+    }
+    let max_count_csv = Charset::ALL
+        .iter()
+        .filter_map(|set| policy.max_counts[set.index()].map(|cap| format!("{}:{}", set.name(), cap)))
+        .collect::<Vec<_>>()
+        .join(",");
+    if !max_count_csv.is_empty() {
+        out.push_str(";max-count=");
+        out.push_str(&max_count_csv);
+    }
+    if let Some(limit) = policy.max_consecutive {
+        out.push_str(&format!(";max-consecutive={}", limit));
+    }
+    if let Some(limit) = policy.max_sequential {
+        out.push_str(&format!(";max-sequential={}", limit));
+    }
+    if policy.letter_first {
+        out.push_str(";letter-first=1");
+    }
+    if policy.letter_last {
+        out.push_str(";letter-last=1");
+    }
+    if policy.no_words {
+        out.push_str(";no-words=1");
+    }
+    if policy.no_site {
+        out.push_str(";no-site=1");
+    }
+    for class in &policy.classes {
+        out.push_str(";class-");
+        out.push_str(&class.name);
+        out.push('=');
+        out.push_str(&escape_value(&class.chars));
+    }
+    out
+}
+
+fn escape_value(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'%' | b';' | b'=' => out.push_str(&format!("%{:02X}", b)),
+            _ => out.push(b as char),
+        }
+    }
+    out
+}
+
+/// Inverse of `escape_value`.
+fn unescape_value(value: &str) -> Option<Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // from_str_radix alone would accept a sign (`%+1`)
+            let hex = value.get(i + 1..i + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
+
+/// Decodes a string produced by `encode` back into a policy.
+///
+/// Fields may appear in any order, but `min`, `max`, `allow` and `force` are required and
+/// each field may appear once. The result is not validated; pass it through `validate()`
+/// before generating. For any validated policy, `decode(&encode(&p))` returns `p`.
+pub fn decode(encoded: &str) -> Result<Policy, PolicyError> {
+    let err = |msg: String| PolicyError::InvalidEncoding(msg);
+
+    let mut policy = default_policy();
+    let mut seen: Vec<&str> = Vec::new();
+    let mut allowed_classes: Vec<&str> = Vec::new();
+    let mut forced_classes: Vec<&str> = Vec::new();
+    for field in encoded.split(';') {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| err(format!("expected key=value, got '{}'", field)))?;
+        if seen.contains(&key) {
+            return Err(err(format!("duplicate field '{}'", key)));
+        }
+        seen.push(key);
+
+        let parse_u8 = |v: &str| {
+            v.parse::<u8>()
+                .map_err(|_| err(format!("{} expects a number in [0,255], got '{}'", key, v)))
+        };
+        match key {
+            "min" => policy.min = parse_u8(value)?,
+            "max" => policy.max = parse_u8(value)?,
+            "allow" => (policy.allow, policy.allow_extended, allowed_classes) = flags_from_csv(value),
+            "force" => (policy.force, policy.force_extended, forced_classes) = flags_from_csv(value),
+            "exclude" => policy.exclude = unescape_value(value).ok_or_else(|| err(format!("bad escape in '{}'", value)))?,
+            "no-ambiguous" if value == "1" => policy.no_ambiguous = true,
+            "layout-safe" if value == "1" => policy.layout_safe = true,
+            "min-count" => {
+                for (set, n) in set_counts_from_csv(value).ok_or_else(|| err(format!("bad SET:N list '{}'", value)))? {
+                    policy.min_counts[set.index()] = n;
+                }
+            }
+            "max-count" => {
+                for (set, n) in set_counts_from_csv(value).ok_or_else(|| err(format!("bad SET:N list '{}'", value)))? {
+                    policy.max_counts[set.index()] = Some(n);
+                }
+            }
+            "max-consecutive" => policy.max_consecutive = Some(parse_u8(value)?),
+            "max-sequential" => policy.max_sequential = Some(parse_u8(value)?),
+            "letter-first" if value == "1" => policy.letter_first = true,
+            "letter-last" if value == "1" => policy.letter_last = true,
+            "no-words" if value == "1" => policy.no_words = true,
+            "no-site" if value == "1" => policy.no_site = true,
+            _ if key.starts_with("class-") => {
+                let chars = unescape_value(value).ok_or_else(|| err(format!("bad escape in '{}'", value)))?;
+                policy.classes.push(Class { name: key["class-".len()..].to_string(), chars, force: false });
+            }
+            _ => return Err(err(format!("unknown field '{}={}'", key, value))),
+        }
+    }
+
+    for required in ["min", "max", "allow", "force"] {
+        if !seen.contains(&required) {
+            return Err(err(format!("missing field '{}'", required)));
+        }
+    }
+
+    // Named classes in allow and force need their characters, and each defined class is allowed
+    for class in &mut policy.classes {
+        if !allowed_classes.contains(&class.name.as_str()) {
+            return Err(err(format!("class '{}' is not in allow", class.name)));
+        }
+        class.force = forced_classes.contains(&class.name.as_str());
+    }
+    for name in allowed_classes.iter().chain(&forced_classes) {
+        if !policy.classes.iter().any(|class| class.name == *name) {
+            return Err(err(format!("unknown set '{}'", name)));
+        }
+    }
+
+    // Forced sets without a listed count need one character, as in validate()
+    for i in 0..4 {
+        if policy.force[i] {
+            policy.min_counts[i] = policy.min_counts[i].max(1);
+        }
+    }
+    Ok(policy)
+}
+
+impl FromStr for Policy {
+    type Err = PolicyError;
+
+    /// Same as `decode`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        decode(s)
+    }
+}
+
+fn charset_from_name(name: &str) -> Option<Charset> {
+    Charset::ALL.iter().copied().find(|set| set.name() == name)
+}
+
+/// Looks up an extended set by its name.
+pub fn extended_from_name(name: &str) -> Option<Extended> {
+    Extended::ALL.iter().copied().find(|set| set.name() == name)
+}
+
+/// Splits a set list into the fixed sets, the extended sets and the names of named classes.
+fn flags_from_csv(csv: &str) -> ([bool; 4], [bool; 4], Vec<&str>) {
+    let mut flags = [false; 4];
+    let mut extended = [false; 4];
+    let mut classes = Vec::new();
+    for name in csv.split(',').filter(|n| !n.is_empty()) {
+        match (charset_from_name(name), extended_from_name(name)) {
+            (Some(set), _) => flags[set.index()] = true,
+            (None, Some(set)) => extended[set.index()] = true,
+            (None, None) => classes.push(name),
+        }
+    }
+    (flags, extended, classes)
+}
+
+fn set_counts_from_csv(csv: &str) -> Option<Vec<(Charset, u8)>> {
+    csv.split(',')
+        .map(|pair| {
+            let (name, n) = pair.split_once(':')?;
+            Some((charset_from_name(name)?, n.parse().ok()?))
+        })
+        .collect()
 }
 
 fn csv_from_flags(flags: [bool; 4]) -> String {
@@ -121,38 +940,631 @@ fn csv_from_flags(flags: [bool; 4]) -> String {
     parts.join(",")
 }
 
-/// Returns concatenated allowed alphabet (in fixed set order).
+/// Returns the characters of a single set or class with the policy's exclusions removed.
+fn set_alphabet(policy: &Policy, set: &[u8]) -> Vec<u8> {
+    set.iter()
+        .copied()
+        .filter(|b| !policy.exclude.contains(b))
+        .filter(|b| !(policy.no_ambiguous && AMBIGUOUS_BYTES.contains(b)))
+        .filter(|b| !policy.layout_safe || layout_safe(*b))
+        .collect()
+}
+
+/// Whether a character is typed with the same key and shift state on US, UK, German and
+/// French keyboards (digits, from the numeric keypad).
+fn layout_safe(b: u8) -> bool {
+    match charset_of(b) {
+        Some(Charset::Symbol) => LAYOUT_SAFE_SYMBOLS.contains(&b),
+        _ => !LAYOUT_MOVED_LETTERS.contains(&b),
+    }
+}
+
+/// Returns concatenated allowed ASCII alphabet (in fixed set order, then the characters named
+/// classes add), minus exclusions. Extended sets are listed by `allowed_extended`.
 pub fn allowed_alphabet(policy: &Policy) -> Vec<u8> {
     let mut out = Vec::with_capacity(LOWER_BYTES.len() + UPPER_BYTES.len() + DIGIT_BYTES.len() + SYMBOL_BYTES.len());
-    if policy.allow[0] {
-        out.extend_from_slice(LOWER_BYTES);
+    for set in Charset::ALL {
+        if policy.allow[set.index()] {
+            out.extend(set_alphabet(policy, set.chars()));
+        }
+    }
+    out.extend(class_extras(policy, &out));
+    out
+}
+
+/// Characters of the named classes that `alphabet` lacks, in class order, minus exclusions.
+pub fn class_extras(policy: &Policy, alphabet: &[u8]) -> Vec<u8> {
+    let mut extras: Vec<u8> = Vec::new();
+    for b in allowed_classes(policy).into_iter().flat_map(|(_, chars)| chars) {
+        if !alphabet.contains(&b) && !extras.contains(&b) {
+            extras.push(b);
+        }
     }
-    if policy.allow[1] {
-        out.extend_from_slice(UPPER_BYTES);
+    extras
+}
+
+/// Estimated entropy, in bits, of a password generated under a validated policy.
+///
+/// Counts the passwords of the shortest allowed length that satisfy the per-set minimum
+/// and maximum counts (so forced sets lower the estimate) and returns log2 of that count.
+/// Longer lengths only add entropy. The run and sequence limits (`max_consecutive`,
+/// `max_sequential`) remove the share of outputs that uniform draws from the whole alphabet
+/// would lose to each of them (exact without per-set counts). Letter positions (`letter_first`, `letter_last`) each add log2 of the
+/// number of letters to the count for the remaining characters.
+#[cfg(feature = "std")]
+pub fn entropy_bits(policy: &Policy) -> f64 {
+    let letter_positions = usize::from(policy.letter_first) + usize::from(policy.letter_last);
+    let len = policy.min as usize - letter_positions;
+
+    // Exponential generating function: set i contributes sum_c n_i^c / c! * x^c over its
+    // allowed counts c; the count of valid strings is len! * [x^len] of the product
+    let mut poly = vec![0.0f64; len + 1];
+    poly[0] = 1.0;
+    for (set, alphabet) in allowed_sets(policy) {
+        let i = set.index();
+        let lo = policy.min_counts[i] as usize;
+        let hi = policy.max_counts[i].map_or(len, |cap| (cap as usize).min(len));
+        let n = alphabet.len() as f64;
+
+        let mut term = vec![0.0f64; len + 1];
+        let mut t = 1.0f64; // n^c / c!
+        for (c, slot) in term.iter_mut().enumerate().take(hi + 1) {
+            if c > 0 {
+                t *= n / c as f64;
+            }
+            if c >= lo {
+                *slot = t;
+            }
+        }
+
+        let mut next = vec![0.0f64; len + 1];
+        for (a, &pa) in poly.iter().enumerate() {
+            for (b, &tb) in term.iter().enumerate().take(len + 1 - a) {
+                next[a + b] += pa * tb;
+            }
+        }
+        poly = next;
     }
-    if policy.allow[2] {
-        out.extend_from_slice(DIGIT_BYTES);
+
+    // Extended sets and named classes add their new characters, with no count constraint
+    // (which only lowers the estimate where a forced set or class has characters of its own)
+    let fixed: Vec<u8> = allowed_sets(policy).into_iter().flat_map(|(_, alphabet)| alphabet).collect();
+    let extended: usize = allowed_extended(policy).iter().map(|(_, chars)| chars.len()).sum();
+    let extras = (class_extras(policy, &fixed).len() + extended) as f64;
+    if extras > 0.0 {
+        let mut next = vec![0.0f64; len + 1];
+        for (a, &pa) in poly.iter().enumerate() {
+            let mut t = 1.0f64; // extras^c / c!
+            for (c, slot) in next.iter_mut().skip(a).enumerate() {
+                if c > 0 {
+                    t *= extras / c as f64;
+                }
+                *slot += pa * t;
+            }
+        }
+        poly = next;
     }
-    if policy.allow[3] {
-        out.extend_from_slice(SYMBOL_BYTES);
+
+    let count = (1..=len).fold(poly[len], |acc, k| acc * k as f64);
+    let mut bits = count.log2();
+    if letter_positions > 0 {
+        bits += letter_positions as f64 * (letters(policy).len() as f64).log2();
     }
-    out
+    let alphabet: Vec<char> = allowed_alphabet(policy)
+        .into_iter()
+        .map(char::from)
+        .chain(allowed_extended(policy).into_iter().flat_map(|(_, chars)| chars.iter().copied()))
+        .collect();
+    if let Some(limit) = policy.max_consecutive {
+        bits += run_limit_share(alphabet.len() as f64, limit as usize, policy.min as usize).log2();
+    }
+    if let Some(limit) = policy.max_sequential {
+        bits += sequence_limit_share(&alphabet, limit as usize, policy.min as usize).log2();
+    }
+    bits
+}
+
+/// Share of the `n^len` strings over `alphabet` without a sequence longer than `limit`.
+///
+/// Tracks the share of strings ending in each character by the sequence they end in:
+/// `single` (none, or a sequence of length 1), and `up[l]`/`down[l]` for sequences of
+/// length `l ≥ 2`; each added character takes `1/n` of its predecessor's share.
+#[cfg(feature = "std")]
+fn sequence_limit_share(alphabet: &[char], limit: usize, len: usize) -> f64 {
+    let n = alphabet.len();
+    let neighbour = |c: char, step: i8| {
+        let d = char::from_u32((c as u32).wrapping_add_signed(step.into()))?;
+        alphabet.iter().position(|&a| a == d).filter(|_| sequence_step(c, d) == Some(step))
+    };
+    let below: Vec<Option<usize>> = alphabet.iter().map(|&c| neighbour(c, -1)).collect();
+    let above: Vec<Option<usize>> = alphabet.iter().map(|&c| neighbour(c, 1)).collect();
+
+    let mut single = vec![1.0 / n as f64; n];
+    let mut up = vec![vec![0.0f64; n]; limit + 1];
+    let mut down = vec![vec![0.0f64; n]; limit + 1];
+    let ending = |single: &[f64], up: &[Vec<f64>], down: &[Vec<f64>], i: usize| {
+        single[i] + (2..=limit).map(|l| up[l][i] + down[l][i]).sum::<f64>()
+    };
+    for _ in 1..len {
+        let total: f64 = (0..n).map(|i| ending(&single, &up, &down, i)).sum();
+        let mut next_single = vec![0.0f64; n];
+        let mut next_up = vec![vec![0.0f64; n]; limit + 1];
+        let mut next_down = vec![vec![0.0f64; n]; limit + 1];
+        for j in 0..n {
+            let mut rest = total;
+            // From the character below j the sequence ascends, from the one above it descends
+            if let Some(p) = below[j] {
+                rest -= ending(&single, &up, &down, p);
+                if limit >= 2 {
+                    next_up[2][j] = single[p] + (2..=limit).map(|l| down[l][p]).sum::<f64>();
+                    for l in 3..=limit {
+                        next_up[l][j] = up[l - 1][p];
+                    }
+                }
+            }
+            if let Some(p) = above[j] {
+                rest -= ending(&single, &up, &down, p);
+                if limit >= 2 {
+                    next_down[2][j] = single[p] + (2..=limit).map(|l| up[l][p]).sum::<f64>();
+                    for l in 3..=limit {
+                        next_down[l][j] = down[l - 1][p];
+                    }
+                }
+            }
+            next_single[j] = rest;
+        }
+        let scale = 1.0 / n as f64;
+        single = next_single.iter().map(|v| v * scale).collect();
+        up = next_up.iter().map(|row| row.iter().map(|v| v * scale).collect()).collect();
+        down = next_down.iter().map(|row| row.iter().map(|v| v * scale).collect()).collect();
+    }
+    (0..n).map(|i| ending(&single, &up, &down, i)).sum()
 }
 
-/// Returns a Vec<(Charset, &'static [u8])> for all forced sets that are allowed.
-pub fn forced_sets(policy: &Policy) -> Vec<(Charset, &'static [u8])> {
-    let mut v = Vec::with_capacity(4);
-    if policy.force[0] && policy.allow[0] {
-        v.push((Charset::Lower, LOWER_BYTES));
+/// Share of the `n^len` strings over `n` characters without a run longer than `limit`.
+///
+/// With `h(m)` the share for length `m`, `h(m) = 1` up to `limit`, and beyond it a valid
+/// string is a valid one of length `m - j` followed by a run of `j ≤ limit` copies of a
+/// character other than its last: `h(m) = (n - 1) / n * sum_j h(m - j) / n^(j - 1)`.
+#[cfg(feature = "std")]
+fn run_limit_share(n: f64, limit: usize, len: usize) -> f64 {
+    let mut h = vec![1.0f64; len + 1];
+    for m in limit + 1..=len {
+        let mut sum = 0.0;
+        let mut scale = 1.0; // n^(j - 1)
+        for j in 1..=limit {
+            sum += h[m - j] / scale;
+            scale *= n;
+        }
+        h[m] = (n - 1.0) / n * sum;
     }
-    if policy.force[1] && policy.allow[1] {
-        v.push((Charset::Upper, UPPER_BYTES));
+    h[len]
+}
+
+/// Returns a Vec<(Charset, alphabet)> for all allowed sets, minus exclusions.
+pub fn allowed_sets(policy: &Policy) -> Vec<(Charset, Vec<u8>)> {
+    Charset::ALL
+        .iter()
+        .filter(|set| policy.allow[set.index()])
+        .map(|&set| (set, set_alphabet(policy, set.chars())))
+        .collect()
+}
+
+/// Returns the named classes of a policy with their alphabets, minus exclusions.
+pub fn allowed_classes(policy: &Policy) -> Vec<(&Class, Vec<u8>)> {
+    policy.classes.iter().map(|class| (class, set_alphabet(policy, &class.chars))).collect()
+}
+
+/// Returns the allowed extended sets of a policy with their characters (exclusions only apply
+/// to ASCII characters).
+pub fn allowed_extended(policy: &Policy) -> Vec<(Extended, &'static [char])> {
+    Extended::ALL
+        .iter()
+        .filter(|set| policy.allow_extended[set.index()])
+        .map(|&set| (set, set.chars()))
+        .collect()
+}
+
+/// Step from `a` to `b` when they are neighbours in a sequence: `Some(1)` for `ab`, `12`
+/// and `AB`, `Some(-1)` for `ba`. Only ASCII letters of one case and digits form sequences.
+pub fn sequence_step(a: char, b: char) -> Option<i8> {
+    let same_kind = (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+        || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+        || (a.is_ascii_digit() && b.is_ascii_digit());
+    match (b as i32) - (a as i32) {
+        1 if same_kind => Some(1),
+        -1 if same_kind => Some(-1),
+        _ => None,
     }
-    if policy.force[2] && policy.allow[2] {
-        v.push((Charset::Digit, DIGIT_BYTES));
+}
+
+/// Finds the words from the embedded wordlist spelled by consecutive ASCII letters of
+/// `chars` (any case), as (0-based start, word) in order of position, then length.
+pub fn find_words(chars: &[char]) -> Vec<(usize, &'static str)> {
+    let longest = WORDS.iter().map(|word| word.len()).max().unwrap_or(0);
+    let mut found = Vec::new();
+    for start in 0..chars.len() {
+        let mut window = String::new();
+        for &c in chars[start..].iter().take(longest) {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            window.push(c.to_ascii_lowercase());
+            if let Ok(i) = WORDS.binary_search(&window.as_str()) {
+                found.push((start, WORDS[i]));
+            }
+        }
     }
-    if policy.force[3] && policy.allow[3] {
-        v.push((Charset::Symbol, SYMBOL_BYTES));
+    found
+}
+
+/// Whether `chars` spells a word from the embedded wordlist (see `find_words`).
+pub fn contains_word(chars: &[char]) -> bool {
+    !find_words(chars).is_empty()
+}
+
+/// Returns the names `no_site` keeps out of passwords for a site: the site as used for
+/// derivation (trimmed and lowercased) and, for a domain, each label but the last
+/// (`accounts.google.com` → `accounts.google.com`, `accounts`, `google`). Names shorter than
+/// 3 characters are left out.
+pub fn site_names(site: &str) -> Vec<String> {
+    let site = site.trim().to_ascii_lowercase();
+    let labels: Vec<&str> = site.split('.').collect();
+    let mut names: Vec<String> = Vec::new();
+    for name in [site.as_str()].into_iter().chain(labels[..labels.len() - 1].iter().copied()) {
+        if name.chars().count() >= MIN_SITE_NAME && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Finds the names of `site` (see `site_names`) that appear in `chars` (any case), as
+/// (0-based start, name) in order of position, then length.
+pub fn find_site_names(chars: &[char], site: &str) -> Vec<(usize, String)> {
+    let lower: Vec<char> = chars.iter().map(|&c| lowercase(c)).collect();
+    let mut names: Vec<(String, Vec<char>)> =
+        site_names(site).into_iter().map(|name| (name.clone(), name.chars().map(lowercase).collect())).collect();
+    names.sort_by_key(|(_, name)| name.len());
+    let mut found = Vec::new();
+    for start in 0..lower.len() {
+        for (name, pattern) in &names {
+            if lower[start..].starts_with(pattern) {
+                found.push((start, name.clone()));
+            }
+        }
+    }
+    found
+}
+
+/// Lowercase form of a character, where it is a single character.
+fn lowercase(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+/// Returns the fixed set a character belongs to, if any.
+pub fn charset_of(b: u8) -> Option<Charset> {
+    Charset::ALL.iter().copied().find(|set| set.chars().contains(&b))
+}
+
+/// Returns a Vec<(Charset, alphabet)> for all forced sets that are allowed, minus exclusions.
+pub fn forced_sets(policy: &Policy) -> Vec<(Charset, Vec<u8>)> {
+    Charset::ALL
+        .iter()
+        .filter(|set| policy.force[set.index()] && policy.allow[set.index()])
+        .map(|&set| (set, set_alphabet(policy, set.chars())))
+        .collect()
+}
+
+/// A policy constraint that an existing password breaks (see `check`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Violation {
+    TooShort { length: usize, min: u8 },
+    TooLong { length: usize, max: u8 },
+    /// Characters at these 1-based positions are outside the allowed alphabet
+    Disallowed { positions: Vec<usize> },
+    TooFew { set: Charset, count: usize, min: u8 },
+    TooMany { set: Charset, count: usize, max: u8 },
+    /// Run of one repeated character starting at this 1-based position
+    Run { position: usize, length: usize, max: u8 },
+    /// Ascending or descending sequence starting at this 1-based position
+    Sequence { position: usize, length: usize, max: u8 },
+    /// No character of a forced extended set or named class
+    MissingClass { name: String },
+    /// The character at this 1-based position must be a letter
+    NotLetter { position: usize },
+    /// Dictionary word starting at this 1-based position
+    Word { position: usize, word: &'static str },
+    /// Site name (see `site_names`) starting at this 1-based position
+    SiteName { position: usize, name: String },
+}
+
+impl core::fmt::Display for Violation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Violation::TooShort { length, min } => write!(f, "too short: {} characters, at least {} required", length, min),
+            Violation::TooLong { length, max } => write!(f, "too long: {} characters, at most {} allowed", length, max),
+            Violation::Disallowed { positions } if positions.len() == 1 => {
+                write!(f, "character {} is not allowed", positions[0])
+            }
+            Violation::Disallowed { positions } => {
+                // Consecutive positions are shown as ranges: 2-5, 9
+                let mut ranges: Vec<(usize, usize)> = Vec::new();
+                for &p in positions {
+                    match ranges.last_mut() {
+                        Some((_, end)) if *end + 1 == p => *end = p,
+                        _ => ranges.push((p, p)),
+                    }
+                }
+                let ranges: Vec<String> = ranges
+                    .iter()
+                    .map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+                    .collect();
+                write!(f, "characters {} are not allowed", ranges.join(", "))
+            }
+            Violation::TooFew { set, count, min } => {
+                write!(f, "too few {} characters: {}, at least {} required", set.name(), count, min)
+            }
+            Violation::TooMany { set, count, max } => {
+                write!(f, "too many {} characters: {}, at most {} allowed", set.name(), count, max)
+            }
+            Violation::Run { position, length, max } => {
+                write!(f, "{} repeated characters from character {}, at most {} allowed", length, position, max)
+            }
+            Violation::Sequence { position, length, max } => {
+                write!(f, "{} sequential characters from character {}, at most {} allowed", length, position, max)
+            }
+            Violation::MissingClass { name } => write!(f, "no {} characters, at least 1 required", name),
+            Violation::NotLetter { position } => write!(f, "character {} must be a letter", position),
+            Violation::Word { position, word } => write!(f, "dictionary word '{}' at character {}", word, position),
+            Violation::SiteName { position, name } => write!(f, "site name '{}' at character {}", name, position),
+        }
+    }
+}
+
+/// Lists the constraints of a validated policy that `password` breaks (none if it complies):
+/// length, characters outside the allowed alphabet (including exclusions), per-set minimum
+/// and maximum counts, forced extended sets and named classes, letters at the first and last
+/// position, runs of a repeated character, sequences and dictionary words. Positions count
+/// characters from 1.
+pub fn check(policy: &Policy, password: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let length = password.chars().count();
+    if length < policy.min as usize {
+        violations.push(Violation::TooShort { length, min: policy.min });
+    }
+    if length > policy.max as usize {
+        violations.push(Violation::TooLong { length, max: policy.max });
+    }
+
+    let alphabet = allowed_alphabet(policy);
+    let extended = allowed_extended(policy);
+    let mut counts = [0usize; 4];
+    let mut disallowed = Vec::new();
+    for (i, c) in password.chars().enumerate() {
+        let byte = u8::try_from(c).ok().filter(u8::is_ascii);
+        match byte.filter(|b| alphabet.contains(b)).and_then(charset_of) {
+            Some(set) => counts[set.index()] += 1,
+            None if extended.iter().any(|(_, chars)| chars.contains(&c)) => {}
+            None => disallowed.push(i + 1),
+        }
+    }
+    if !disallowed.is_empty() {
+        violations.push(Violation::Disallowed { positions: disallowed });
+    }
+    for set in Charset::ALL {
+        let (i, count) = (set.index(), counts[set.index()]);
+        if policy.allow[i] && count < policy.min_counts[i] as usize {
+            violations.push(Violation::TooFew { set, count, min: policy.min_counts[i] });
+        }
+        if let Some(max) = policy.max_counts[i].filter(|&max| count > max as usize) {
+            violations.push(Violation::TooMany { set, count, max });
+        }
+    }
+    for (set, chars) in extended.iter().filter(|(set, _)| policy.force_extended[set.index()]) {
+        if !password.chars().any(|c| chars.contains(&c)) {
+            violations.push(Violation::MissingClass { name: set.name().to_string() });
+        }
+    }
+    for (class, alphabet) in allowed_classes(policy).into_iter().filter(|(class, _)| class.force) {
+        if !password.bytes().any(|b| alphabet.contains(&b)) {
+            violations.push(Violation::MissingClass { name: class.name.clone() });
+        }
+    }
+
+    let ends = [(policy.letter_first, password.chars().next(), 1), (policy.letter_last, password.chars().last(), length)];
+    for (_, c, position) in ends.into_iter().filter(|&(wanted, _, _)| wanted) {
+        if c.is_some_and(|c| !c.is_alphabetic()) {
+            violations.push(Violation::NotLetter { position });
+        }
+    }
+
+    if let Some(max) = policy.max_consecutive {
+        // Runs as (start, length), closed by a different character or the end
+        let mut run: Option<(char, usize, usize)> = None;
+        for (i, c) in password.chars().map(Some).chain([None]).enumerate() {
+            match run {
+                Some((prev, _, ref mut length)) if Some(prev) == c => *length += 1,
+                _ => {
+                    if let Some((_, start, length)) = run.filter(|&(_, _, length)| length > max as usize) {
+                        violations.push(Violation::Run { position: start + 1, length, max });
+                    }
+                    run = c.map(|c| (c, i, 1));
+                }
+            }
+        }
+    }
+
+    if let Some(max) = policy.max_sequential {
+        // Sequences as (start, step, length); a character can end one sequence and start the next
+        let chars: Vec<char> = password.chars().collect();
+        let mut sequence = (0, 0, 1);
+        for i in 1..=chars.len() {
+            let step = chars.get(i).and_then(|&c| sequence_step(chars[i - 1], c));
+            match step {
+                Some(step) if step == sequence.1 => sequence.2 += 1,
+                _ => {
+                    let (start, _, length) = sequence;
+                    if length > max as usize {
+                        violations.push(Violation::Sequence { position: start + 1, length, max });
+                    }
+                    sequence = match step {
+                        Some(step) => (i - 1, step, 2),
+                        None => (i, 0, 1),
+                    };
+                }
+            }
+        }
+    }
+
+    if policy.no_words {
+        let chars: Vec<char> = password.chars().collect();
+        for (start, word) in find_words(&chars) {
+            violations.push(Violation::Word { position: start + 1, word });
+        }
+    }
+    violations
+}
+
+/// As `check`, also reporting the names of `site` in the password when the policy has
+/// `no_site` (`check` alone cannot, as it does not know the site).
+pub fn check_for_site(policy: &Policy, password: &str, site: &str) -> Vec<Violation> {
+    let mut violations = check(policy, password);
+    if policy.no_site {
+        let chars: Vec<char> = password.chars().collect();
+        for (start, name) in find_site_names(&chars, site) {
+            violations.push(Violation::SiteName { position: start + 1, name });
+        }
+    }
+    violations
+}
+
+/// Parses an Apple `passwordrules` string into an (unvalidated) policy.
+///
+/// Supported rules: `required`, `allowed`, `max-consecutive`, `minlength`, `maxlength`;
+/// unknown rules are ignored as the spec asks. Character classes are `upper`, `lower`,
+/// `digit`, `special`, `ascii-printable`, `unicode` (treated as ascii-printable) and custom
+/// `[...]` sets. Custom sets are mapped onto the fixed sets via `allow` + `exclude`.
+///
+/// A `required` rule is satisfied by forcing one of the fixed sets it covers; a rule whose
+/// characters cannot be expressed that way is reported as unsupported. Missing lengths
+/// fall back to the default policy bounds.
+pub fn from_passwordrules(rules: &str) -> Result<Policy, PolicyError> {
+    let err = |msg: String| PolicyError::InvalidPasswordRules(msg);
+
+    let mut allowed_chars: Vec<u8> = Vec::new();
+    let mut required: Vec<Vec<u8>> = Vec::new();
+    let mut min_length: Option<u8> = None;
+    let mut max_length: Option<u8> = None;
+    let mut max_consecutive: Option<u8> = None;
+
+    for rule in rules.split(';').map(str::trim).filter(|r| !r.is_empty()) {
+        let (name, value) = rule
+            .split_once(':')
+            .ok_or_else(|| err(format!("expected 'name: value' in '{}'", rule)))?;
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let parse_len = |v: &str| {
+            v.parse::<u32>()
+                .map(|n| n.min(u8::MAX as u32) as u8)
+                .map_err(|_| err(format!("{} expects a number, got '{}'", name, v)))
+        };
+        match name.as_str() {
+            "required" => {
+                let chars = parse_rule_classes(value)?;
+                allowed_chars.extend_from_slice(&chars);
+                required.push(chars);
+            }
+            "allowed" => allowed_chars.extend(parse_rule_classes(value)?),
+            "minlength" => min_length = Some(parse_len(value)?),
+            "maxlength" => max_length = Some(parse_len(value)?),
+            "max-consecutive" => max_consecutive = Some(parse_len(value)?),
+            _ => {} // unknown rules are ignored per the spec
+        }
+    }
+
+    // No character rules means ascii-printable
+    if allowed_chars.is_empty() {
+        allowed_chars = Charset::ALL.iter().flat_map(|set| set.chars()).copied().collect();
+    }
+
+    let mut policy = default_policy();
+    for (i, set) in Charset::ALL.iter().map(|set| set.chars()).enumerate() {
+        let in_set: Vec<u8> = set.iter().copied().filter(|b| allowed_chars.contains(b)).collect();
+        policy.allow[i] = !in_set.is_empty();
+        if policy.allow[i] {
+            policy.exclude.extend(set.iter().copied().filter(|b| !in_set.contains(b)));
+        }
+    }
+
+    for chars in &required {
+        // Sets touched by this rule; forcing one of them satisfies it if the whole allowed
+        // part of that set lies inside the rule
+        let touched: Vec<usize> = (0..4)
+            .filter(|&i| Charset::ALL[i].chars().iter().any(|b| chars.contains(b)))
+            .collect();
+        if touched.iter().any(|&i| policy.force[i]) {
+            continue;
+        }
+        let satisfiable = touched.iter().copied().find(|&i| {
+            Charset::ALL[i]
+                .chars()
+                .iter()
+                .filter(|b| !policy.exclude.contains(b))
+                .all(|b| chars.contains(b))
+        });
+        match satisfiable {
+            Some(i) => policy.force[i] = true,
+            None => {
+                return Err(err(format!(
+                    "cannot express required characters '{}'",
+                    String::from_utf8_lossy(chars)
+                )))
+            }
+        }
+    }
+
+    policy.min = min_length.unwrap_or(policy.min).max(1);
+    policy.max = max_length.unwrap_or(policy.max.max(policy.min));
+    if min_length.is_none() {
+        policy.min = policy.min.min(policy.max);
+    }
+    policy.max_consecutive = max_consecutive;
+    Ok(policy)
+}
+
+/// Expands a comma-separated passwordrules class list into its characters.
+fn parse_rule_classes(value: &str) -> Result<Vec<u8>, PolicyError> {
+    let err = |msg: String| PolicyError::InvalidPasswordRules(msg);
+    let mut out = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        if let Some(custom) = rest.strip_prefix('[') {
+            // A `]` directly after `[` is a member; the next `]` closes the set
+            let start = usize::from(custom.starts_with(']'));
+            let close = custom[start..]
+                .find(']')
+                .map(|i| i + start)
+                .ok_or_else(|| err(format!("unterminated custom class in '{}'", value)))?;
+            let (members, tail) = (&custom[..close], &custom[close + 1..]);
+            out.extend(members.bytes().filter(|b| b.is_ascii_graphic()));
+            rest = tail.trim_start().trim_start_matches(',').trim_start();
+            continue;
+        }
+        let (name, tail) = rest.split_once(',').unwrap_or((rest, ""));
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upper" => out.extend_from_slice(UPPER_BYTES),
+            "lower" => out.extend_from_slice(LOWER_BYTES),
+            "digit" => out.extend_from_slice(DIGIT_BYTES),
+            "special" => out.extend_from_slice(SYMBOL_BYTES),
+            "ascii-printable" | "unicode" => out.extend(Charset::ALL.iter().flat_map(|set| set.chars())),
+            other => return Err(err(format!("unknown character class '{}'", other))),
+        }
+        rest = tail.trim_start();
     }
-    v
+    Ok(out)
 }
@@ -1,67 +1,119 @@
+use alloc::{boxed::Box, vec::Vec};
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Sha256, Sha512};
 use thiserror::Error;
 use zeroize::Zeroize;
 
+use crate::generator::push_field;
+use crate::memlock::Locked;
+
 pub const PRNG_BLOCK: usize = 32;
+/// HKDF-Expand produces at most 255 blocks (RFC 5869), so a stream yields at most
+/// `255 * PRNG_BLOCK` = 8160 bytes.
+pub const PRNG_MAX_BYTES: usize = 255 * PRNG_BLOCK;
+
+/// Block of the HKDF-SHA512 stream of algorithm v5; it yields at most 255 blocks (16320 bytes).
+pub const PRNG_BLOCK_SHA512: usize = 64;
+
+/// Bytes in a ChaCha20 keystream block.
+const CHACHA_BLOCK: usize = 64;
+/// The 32-bit block counter of RFC 8439 ChaCha20 allows 2^32 blocks (256 GiB) per key.
+const CHACHA_BLOCKS: u64 = 1 << 32;
+
+/// Bytes read from the BLAKE3 output at a time (one compression).
+#[cfg(feature = "blake3")]
+const BLAKE3_BLOCK: usize = 64;
+/// `blake3::derive_key` context of the algorithm v4 stream key.
+#[cfg(feature = "blake3")]
+const BLAKE3_CONTEXT: &str = "pwgen 2026-10-16 site stream v4";
 
 type HmacSha256 = Hmac<Sha256>;
+type HmacSha512 = Hmac<Sha512>;
 
 #[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PrngError {
     #[error("internal error initializing HMAC")] 
     HmacInit,
+    #[error("PRNG stream exhausted its output limit")]
+    Exhausted,
 }
 
 /// Context bytes must be the exact encoding described in policy.rs/generator.rs.
 pub struct HkdfStream {
-    prk: [u8; 32],
+    secret: Locked<StreamSecret>,
     info: Vec<u8>,
-    counter: u8, // next T(n) index to generate (starts at 1)
-    block: [u8; PRNG_BLOCK],
+    counter: u8, // n of the T(n) in `block` (0 before the first refill)
     block_pos: usize,
+}
+
+/// The key material of a stream, kept in locked memory with the `mlock` feature.
+struct StreamSecret {
+    prk: [u8; 32],
+    block: [u8; PRNG_BLOCK],
     prev_block: [u8; PRNG_BLOCK], // T(n-1)
 }
 
+impl Zeroize for StreamSecret {
+    fn zeroize(&mut self) {
+        self.prk.zeroize();
+        self.block.zeroize();
+        self.prev_block.zeroize();
+    }
+}
+
 /// key = 32 bytes from kdf::derive_site_key
 pub fn from_key_and_context(key: &[u8; 32], info: &[u8]) -> Result<HkdfStream, PrngError> {
+    with_salt(b"pwgen-hkdf-salt-v1", key, info)
+}
+
+fn with_salt(salt: &[u8], key: &[u8; 32], info: &[u8]) -> Result<HkdfStream, PrngError> {
     // PRK = HKDF-Extract(salt, IKM)
-    let mut mac = HmacSha256::new_from_slice(b"pwgen-hkdf-salt-v1").map_err(|_| PrngError::HmacInit)?;
+    let mut mac = HmacSha256::new_from_slice(salt).map_err(|_| PrngError::HmacInit)?;
     mac.update(key);
-    let prk_bytes = mac.finalize().into_bytes();
+    let mut prk_bytes = mac.finalize().into_bytes();
 
-    let mut prk = [0u8; 32];
-    prk.copy_from_slice(&prk_bytes);
+    let mut secret = Locked::new(Box::new(StreamSecret {
+        prk: [0u8; 32],
+        block: [0u8; PRNG_BLOCK],
+        prev_block: [0u8; PRNG_BLOCK],
+    }));
+    secret.prk.copy_from_slice(&prk_bytes);
+    prk_bytes.zeroize();
 
     Ok(HkdfStream {
-        prk,
+        secret,
         info: info.to_vec(),
         counter: 0,
-        block: [0u8; PRNG_BLOCK],
         block_pos: PRNG_BLOCK, // force initial refill
-        prev_block: [0u8; PRNG_BLOCK],
     })
 }
 
 impl HkdfStream {
     fn refill_block(&mut self) -> Result<(), PrngError> {
-        // Generate next T(n)
-        self.counter = self.counter.wrapping_add(1);
-        debug_assert!(self.counter != 0, "HKDF counter wrapped");
+        // Generate next T(n); T(256) does not exist, and a wrapped counter would repeat T(1)
+        if self.counter == u8::MAX {
+            return Err(PrngError::Exhausted);
+        }
+        self.counter += 1;
 
-        let mut mac = HmacSha256::new_from_slice(&self.prk).map_err(|_| PrngError::HmacInit)?;
+        let mut mac = HmacSha256::new_from_slice(&self.secret.prk).map_err(|_| PrngError::HmacInit)?;
         
         if self.counter == 1 {
             mac.update(&self.info);
             mac.update(&[1]);
         } else {
-            mac.update(&self.prev_block);
+            mac.update(&self.secret.prev_block);
             mac.update(&self.info);
             mac.update(&[self.counter]);
         }
-        let t = mac.finalize().into_bytes();
-        self.block.copy_from_slice(&t);
-        self.prev_block.copy_from_slice(&t);
+        let mut t = mac.finalize().into_bytes();
+        self.secret.block.copy_from_slice(&t);
+        self.secret.prev_block.copy_from_slice(&t);
+        t.zeroize();
         self.block_pos = 0;
         Ok(())
     }
@@ -71,37 +123,558 @@ impl HkdfStream {
         if self.block_pos >= PRNG_BLOCK {
             self.refill_block()?;
         }
-        let b = self.block[self.block_pos];
+        let b = self.secret.block[self.block_pos];
         self.block_pos += 1;
         Ok(b)
     }
 
-    /// Fills out with deterministic bytes.
-    pub fn fill(&mut self, out: &mut [u8]) -> Result<(), PrngError> {
-        for slot in out.iter_mut() {
-            *slot = self.next_u8()?;
+    /// Bytes the stream can still produce before `PrngError::Exhausted`.
+    pub fn remaining(&self) -> usize {
+        (usize::from(u8::MAX) - usize::from(self.counter)) * PRNG_BLOCK + (PRNG_BLOCK - self.block_pos)
+    }
+
+    /// Fills out with deterministic bytes, the same as that many `next_u8` calls. Copies
+    /// whatever is left of the current block, then whole blocks, then the head of the next.
+    /// Fails without consuming anything if `out` is longer than `remaining()`.
+    pub fn fill(&mut self, mut out: &mut [u8]) -> Result<(), PrngError> {
+        if out.len() > self.remaining() {
+            return Err(PrngError::Exhausted);
+        }
+        while !out.is_empty() {
+            if self.block_pos >= PRNG_BLOCK {
+                self.refill_block()?;
+            }
+            let n = out.len().min(PRNG_BLOCK - self.block_pos);
+            let (head, rest) = out.split_at_mut(n);
+            head.copy_from_slice(&self.secret.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            out = rest;
         }
         Ok(())
     }
 
-    /// Helper: draw an unbiased integer in [0, n) via rejection sampling.
+    /// An independent substream for `label`, so that separate parts of a derivation can each
+    /// draw from their own stream and adding one never shifts the bytes another consumes.
+    ///
+    /// The substream keeps the PRK and expands over
+    /// `pwgen-fork|parent=<len>:<info>|label=<len>:<label>`, which no generator context (all
+    /// `pwgen-v*`) can equal; it depends only on the parent's key, context and `label`, not on
+    /// how much of the parent has been read. Forks can be forked in turn.
+    pub fn fork(&self, label: &[u8]) -> HkdfStream {
+        let mut info = Vec::with_capacity(self.info.len() + label.len() + 32);
+        info.extend_from_slice(b"pwgen-fork");
+        push_field(&mut info, b"parent", &self.info);
+        push_field(&mut info, b"label", label);
+        let secret = Locked::new(Box::new(StreamSecret {
+            prk: self.secret.prk,
+            block: [0u8; PRNG_BLOCK],
+            prev_block: [0u8; PRNG_BLOCK],
+        }));
+        HkdfStream { secret, info, counter: 0, block_pos: PRNG_BLOCK }
+    }
+
+    /// Helper: draw an unbiased integer in [0, n) via rejection sampling, one byte per draw.
+    /// `n` must be at most 256; larger ranges take `next_index_u32`.
     pub fn next_index(&mut self, n: usize) -> Result<usize, PrngError> {
-        assert!(n > 0, "n must be > 0");
-        let limit = (256 / n) * n; // largest multiple of n less than 256
-        loop {
-            let byte = self.next_u8()? as usize;
-            if byte < limit {
-                return Ok(byte % n);
+        sample_index(n, || self.next_u8())
+    }
+
+    /// Draws an unbiased integer in [0, n) from four bytes (little-endian) per draw, for
+    /// alphabets and wordlists beyond 256 entries. It consumes the stream differently from
+    /// `next_index`, so switching a generator between them changes its outputs.
+    pub fn next_index_u32(&mut self, n: u32) -> Result<u32, PrngError> {
+        sample_index_u32(n, |bytes| self.fill(bytes))
+    }
+}
+
+/// HKDF-SHA512 over the site key and context (salt `pwgen-hkdf-sha512-salt-v1`), the PRNG of
+/// algorithm v5 for deployments that require SHA-512. It is read exactly as `HkdfStream`, in
+/// 64-byte blocks.
+pub struct HkdfSha512Stream {
+    secret: Locked<Sha512Secret>,
+    info: Vec<u8>,
+    counter: u8, // n of the T(n) in `block` (0 before the first refill)
+    block_pos: usize,
+}
+
+/// The key material of an HKDF-SHA512 stream, kept in locked memory with `mlock`.
+struct Sha512Secret {
+    prk: [u8; PRNG_BLOCK_SHA512],
+    block: [u8; PRNG_BLOCK_SHA512], // also T(n-1) for the next refill
+}
+
+impl Zeroize for Sha512Secret {
+    fn zeroize(&mut self) {
+        self.prk.zeroize();
+        self.block.zeroize();
+    }
+}
+
+/// key = 32 bytes from kdf::derive_site_key
+pub fn sha512_from_key_and_context(key: &[u8; 32], info: &[u8]) -> Result<HkdfSha512Stream, PrngError> {
+    // PRK = HKDF-Extract(salt, IKM)
+    let mut mac = HmacSha512::new_from_slice(b"pwgen-hkdf-sha512-salt-v1").map_err(|_| PrngError::HmacInit)?;
+    mac.update(key);
+    let mut prk_bytes = mac.finalize().into_bytes();
+
+    let mut secret = Locked::new(Box::new(Sha512Secret {
+        prk: [0u8; PRNG_BLOCK_SHA512],
+        block: [0u8; PRNG_BLOCK_SHA512],
+    }));
+    secret.prk.copy_from_slice(&prk_bytes);
+    prk_bytes.zeroize();
+
+    Ok(HkdfSha512Stream { secret, info: info.to_vec(), counter: 0, block_pos: PRNG_BLOCK_SHA512 })
+}
+
+impl HkdfSha512Stream {
+    fn refill_block(&mut self) -> Result<(), PrngError> {
+        if self.counter == u8::MAX {
+            return Err(PrngError::Exhausted);
+        }
+        self.counter += 1;
+
+        let mut mac = HmacSha512::new_from_slice(&self.secret.prk).map_err(|_| PrngError::HmacInit)?;
+        if self.counter > 1 {
+            mac.update(&self.secret.block);
+        }
+        mac.update(&self.info);
+        mac.update(&[self.counter]);
+        let mut t = mac.finalize().into_bytes();
+        self.secret.block.copy_from_slice(&t);
+        t.zeroize();
+        self.block_pos = 0;
+        Ok(())
+    }
+
+    /// Returns next byte from the stream; refills internally as needed.
+    pub fn next_u8(&mut self) -> Result<u8, PrngError> {
+        if self.block_pos >= PRNG_BLOCK_SHA512 {
+            self.refill_block()?;
+        }
+        let b = self.secret.block[self.block_pos];
+        self.block_pos += 1;
+        Ok(b)
+    }
+
+    /// Bytes the stream can still produce before `PrngError::Exhausted`.
+    pub fn remaining(&self) -> usize {
+        (usize::from(u8::MAX) - usize::from(self.counter)) * PRNG_BLOCK_SHA512 + (PRNG_BLOCK_SHA512 - self.block_pos)
+    }
+
+    /// As `HkdfStream::fill`.
+    pub fn fill(&mut self, mut out: &mut [u8]) -> Result<(), PrngError> {
+        if out.len() > self.remaining() {
+            return Err(PrngError::Exhausted);
+        }
+        while !out.is_empty() {
+            if self.block_pos >= PRNG_BLOCK_SHA512 {
+                self.refill_block()?;
             }
+            let n = out.len().min(PRNG_BLOCK_SHA512 - self.block_pos);
+            let (head, rest) = out.split_at_mut(n);
+            head.copy_from_slice(&self.secret.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            out = rest;
         }
+        Ok(())
+    }
+
+    /// As `HkdfStream::fork`: the same PRK over
+    /// `pwgen-fork|parent=<len>:<info>|label=<len>:<label>`.
+    pub fn fork(&self, label: &[u8]) -> HkdfSha512Stream {
+        let mut info = Vec::with_capacity(self.info.len() + label.len() + 32);
+        info.extend_from_slice(b"pwgen-fork");
+        push_field(&mut info, b"parent", &self.info);
+        push_field(&mut info, b"label", label);
+        let secret = Locked::new(Box::new(Sha512Secret { prk: self.secret.prk, block: [0u8; PRNG_BLOCK_SHA512] }));
+        HkdfSha512Stream { secret, info, counter: 0, block_pos: PRNG_BLOCK_SHA512 }
+    }
+
+    /// As `HkdfStream::next_index`.
+    pub fn next_index(&mut self, n: usize) -> Result<usize, PrngError> {
+        sample_index(n, || self.next_u8())
+    }
+
+    /// As `HkdfStream::next_index_u32`.
+    pub fn next_index_u32(&mut self, n: u32) -> Result<u32, PrngError> {
+        sample_index_u32(n, |bytes| self.fill(bytes))
+    }
+}
+
+/// A ChaCha20 keystream (RFC 8439, zero nonce), the PRNG of algorithm v3: faster than
+/// HMAC-SHA256 for long outputs, and up to 256 GiB per stream.
+pub struct ChaChaStream {
+    secret: Locked<ChaChaSecret>,
+    next_block: u64, // keystream block after `block`
+    block_pos: usize,
+}
+
+/// The key and current block of a ChaCha20 stream, kept in locked memory with `mlock`.
+struct ChaChaSecret {
+    key: [u8; 32],
+    block: [u8; CHACHA_BLOCK],
+}
+
+impl Zeroize for ChaChaSecret {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.block.zeroize();
     }
 }
 
+/// key = 32 bytes from kdf::derive_site_key. The ChaCha20 key is the first 32 bytes of the
+/// HKDF stream over the same key and context with salt `pwgen-chacha20-salt-v1`.
+pub fn chacha_from_key_and_context(key: &[u8; 32], info: &[u8]) -> Result<ChaChaStream, PrngError> {
+    let mut hkdf = with_salt(b"pwgen-chacha20-salt-v1", key, info)?;
+    let mut secret = Locked::new(Box::new(ChaChaSecret { key: [0u8; 32], block: [0u8; CHACHA_BLOCK] }));
+    hkdf.fill(&mut secret.key)?;
+    Ok(ChaChaStream { secret, next_block: 0, block_pos: CHACHA_BLOCK })
+}
+
+/// Writes the keystream of `key` from block `first` over `out`, a whole number of blocks.
+fn chacha_keystream(key: &[u8; 32], first: u64, out: &mut [u8]) {
+    out.fill(0);
+    let mut cipher = ChaCha20::new(key.into(), &Default::default());
+    cipher.seek(first * CHACHA_BLOCK as u64);
+    cipher.apply_keystream(out);
+}
+
+impl ChaChaStream {
+    fn refill_block(&mut self) -> Result<(), PrngError> {
+        if self.next_block == CHACHA_BLOCKS {
+            return Err(PrngError::Exhausted);
+        }
+        let secret = &mut *self.secret;
+        chacha_keystream(&secret.key, self.next_block, &mut secret.block);
+        self.next_block += 1;
+        self.block_pos = 0;
+        Ok(())
+    }
+
+    /// Returns next byte from the stream; refills internally as needed.
+    pub fn next_u8(&mut self) -> Result<u8, PrngError> {
+        if self.block_pos >= CHACHA_BLOCK {
+            self.refill_block()?;
+        }
+        let b = self.secret.block[self.block_pos];
+        self.block_pos += 1;
+        Ok(b)
+    }
+
+    /// Bytes the stream can still produce before `PrngError::Exhausted` (saturating on
+    /// 32-bit targets).
+    pub fn remaining(&self) -> usize {
+        let bytes = (CHACHA_BLOCKS - self.next_block) * CHACHA_BLOCK as u64 + (CHACHA_BLOCK - self.block_pos) as u64;
+        usize::try_from(bytes).unwrap_or(usize::MAX)
+    }
+
+    /// Fills out with deterministic bytes, the same as that many `next_u8` calls. Whole
+    /// blocks are generated straight into `out`. Fails without consuming anything if `out`
+    /// is longer than `remaining()`.
+    pub fn fill(&mut self, mut out: &mut [u8]) -> Result<(), PrngError> {
+        if out.len() > self.remaining() {
+            return Err(PrngError::Exhausted);
+        }
+        while !out.is_empty() {
+            if self.block_pos >= CHACHA_BLOCK && out.len() >= CHACHA_BLOCK {
+                let (blocks, rest) = out.split_at_mut(out.len() / CHACHA_BLOCK * CHACHA_BLOCK);
+                chacha_keystream(&self.secret.key, self.next_block, blocks);
+                self.next_block += (blocks.len() / CHACHA_BLOCK) as u64;
+                out = rest;
+                continue;
+            }
+            if self.block_pos >= CHACHA_BLOCK {
+                self.refill_block()?;
+            }
+            let n = out.len().min(CHACHA_BLOCK - self.block_pos);
+            let (head, rest) = out.split_at_mut(n);
+            head.copy_from_slice(&self.secret.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            out = rest;
+        }
+        Ok(())
+    }
+
+    /// An independent substream for `label`, as `HkdfStream::fork`: its key is derived from
+    /// this stream's key (which binds the context) over `pwgen-fork|label=<len>:<label>`.
+    pub fn fork(&self, label: &[u8]) -> ChaChaStream {
+        let mut info = Vec::with_capacity(label.len() + 24);
+        info.extend_from_slice(b"pwgen-fork");
+        push_field(&mut info, b"label", label);
+        chacha_from_key_and_context(&self.secret.key, &info).expect("HMAC-SHA256 takes keys of any length")
+    }
+
+    /// As `HkdfStream::next_index`.
+    pub fn next_index(&mut self, n: usize) -> Result<usize, PrngError> {
+        sample_index(n, || self.next_u8())
+    }
+
+    /// As `HkdfStream::next_index_u32`.
+    pub fn next_index_u32(&mut self, n: u32) -> Result<u32, PrngError> {
+        sample_index_u32(n, |bytes| self.fill(bytes))
+    }
+}
+
+/// A BLAKE3 stream, the PRNG of algorithm v4 (`blake3` feature): the extended output of
+/// BLAKE3 keyed with `blake3::derive_key("pwgen 2026-10-16 site stream v4", key)` over the
+/// context. One hash replaces HKDF-Extract and every HMAC-SHA256 of HKDF-Expand, and the
+/// output is not limited to 8160 bytes.
+#[cfg(feature = "blake3")]
+pub struct Blake3Stream {
+    secret: Locked<Blake3Secret>,
+    info: Vec<u8>,
+    block_pos: usize,
+}
+
+/// The stream key, output reader and current block, kept in locked memory with `mlock`.
+#[cfg(feature = "blake3")]
+struct Blake3Secret {
+    key: [u8; 32],
+    reader: blake3::OutputReader,
+    block: [u8; BLAKE3_BLOCK],
+}
+
+#[cfg(feature = "blake3")]
+impl Zeroize for Blake3Secret {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.reader.zeroize();
+        self.block.zeroize();
+    }
+}
+
+/// key = 32 bytes from kdf::derive_site_key
+#[cfg(feature = "blake3")]
+pub fn blake3_from_key_and_context(key: &[u8; 32], info: &[u8]) -> Blake3Stream {
+    let mut stream_key = blake3::derive_key(BLAKE3_CONTEXT, key);
+    let stream = Blake3Stream::keyed(&stream_key, info.to_vec());
+    stream_key.zeroize();
+    stream
+}
+
+#[cfg(feature = "blake3")]
+impl Blake3Stream {
+    fn keyed(key: &[u8; 32], info: Vec<u8>) -> Blake3Stream {
+        let reader = blake3::Hasher::new_keyed(key).update(&info).finalize_xof();
+        let secret = Locked::new(Box::new(Blake3Secret { key: *key, reader, block: [0u8; BLAKE3_BLOCK] }));
+        Blake3Stream { secret, info, block_pos: BLAKE3_BLOCK }
+    }
+
+    fn refill_block(&mut self) -> Result<(), PrngError> {
+        if self.secret.reader.position() > u64::MAX - BLAKE3_BLOCK as u64 {
+            return Err(PrngError::Exhausted);
+        }
+        let secret = &mut *self.secret;
+        secret.reader.fill(&mut secret.block);
+        self.block_pos = 0;
+        Ok(())
+    }
+
+    /// Returns next byte from the stream; refills internally as needed.
+    pub fn next_u8(&mut self) -> Result<u8, PrngError> {
+        if self.block_pos >= BLAKE3_BLOCK {
+            self.refill_block()?;
+        }
+        let b = self.secret.block[self.block_pos];
+        self.block_pos += 1;
+        Ok(b)
+    }
+
+    /// Bytes the stream can still produce before `PrngError::Exhausted` (BLAKE3 outputs up
+    /// to 2^64 bytes; saturating).
+    pub fn remaining(&self) -> usize {
+        let unread = (u64::MAX - self.secret.reader.position()) / BLAKE3_BLOCK as u64 * BLAKE3_BLOCK as u64;
+        usize::try_from(unread.saturating_add((BLAKE3_BLOCK - self.block_pos) as u64)).unwrap_or(usize::MAX)
+    }
+
+    /// Fills out with deterministic bytes, the same as that many `next_u8` calls. Whole
+    /// blocks are read straight into `out`. Fails without consuming anything if `out` is
+    /// longer than `remaining()`.
+    pub fn fill(&mut self, mut out: &mut [u8]) -> Result<(), PrngError> {
+        if out.len() > self.remaining() {
+            return Err(PrngError::Exhausted);
+        }
+        while !out.is_empty() {
+            if self.block_pos >= BLAKE3_BLOCK && out.len() >= BLAKE3_BLOCK {
+                let (blocks, rest) = out.split_at_mut(out.len() / BLAKE3_BLOCK * BLAKE3_BLOCK);
+                self.secret.reader.fill(blocks);
+                out = rest;
+                continue;
+            }
+            if self.block_pos >= BLAKE3_BLOCK {
+                self.refill_block()?;
+            }
+            let n = out.len().min(BLAKE3_BLOCK - self.block_pos);
+            let (head, rest) = out.split_at_mut(n);
+            head.copy_from_slice(&self.secret.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            out = rest;
+        }
+        Ok(())
+    }
+
+    /// An independent substream for `label`, as `HkdfStream::fork`: the same key over
+    /// `pwgen-fork|parent=<len>:<info>|label=<len>:<label>`.
+    pub fn fork(&self, label: &[u8]) -> Blake3Stream {
+        let mut info = Vec::with_capacity(self.info.len() + label.len() + 32);
+        info.extend_from_slice(b"pwgen-fork");
+        push_field(&mut info, b"parent", &self.info);
+        push_field(&mut info, b"label", label);
+        Blake3Stream::keyed(&self.secret.key, info)
+    }
+
+    /// As `HkdfStream::next_index`.
+    pub fn next_index(&mut self, n: usize) -> Result<usize, PrngError> {
+        sample_index(n, || self.next_u8())
+    }
+
+    /// As `HkdfStream::next_index_u32`.
+    pub fn next_index_u32(&mut self, n: u32) -> Result<u32, PrngError> {
+        sample_index_u32(n, |bytes| self.fill(bytes))
+    }
+}
+
+/// The PRNG of a derivation, as `generator::AlgoVersion` selects it.
+pub enum Stream {
+    /// HKDF-SHA256 (algorithm v1 and v2)
+    Hkdf(HkdfStream),
+    /// ChaCha20 (algorithm v3)
+    ChaCha(ChaChaStream),
+    /// HKDF-SHA512 (algorithm v5)
+    HkdfSha512(HkdfSha512Stream),
+    /// BLAKE3 (algorithm v4)
+    #[cfg(feature = "blake3")]
+    Blake3(Blake3Stream),
+}
+
+impl Stream {
+    pub fn next_u8(&mut self) -> Result<u8, PrngError> {
+        match self {
+            Stream::Hkdf(s) => s.next_u8(),
+            Stream::ChaCha(s) => s.next_u8(),
+            Stream::HkdfSha512(s) => s.next_u8(),
+            #[cfg(feature = "blake3")]
+            Stream::Blake3(s) => s.next_u8(),
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        match self {
+            Stream::Hkdf(s) => s.remaining(),
+            Stream::ChaCha(s) => s.remaining(),
+            Stream::HkdfSha512(s) => s.remaining(),
+            #[cfg(feature = "blake3")]
+            Stream::Blake3(s) => s.remaining(),
+        }
+    }
+
+    pub fn fill(&mut self, out: &mut [u8]) -> Result<(), PrngError> {
+        match self {
+            Stream::Hkdf(s) => s.fill(out),
+            Stream::ChaCha(s) => s.fill(out),
+            Stream::HkdfSha512(s) => s.fill(out),
+            #[cfg(feature = "blake3")]
+            Stream::Blake3(s) => s.fill(out),
+        }
+    }
+
+    /// A substream of the same kind.
+    pub fn fork(&self, label: &[u8]) -> Stream {
+        match self {
+            Stream::Hkdf(s) => Stream::Hkdf(s.fork(label)),
+            Stream::ChaCha(s) => Stream::ChaCha(s.fork(label)),
+            Stream::HkdfSha512(s) => Stream::HkdfSha512(s.fork(label)),
+            #[cfg(feature = "blake3")]
+            Stream::Blake3(s) => Stream::Blake3(s.fork(label)),
+        }
+    }
+
+    pub fn next_index(&mut self, n: usize) -> Result<usize, PrngError> {
+        sample_index(n, || self.next_u8())
+    }
+
+    pub fn next_index_u32(&mut self, n: u32) -> Result<u32, PrngError> {
+        sample_index_u32(n, |bytes| self.fill(bytes))
+    }
+}
+
+fn sample_index(n: usize, mut next_u8: impl FnMut() -> Result<u8, PrngError>) -> Result<usize, PrngError> {
+    assert!(n > 0, "n must be > 0");
+    assert!(n <= 256, "n must be <= 256; use next_index_u32");
+    let limit = (256 / n) * n; // largest multiple of n less than 256
+    loop {
+        let byte = next_u8()? as usize;
+        if byte < limit {
+            return Ok(byte % n);
+        }
+    }
+}
+
+fn sample_index_u32(n: u32, mut fill: impl FnMut(&mut [u8]) -> Result<(), PrngError>) -> Result<u32, PrngError> {
+    assert!(n > 0, "n must be > 0");
+    // largest multiple of n not above 2^32
+    let limit = (1u64 << 32) / u64::from(n) * u64::from(n);
+    loop {
+        let mut bytes = [0u8; 4];
+        fill(&mut bytes)?;
+        let draw = u64::from(u32::from_le_bytes(bytes));
+        if draw < limit {
+            return Ok((draw % u64::from(n)) as u32);
+        }
+    }
+}
+
+/// The streams as rand `RngCore`s, for distributions, shuffles and other rand utilities.
+/// Integers are read little-endian from the stream; the infallible methods panic if the
+/// stream fails, which `try_fill_bytes` reports instead. HMAC-SHA256 and HMAC-SHA512 in
+/// HKDF-Expand, ChaCha20 and keyed BLAKE3 are PRFs, so the streams are fit for keys and secrets (`CryptoRng`).
+macro_rules! rng_core {
+    ($($stream:ty),*) => {$(
+        impl RngCore for $stream {
+            fn next_u32(&mut self) -> u32 {
+                rand_core::impls::next_u32_via_fill(self)
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                rand_core::impls::next_u64_via_fill(self)
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                self.fill(dest).expect("PRNG stream failed");
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+                self.fill(dest).map_err(|_| {
+                    rand_core::Error::from(core::num::NonZeroU32::new(rand_core::Error::CUSTOM_START).expect("nonzero"))
+                })
+            }
+        }
+
+        impl CryptoRng for $stream {}
+    )*};
+}
+
+rng_core!(HkdfStream, HkdfSha512Stream, ChaChaStream, Stream);
+#[cfg(feature = "blake3")]
+rng_core!(Blake3Stream);
+
 impl Drop for HkdfStream {
     fn drop(&mut self) {
-        self.prk.zeroize();
+        // The secret zeroizes itself
+        self.info.zeroize();
+    }
+}
+
+impl Drop for HkdfSha512Stream {
+    fn drop(&mut self) {
+        self.info.zeroize();
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl Drop for Blake3Stream {
+    fn drop(&mut self) {
         self.info.zeroize();
-        self.block.zeroize();
-        self.prev_block.zeroize();
     }
 }
@@ -0,0 +1,238 @@
+#[cfg(feature = "config")]
+use std::path::Path;
+
+use thiserror::Error;
+
+/// One site in a profile file: what `generate` needs to rederive its password, plus what
+/// exports to other password managers show next to it.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(default, deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct Profile {
+    /// Site the password is derived for, as with `--site`
+    pub site: String,
+    /// Username mixed into the derivation, as with `--username`
+    #[cfg_attr(feature = "config", serde(skip_serializing_if = "String::is_empty"))]
+    pub username: String,
+    /// Password version, as with `--version`
+    pub version: Option<u32>,
+    /// Exact length, as with `--length`
+    pub length: Option<u32>,
+    /// Encoded policy (as printed by `generate --json`), as with `--policy`
+    pub policy: Option<String>,
+    /// Site-published rules, as with `--passwordrules`
+    pub passwordrules: Option<String>,
+    /// Template pattern, as with `--pattern`
+    pub pattern: Option<String>,
+    /// Entry name in exports (default: the site, plus `/<username>` if set)
+    pub name: Option<String>,
+    /// Login URL in exports (default: `https://<site>` if the site is a domain name)
+    pub url: Option<String>,
+    /// Free-form notes copied into exports
+    pub notes: Option<String>,
+}
+
+impl Profile {
+    /// Name of the exported entry.
+    pub fn entry_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None if self.username.is_empty() => self.site.clone(),
+            None => format!("{}/{}", self.site, self.username),
+        }
+    }
+
+    /// Login URL of the exported entry, if one is set or the site looks like a domain name.
+    pub fn login_url(&self) -> Option<String> {
+        if self.url.is_some() {
+            return self.url.clone();
+        }
+        let site = self.site.trim();
+        let domain = site.contains('.')
+            && site.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b >= 0x80);
+        if site.contains("://") {
+            Some(site.to_string())
+        } else if domain {
+            Some(format!("https://{}", site))
+        } else {
+            None
+        }
+    }
+}
+
+/// The profile as one line of `pwgen list --format picker`: the site, then a tab and the
+/// username if it has one. `parse_picker_line` reads it back.
+pub fn picker_line(profile: &Profile) -> String {
+    let site = profile.site.trim();
+    if profile.username.is_empty() {
+        site.to_string()
+    } else {
+        format!("{}\t{}", site, profile.username)
+    }
+}
+
+/// The site and username of a line chosen from `pwgen list --format picker` (the username is
+/// empty if the line has none). A line without a tab is taken as a site on its own.
+pub fn parse_picker_line(line: &str) -> (&str, &str) {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match line.split_once('\t') {
+        Some((site, username)) => (site.trim(), username),
+        None => (line.trim(), ""),
+    }
+}
+
+/// Finds the profile `rotate` bumps: the one for `site` (compared case-insensitively) and
+/// `username`, or the only one for `site` if no username is given.
+pub fn find(profiles: &[Profile], site: &str, username: Option<&str>) -> Result<Option<usize>, ProfilesError> {
+    let site = site.trim();
+    let mut matches = profiles.iter().enumerate().filter(|(_, profile)| {
+        profile.site.trim().eq_ignore_ascii_case(site) && username.is_none_or(|username| profile.username == username)
+    });
+    match (matches.next(), matches.next()) {
+        (Some(_), Some(_)) => Err(ProfilesError::Ambiguous { site: site.to_string() }),
+        (found, _) => Ok(found.map(|(i, _)| i)),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProfilesError {
+    #[error("failed to read profiles {path}: {message}")]
+    Read { path: String, message: String },
+
+    #[error("invalid profiles {path}: {message}")]
+    Parse { path: String, message: String },
+
+    #[error("failed to write profiles {path}: {message}")]
+    Write { path: String, message: String },
+
+    #[error("several profiles are for site '{site}' (pick one with --username)")]
+    Ambiguous { site: String },
+}
+
+/// Layout of a profile file: a list of `[[site]]` tables.
+#[cfg(feature = "config")]
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    #[serde(default)]
+    site: Vec<Profile>,
+}
+
+/// Parses profile file contents (TOML with one `[[site]]` table per profile).
+#[cfg(feature = "config")]
+pub fn parse(input: &str) -> Result<Vec<Profile>, ProfilesError> {
+    let parse_error = |message: String| ProfilesError::Parse { path: "<input>".to_string(), message };
+    let file: ProfileFile = toml::from_str(input).map_err(|e| parse_error(e.message().to_string()))?;
+    for (i, profile) in file.site.iter().enumerate() {
+        if profile.site.trim().is_empty() {
+            return Err(parse_error(format!("site #{} has no site", i + 1)));
+        }
+    }
+    let mut names: Vec<String> = file.site.iter().map(Profile::entry_name).collect();
+    names.sort();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(parse_error(format!("two sites are exported as '{}' (set name)", pair[0])));
+    }
+    Ok(file.site)
+}
+
+/// Loads profiles from a file.
+#[cfg(feature = "config")]
+pub fn load(path: &Path) -> Result<Vec<Profile>, ProfilesError> {
+    let display = path.display().to_string();
+    let input = std::fs::read_to_string(path).map_err(|e| ProfilesError::Read {
+        path: display.clone(),
+        message: e.to_string(),
+    })?;
+    parse(&input).map_err(|e| match e {
+        ProfilesError::Parse { message, .. } => ProfilesError::Parse { path: display, message },
+        other => other,
+    })
+}
+
+/// Writes profiles to a file. An existing file is edited in place: values that changed are
+/// replaced, profiles added or removed at the end, and comments and formatting are kept.
+/// The file is replaced atomically (a temporary file in the same directory, then a rename),
+/// so a crash leaves either the old or the new contents.
+#[cfg(feature = "config")]
+pub fn save(path: &Path, profiles: &[Profile]) -> Result<(), ProfilesError> {
+    let display = path.display().to_string();
+    let write_error = |message: String| ProfilesError::Write { path: display.clone(), message };
+
+    let existing = match std::fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ProfilesError::Read { path: display.clone(), message: e.to_string() }),
+    };
+    let output = update(&existing, profiles).map_err(|e| match e {
+        ProfilesError::Parse { message, .. } => ProfilesError::Parse { path: display.clone(), message },
+        other => other,
+    })?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| write_error(e.to_string()))?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = std::path::PathBuf::from(temp);
+    let written = std::fs::write(&temp, output).and_then(|()| std::fs::rename(&temp, path));
+    written.map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        write_error(e.to_string())
+    })
+}
+
+/// Rewrites profile file contents `input` to hold `profiles`, keeping its comments and the
+/// formatting of every value that did not change (see `save`).
+#[cfg(feature = "config")]
+pub fn update(input: &str, profiles: &[Profile]) -> Result<String, ProfilesError> {
+    use toml_edit::{ArrayOfTables, DocumentMut, Item};
+
+    let parse_error = |message: String| ProfilesError::Parse { path: "<input>".to_string(), message };
+    let write_error = |message: String| ProfilesError::Write { path: "<input>".to_string(), message };
+    let mut doc: DocumentMut = input.parse().map_err(|e: toml_edit::TomlError| parse_error(e.message().to_string()))?;
+    let wanted = toml::to_string(&ProfileFile { site: profiles.to_vec() }).map_err(|e| write_error(e.to_string()))?;
+    let wanted: DocumentMut = wanted.parse().map_err(|e: toml_edit::TomlError| write_error(e.message().to_string()))?;
+    let empty = ArrayOfTables::new();
+    let wanted = wanted.get("site").and_then(Item::as_array_of_tables).unwrap_or(&empty);
+
+    let sites = doc
+        .entry("site")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| parse_error("site must be a list of [[site]] tables".to_string()))?;
+    for (i, new) in wanted.iter().enumerate() {
+        let Some(old) = sites.get_mut(i) else {
+            sites.push(new.clone());
+            continue;
+        };
+        let stale: Vec<String> = old.iter().map(|(key, _)| key.to_string()).filter(|key| !new.contains_key(key)).collect();
+        for key in stale {
+            old.remove(&key);
+        }
+        for (key, item) in new.iter() {
+            let (Some(value), Some(current)) = (item.as_value(), old.get_mut(key)) else {
+                old.insert(key, item.clone());
+                continue;
+            };
+            let Some(current) = current.as_value_mut() else {
+                *current = item.clone();
+                continue;
+            };
+            if current.to_string().trim() != value.to_string().trim() {
+                let decor = current.decor().clone();
+                *current = value.clone();
+                *current.decor_mut() = decor;
+            }
+        }
+    }
+    while sites.len() > wanted.len() {
+        sites.remove(sites.len() - 1);
+    }
+    if sites.is_empty() {
+        doc.remove("site");
+    }
+    Ok(doc.to_string())
+}
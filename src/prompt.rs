@@ -0,0 +1,156 @@
+use std::io;
+use std::time::Duration;
+
+/// How the master prompt behaves on the terminal.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PromptOptions {
+    /// Abort when no key is pressed for this long
+    pub timeout: Option<Duration>,
+    /// Echo one `*` per character typed
+    pub mask: bool,
+}
+
+impl PromptOptions {
+    /// Whether the plain silent prompt is enough.
+    pub fn is_plain(&self) -> bool {
+        self.timeout.is_none() && !self.mask
+    }
+}
+
+/// Reads a secret from the terminal (not stdin), with echo off.
+///
+/// Line editing is limited to backspace and Ctrl-U; Ctrl-C and Ctrl-D on an empty line abort.
+#[cfg(unix)]
+pub fn read_secret(prompt: &str, options: PromptOptions) -> io::Result<String> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use zeroize::Zeroizing;
+
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+    let _raw = RawMode::enable(fd)?;
+
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+
+    let timeout_ms = options.timeout.map_or(-1, |t| i32::try_from(t.as_millis()).unwrap_or(i32::MAX));
+    let mut bytes = Zeroizing::new(Vec::new());
+    let mut chars = 0usize;
+    loop {
+        let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        // SAFETY: `poll` points to one valid pollfd for the duration of the call
+        match unsafe { libc::poll(&mut poll, 1, timeout_ms) } {
+            0 => {
+                tty.write_all(b"\n")?;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "prompt timed out"));
+            }
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            _ => {}
+        }
+
+        let mut byte = [0u8; 1];
+        if tty.read(&mut byte)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "terminal closed"));
+        }
+        match byte[0] {
+            b'\r' | b'\n' => break,
+            // Ctrl-C, or Ctrl-D on an empty line
+            0x03 => return abort(&mut tty),
+            0x04 if bytes.is_empty() => return abort(&mut tty),
+            0x04 => {}
+            // Backspace / DEL: drop the last UTF-8 character
+            0x08 | 0x7f => {
+                while let Some(b) = bytes.pop() {
+                    if b & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+                if chars > 0 {
+                    chars -= 1;
+                    if options.mask {
+                        tty.write_all(b"\x08 \x08")?;
+                    }
+                }
+            }
+            // Ctrl-U: clear the line
+            0x15 => {
+                bytes.clear();
+                if options.mask {
+                    tty.write_all(&b"\x08 \x08".repeat(chars))?;
+                }
+                chars = 0;
+            }
+            b => {
+                bytes.push(b);
+                // Count (and mask) characters, not UTF-8 continuation bytes
+                if b & 0xc0 != 0x80 {
+                    chars += 1;
+                    if options.mask {
+                        tty.write_all(b"*")?;
+                    }
+                }
+            }
+        }
+        tty.flush()?;
+    }
+    tty.write_all(b"\n")?;
+
+    String::from_utf8(std::mem::take(&mut *bytes))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "master secret is not valid UTF-8"))
+}
+
+#[cfg(unix)]
+fn abort(tty: &mut std::fs::File) -> io::Result<String> {
+    use std::io::Write;
+    tty.write_all(b"\n")?;
+    Err(io::Error::new(io::ErrorKind::Interrupted, "prompt aborted"))
+}
+
+#[cfg(not(unix))]
+pub fn read_secret(_prompt: &str, _options: PromptOptions) -> io::Result<String> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--prompt-timeout and --prompt-mask are only supported on Unix"))
+}
+
+/// Puts the terminal in non-canonical, no-echo mode with signals off (so Ctrl-C reaches the
+/// prompt and the terminal is always restored) until dropped.
+#[cfg(unix)]
+struct RawMode {
+    fd: std::os::fd::RawFd,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable(fd: std::os::fd::RawFd) -> io::Result<Self> {
+        // SAFETY: termios is plain data, and tcgetattr fully initializes it on success
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid termios derived from the current settings
+        if unsafe { libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { fd, saved })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable` on the same descriptor
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.saved);
+        }
+    }
+}
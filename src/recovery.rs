@@ -0,0 +1,48 @@
+use crate::kdf;
+use crate::generator::{site_stream, GenError};
+
+/// Code alphabet: uppercase letters and digits without 0/O, 1/I (32 characters).
+pub(crate) const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Code shape; `X` is one drawn character. Bound into the derivation context.
+const CODE_SHAPE: &str = "XXXX-XXXX";
+
+/// Most codes that can be requested at once.
+pub const MAX_CODES: usize = 100;
+
+/// Generates `count` deterministic recovery codes of the form `XXXX-XXXX`.
+///
+/// Codes are drawn in sequence from a single stream, and the count is not part of the
+/// context, so asking for more codes extends the same list rather than replacing it.
+pub fn generate_codes<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    count: usize,
+    version: u32,
+) -> Result<Vec<String>, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    if !(1..=MAX_CODES).contains(&count) {
+        return Err(GenError::InvalidInput("recovery code count must be within [1,100]"));
+    }
+
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"recovery", CODE_SHAPE.as_bytes(), version)?;
+
+    let mut codes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut code = String::with_capacity(CODE_SHAPE.len());
+        for ch in CODE_SHAPE.bytes() {
+            if ch == b'X' {
+                let idx = rng.next_index(CODE_ALPHABET.len())?;
+                code.push(CODE_ALPHABET[idx] as char);
+            } else {
+                code.push(ch as char);
+            }
+        }
+        codes.push(code);
+    }
+    Ok(codes)
+}
@@ -0,0 +1,224 @@
+//! `pwgen self-test`: derives the built-in vector matrix (see `vectors::matrix`) and compares
+//! each step with the outputs recorded here when the algorithm was fixed, so a packager can
+//! check that a build (another compiler, target or dependency version) derives the same
+//! passwords before shipping it.
+
+use crate::generator::GenError;
+use crate::vectors::{self, Outputs};
+
+/// Recorded outputs of one matrix vector; bytes are lowercase hex.
+pub struct Golden {
+    pub name: &'static str,
+    /// KDF output
+    pub site_key: &'static str,
+    /// First `vectors::STREAM_BYTES` bytes of the PRNG
+    pub stream: &'static str,
+    /// Password or token
+    pub password: &'static str,
+}
+
+/// The recorded outputs, in the order of `vectors::matrix`.
+pub const GOLDEN: &[Golden] = &[
+    Golden {
+        name: "default",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "3cea6b81f9114db9e207b2a193ff84d0cadb62a643158fe46d4d229ebd226c1ffbcb5e8f40741d78f7a8d62a98f79d20211f98fafc2f9721524de4e78c17b064",
+        password: "r\\,K2~ohN:f'",
+    },
+    Golden {
+        name: "site-normalized",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "3cea6b81f9114db9e207b2a193ff84d0cadb62a643158fe46d4d229ebd226c1ffbcb5e8f40741d78f7a8d62a98f79d20211f98fafc2f9721524de4e78c17b064",
+        password: "r\\,K2~ohN:f'",
+    },
+    Golden {
+        name: "site-unicode",
+        site_key: "ae845d7d7a466534eed577960fa82b77d1903bfb2e15e3c462c3e6bb4a301691",
+        stream: "b6013fe3bc8da3b7290e1c359334c761e08f329764409db74768fa7827f227383a2919e35ae37926ef217339fc6ff59932c6d9b73391314505b2767ebfb542f1",
+        password: "\"YoWYCPb10)|2e",
+    },
+    Golden {
+        name: "master-unicode",
+        site_key: "ec7182892b059384cf4eb7de5ed0875ea53a5a1581fc7c2f086d52852ded7510",
+        stream: "d708b5e23ecab9c86b5ba537b5f5c8615e66a8644290318b8041474a57517edb1a6ec4725d06b40310d4c97f3e6d2faf7ce457072af29d466389a40bb4f83c02",
+        password: "o3}~_!ebj+_i",
+    },
+    Golden {
+        name: "username",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "a1c006605c65968b1a8af96b8a116c2b08646864f27aed5d5a5f5afec740939545df3c9a64a5ea304e426c4f19f0664ae5bfc00c083a8af2eeb693f403287f21",
+        password: "dTprTiA~RgU5o",
+    },
+    Golden {
+        name: "version-2",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "703c0e9a9a07a06af7847b26c8b04fb8619e2647a50b823d0ca6518df72b94ac3be342855cf7820953f159ef11a18f8eeab4d755648a6ee44a09e164bb185190",
+        password: "@99nMEN&eo8<}h",
+    },
+    Golden {
+        name: "version-max",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "f931467b2c011d0f9db75b9a31eae3fbb3a9a0b8f4da9057f03165a0022a6ed0dd5a02589730a5352bd8cb3f603cdd161cd155806d31f4b20fac2f3edf14f28d",
+        password: "/9p]S&XE#)DX}}|b",
+    },
+    Golden {
+        name: "policy-strict",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "5830ddd8130a6d5a3a22f37d6f53b4aca3f30858c76bc782f14aadff3b96c7c0ecb58dd604013045b28ba30246241cdfe6c609615aa28ab6b22818a8f98ef317",
+        password: "y7G22K_\"DA+o>Nbjwci3",
+    },
+    Golden {
+        name: "policy-pin",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "a2d41a3ac38ac0b4420ef12c4adb3153816f590d9e4c82f227dab37b6dcac4ef15b73e21d6f1fadb44adc9ec28981cf7f8f271460bd68869687a4a5a231a2bf5",
+        password: "528682",
+    },
+    Golden {
+        name: "policy-unicode",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "440ad65f1caea4776368aaef40c0e9a7ccdf2e3bf59e3bf23d6a8c36aebc1486af160eb621778eaa5454edd6f5fa18555edac5d41b0b04b21b24f4e7478b7c42",
+        password: "ØФнщЫЬÝΧЧÆΒΛÊ2ΗС",
+    },
+    Golden {
+        name: "policy-emoji",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "1a2fa298f507257233594d541fd94af21acf995a690a442dbb2e5fe87eb071a2ba8a44df1ee7e62a48f2562688f35d39e503a3a0836134aa86ba3e7f416874ba",
+        password: "🍟🌻🎈🍔🦀🐊",
+    },
+    Golden {
+        name: "pattern",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "305ba5b7ff616acfa804cafb9263ea0800a18c5c871268efcaddecc5bfb05c7ebee2ecc23db3cbb041a2e103cf76a8658cf1c2eff1ccea6ed034dbecd7ff3966",
+        password: "Jextic-7842-Q'i",
+    },
+    Golden {
+        name: "token-hex",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "873a02c3afa50a770f5e5ebed6f5a91d2aac1e6be17b57ab7978681bae3c882be2355b93b6e99d6fc2bde2da5041b0f880c36c1e4cded12beceab3ed6d201a5d",
+        password: "873a02c3afa50a770f5e5ebed6f5a91d",
+    },
+    Golden {
+        name: "token-base64url",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "0cbd36ba20afc76432ec613ccb985e548733d018adaa5bc47bc7ffec37b5e74cdeee538f3724ee021f4a244513975d9a8803614f0b1e7dfd9f1e3168481faa54",
+        password: "DL02uiCvx2Qy7GE8y5heVIcz0BitqlvE",
+    },
+    Golden {
+        name: "token-base58",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "79c196bf55cfe54d988070528fca4584b3e47c511bc03572f2165acef604befcb16fb29e4e3b3c48942233c313ed1de4560cbac8a6f3648541115ef0e75a3164",
+        password: "9CHYTcuGbpMjXiUXa1YQbvoNH3JhhXscrN8wPUA6HcNX",
+    },
+    Golden {
+        name: "algo-v2",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "fd24f626797d117a126be54d4232a108e720c86baaac009a5a11538b27765a60160f369ce8683461ba0e8b7be78201e54f510692928c956b772bf0d2ed70ecbd",
+        password: "%:MoisGG'oCYKrD",
+    },
+    Golden {
+        name: "algo-v2-delimiters",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "c8d271f84e101cdf7c131dca116390b8a021799b8790ce0a888979b69069ecd32bd9617e5125661ce3254cc7c4170f0cbc2a968afea0c066d23ea213e66f3b43",
+        password: "qrD&;FCg}tuZ",
+    },
+    Golden {
+        name: "algo-v3",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "8ff488c220356b2be4b9765d7df9779b4921d4fca049b9bd47fc38c87ec14d4034cdef3d7016fe25f801192e66bf4e90eac4d793eb1133a930269d64110af35d",
+        password: "!&AoR,G,RH1Gza~",
+    },
+    #[cfg(feature = "blake3")]
+    Golden {
+        name: "algo-v4",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "eb9295fad8f6be317fe718ef9856ccf903943fb131b815259c6f56468353b6a86ee33e5e67fca380f28ab275f463bd1602c6a834a0f1bb758302dcf27ae87cd4",
+        password: "71]34XX\"Id[y",
+    },
+    Golden {
+        name: "algo-v5",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "3edea438e43edf994b70fc63c5521700f226a153be588efb161107445d5fa70fd0724944ae0f28e9228f07fdb85f0334d09a65017c66bd42e9fd4860f16129d2",
+        password: ".*4_'@gM8x?ta!",
+    },
+    Golden {
+        name: "algo-v5-token",
+        site_key: "2a6ae5760557a00dee3324e47eef2b398a7130c2bd68db64acc2fc6a120156bf",
+        stream: "88158968e540b0de2e3bb121449a6bf70d213a259084df76ddce74934f24fc7292334ad59146dd74c53623f9e7d0e56f83fdd840dbae802e5f331cab66c84ab5",
+        password: "88158968e540b0de2e3bb121449a6bf70d213a259084df76ddce74934f24fc7292334ad59146dd74c53623f9e7d0e56f83fdd840dbae802e5f331cab66c84ab55a750deee29cfc4cf496ce83dc0dba275f9078d8ce68a1b08ad2569e04acdc8f6f3de32c",
+    },
+    Golden {
+        name: "argon2id-custom",
+        site_key: "9ff1453889f5bf8c9f0514151055e55cf4f1d55b8cadf677e33bd79506f5d121",
+        stream: "67b87329874d1f9c2849b7012163cce9a3034255dca6dfb805e4fe1f3582d130f05d0aad6b94cadbea9934b32bb820c6e97a4820eb1f36445a14583e0f4c8487",
+        password: "dPO)},H|F:Qbgw\"",
+    },
+    Golden {
+        name: "argon2id-balanced",
+        site_key: "f5d839e8e4203d728f7743800a5b9e20fad31158b7d09443c4b9151fb37e9f08",
+        stream: "b55c0eb85df7d18634c1ec7a22f051901c29d461724b9bf8c6a58b9ca2ac1c5547011c4355c54373eb73695b1eb477a9a12bd5dde4858fb5e431eb4fbfada5d6",
+        password: "PPeI}ZD0~>oaC",
+    },
+    Golden {
+        name: "pbkdf2",
+        site_key: "c33a6bafad4a40493bf413639add98aec435be08af66a4526fd8481bbba5d213",
+        stream: "0187b11a0fd8a25dbdb839a940bca82cc003987d7bbcd688768c443227206b25521c37374ddf15dc7e670e49aea522eae91aaaa729a4bda111fc6d2499483e5b",
+        password: "[(dSp.}Q5/Aa#",
+    },
+    Golden {
+        name: "pbkdf2-algo-v2",
+        site_key: "c33a6bafad4a40493bf413639add98aec435be08af66a4526fd8481bbba5d213",
+        stream: "074f1471bd76da9e3f8a5c3846ac5f5c359740b0cff698c10df2ee075600d02c0fe76a79a0df03a3dc08914382cd1e00c59c942050c921f8f2197a5a113c2f17",
+        password: "~<\"$zu<u1T)4~c",
+    },
+];
+
+/// Outcome of one vector.
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    /// Steps whose output differs from the recorded one: `kdf`, `prng` or `password`; or
+    /// `inputs` or `golden` if the vector is missing from the matrix or from `GOLDEN`
+    pub failed: Vec<&'static str>,
+    /// Set if the vector could not be derived at all
+    pub error: Option<GenError>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.failed.is_empty() && self.error.is_none()
+    }
+}
+
+/// Compares the outputs of `golden` with those derived.
+pub fn compare(golden: &Golden, outputs: &Outputs) -> Vec<&'static str> {
+    [
+        ("kdf", golden.site_key == outputs.site_key),
+        ("prng", golden.stream == outputs.stream),
+        ("password", golden.password == outputs.password),
+    ]
+    .into_iter()
+    .filter_map(|(step, same)| (!same).then_some(step))
+    .collect()
+}
+
+/// Derives every vector of the matrix and checks it against `GOLDEN`; a vector missing from
+/// either fails.
+pub fn run() -> Vec<Check> {
+    let matrix = vectors::matrix();
+    let mut checks: Vec<Check> = GOLDEN
+        .iter()
+        .map(|golden| {
+            let Some((_, inputs)) = matrix.iter().find(|(name, _)| *name == golden.name) else {
+                return Check { name: golden.name, failed: vec!["inputs"], error: None };
+            };
+            match vectors::compute(inputs) {
+                Ok(outputs) => Check { name: golden.name, failed: compare(golden, &outputs), error: None },
+                Err(e) => Check { name: golden.name, failed: Vec::new(), error: Some(e) },
+            }
+        })
+        .collect();
+    for (name, _) in matrix.iter().filter(|(name, _)| GOLDEN.iter().all(|golden| golden.name != *name)) {
+        checks.push(Check { name, failed: vec!["golden"], error: None });
+    }
+    checks
+}
@@ -0,0 +1,442 @@
+//! `pwgen serve --stdio`: JSON-RPC 2.0 over stdin and stdout, one request object per line and
+//! one response line per request (none for notifications), so editors, launchers and scripts
+//! can keep one pwgen running instead of spawning it (and running Argon2) per password.
+//!
+//! `pwgen serve --http <ADDR>`: the same methods as `POST /<method>` on a loopback address, for
+//! frontends that cannot spawn processes. The body is the params object and the response the
+//! result (or `{"error": <message>}`); every request needs `Authorization: Bearer <token>`
+//! with the token printed at start.
+//!
+//! Methods:
+//!
+//! ```text
+//! generate        {"site": ..., "username": ..., "version": ..., <policy>}  ->  like generate --json
+//! encode-policy   {<policy>}                                                ->  {"policy", "entropy_bits"}
+//! fingerprint     {"emoji": bool}                                           ->  like fingerprint --json
+//! ```
+//!
+//! `pwgen generate --requests <FILE|->`: a stream of bare `generate` params objects, one per
+//! line, each answered with a line of `generate --json` output (or `{"error": <message>}`),
+//! for programs that want passwords without speaking JSON-RPC. An `id` member is echoed.
+//!
+//! `<policy>` takes the generate flags as keys without the dashes (`length`, `allow`,
+//! `min-count`, `pattern`, `format`, ...): strings and numbers are flag values, `true` sets a
+//! switch and arrays list comma-separated values (or repeat the flag, for `class`). The master
+//! secret and KDF options are fixed when the server starts.
+
+use std::io::{self, BufRead, Write};
+
+use pwgen::fingerprint;
+use serde_json::{json, Map, Value};
+
+use super::{
+    master_input, normalize_site, output, parse_generate_args, plan_generation, query_yubikey, GenPlan, GenerateArgs, MasterArgs,
+    Session,
+};
+
+/// Invalid JSON.
+const PARSE_ERROR: i64 = -32700;
+/// Valid JSON but not a request object.
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Derivation failed (KDF, YubiKey or agent errors).
+const SERVER_ERROR: i64 = -32000;
+
+/// Generate flags `encode-policy` accepts as params.
+const POLICY_KEYS: &[&str] = &[
+    "length", "min", "max", "allow", "force", "class", "min-count", "max-count", "no-lower", "no-upper",
+    "no-digit", "no-symbol", "exclude", "no-ambiguous", "layout-safe", "letter-first", "letter-last", "policy",
+    "max-consecutive", "no-repeat", "max-sequential", "no-words", "no-site", "passwordrules", "min-bits",
+];
+
+/// Flags whose values may contain commas, so arrays repeat the flag instead of joining.
+const REPEATED_KEYS: &[&str] = &["class"];
+
+/// Generate flags `generate` accepts as params besides `POLICY_KEYS`.
+const GENERATE_KEYS: &[&str] = &["site", "username", "version", "pattern", "format", "bytes"];
+
+/// A JSON-RPC error object.
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+}
+
+/// Longest HTTP request body the server reads.
+#[cfg(feature = "http")]
+const MAX_BODY: usize = 16 * 1024;
+
+/// Answers requests from stdin until it closes, then wipes the master.
+///
+/// `input` supplies the per-request options (`--site-mode` and `--yubikey`).
+pub fn stdio(session: Session, input: &MasterArgs) -> io::Result<()> {
+    let result = serve_lines(&session, input, io::stdin().lock(), io::stdout().lock());
+    drop(session);
+    result
+}
+
+/// Answers `generate --requests` lines from `reader` until it ends, then wipes the master.
+pub fn requests(session: Session, input: &MasterArgs, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    let mut result = Ok(());
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut response = request_response(&session, input, &line);
+        result = writeln!(writer, "{}", response).and_then(|()| writer.flush());
+        output::wipe(&mut response);
+        if result.is_err() {
+            break;
+        }
+    }
+    drop(session);
+    result
+}
+
+/// Answers one `generate --requests` line.
+fn request_response(session: &Session, input: &MasterArgs, line: &str) -> Value {
+    let mut params: Map<String, Value> = match serde_json::from_str(line) {
+        Ok(Value::Object(params)) => params,
+        Ok(_) => return json!({"error": "request must be an object"}),
+        Err(e) => return json!({"error": e.to_string()}),
+    };
+    let mut response = Map::new();
+    if let Some(id) = params.remove("id") {
+        response.insert("id".to_string(), id);
+    }
+    match generate(session, input, &params) {
+        Ok(Value::Object(fields)) => {
+            response.insert("schema".to_string(), json!(output::SCHEMA));
+            response.extend(fields);
+        }
+        Ok(_) => unreachable!("generate output is an object"),
+        Err(e) => {
+            response.insert("error".to_string(), Value::String(e.message));
+        }
+    }
+    Value::Object(response)
+}
+
+/// A bound HTTP listener, so the port is taken before the master is read.
+#[cfg(feature = "http")]
+pub struct HttpListener(tiny_http::Server);
+
+#[cfg(feature = "http")]
+pub fn bind_http(addr: std::net::SocketAddr) -> io::Result<HttpListener> {
+    tiny_http::Server::http(addr).map(HttpListener).map_err(io::Error::other)
+}
+
+/// Answers HTTP requests until the process is stopped.
+///
+/// Prints the address and the session's auth token on stdout first.
+#[cfg(feature = "http")]
+pub fn http(listener: &HttpListener, session: Session, input: &MasterArgs) -> io::Result<()> {
+    use rand_core::{OsRng, RngCore};
+
+    let server = &listener.0;
+    let mut bytes = zeroize::Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(&mut *bytes);
+    let token = zeroize::Zeroizing::new(pwgen::format::Format::Hex.encode(&*bytes));
+    if let Some(addr) = server.server_addr().to_ip() {
+        println!("Listening on http://{}", addr);
+    }
+    println!("Token: {}", *token);
+    io::stdout().flush()?;
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = http_request(&session, input, &token, &mut request);
+        let response = tiny_http::Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json"))
+            .with_header(header("Cache-Control", "no-store"));
+        let response = if status == 401 {
+            response.with_header(header("WWW-Authenticate", "Bearer"))
+        } else {
+            response
+        };
+        // A client that hangs up early only loses its own answer
+        let _ = request.respond(response);
+    }
+    drop(session);
+    Ok(())
+}
+
+/// Authenticates, routes and answers one HTTP request: the status code and the JSON body.
+#[cfg(feature = "http")]
+fn http_request(session: &Session, input: &MasterArgs, token: &str, request: &mut tiny_http::Request) -> (u16, Value) {
+    use std::io::Read;
+    use tiny_http::Method;
+
+    let authorized = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.trim().as_bytes(), token.as_bytes()));
+    if !authorized {
+        return (401, json!({"error": "missing or wrong bearer token"}));
+    }
+
+    let path = request.url().split('?').next().unwrap_or_default();
+    let method = match path.strip_prefix('/') {
+        Some(method @ ("generate" | "encode-policy" | "fingerprint")) => method.to_string(),
+        _ => return (404, json!({"error": format!("no such endpoint '{}'", path)})),
+    };
+    match request.method() {
+        Method::Post => {}
+        Method::Get if method == "fingerprint" => {}
+        other => return (405, json!({"error": format!("{} is not allowed on {}", other, path)})),
+    }
+
+    if request.body_length().is_some_and(|len| len > MAX_BODY) {
+        return (413, json!({"error": format!("request body is over {} bytes", MAX_BODY)}));
+    }
+    let mut body = zeroize::Zeroizing::new(String::new());
+    let limit = MAX_BODY as u64 + 1;
+    if let Err(e) = request.as_reader().take(limit).read_to_string(&mut body) {
+        return (400, json!({"error": format!("cannot read the request body: {}", e)}));
+    }
+    if body.len() > MAX_BODY {
+        return (413, json!({"error": format!("request body is over {} bytes", MAX_BODY)}));
+    }
+    let params = if body.trim().is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_str(&body) {
+            Ok(params) => params,
+            Err(e) => return (400, json!({"error": e.to_string()})),
+        }
+    };
+
+    match call(session, input, &method, Some(&params)) {
+        Ok(result) => (200, result),
+        Err(e) => {
+            let status = match e.code {
+                SERVER_ERROR => 500,
+                METHOD_NOT_FOUND => 404,
+                _ => 400,
+            };
+            (status, json!({"error": e.message}))
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn header(field: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+/// Compares without an early exit, so response times do not reveal how much of the token
+/// was right.
+#[cfg(feature = "http")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn serve_lines(session: &Session, input: &MasterArgs, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_line(session, input, &line) {
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers one request line; `None` for a notification.
+fn handle_line(session: &Session, input: &MasterArgs, line: &str) -> Option<Value> {
+    respond(line.as_bytes(), |method, request| call(session, input, method, request.get("params")))
+}
+
+/// Parses one JSON-RPC request and answers it with the result of `dispatch(method, request)`;
+/// `None` for a notification.
+pub fn respond(
+    message: &[u8],
+    dispatch: impl FnOnce(&str, &Map<String, Value>) -> Result<Value, RpcError>,
+) -> Option<Value> {
+    let request: Value = match serde_json::from_slice(message) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+    };
+    let Some(request) = request.as_object() else {
+        return Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "request must be an object")));
+    };
+    let id = request.get("id").cloned();
+    let result = match (request.get("jsonrpc"), request.get("method")) {
+        (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => dispatch(method, request),
+        _ => Err(RpcError::new(INVALID_REQUEST, "expected \"jsonrpc\": \"2.0\" and a method name")),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error_response(id, e),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}})
+}
+
+/// Runs one method with its params.
+pub fn call(session: &Session, input: &MasterArgs, method: &str, params: Option<&Value>) -> Result<Value, RpcError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("rpc", method).entered();
+    let result = dispatch(session, input, method, params);
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        tracing::warn!(code = e.code, message = %e.message, "request failed");
+    }
+    result
+}
+
+fn dispatch(session: &Session, input: &MasterArgs, method: &str, params: Option<&Value>) -> Result<Value, RpcError> {
+    let empty = Map::new();
+    let params = match params {
+        None | Some(Value::Null) => &empty,
+        Some(Value::Object(params)) => params,
+        Some(_) => return Err(RpcError::new(INVALID_PARAMS, "params must be an object")),
+    };
+    match method {
+        "generate" => generate(session, input, params),
+        "encode-policy" => encode_policy(session, params),
+        "fingerprint" => fingerprint(session, input, params),
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    }
+}
+
+fn generate(session: &Session, input: &MasterArgs, params: &Map<String, Value>) -> Result<Value, RpcError> {
+    let args = generate_args(params, GENERATE_KEYS, None)?;
+    let GenPlan { mode, params_key, params: encoded, entropy } =
+        plan_generation(&args, &session.cfg).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    let site = request_site(session, input, &args.site)?;
+    let username = Some(args.username.as_str()).filter(|u| !u.is_empty());
+
+    let factors = site_factors(session, input, &site)?;
+    let password = mode
+        .generate(master_input(&session.master, session.scheme, session.algo, &factors), &site, username, args.version)
+        .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+
+    result(&output::Generate {
+        password: &password,
+        length: password.chars().count(),
+        site: &site,
+        username: &args.username,
+        version: args.version,
+        params: output::Params::new(params_key, &encoded),
+        entropy_bits: output::round_bits(entropy),
+        derivation: output::Derivation::new(session.scheme, session.algo),
+    })
+}
+
+fn encode_policy(session: &Session, params: &Map<String, Value>) -> Result<Value, RpcError> {
+    // The site is required by the generate flags but plays no part in the policy
+    let args = generate_args(params, &[], Some("--site=-"))?;
+    let GenPlan { params: encoded, entropy, .. } =
+        plan_generation(&args, &session.cfg).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    Ok(json!({"policy": encoded, "entropy_bits": output::round_bits(entropy)}))
+}
+
+fn fingerprint(session: &Session, input: &MasterArgs, params: &Map<String, Value>) -> Result<Value, RpcError> {
+    let emoji = match params.get("emoji") {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(emoji)) => *emoji,
+        Some(_) => return Err(RpcError::new(INVALID_PARAMS, "emoji must be a boolean")),
+    };
+    if let Some(key) = params.keys().find(|key| *key != "emoji") {
+        return Err(RpcError::new(INVALID_PARAMS, format!("unknown parameter '{}'", key)));
+    }
+
+    let site = request_site(session, input, fingerprint::FINGERPRINT_SITE)?;
+    let factors = site_factors(session, input, &site)?;
+    let master = master_input(&session.master, session.scheme, session.algo, &factors);
+    let fp = if emoji {
+        fingerprint::emoji_fingerprint(master)
+    } else {
+        fingerprint::master_fingerprint(master)
+    }
+    .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+    result(&output::Fingerprint { fingerprint: &fp, derivation: output::Derivation::new(session.scheme, session.algo) })
+}
+
+/// Normalizes a requested site like `--site` on the command line.
+fn request_site(session: &Session, input: &MasterArgs, site: &str) -> Result<String, RpcError> {
+    if site.trim().is_empty() {
+        return Err(RpcError::new(INVALID_PARAMS, "site must be nonempty after trim"));
+    }
+    normalize_site(site, input.site_mode, &session.cfg).map_err(|e| RpcError::new(INVALID_PARAMS, format!("site: {}", e)))
+}
+
+/// The session's factors plus the YubiKey's response for `site`, which differs per site.
+fn site_factors(session: &Session, input: &MasterArgs, site: &str) -> Result<super::Factors, RpcError> {
+    let yubikey = query_yubikey(input.yubikey, site)
+        .map_err(|()| RpcError::new(SERVER_ERROR, "YubiKey challenge-response failed"))?;
+    Ok(super::Factors { yubikey, ..session.factors.clone() })
+}
+
+/// Parses params as generate flags, accepting `POLICY_KEYS` and `extra_keys`; underscores in
+/// keys stand for dashes.
+fn generate_args(params: &Map<String, Value>, extra_keys: &[&str], fixed: Option<&str>) -> Result<GenerateArgs, RpcError> {
+    let mut argv: Vec<String> = fixed.into_iter().map(String::from).collect();
+    for (key, value) in params {
+        let flag = key.replace('_', "-");
+        if !POLICY_KEYS.contains(&flag.as_str()) && !extra_keys.contains(&flag.as_str()) {
+            return Err(RpcError::new(INVALID_PARAMS, format!("unknown parameter '{}'", key)));
+        }
+        let value = match value {
+            Value::Null | Value::Bool(false) => continue,
+            Value::Bool(true) => None,
+            Value::Array(items) if REPEATED_KEYS.contains(&flag.as_str()) => {
+                for item in items {
+                    argv.push(format!("--{}={}", flag, scalar(item).ok_or_else(|| invalid_value(key))?));
+                }
+                continue;
+            }
+            Value::Array(items) => {
+                let items: Option<Vec<String>> = items.iter().map(scalar).collect();
+                Some(items.ok_or_else(|| invalid_value(key))?.join(","))
+            }
+            value => Some(scalar(value).ok_or_else(|| invalid_value(key))?),
+        };
+        argv.push(match value {
+            Some(value) => format!("--{}={}", flag, value),
+            None => format!("--{}", flag),
+        });
+    }
+
+    parse_generate_args(argv).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn invalid_value(key: &str) -> RpcError {
+    RpcError::new(INVALID_PARAMS, format!("parameter '{}' must be a string, number, boolean or array of those", key))
+}
+
+/// A method result: the fields of the matching `--json` output, without the schema version.
+fn result(output: &impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(output).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,95 @@
+//! SuperGenPass compatibility (`generate --compat sgp`): the passwords of the SuperGenPass
+//! bookmarklet, since many accounts were created with it.
+//!
+//! The password is the first `length` characters of `hash^n(master || ":" || domain)`, where
+//! `hash` is the Base64 of the MD5 (or SHA-512) digest with `+`, `/` and `=` replaced by `9`, `8`
+//! and `A`, and n is 10, or the first later round whose password starts with a lowercase letter
+//! and contains an uppercase letter and a digit. A SuperGenPass "secret password" is appended
+//! to the master.
+
+use md5::Md5;
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::format::Format;
+
+/// Shortest password SuperGenPass generates.
+pub const MIN_LENGTH: usize = 4;
+
+/// Longest password SuperGenPass generates.
+pub const MAX_LENGTH: usize = 24;
+
+/// SuperGenPass's default length.
+pub const DEFAULT_LENGTH: usize = 10;
+
+/// Rounds hashed before the password is checked.
+const ROUNDS: usize = 10;
+
+/// Digest SuperGenPass hashes with.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    #[default]
+    Md5,
+    Sha512,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum SgpError {
+    #[error("SuperGenPass passwords are 4 to 24 characters long")]
+    Length,
+}
+
+/// Entropy of a password of `length` characters in bits: a lowercase letter, then Base64
+/// characters (not counting the uppercase letter and digit it must contain).
+pub fn entropy_bits(length: usize) -> Result<f64, SgpError> {
+    check_length(length)?;
+    Ok(26f64.log2() + (length - 1) as f64 * 6.0)
+}
+
+fn check_length(length: usize) -> Result<(), SgpError> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        return Err(SgpError::Length);
+    }
+    Ok(())
+}
+
+/// Generates the SuperGenPass password for `domain`, the domain the bookmarklet shows.
+pub fn generate(master: &str, domain: &str, length: usize, hash: HashAlgorithm) -> Result<String, SgpError> {
+    check_length(length)?;
+    let mut input = Zeroizing::new(format!("{}:{}", master, domain));
+    let mut round = 0;
+    while round < ROUNDS || !is_valid(&input[..length]) {
+        input = hash_round(&input, hash);
+        round += 1;
+    }
+    Ok(input[..length].to_string())
+}
+
+/// One round: the Base64 of the digest, in SuperGenPass's alphabet.
+fn hash_round(input: &str, hash: HashAlgorithm) -> Zeroizing<String> {
+    let digest = Zeroizing::new(match hash {
+        HashAlgorithm::Md5 => Md5::digest(input.as_bytes()).to_vec(),
+        HashAlgorithm::Sha512 => Sha512::digest(input.as_bytes()).to_vec(),
+    });
+    let mut encoded = Format::Base64Url.encode(&digest).into_bytes();
+    for b in encoded.iter_mut() {
+        *b = match *b {
+            b'-' => b'9',
+            b'_' => b'8',
+            b => b,
+        };
+    }
+    // Padding becomes 'A'
+    while !encoded.len().is_multiple_of(4) {
+        encoded.push(b'A');
+    }
+    Zeroizing::new(String::from_utf8(encoded).expect("Base64 is ASCII"))
+}
+
+/// Starts with a lowercase letter and contains an uppercase letter and a digit.
+fn is_valid(password: &str) -> bool {
+    password.starts_with(|c: char| c.is_ascii_lowercase())
+        && password.contains(|c: char| c.is_ascii_uppercase())
+        && password.contains(|c: char| c.is_ascii_digit())
+}
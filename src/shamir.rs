@@ -0,0 +1,194 @@
+//! Shamir secret sharing of the master (`pwgen share`): the master is split into `n` shares,
+//! any `k` of which recover it while fewer reveal nothing about it, for a paper backup spread
+//! among trusted people.
+//!
+//! Each byte of the secret is the constant term of its own random polynomial of degree `k - 1`
+//! over GF(2^8) (the AES field, `x^8 + x^4 + x^3 + x + 1`); share `i` holds the polynomials'
+//! values at `x = i`. Shares are written as `pwgen-share-v1-K-I-HEX-CHECK`, where `CHECK` is
+//! the first 2 bytes of `SHA256` of everything before it, to catch copying mistakes.
+
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Prefix of an encoded share.
+pub const SHARE_PREFIX: &str = "pwgen-share-v1";
+
+/// Longest secret that can be split, in bytes.
+pub const MAX_SECRET_LEN: usize = 1024;
+
+/// One share of a split secret.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Share {
+    /// Shares needed to recover the secret
+    pub threshold: u8,
+    /// Point the share was evaluated at (1 to 255)
+    pub index: u8,
+    /// One byte per byte of the secret
+    pub data: Vec<u8>,
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum ShareError {
+    #[error("threshold must be at least 2 and at most the number of shares (at most 255)")]
+    Counts,
+    #[error("secret must be 1 to 1024 bytes")]
+    SecretLength,
+    #[error("share is not pwgen-share-v1-K-I-HEX-CHECK")]
+    Syntax,
+    #[error("wrong checksum; check the share for copying mistakes")]
+    Checksum,
+    #[error("shares come from different splits (thresholds or lengths differ)")]
+    Mismatch,
+    #[error("share {0} is given twice")]
+    Duplicate(u8),
+    #[error("{needed} shares are needed, {given} given")]
+    TooFew { needed: u8, given: usize },
+}
+
+impl Share {
+    /// The share as written down: `pwgen-share-v1-K-I-HEX-CHECK`.
+    pub fn encode(&self) -> String {
+        let body = format!("{}-{}-{}-{}", SHARE_PREFIX, self.threshold, self.index, hex(&self.data));
+        let check = checksum(&body);
+        format!("{}-{}", body, check)
+    }
+
+    /// Parses an encoded share. Whitespace is ignored, so it may be grouped on paper, and hex
+    /// digits may be of either case.
+    pub fn parse(text: &str) -> Result<Share, ShareError> {
+        let text = Zeroizing::new(text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase());
+        let rest = text.strip_prefix(SHARE_PREFIX).and_then(|rest| rest.strip_prefix('-')).ok_or(ShareError::Syntax)?;
+        let fields: Vec<&str> = rest.split('-').collect();
+        let [threshold, index, data, check] = fields.as_slice() else {
+            return Err(ShareError::Syntax);
+        };
+        let number = |s: &str| match s.parse::<u8>() {
+            Ok(n) if n > 0 && s.bytes().all(|b| b.is_ascii_digit()) => Ok(n),
+            _ => Err(ShareError::Syntax),
+        };
+        let share = Share { threshold: number(threshold)?, index: number(index)?, data: unhex(data)? };
+        if share.threshold < 2 || share.data.is_empty() || check.len() != 4 {
+            return Err(ShareError::Syntax);
+        }
+        let body = &text[..text.len() - check.len() - 1];
+        if checksum(body) != *check {
+            return Err(ShareError::Checksum);
+        }
+        Ok(share)
+    }
+}
+
+/// Splits `secret` into `shares` shares, any `threshold` of which recover it.
+pub fn split(secret: &[u8], threshold: u8, shares: u8, rng: &mut (impl RngCore + CryptoRng)) -> Result<Vec<Share>, ShareError> {
+    if threshold < 2 || threshold > shares {
+        return Err(ShareError::Counts);
+    }
+    if secret.is_empty() || secret.len() > MAX_SECRET_LEN {
+        return Err(ShareError::SecretLength);
+    }
+    let mut out: Vec<Share> =
+        (1..=shares).map(|index| Share { threshold, index, data: Vec::with_capacity(secret.len()) }).collect();
+    let mut coefficients = Zeroizing::new(vec![0u8; usize::from(threshold)]);
+    for &byte in secret {
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+        for share in &mut out {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+    Ok(out)
+}
+
+/// Recovers the secret from at least `threshold` shares of one split. Extra shares are
+/// ignored; shares of a different split give a wrong secret if their threshold and length match.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, ShareError> {
+    let first = shares.first().ok_or(ShareError::TooFew { needed: 2, given: 0 })?;
+    if shares.iter().any(|s| s.threshold != first.threshold || s.data.len() != first.data.len()) {
+        return Err(ShareError::Mismatch);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(ShareError::Duplicate(share.index));
+        }
+    }
+    let needed = first.threshold;
+    if shares.len() < usize::from(needed) {
+        return Err(ShareError::TooFew { needed, given: shares.len() });
+    }
+    let shares = &shares[..usize::from(needed)];
+
+    // Lagrange basis at x = 0: prod(x_j / (x_j - x_i)), where subtraction is XOR
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|si| {
+            shares.iter().filter(|sj| sj.index != si.index).fold(1, |w, sj| {
+                mul(w, mul(sj.index, inverse(sj.index ^ si.index)))
+            })
+        })
+        .collect();
+    let mut secret = Zeroizing::new(vec![0u8; first.data.len()]);
+    for (share, &weight) in shares.iter().zip(&weights) {
+        for (out, &y) in secret.iter_mut().zip(&share.data) {
+            *out ^= mul(y, weight);
+        }
+    }
+    Ok(secret)
+}
+
+/// Evaluates the polynomial with the given coefficients (constant first) at `x`.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// Multiplication in GF(2^8) modulo `x^8 + x^4 + x^3 + x + 1`, without secret-dependent branches.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse in GF(2^8): `a^254` (`a` is nonzero).
+fn inverse(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, power);
+        }
+        power = mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn checksum(body: &str) -> String {
+    hex(&Sha256::digest(body.as_bytes())[..2])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, ShareError> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return Err(ShareError::Syntax);
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| ShareError::Syntax))
+        .collect()
+}
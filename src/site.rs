@@ -0,0 +1,202 @@
+use std::net::IpAddr;
+
+use thiserror::Error;
+use unicode_security::confusable_detection::skeleton;
+use unicode_security::MixedScript;
+
+/// How `--site` is reduced before derivation.
+///
+/// Every mode can change the site identifier, and therefore every password, so the same
+/// mode must be used on every device.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    any(feature = "serde", feature = "config"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SiteMode {
+    /// The site as given, trimmed and lowercased (a URL is reduced to its host)
+    #[default]
+    Exact,
+    /// The registrable domain of the site's host per the Public Suffix List
+    /// (`https://accounts.google.com/login` → `google.com`)
+    Registrable,
+}
+
+impl SiteMode {
+    /// Name used on the command line and in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            SiteMode::Exact => "exact",
+            SiteMode::Registrable => "registrable",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SiteError {
+    #[error("site has no host")]
+    MissingHost,
+
+    #[error("{0} is a public suffix and has no registrable domain")]
+    PublicSuffix(String),
+
+    #[error("registrable-domain normalization requires the psl feature")]
+    PslUnavailable,
+}
+
+/// Reduces a site as given on the command line to the identifier used for derivation.
+///
+/// A pasted URL is reduced to its host in every mode (see `is_url`), and internationalized
+/// domain names are brought to one Unicode form (see `to_unicode`), so a site typed in
+/// Unicode or pasted as punycode derives one password.
+pub fn normalize(site: &str, mode: SiteMode) -> Result<String, SiteError> {
+    let site = site.trim().to_lowercase();
+    match mode {
+        SiteMode::Exact if !is_url(&site) => Ok(to_unicode(&site).unwrap_or(site)),
+        SiteMode::Exact => {
+            let host = host(&site).ok_or(SiteError::MissingHost)?.trim_end_matches('.');
+            Ok(to_unicode(host).unwrap_or_else(|| host.to_string()))
+        }
+        SiteMode::Registrable => {
+            let host = host(&site).ok_or(SiteError::MissingHost)?;
+            registrable_domain(&to_unicode(host).unwrap_or_else(|| host.to_string()))
+        }
+    }
+}
+
+/// Whether `site` is a URL rather than a plain identifier: it has an explicit scheme
+/// (`https://example.com/login`).
+///
+/// Schemeless input is left alone, so sites that carry a path or port on purpose
+/// (`example.com/work`, `example.com:8443`) keep their own passwords.
+pub fn is_url(site: &str) -> bool {
+    site.contains("://")
+}
+
+/// Converts an internationalized domain name to its Unicode form per UTS #46 (nontransitional),
+/// so `xn--mnchen-3ya.de`, `MÜNCHEN.de` and `münchen.de` all become `münchen.de`.
+///
+/// ASCII names without punycode labels are returned unchanged, so existing sites keep their
+/// passwords. Returns `None` if `host` is not a valid domain name.
+pub fn to_unicode(host: &str) -> Option<String> {
+    if host.is_ascii() && !host.split('.').any(|label| label.starts_with("xn--")) {
+        return Some(host.to_string());
+    }
+    match idna::domain_to_unicode(host) {
+        (unicode, Ok(())) => Some(unicode),
+        (_, Err(_)) => None,
+    }
+}
+
+/// Returns the first label of `host` that may be a homograph of another name: one mixing
+/// scripts (a Cyrillic `а` in `pаypal`) or written entirely in lookalikes of ASCII letters
+/// (`аррӏе`). Such sites derive a different password than the name they imitate.
+pub fn homograph_label(host: &str) -> Option<&str> {
+    host.split('.')
+        .filter(|label| !label.is_ascii())
+        .find(|label| !label.is_single_script() || skeleton(label).all(|c| c.is_ascii()))
+}
+
+/// Returns the registrable domain of `host`: its public suffix plus one more label
+/// (`accounts.google.com` → `google.com`, `www.bbc.co.uk` → `bbc.co.uk`).
+///
+/// Both the ICANN and private sections of the list are used, so `alice.github.io` stays
+/// distinct from `bob.github.io`. IP addresses and single-label hosts are returned as is.
+pub fn registrable_domain(host: &str) -> Result<String, SiteError> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() {
+        return Err(SiteError::MissingHost);
+    }
+    if !host.contains('.') || host.parse::<IpAddr>().is_ok() {
+        return Ok(host);
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    match psl::suffix_labels(&labels)? {
+        n if n < labels.len() => Ok(labels[labels.len() - n - 1..].join(".")),
+        _ => Err(SiteError::PublicSuffix(host)),
+    }
+}
+
+/// Extracts the host from a URL: drops the scheme, credentials, port, path, query and
+/// fragment (`https://user:pw@accounts.google.com:443/login?x#y` → `accounts.google.com`).
+/// Returns `None` if nothing is left.
+pub fn host(site: &str) -> Option<&str> {
+    let rest = site.split_once("://").map_or(site, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host_port.strip_prefix('[') {
+        // Bracketed IPv6 literal, possibly followed by a port
+        Some(v6) => v6.split_once(']').map_or(v6, |(addr, _)| addr),
+        None => host_port.split_once(':').map_or(host_port, |(host, _)| host),
+    };
+    Some(host).filter(|h| !h.is_empty())
+}
+
+#[cfg(feature = "psl")]
+mod psl {
+    use std::collections::HashSet;
+    use std::sync::OnceLock;
+
+    use super::SiteError;
+
+    /// Public Suffix List (https://publicsuffix.org), Mozilla Public License 2.0.
+    const LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+    struct Rules {
+        /// Normal and wildcard (`*.ck`) rules
+        rules: HashSet<&'static str>,
+        /// Exception rules, without the leading `!`
+        exceptions: HashSet<&'static str>,
+    }
+
+    fn rules() -> &'static Rules {
+        static RULES: OnceLock<Rules> = OnceLock::new();
+        RULES.get_or_init(|| {
+            let mut rules = HashSet::new();
+            let mut exceptions = HashSet::new();
+            // One rule per line, up to the first whitespace; `//` starts a comment line
+            for rule in LIST.lines().filter_map(|line| line.split_whitespace().next()) {
+                if rule.starts_with("//") {
+                    continue;
+                }
+                match rule.strip_prefix('!') {
+                    Some(exception) => exceptions.insert(exception),
+                    None => rules.insert(rule),
+                };
+            }
+            Rules { rules, exceptions }
+        })
+    }
+
+    /// Number of trailing labels forming the public suffix, per the list's algorithm:
+    /// exception rules win, then the longest matching rule, else the implicit `*` rule.
+    pub(super) fn suffix_labels(labels: &[&str]) -> Result<usize, SiteError> {
+        let rules = rules();
+        let n = labels.len();
+        // Longest candidates first, so the first match is the prevailing one
+        for i in 0..n {
+            if rules.exceptions.contains(labels[i..].join(".").as_str()) {
+                return Ok(n - i - 1);
+            }
+        }
+        for i in 0..n {
+            let candidate = labels[i..].join(".");
+            let wildcard = format!("*.{}", labels[i + 1..].join("."));
+            if rules.rules.contains(candidate.as_str()) || (i + 1 < n && rules.rules.contains(wildcard.as_str())) {
+                return Ok(n - i);
+            }
+        }
+        Ok(1)
+    }
+}
+
+#[cfg(not(feature = "psl"))]
+mod psl {
+    use super::SiteError;
+
+    pub(super) fn suffix_labels(_labels: &[&str]) -> Result<usize, SiteError> {
+        Err(SiteError::PslUnavailable)
+    }
+}
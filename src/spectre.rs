@@ -0,0 +1,143 @@
+//! Spectre compatibility (`generate --compat spectre`): the passwords of Spectre, formerly
+//! Master Password, algorithm version 3, so its long-time users can regenerate their existing
+//! passwords.
+//!
+//! Master key: `scrypt(master, "com.lyndir.masterpassword" || len(name) || name, N=32768, r=8,
+//! p=2, 64 bytes)` for the user's full name. Site key: `HMAC-SHA256(master key,
+//! "com.lyndir.masterpassword" || len(site) || site || counter)`, lengths and counter as 32-bit
+//! big-endian integers of UTF-8 byte counts. The first site key byte picks one of the
+//! password type's templates, and each following byte a character of the class at that
+//! position of the template.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Scope of authentication (password) site keys.
+const SCOPE: &str = "com.lyndir.masterpassword";
+
+/// scrypt costs of algorithm version 3: N = 2^15, r = 8, p = 2.
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 2;
+
+/// Spectre password types.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PasswordType {
+    /// 20 characters, e.g. `W6@692^B1#&@gVdSdLZ@`
+    Maximum,
+    /// 14 characters, e.g. `Jejr5[RepuSosp` (Spectre's default)
+    #[default]
+    Long,
+    /// 8 characters, e.g. `Jej2$Quv`
+    Medium,
+    /// 4 characters, e.g. `Jej2`
+    Short,
+    /// 8 letters and digits, e.g. `WAo2xIg6`
+    Basic,
+    /// 4 digits, e.g. `7662`
+    Pin,
+    /// 9 lowercase letters, e.g. `jejraquvo`
+    Name,
+    /// Four pronounceable words, e.g. `jejr quv cabsibu tam`
+    Phrase,
+}
+
+impl PasswordType {
+    fn templates(self) -> &'static [&'static str] {
+        match self {
+            PasswordType::Maximum => &["anoxxxxxxxxxxxxxxxxx", "axxxxxxxxxxxxxxxxxno"],
+            PasswordType::Long => &[
+                "CvcvnoCvcvCvcv", "CvcvCvcvnoCvcv", "CvcvCvcvCvcvno", "CvccnoCvcvCvcv", "CvccCvcvnoCvcv",
+                "CvccCvcvCvcvno", "CvcvnoCvccCvcv", "CvcvCvccnoCvcv", "CvcvCvccCvcvno", "CvcvnoCvcvCvcc",
+                "CvcvCvcvnoCvcc", "CvcvCvcvCvccno", "CvccnoCvccCvcv", "CvccCvccnoCvcv", "CvccCvccCvcvno",
+                "CvcvnoCvccCvcc", "CvcvCvccnoCvcc", "CvcvCvccCvccno", "CvccnoCvcvCvcc", "CvccCvcvnoCvcc",
+                "CvccCvcvCvccno",
+            ],
+            PasswordType::Medium => &["CvcnoCvc", "CvcCvcno"],
+            PasswordType::Short => &["Cvcn"],
+            PasswordType::Basic => &["aaanaaan", "aannaaan", "aaannaaa"],
+            PasswordType::Pin => &["nnnn"],
+            PasswordType::Name => &["cvccvcvcv"],
+            PasswordType::Phrase => &["cvcc cvc cvccvcv cvc", "cvc cvccvcvcv cvcv", "cv cvccv cvc cvcvccv"],
+        }
+    }
+
+    /// Entropy of the password in bits: that of its weakest template, plus the template choice.
+    pub fn entropy_bits(self) -> f64 {
+        let templates = self.templates();
+        let weakest = templates
+            .iter()
+            .map(|template| template.chars().map(|class| (class_chars(class).len() as f64).log2()).sum::<f64>())
+            .fold(f64::INFINITY, f64::min);
+        weakest + (templates.len() as f64).log2()
+    }
+}
+
+/// Characters of a template class.
+fn class_chars(class: char) -> &'static [u8] {
+    match class {
+        'V' => b"AEIOU",
+        'C' => b"BCDFGHJKLMNPQRSTVWXYZ",
+        'v' => b"aeiou",
+        'c' => b"bcdfghjklmnpqrstvwxyz",
+        'A' => b"AEIOUBCDFGHJKLMNPQRSTVWXYZ",
+        'a' => b"AEIOUaeiouBCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz",
+        'n' => b"0123456789",
+        'o' => b"@&%?,=[]_:-+*$#!'^~;()/.",
+        'x' => b"AEIOUaeiouBCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz0123456789!@#$%^&*()",
+        _ => b" ",
+    }
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum SpectreError {
+    #[error("Spectre needs the user's full name (--username)")]
+    EmptyName,
+    #[error("scrypt failed: {0}")]
+    Scrypt(String),
+    #[error("HMAC initialization failed")]
+    HmacInit,
+}
+
+/// Derives the master key of `full_name`, the slow step shared by all sites.
+pub fn master_key(master: &str, full_name: &str) -> Result<Zeroizing<[u8; 64]>, SpectreError> {
+    if full_name.is_empty() {
+        return Err(SpectreError::EmptyName);
+    }
+    let salt = [SCOPE.as_bytes(), &(full_name.len() as u32).to_be_bytes(), full_name.as_bytes()].concat();
+    let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, 64).map_err(|e| SpectreError::Scrypt(e.to_string()))?;
+    let mut key = Zeroizing::new([0u8; 64]);
+    scrypt::scrypt(master.as_bytes(), &salt, &params, &mut *key).map_err(|e| SpectreError::Scrypt(e.to_string()))?;
+    Ok(key)
+}
+
+/// Renders the password of `site` from a master key.
+pub fn site_password(master_key: &[u8; 64], site: &str, counter: u32, password_type: PasswordType) -> Result<String, SpectreError> {
+    let mut mac = HmacSha256::new_from_slice(master_key).map_err(|_| SpectreError::HmacInit)?;
+    mac.update(SCOPE.as_bytes());
+    mac.update(&(site.len() as u32).to_be_bytes());
+    mac.update(site.as_bytes());
+    mac.update(&counter.to_be_bytes());
+    let site_key = Zeroizing::new(<[u8; 32]>::from(mac.finalize().into_bytes()));
+
+    let templates = password_type.templates();
+    let template = templates[usize::from(site_key[0]) % templates.len()];
+    Ok(template
+        .chars()
+        .zip(&site_key[1..])
+        .map(|(class, &byte)| {
+            let chars = class_chars(class);
+            char::from(chars[usize::from(byte) % chars.len()])
+        })
+        .collect())
+}
+
+/// Generates the Spectre password of `site` for the user `full_name`.
+pub fn generate(master: &str, full_name: &str, site: &str, counter: u32, password_type: PasswordType) -> Result<String, SpectreError> {
+    let key = master_key(master, full_name)?;
+    site_password(&key, site, counter, password_type)
+}
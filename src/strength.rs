@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Lowest score `--enforce-strength` accepts: at least 10^8 guesses, which the slow KDF
+/// turns into years of work for a single attacker.
+pub const MIN_SCORE: u8 = 3;
+
+/// Only this many leading characters are analyzed; the estimate for the prefix is a lower
+/// bound for the whole secret.
+pub const MAX_ANALYZED_CHARS: usize = 128;
+
+/// Most frequent passwords from public breach corpora, most common first. The position is
+/// the number of guesses an attacker needs.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111",
+    "1234567", "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein",
+    "696969", "shadow", "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890",
+    "michael", "654321", "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx",
+    "123qwe", "killer", "trustno1", "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter",
+    "buster", "soccer", "harley", "batman", "andrew", "tigger", "sunshine", "iloveyou",
+    "charlie", "robert", "thomas", "hockey", "ranger", "daniel", "starwars", "112233",
+    "george", "computer", "michelle", "jessica", "pepper", "zxcvbn", "555555", "11111111",
+    "131313", "freedom", "777777", "pass", "maggie", "159753", "aaaaaa", "ginger",
+    "princess", "joshua", "cheese", "amanda", "summer", "love", "ashley", "nicole",
+    "chelsea", "biteme", "matthew", "access", "yankees", "987654321", "dallas", "austin",
+    "thunder", "taylor", "matrix", "minecraft", "welcome", "admin", "login", "passw0rd",
+    "hello", "secret", "whatever", "qwerty123", "password1", "123abc", "flower", "lovely",
+    "solo", "guest", "changeme", "root", "toor", "default", "letmein1", "monkey1",
+    "dragon1", "master1", "football1", "baseball1", "abc12345", "zaq12wsx", "sunshine1",
+    "iloveyou1", "password123", "admin123", "welcome1", "hello123", "samsung", "apple",
+    "google", "qwe123", "asdf", "asdfasdf", "1q2w3e4r", "1q2w3e", "q1w2e3r4", "passpass",
+];
+
+/// Frequent English words that turn up in passwords and passphrases. Each counts as one of
+/// the `COMMON_WORD_GUESSES` most frequent words.
+const COMMON_WORDS: &[&str] = &[
+    "love", "life", "angel", "baby", "happy", "music", "money", "magic", "star", "blue",
+    "red", "green", "black", "white", "orange", "purple", "silver", "golden", "gold",
+    "winter", "spring", "autumn", "sun", "moon", "sky", "fire", "water", "earth", "storm",
+    "rain", "snow", "tiger", "lion", "eagle", "wolf", "bear", "horse", "dog", "cat", "fish",
+    "bird", "king", "queen", "prince", "god", "jesus", "heaven", "hell", "house", "home",
+    "family", "friend", "friends", "forever", "always", "never", "sweet", "sugar", "honey",
+    "candy", "cookie", "chocolate", "coffee", "pizza", "banana", "cherry", "lemon", "peach",
+    "rose", "lily", "daisy", "tree", "forest", "river", "ocean", "mountain", "island",
+    "beach", "city", "world", "power", "hidden", "ghost", "spirit", "soul", "heart", "mind",
+    "dream", "hope", "faith", "peace", "light", "dark", "night", "day", "time", "school",
+    "work", "game", "player", "team", "winner", "champion", "hero", "super", "man", "woman",
+    "girl", "boy", "lady", "doctor", "teacher", "internet", "phone", "correct", "battery",
+    "staple", "table", "chair", "window", "door", "paper", "book", "letter", "number",
+    "pass", "word", "key", "lock", "open", "test", "user", "name", "mother", "father",
+    "sister", "brother", "summer", "dragon", "monkey", "secret", "shadow", "master",
+];
+
+/// Guesses charged for a word from `COMMON_WORDS`.
+const COMMON_WORD_GUESSES: f64 = 5_000.0;
+
+/// Guesses per character for text no pattern explains (as in zxcvbn).
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// Extra guesses charged per additional pattern, so many short matches cost more than one
+/// long one (zxcvbn's `MIN_GUESSES_BEFORE_GROWING_SEQUENCE`).
+const PATTERN_GROWTH_GUESSES: f64 = 10_000.0;
+
+/// Keyboard rows used to find straight key runs (`qwerty`, `asdf`, `7890`).
+const KEYBOARD_ROWS: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+
+/// Estimated strength of a secret, in the manner of zxcvbn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    /// Estimated number of guesses to find the secret, as log10
+    pub guesses_log10: f64,
+    /// 0 (too guessable) to 4 (very unguessable), with zxcvbn's thresholds of 10^3, 10^6,
+    /// 10^8 and 10^10 guesses
+    pub score: u8,
+    /// What makes the secret weak, for scores below `MIN_SCORE` with a recognizable pattern
+    /// to blame
+    pub warning: Option<&'static str>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Pattern {
+    CommonPassword,
+    CommonWord,
+    Sequence,
+    Repeat,
+    Keyboard,
+    Date,
+    Bruteforce,
+}
+
+impl Pattern {
+    fn warning(self) -> Option<&'static str> {
+        match self {
+            Pattern::CommonPassword => Some("this is one of the most common passwords"),
+            Pattern::CommonWord => Some("single common words are easy to guess; use several unrelated words"),
+            Pattern::Sequence => Some("sequences like abc or 6543 are easy to guess"),
+            Pattern::Repeat => Some("repeats like aaa or abcabc are easy to guess"),
+            Pattern::Keyboard => Some("straight rows of keys like qwerty are easy to guess"),
+            Pattern::Date => Some("dates and years are easy to guess"),
+            Pattern::Bruteforce => None,
+        }
+    }
+}
+
+/// A pattern covering `chars[start..end]`.
+struct Match {
+    start: usize,
+    end: usize,
+    guesses_log10: f64,
+    pattern: Pattern,
+}
+
+/// Estimates how many guesses an attacker needs for `secret`.
+///
+/// The secret is split into the most guessable sequence of patterns (common passwords and
+/// words, also reversed or with l33t substitutions, sequences, repeats, keyboard rows,
+/// dates, and brute force for the rest), and the guesses of that sequence are reported.
+pub fn estimate(secret: &str) -> Estimate {
+    let chars: Vec<char> = secret.chars().take(MAX_ANALYZED_CHARS).collect();
+    if chars.is_empty() {
+        return Estimate { guesses_log10: 0.0, score: 0, warning: None };
+    }
+    let matches = find_matches(&chars);
+    let (guesses_log10, weakest) = most_guessable(&chars, &matches);
+    let score = score(guesses_log10);
+    let warning = weakest.filter(|_| score < MIN_SCORE).and_then(Pattern::warning);
+    Estimate { guesses_log10, score, warning }
+}
+
+fn score(guesses_log10: f64) -> u8 {
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Finds the sequence of non-overlapping matches covering the secret with the fewest total
+/// guesses: `l! * product(guesses) + PATTERN_GROWTH_GUESSES^(l - 1)` for `l` matches.
+/// Returns its guesses (log10) and the pattern of its longest non-brute-force match.
+fn most_guessable(chars: &[char], matches: &[Match]) -> (f64, Option<Pattern>) {
+    let n = chars.len();
+    // best[end][l]: lowest sum of log10 guesses covering chars[..end] with l matches, and
+    // the index of the last match
+    let mut best: Vec<Vec<Option<(f64, usize)>>> = vec![vec![None; n + 1]; n + 1];
+    best[0][0] = Some((0.0, usize::MAX));
+    for end in 1..=n {
+        for (idx, m) in matches.iter().enumerate().filter(|(_, m)| m.end == end) {
+            for l in 1..=end {
+                let Some((prev, _)) = best[m.start][l - 1] else { continue };
+                let total = prev + m.guesses_log10;
+                if best[end][l].is_none_or(|(current, _)| total < current) {
+                    best[end][l] = Some((total, idx));
+                }
+            }
+        }
+    }
+
+    let mut log_factorial = 0.0;
+    let mut winner = (f64::INFINITY, 0);
+    for (l, entry) in best[n].iter().enumerate().skip(1) {
+        log_factorial += (l as f64).log10();
+        if let Some((sum, _)) = *entry {
+            let total = log10_sum(log_factorial + sum, (l - 1) as f64 * PATTERN_GROWTH_GUESSES.log10());
+            if total < winner.0 {
+                winner = (total, l);
+            }
+        }
+    }
+
+    // Walk back through the winning sequence to find the pattern to blame
+    let (guesses_log10, mut l) = winner;
+    let mut end = n;
+    let mut weakest: Option<&Match> = None;
+    while l > 0 {
+        let (_, idx) = best[end][l].expect("winning sequence is complete");
+        let m = &matches[idx];
+        if m.pattern != Pattern::Bruteforce && weakest.is_none_or(|w| m.end - m.start > w.end - w.start) {
+            weakest = Some(m);
+        }
+        end = m.start;
+        l -= 1;
+    }
+    (guesses_log10, weakest.map(|m| m.pattern))
+}
+
+/// `log10(10^a + 10^b)`
+fn log10_sum(a: f64, b: f64) -> f64 {
+    let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+    hi + (1.0 + 10f64.powf(lo - hi)).log10()
+}
+
+fn find_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for start in 0..chars.len() {
+        for end in start + 1..=chars.len() {
+            // Brute force: every span, so any text can be covered
+            let len = (end - start) as f64;
+            let guesses_log10 = if end - start == 1 { (BRUTEFORCE_CARDINALITY + 1.0).log10() } else { len * BRUTEFORCE_CARDINALITY.log10() };
+            matches.push(Match { start, end, guesses_log10, pattern: Pattern::Bruteforce });
+        }
+    }
+    dictionary_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    matches
+}
+
+/// Common passwords and words, as typed, reversed or with l33t substitutions undone.
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let ranks = ranked_dictionary();
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len().min(start + 32) {
+            let token = &chars[start..end];
+            let lower: String = token.iter().flat_map(|c| c.to_lowercase()).collect();
+            let case_log10 = uppercase_variations(token).log10();
+            let mut candidates = vec![(lower.clone(), 0.0)];
+            candidates.push((lower.chars().rev().collect(), 2f64.log10()));
+            for table in L33T_TABLES {
+                let (unleeted, substitutions) = unleet(&lower, table);
+                if substitutions > 0 {
+                    candidates.push((unleeted, substitutions as f64 * 2f64.log10()));
+                }
+            }
+            for (candidate, extra_log10) in candidates {
+                if let Some(&(rank, pattern)) = ranks.get(candidate.as_str()) {
+                    let guesses_log10 = rank.log10() + case_log10 + extra_log10;
+                    matches.push(Match { start, end, guesses_log10, pattern });
+                }
+            }
+        }
+    }
+}
+
+fn ranked_dictionary() -> &'static HashMap<&'static str, (f64, Pattern)> {
+    static RANKS: OnceLock<HashMap<&'static str, (f64, Pattern)>> = OnceLock::new();
+    RANKS.get_or_init(|| {
+        let mut ranks = HashMap::new();
+        for &word in COMMON_WORDS {
+            ranks.insert(word, (COMMON_WORD_GUESSES, Pattern::CommonWord));
+        }
+        // Passwords take precedence over words, and the most common rank wins
+        for (rank, &password) in COMMON_PASSWORDS.iter().enumerate().rev() {
+            ranks.insert(password, ((rank + 1) as f64, Pattern::CommonPassword));
+        }
+        ranks
+    })
+}
+
+/// l33t substitutions undone before looking a token up; `1` and `|` may stand for `i` or `l`.
+const L33T_TABLES: [&[(char, char)]; 2] = [
+    &[('4', 'a'), ('@', 'a'), ('8', 'b'), ('(', 'c'), ('3', 'e'), ('6', 'g'), ('9', 'g'), ('1', 'i'), ('!', 'i'),
+      ('|', 'i'), ('0', 'o'), ('$', 's'), ('5', 's'), ('7', 't'), ('+', 't'), ('2', 'z')],
+    &[('4', 'a'), ('@', 'a'), ('8', 'b'), ('(', 'c'), ('3', 'e'), ('6', 'g'), ('9', 'g'), ('1', 'l'), ('!', 'i'),
+      ('|', 'l'), ('0', 'o'), ('$', 's'), ('5', 's'), ('7', 't'), ('+', 't'), ('2', 'z')],
+];
+
+fn unleet(token: &str, table: &[(char, char)]) -> (String, usize) {
+    let mut substitutions = 0;
+    let unleeted = token
+        .chars()
+        .map(|c| match table.iter().find(|&&(from, _)| from == c) {
+            Some(&(_, to)) => {
+                substitutions += 1;
+                to
+            }
+            None => c,
+        })
+        .collect();
+    (unleeted, substitutions)
+}
+
+/// Capitalizations an attacker tries for a token: 1 for lowercase, 2 for a capitalized
+/// first or last letter or all caps, else the ways to place its uppercase letters.
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = upper == 1 && (token[0].is_uppercase() || token[token.len() - 1].is_uppercase());
+    if first_only || lower == 0 {
+        return 2.0;
+    }
+    (1..=upper.min(lower)).map(|k| binomial(upper + lower, k)).sum()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+/// Runs of at least three letters or digits with a constant step of one (`abc`, `9876`).
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let step = chars[start + 1] as i64 - chars[start] as i64;
+        let same_class = |a: char, b: char| (a.is_ascii_digit() && b.is_ascii_digit()) || (a.is_ascii_alphabetic() && b.is_ascii_alphabetic());
+        let mut end = start + 1;
+        while end < chars.len()
+            && step.abs() == 1
+            && chars[end] as i64 - chars[end - 1] as i64 == step
+            && same_class(chars[end], chars[start])
+        {
+            end += 1;
+        }
+        if end - start >= 3 {
+            let first = chars[start];
+            let base: f64 = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if step < 0 { 2.0 } else { 1.0 };
+            let guesses = base * direction * (end - start) as f64;
+            matches.push(Match { start, end, guesses_log10: guesses.log10(), pattern: Pattern::Sequence });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// A block repeated at least twice covering three or more characters (`aaa`, `abcabc`).
+///
+/// Like zxcvbn, takes the longest repeat from each position (with the shortest block) and
+/// continues after it, so the base block is estimated once per repeat.
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        // (covered length, block length, repeat count)
+        let mut longest = (0, 0, 0);
+        for block in 1..=(chars.len() - start) / 2 {
+            let base = &chars[start..start + block];
+            let mut count = 1;
+            while start + (count + 1) * block <= chars.len() && &chars[start + count * block..start + (count + 1) * block] == base {
+                count += 1;
+            }
+            if count >= 2 && count * block >= 3 && count * block > longest.0 {
+                longest = (count * block, block, count);
+            }
+        }
+        let (covered, block, count) = longest;
+        if covered == 0 {
+            start += 1;
+            continue;
+        }
+        let base = &chars[start..start + block];
+        let base_log10 = if block == 1 {
+            cardinality(base[0]).log10()
+        } else {
+            most_guessable(base, &find_matches(base)).0
+        };
+        let guesses_log10 = base_log10 + (count as f64).log10();
+        matches.push(Match { start, end: start + covered, guesses_log10, pattern: Pattern::Repeat });
+        start += covered;
+    }
+}
+
+fn cardinality(c: char) -> f64 {
+    if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else {
+        33.0
+    }
+}
+
+/// Runs of four or more adjacent keys along a keyboard row, in either direction.
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let position = |c: char| {
+        let c = c.to_ascii_lowercase();
+        KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| keys.find(c).map(|col| (row, col as i64)))
+    };
+    for start in 0..chars.len() {
+        let Some((row, col)) = position(chars[start]) else { continue };
+        for step in [1, -1] {
+            let mut end = start + 1;
+            while end < chars.len() && position(chars[end]) == Some((row, col + step * (end - start) as i64)) {
+                end += 1;
+            }
+            if end - start >= 4 {
+                // About 47 starting keys, then one of ~4 neighbours per further key
+                let guesses_log10 = 47f64.log10() + (end - start - 1) as f64 * 4f64.log10();
+                let case_log10 = uppercase_variations(&chars[start..end]).log10();
+                matches.push(Match { start, end, guesses_log10: guesses_log10 + case_log10, pattern: Pattern::Keyboard });
+            }
+        }
+    }
+}
+
+/// Years 1900–2049 and all-digit dates (`ddmmyyyy`, `mmddyyyy`, `yyyymmdd`, `ddmmyy`).
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    const YEARS: f64 = 150.0;
+    let digits_at = |start: usize, len: usize| -> Option<u32> {
+        let token: String = chars.get(start..start + len)?.iter().collect();
+        token.bytes().all(|b| b.is_ascii_digit()).then(|| token.parse().ok()).flatten()
+    };
+    let valid = |day: u32, month: u32| (1..=31).contains(&day) && (1..=12).contains(&month);
+    for start in 0..chars.len() {
+        if digits_at(start, 4).is_some_and(|y| (1900..2050).contains(&y)) {
+            matches.push(Match { start, end: start + 4, guesses_log10: YEARS.log10(), pattern: Pattern::Date });
+        }
+        if let Some(n) = digits_at(start, 8) {
+            let (a, b, c) = (n / 1_000_000, n / 10_000 % 100, n % 10_000);
+            let (y, m, d) = (n / 10_000, n / 100 % 100, n % 100);
+            if ((valid(a, b) || valid(b, a)) && (1900..2050).contains(&c)) || ((1900..2050).contains(&y) && valid(d, m)) {
+                matches.push(Match { start, end: start + 8, guesses_log10: (365.0 * YEARS).log10(), pattern: Pattern::Date });
+            }
+        }
+        if let Some(n) = digits_at(start, 6) {
+            let (a, b) = (n / 10_000, n / 100 % 100);
+            if valid(a, b) || valid(b, a) {
+                matches.push(Match { start, end: start + 6, guesses_log10: (365.0 * 100.0f64).log10(), pattern: Pattern::Date });
+            }
+        }
+    }
+}
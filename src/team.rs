@@ -0,0 +1,64 @@
+//! Team masters (`--team FILE`): a master combined from secrets contributed by each member of a
+//! small team, so every member holding the team file derives the same shared service passwords
+//! without a vault.
+//!
+//! Each contribution is hashed as `SHA256(b"pwgen-team-member-v1:" || secret)`; the digests are
+//! sorted, so the order of the file does not matter, and
+//! `HKDF-SHA256(salt = b"pwgen-team-v1", ikm = sorted digests, info = b"master")` gives 32 bytes
+//! whose lowercase hex is used as the master. Adding, removing or changing a contribution
+//! changes every password.
+
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Fewest contributions a team master is combined from.
+pub const MIN_MEMBERS: usize = 2;
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum TeamError {
+    #[error("a team needs at least 2 contributed secrets, one per line")]
+    TooFew,
+    #[error("contributions {0} and {1} are the same secret")]
+    Duplicate(usize, usize),
+}
+
+/// The contributions of a team file: one secret per line, surrounding whitespace trimmed;
+/// empty lines and lines starting with `#` are skipped.
+pub fn parse(text: &str) -> Vec<&str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).collect()
+}
+
+/// Combines the contributions into the team master.
+pub fn team_master(secrets: &[&str]) -> Result<Zeroizing<String>, TeamError> {
+    if secrets.len() < MIN_MEMBERS {
+        return Err(TeamError::TooFew);
+    }
+    let digests: Vec<Zeroizing<[u8; 32]>> = secrets
+        .iter()
+        .map(|secret| {
+            let mut hasher = Sha256::new();
+            hasher.update(b"pwgen-team-member-v1:");
+            hasher.update(secret.as_bytes());
+            Zeroizing::new(hasher.finalize().into())
+        })
+        .collect();
+    for (i, digest) in digests.iter().enumerate() {
+        if let Some(j) = digests[..i].iter().position(|other| other == digest) {
+            return Err(TeamError::Duplicate(j + 1, i + 1));
+        }
+    }
+
+    let mut sorted: Vec<&[u8; 32]> = digests.iter().map(|d| &**d).collect();
+    sorted.sort();
+    let mut ikm = Zeroizing::new(Vec::with_capacity(32 * sorted.len()));
+    for digest in sorted {
+        ikm.extend_from_slice(digest);
+    }
+    let mut okm = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(b"pwgen-team-v1"), &ikm)
+        .expand(b"master", &mut *okm)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Ok(Zeroizing::new(okm.iter().map(|b| format!("{:02x}", b)).collect()))
+}
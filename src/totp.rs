@@ -0,0 +1,61 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use zeroize::Zeroizing;
+
+use crate::format;
+use crate::kdf;
+use crate::generator::{site_stream, GenError};
+
+/// Secret length in bytes (160 bits, the RFC 4226 recommendation for HMAC-SHA1).
+pub const SECRET_LEN: usize = 20;
+
+/// Time step in seconds used by authenticator apps.
+pub const PERIOD: u64 = 30;
+
+/// Code length used by authenticator apps.
+pub const DIGITS: u32 = 6;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// Derives the TOTP secret for a site (`totp=sha1;bytes=20` context).
+pub fn derive_secret<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    version: u32,
+) -> Result<Zeroizing<Vec<u8>>, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"totp", b"sha1;bytes=20", version)?;
+
+    let mut secret = Zeroizing::new(vec![0u8; SECRET_LEN]);
+    rng.fill(&mut secret)?;
+    Ok(secret)
+}
+
+/// Base32 form of a secret, as entered into an authenticator app during enrollment.
+pub fn secret_base32(secret: &[u8]) -> Zeroizing<String> {
+    Zeroizing::new(format::encode_base32(secret))
+}
+
+/// Computes the RFC 6238 (HMAC-SHA1) code for a Unix time.
+pub fn code_at(secret: &[u8], unix_time: u64, period: u64, digits: u32) -> u32 {
+    debug_assert!(period > 0 && (1..=9).contains(&digits));
+    let counter = unix_time / period;
+
+    let mut mac = HmacSha1::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation (RFC 4226 §5.3)
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    value % 10u32.pow(digits)
+}
+
+/// Formats a code with leading zeros to `digits` characters.
+pub fn format_code(code: u32, digits: u32) -> String {
+    format!("{:0width$}", code, width = digits as usize)
+}
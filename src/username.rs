@@ -0,0 +1,71 @@
+use crate::kdf;
+use crate::generator::{draw_tokens, site_stream, GenError};
+use crate::pattern;
+
+/// Shape of a derived username.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Style {
+    /// Two pronounceable words and two digits, e.g. `tewos-qezam-42`
+    WordWordDigits,
+    /// One pronounceable word and four digits, e.g. `tewosi4821`
+    WordDigits,
+    /// Two pronounceable words run together, e.g. `tewosqezam`
+    WordWord,
+}
+
+impl Style {
+    /// Name used on the command line and in the derivation context.
+    pub fn name(self) -> &'static str {
+        match self {
+            Style::WordWordDigits => "word-word-digits",
+            Style::WordDigits => "word-digits",
+            Style::WordWord => "word-word",
+        }
+    }
+
+    /// Template pattern (see `pattern::parse`) the style draws from.
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Style::WordWordDigits => "cvcvc-cvcvc-nn",
+            Style::WordDigits => "cvcvcvnnnn",
+            Style::WordWord => "cvcvccvcvc",
+        }
+    }
+}
+
+/// Generates a deterministic pseudonymous username for a site.
+///
+/// The context leaves the user field empty and uses `username=<style>`, so each style
+/// gives an independent name and bumping `version` gives a fresh identity.
+pub fn generate_username<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    style: Style,
+    version: u32,
+) -> Result<String, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+
+    let tokens = pattern::parse(style.pattern())?;
+    let mut rng = site_stream(master.into(), &site_id, b"", b"username", style.name().as_bytes(), version)?;
+    draw_tokens(&mut rng, &tokens)
+}
+
+/// Normalizes an email domain (trimmed, lowercased, leading `@` removed) and checks its shape.
+pub fn normalize_domain(domain: &str) -> Result<String, GenError> {
+    let domain = domain.trim().trim_start_matches('@').to_ascii_lowercase();
+    if !domain.contains('.') || domain.starts_with('.') || domain.ends_with('.') || domain.contains(['@', ' ']) {
+        return Err(GenError::InvalidInput("email domain must look like example.org"));
+    }
+    Ok(domain)
+}
+
+/// Builds an email alias at a catch-all domain you control, e.g. `tewos-qezam-42@example.org`.
+pub fn email_alias(username: &str, domain: &str) -> Result<String, GenError> {
+    Ok(format!("{}@{}", username, normalize_domain(domain)?))
+}
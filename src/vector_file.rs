@@ -0,0 +1,169 @@
+//! `pwgen vectors`: test vector files, the JSON form of `pwgen::vectors` (schema in the README).
+
+use pwgen::format::Format;
+use pwgen::generator::AlgoVersion;
+use pwgen::kdf::{KdfParams, KdfScheme};
+use pwgen::vectors::{Inputs, Output, Outputs, Vector, STREAM_BYTES};
+use serde_json::{json, Map, Value};
+
+/// Value of the `schema` member.
+pub const SCHEMA: &str = "pwgen-vectors-v1";
+
+/// The JSON document of a vector file.
+pub fn to_json(vectors: &[Vector]) -> Value {
+    json!({
+        "schema": SCHEMA,
+        "generator": format!("pwgen {}", env!("CARGO_PKG_VERSION")),
+        "stream_bytes": STREAM_BYTES,
+        "vectors": vectors.iter().map(vector_json).collect::<Vec<_>>(),
+    })
+}
+
+fn vector_json(vector: &Vector) -> Value {
+    let expected = &vector.expected;
+    json!({
+        "name": vector.name,
+        "inputs": inputs_json(&vector.inputs),
+        "expected": {
+            "site_id": expected.site_id,
+            "site_key": expected.site_key,
+            "context": expected.context,
+            "stream": expected.stream,
+            "password": expected.password,
+        },
+    })
+}
+
+fn inputs_json(inputs: &Inputs) -> Value {
+    let kdf = match inputs.kdf {
+        KdfScheme::Argon2id(params) => {
+            json!({ "algorithm": "argon2id", "m": params.mem_kib, "t": params.t_cost, "p": params.p_cost })
+        }
+        KdfScheme::Pbkdf2Sha256 { iterations } => json!({ "algorithm": "pbkdf2-sha256", "iterations": iterations }),
+    };
+    let output = match &inputs.output {
+        Output::Policy(encoded) => json!({ "type": "policy", "policy": encoded }),
+        Output::Pattern(pattern) => json!({ "type": "pattern", "pattern": pattern }),
+        Output::Token(format, bytes) => json!({ "type": "token", "format": format.name(), "bytes": bytes }),
+    };
+    json!({
+        "master": inputs.master,
+        "site": inputs.site,
+        "username": inputs.username,
+        "version": inputs.version,
+        "algo_version": inputs.algo.number(),
+        "kdf": kdf,
+        "output": output,
+    })
+}
+
+/// Renders a vector file, pretty-printed.
+pub fn render(vectors: &[Vector]) -> String {
+    let mut text = serde_json::to_string_pretty(&to_json(vectors)).expect("JSON values serialize");
+    text.push('\n');
+    text
+}
+
+/// Parses a vector file, possibly written by another implementation. Expected outputs it does
+/// not record are left empty; each vector must record at least the password.
+pub fn parse(text: &str) -> Result<Vec<Vector>, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let root = root.as_object().ok_or("not a JSON object")?;
+    match root.get("schema").and_then(Value::as_str) {
+        Some(SCHEMA) => {}
+        Some(other) => return Err(format!("unsupported schema '{}' (expected {})", other, SCHEMA)),
+        None => return Err("missing schema".to_string()),
+    }
+    let vectors = root.get("vectors").and_then(Value::as_array).ok_or("missing vectors array")?;
+    vectors
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| parse_vector(vector).map_err(|e| format!("vector #{}: {}", i + 1, e)))
+        .collect()
+}
+
+fn parse_vector(vector: &Value) -> Result<Vector, String> {
+    let vector = vector.as_object().ok_or("not an object")?;
+    let inputs = vector.get("inputs").and_then(Value::as_object).ok_or("missing inputs")?;
+    let expected = vector.get("expected").and_then(Value::as_object).ok_or("missing expected")?;
+    let optional = |key: &str| match expected.get(key) {
+        Some(_) => string(expected, key),
+        None => Ok(String::new()),
+    };
+    Ok(Vector {
+        name: string(vector, "name")?,
+        inputs: parse_inputs(inputs)?,
+        expected: Outputs {
+            site_id: optional("site_id")?,
+            site_key: optional("site_key")?.to_ascii_lowercase(),
+            context: optional("context")?,
+            stream: optional("stream")?.to_ascii_lowercase(),
+            password: string(expected, "password")?,
+        },
+    })
+}
+
+fn parse_inputs(inputs: &Map<String, Value>) -> Result<Inputs, String> {
+    let kdf = inputs.get("kdf").and_then(Value::as_object).ok_or("missing inputs.kdf")?;
+    let kdf = match string(kdf, "algorithm")?.as_str() {
+        "argon2id" => KdfScheme::Argon2id(KdfParams {
+            mem_kib: number(kdf, "m")?,
+            t_cost: number(kdf, "t")?,
+            p_cost: number(kdf, "p")?,
+        }),
+        "pbkdf2-sha256" => KdfScheme::Pbkdf2Sha256 { iterations: number(kdf, "iterations")? },
+        other => return Err(format!("unknown kdf algorithm '{}'", other)),
+    };
+    let output = inputs.get("output").and_then(Value::as_object).ok_or("missing inputs.output")?;
+    let output = match string(output, "type")?.as_str() {
+        "policy" => Output::Policy(string(output, "policy")?),
+        "pattern" => Output::Pattern(string(output, "pattern")?),
+        "token" => {
+            let format = match string(output, "format")?.as_str() {
+                "hex" => Format::Hex,
+                "base64url" => Format::Base64Url,
+                "base58" => Format::Base58,
+                other => return Err(format!("unknown token format '{}'", other)),
+            };
+            Output::Token(format, number(output, "bytes")? as usize)
+        }
+        other => return Err(format!("unknown output type '{}'", other)),
+    };
+    let algo = number(inputs, "algo_version")?;
+    Ok(Inputs {
+        master: string(inputs, "master")?,
+        site: string(inputs, "site")?,
+        username: string(inputs, "username")?,
+        version: number(inputs, "version")?,
+        kdf,
+        algo: AlgoVersion::from_number(algo).ok_or_else(|| format!("unknown algo_version {}", algo))?,
+        output,
+    })
+}
+
+fn string(object: &Map<String, Value>, key: &str) -> Result<String, String> {
+    object.get(key).and_then(Value::as_str).map(str::to_string).ok_or_else(|| format!("{} must be a string", key))
+}
+
+fn number(object: &Map<String, Value>, key: &str) -> Result<u32, String> {
+    object
+        .get(key)
+        .and_then(Value::as_u64)
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or_else(|| format!("{} must be an integer from 0 to {}", key, u32::MAX))
+}
+
+/// The outputs a vector records that differ from `actual`, as (step, expected, actual).
+pub fn diff<'a>(expected: &'a Outputs, actual: &'a Outputs) -> Vec<(&'static str, &'a str, &'a str)> {
+    [
+        ("site_id", &expected.site_id, &actual.site_id),
+        ("site_key", &expected.site_key, &actual.site_key),
+        ("context", &expected.context, &actual.context),
+        ("stream", &expected.stream, &actual.stream),
+        ("password", &expected.password, &actual.password),
+    ]
+    .into_iter()
+    .filter(|(_, expected, actual)| !expected.is_empty() && expected != actual)
+    .map(|(step, expected, actual)| (step, expected.as_str(), actual.as_str()))
+    .collect()
+}
@@ -0,0 +1,233 @@
+//! Test vectors for independent implementations (mobile apps, browser extensions): a matrix of
+//! inputs with what this crate derives from them at each step, so a port that differs can see
+//! where it departs: the site id after normalization, the KDF's site key, the PRNG context and
+//! the start of its stream, and the password.
+
+use zeroize::Zeroizing;
+
+use crate::format::Format;
+use crate::generator::{self, AlgoVersion, GenError};
+use crate::kdf::{self, Kdf, KdfError, KdfParams, KdfProfile, KdfScheme, KDF_OUT_LEN};
+use crate::policy;
+
+/// Bytes of the PRNG stream recorded in each vector.
+pub const STREAM_BYTES: usize = 64;
+
+/// What a vector generates.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Output {
+    /// `generator::generate_password` with an encoded policy
+    Policy(String),
+    /// `generator::generate_from_pattern`
+    Pattern(String),
+    /// `generator::generate_token`
+    Token(Format, usize),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Inputs {
+    pub master: String,
+    pub site: String,
+    pub username: String,
+    pub version: u32,
+    pub kdf: KdfScheme,
+    pub algo: AlgoVersion,
+    pub output: Output,
+}
+
+/// What this crate derives from `Inputs`; bytes are lowercase hex.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Outputs {
+    /// Site after trimming and lowercasing, as passed to the KDF
+    pub site_id: String,
+    /// KDF output
+    pub site_key: String,
+    /// PRNG info context
+    pub context: String,
+    /// First `STREAM_BYTES` bytes of the PRNG
+    pub stream: String,
+    /// Password or token
+    pub password: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Vector {
+    pub name: String,
+    pub inputs: Inputs,
+    pub expected: Outputs,
+}
+
+/// A KDF whose key was derived already, so the generators do not run it again.
+struct Derived<'a> {
+    key: [u8; KDF_OUT_LEN],
+    kdf: &'a KdfScheme,
+}
+
+impl Kdf for Derived<'_> {
+    fn derive(&self, _master: &[u8], _site: &str) -> Result<[u8; KDF_OUT_LEN], KdfError> {
+        Ok(self.key)
+    }
+
+    fn context_id(&self) -> String {
+        self.kdf.context_id()
+    }
+}
+
+/// Derives the outputs of `inputs`.
+pub fn compute(inputs: &Inputs) -> Result<Outputs, GenError> {
+    let site_id = inputs.site.trim().to_ascii_lowercase();
+    let key = Zeroizing::new(inputs.kdf.derive(inputs.master.as_bytes(), &site_id)?);
+
+    let (label, params): (&[u8], String) = match &inputs.output {
+        Output::Policy(encoded) => (b"policy", policy::encode(&policy::validate(&policy::decode(encoded)?)?)),
+        Output::Pattern(pattern) => (b"pattern", pattern.clone()),
+        Output::Token(format, bytes) => (b"format", crate::format::params(*format, *bytes)),
+    };
+    let context = generator::context(
+        inputs.algo,
+        &inputs.kdf,
+        &site_id,
+        inputs.username.as_bytes(),
+        label,
+        params.as_bytes(),
+        inputs.version,
+    );
+    let mut stream = Zeroizing::new([0u8; STREAM_BYTES]);
+    inputs.algo.stream(&key, &context)?.fill(&mut *stream)?;
+
+    let master = kdf::MasterInput::new(inputs.master.as_str(), Derived { key: *key, kdf: &inputs.kdf }).with_algo(inputs.algo);
+    let username = Some(inputs.username.as_str()).filter(|u| !u.is_empty());
+    let password = match &inputs.output {
+        Output::Policy(encoded) => {
+            generator::generate_password(master, &inputs.site, username, &policy::decode(encoded)?, inputs.version)?
+        }
+        Output::Pattern(pattern) => generator::generate_from_pattern(master, &inputs.site, username, pattern, inputs.version)?,
+        Output::Token(format, bytes) => {
+            generator::generate_token(master, &inputs.site, username, *format, *bytes, inputs.version)?
+        }
+    };
+
+    Ok(Outputs {
+        site_id,
+        site_key: Format::Hex.encode(&*key),
+        context: String::from_utf8_lossy(&context).into_owned(),
+        stream: Format::Hex.encode(&*stream),
+        password,
+    })
+}
+
+/// The built-in input matrix: a base case and variations of one or two inputs each.
+pub fn matrix() -> Vec<(&'static str, Inputs)> {
+    let fast = KdfScheme::Argon2id(KdfProfile::Fast.params());
+    let base = Inputs {
+        master: "correct horse battery staple".to_string(),
+        site: "example.com".to_string(),
+        username: String::new(),
+        version: 1,
+        kdf: fast,
+        algo: AlgoVersion::V1,
+        output: Output::Policy(policy::encode(&policy::default_policy())),
+    };
+    let with = |change: &dyn Fn(&mut Inputs)| {
+        let mut inputs = base.clone();
+        change(&mut inputs);
+        inputs
+    };
+    let strict = policy::Policy {
+        min: 20,
+        max: 20,
+        allow: [true, true, true, true],
+        force: [true, true, true, true],
+        exclude: b"0O1lI".to_vec(),
+        no_ambiguous: false,
+        layout_safe: false,
+        min_counts: [1, 1, 2, 2],
+        max_counts: [None, None, None, Some(4)],
+        max_consecutive: Some(2),
+        max_sequential: None,
+        letter_first: false,
+        letter_last: false,
+        no_words: false,
+        no_site: false,
+        classes: Vec::new(),
+        allow_extended: [false; 4],
+        force_extended: [false; 4],
+    };
+    let digits = policy::Policy { min: 6, max: 6, allow: [false, false, true, false], ..policy::default_policy() };
+    let unicode = policy::Policy {
+        min: 16,
+        max: 16,
+        allow: [true, false, true, false],
+        allow_extended: [true, true, true, false],
+        force_extended: [true, true, true, false],
+        ..policy::default_policy()
+    };
+    let emoji = policy::Policy {
+        min: 6,
+        max: 6,
+        allow: [false; 4],
+        allow_extended: [false, false, false, true],
+        ..policy::default_policy()
+    };
+
+    vec![
+        ("default", base.clone()),
+        ("site-normalized", with(&|i| i.site = "  Example.COM ".to_string())),
+        ("site-unicode", with(&|i| i.site = "bücher.example".to_string())),
+        ("master-unicode", with(&|i| i.master = "pässwörd 🔑".to_string())),
+        ("username", with(&|i| i.username = "alice@example.com".to_string())),
+        ("version-2", with(&|i| i.version = 2)),
+        ("version-max", with(&|i| i.version = u32::MAX)),
+        ("policy-strict", with(&|i| i.output = Output::Policy(policy::encode(&strict)))),
+        ("policy-pin", with(&|i| i.output = Output::Policy(policy::encode(&digits)))),
+        ("policy-unicode", with(&|i| i.output = Output::Policy(policy::encode(&unicode)))),
+        ("policy-emoji", with(&|i| i.output = Output::Policy(policy::encode(&emoji)))),
+        ("pattern", with(&|i| i.output = Output::Pattern("Cvccvc-nnnn-Aoa".to_string()))),
+        ("token-hex", with(&|i| i.output = Output::Token(Format::Hex, 16))),
+        ("token-base64url", with(&|i| i.output = Output::Token(Format::Base64Url, 24))),
+        ("token-base58", with(&|i| i.output = Output::Token(Format::Base58, 32))),
+        ("algo-v2", with(&|i| i.algo = AlgoVersion::V2)),
+        (
+            "algo-v2-delimiters",
+            with(&|i| {
+                i.algo = AlgoVersion::V2;
+                i.username = "a|user=b".to_string();
+            }),
+        ),
+        ("algo-v3", with(&|i| i.algo = AlgoVersion::V3)),
+        #[cfg(feature = "blake3")]
+        ("algo-v4", with(&|i| i.algo = AlgoVersion::V4)),
+        ("algo-v5", with(&|i| i.algo = AlgoVersion::V5)),
+        (
+            "algo-v5-token",
+            with(&|i| {
+                i.algo = AlgoVersion::V5;
+                i.output = Output::Token(Format::Hex, 100);
+            }),
+        ),
+        (
+            "argon2id-custom",
+            with(&|i| i.kdf = KdfScheme::Argon2id(KdfParams { mem_kib: 4096, t_cost: 1, p_cost: 2 })),
+        ),
+        ("argon2id-balanced", with(&|i| i.kdf = KdfScheme::Argon2id(KdfProfile::Balanced.params()))),
+        ("pbkdf2", with(&|i| i.kdf = KdfScheme::Pbkdf2Sha256 { iterations: 1000 })),
+        (
+            "pbkdf2-algo-v2",
+            with(&|i| {
+                i.kdf = KdfScheme::Pbkdf2Sha256 { iterations: 1000 };
+                i.algo = AlgoVersion::V2;
+            }),
+        ),
+    ]
+}
+
+/// The built-in matrix with this crate's outputs.
+pub fn export() -> Result<Vec<Vector>, GenError> {
+    matrix()
+        .into_iter()
+        .map(|(name, inputs)| {
+            let expected = compute(&inputs)?;
+            Ok(Vector { name: name.to_string(), inputs, expected })
+        })
+        .collect()
+}
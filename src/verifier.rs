@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand_core::OsRng;
+use thiserror::Error;
+
+use crate::config;
+use crate::kdf;
+
+/// File name of the verifier, next to the config file.
+pub const VERIFIER_FILE: &str = "verifier";
+
+#[derive(Error, Debug)]
+pub enum VerifierError {
+    #[error("failed to read verifier {path}: {message}")]
+    Read { path: String, message: String },
+
+    #[error("failed to write verifier {path}: {message}")]
+    Write { path: String, message: String },
+
+    #[error("verifier {0} already exists (use --force to replace it)")]
+    Exists(String),
+
+    #[error("invalid verifier: {0}")]
+    Invalid(String),
+
+    #[error("argon2 error: {0}")]
+    Hash(String),
+}
+
+/// Returns the verifier location: `verifier` in the directory of `config::default_path()`.
+pub fn default_path() -> Option<PathBuf> {
+    path_for(None)
+}
+
+/// Returns the verifier location of a persona: `verifier-<name>` next to `default_path()`.
+pub fn path_for(persona: Option<&str>) -> Option<PathBuf> {
+    let config = config::default_path()?;
+    let file = match persona {
+        Some(name) => format!("{}-{}", VERIFIER_FILE, name),
+        None => VERIFIER_FILE.to_string(),
+    };
+    Some(config.parent().unwrap_or(Path::new("")).join(file))
+}
+
+/// Hashes the master secret into a verifier: an Argon2id PHC string with a random salt and
+/// the `fast` profile's costs (e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`).
+///
+/// The verifier only answers whether a master matches; it is never used for derivation, but
+/// anyone holding it can test master guesses offline at the cost of one Argon2id run each.
+pub fn create(master: &str) -> Result<String, VerifierError> {
+    let params = kdf::KdfProfile::Fast.params();
+    let params = argon2::Params::new(params.mem_kib, params.t_cost, params.p_cost, None)
+        .map_err(|e| VerifierError::Hash(e.to_string()))?;
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2
+        .hash_password(master.as_bytes(), &salt)
+        .map_err(|e| VerifierError::Hash(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+/// Checks `master` against a verifier made by `create`, with the costs recorded in it.
+pub fn verify(verifier: &str, master: &str) -> Result<bool, VerifierError> {
+    let hash = PasswordHash::new(verifier.trim()).map_err(|e| VerifierError::Invalid(e.to_string()))?;
+    if hash.algorithm != argon2::Algorithm::Argon2id.ident() {
+        return Err(VerifierError::Invalid(format!("unsupported algorithm {}", hash.algorithm)));
+    }
+    match Argon2::default().verify_password(master.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(VerifierError::Invalid(e.to_string())),
+    }
+}
+
+/// Reads the verifier at `path`; a missing file yields `None`.
+pub fn load(path: &Path) -> Result<Option<String>, VerifierError> {
+    match std::fs::read_to_string(path) {
+        Ok(verifier) => Ok(Some(verifier)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(VerifierError::Read { path: path.display().to_string(), message: e.to_string() }),
+    }
+}
+
+/// Writes `verifier` to `path`, creating its directory. An existing file is only replaced
+/// when `overwrite` is set.
+pub fn save(path: &Path, verifier: &str, overwrite: bool) -> Result<(), VerifierError> {
+    let display = path.display().to_string();
+    if !overwrite && path.exists() {
+        return Err(VerifierError::Exists(display));
+    }
+    let write_error = |e: std::io::Error| VerifierError::Write { path: display.clone(), message: e.to_string() };
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(write_error)?;
+    }
+    std::fs::write(path, format!("{}\n", verifier)).map_err(write_error)
+}
@@ -0,0 +1,57 @@
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::format;
+use crate::kdf;
+use crate::generator::{site_stream, GenError};
+
+/// Deterministic WireGuard (X25519) keypair.
+pub struct Keypair {
+    /// Clamped private key; zeroized on drop
+    pub private_key: Zeroizing<[u8; 32]>,
+    pub public_key: [u8; 32],
+}
+
+impl Keypair {
+    /// Private key in WireGuard's base64 format (as printed by `wg genkey`).
+    pub fn private_key_base64(&self) -> Zeroizing<String> {
+        Zeroizing::new(format::encode_base64(&self.private_key[..]))
+    }
+
+    /// Public key in WireGuard's base64 format (as printed by `wg pubkey`).
+    pub fn public_key_base64(&self) -> String {
+        format::encode_base64(&self.public_key)
+    }
+}
+
+/// Derives a WireGuard keypair for a site.
+///
+/// The private key is 32 stream bytes over the `wireguard=x25519` context, clamped as
+/// X25519 requires; the public key is its X25519 base point multiple.
+pub fn derive_keypair<'m, K: kdf::Kdf>(
+    master: impl Into<kdf::MasterInput<'m, K>>,
+    site: &str,
+    username: Option<&str>,
+    version: u32,
+) -> Result<Keypair, GenError> {
+    // Normalize inputs
+    let site_id = site.trim().to_ascii_lowercase();
+    let username_bytes = username.unwrap_or("").as_bytes();
+
+    let mut rng = site_stream(master.into(), &site_id, username_bytes, b"wireguard", b"x25519", version)?;
+
+    let mut private_key = Zeroizing::new([0u8; 32]);
+    rng.fill(&mut private_key[..])?;
+    private_key[0] &= 248;
+    private_key[31] &= 127;
+    private_key[31] |= 64;
+
+    // StaticSecret takes the key by value and zeroizes its own copy on drop; the copy made
+    // for the call is wiped here
+    let mut bytes = *private_key;
+    let secret = StaticSecret::from(bytes);
+    bytes.zeroize();
+    let public_key = PublicKey::from(&secret).to_bytes();
+
+    Ok(Keypair { private_key, public_key })
+}
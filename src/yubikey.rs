@@ -0,0 +1,109 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::format;
+use crate::kdf;
+
+/// Environment variable naming the `ykchalresp` program to run instead of the one on `PATH`.
+pub const YKCHALRESP_ENV: &str = "PWGEN_YKCHALRESP";
+
+/// Length of an HMAC-SHA1 response.
+pub const RESPONSE_LEN: usize = 20;
+
+/// Length of the challenge sent for a site.
+pub const CHALLENGE_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum YubikeyError {
+    #[error("YubiKey slot must be 1 or 2")]
+    InvalidSlot,
+
+    #[error("failed to run {program}: {message} (install yubikey-personalization or set PWGEN_YKCHALRESP)")]
+    Spawn { program: String, message: String },
+
+    #[error("YubiKey challenge-response failed: {0}")]
+    Device(String),
+
+    #[error("unexpected YubiKey response (expected {RESPONSE_LEN} bytes of hex)")]
+    InvalidResponse,
+}
+
+/// Challenge sent to the YubiKey for a site: `SHA256(b"pwgen-yubikey-v1:" || site_id)`.
+pub fn challenge(site: &str) -> [u8; CHALLENGE_LEN] {
+    let site_id = site.trim().to_ascii_lowercase();
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-yubikey-v1:");
+    hasher.update(site_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Hashes a response for `kdf::MasterInput::with_yubikey`:
+/// `SHA256(b"pwgen-yubikey-response-v1:" || response)`.
+pub fn response_digest(response: &[u8; RESPONSE_LEN]) -> [u8; kdf::KEYFILE_DIGEST_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pwgen-yubikey-response-v1:");
+    hasher.update(response);
+    hasher.finalize().into()
+}
+
+/// Sends `challenge` to the HMAC-SHA1 challenge-response `slot` (1 or 2) of the attached
+/// YubiKey, using `ykchalresp` (or `$PWGEN_YKCHALRESP`).
+///
+/// The slot must be programmed for HMAC-SHA1 in variable-length mode, e.g. with
+/// `ykman otp chalresp --generate 2`. Blocks until the key is touched if it requires it.
+pub fn challenge_response(slot: u8, challenge: &[u8]) -> Result<Zeroizing<[u8; RESPONSE_LEN]>, YubikeyError> {
+    let program = std::env::var_os(YKCHALRESP_ENV)
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "ykchalresp".into());
+    challenge_response_with(Path::new(&program), slot, challenge)
+}
+
+/// Like `challenge_response`, running `program` with `ykchalresp`'s arguments
+/// (`-<slot> -x <hex challenge>`, printing the hex response).
+pub fn challenge_response_with(
+    program: &Path,
+    slot: u8,
+    challenge: &[u8],
+) -> Result<Zeroizing<[u8; RESPONSE_LEN]>, YubikeyError> {
+    if !(1..=2).contains(&slot) {
+        return Err(YubikeyError::InvalidSlot);
+    }
+    let output = Command::new(program)
+        .arg(format!("-{}", slot))
+        .arg("-x")
+        .arg(format::Format::Hex.encode(challenge))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| YubikeyError::Spawn {
+            program: program.display().to_string(),
+            message: e.to_string(),
+        })?;
+    let mut stdout = output.stdout;
+    if !output.status.success() {
+        stdout.zeroize();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(YubikeyError::Device(stderr.trim().to_string()));
+    }
+
+    let response = parse_hex_response(&stdout);
+    stdout.zeroize();
+    response.ok_or(YubikeyError::InvalidResponse)
+}
+
+/// Decodes `ykchalresp -x` output: 40 hex digits and a trailing newline.
+fn parse_hex_response(output: &[u8]) -> Option<Zeroizing<[u8; RESPONSE_LEN]>> {
+    let hex = output.trim_ascii();
+    if hex.len() != RESPONSE_LEN * 2 {
+        return None;
+    }
+    let mut response = Zeroizing::new([0u8; RESPONSE_LEN]);
+    for (byte, pair) in response.iter_mut().zip(hex.chunks(2)) {
+        let digits = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    Some(response)
+}
@@ -1,5 +1,6 @@
-use pwgen::{generator, policy};
+use pwgen::{generator, policy, prng};
 
+#[allow(clippy::too_many_arguments)]
 fn gen(
     master: &str,
     site: &str,
@@ -10,7 +11,7 @@ fn gen(
     force: [bool; 4],
     version: u32,
 ) -> String {
-    let pol = policy::Policy { min, max, allow, force };
+    let pol = policy::Policy { min, max, allow, force, ..Default::default() };
     let pol = policy::validate(&pol).unwrap();
     generator::generate_password(master, site, username, &pol, version).unwrap()
 }
@@ -35,7 +36,7 @@ fn length_bounds_and_fixed_length() {
 fn allowed_alphabet_only() {
     let allow = [true, false, true, false]; // lower + digit
     let force = [false, false, false, false];
-    let pol = policy::validate(&policy::Policy { min: 16, max: 16, allow, force }).unwrap();
+    let pol = policy::validate(&policy::Policy { min: 16, max: 16, allow, force, ..Default::default() }).unwrap();
     let s = generator::generate_password("m", "ex", None, &pol, 1).unwrap();
 
     let alphabet = policy::allowed_alphabet(&pol);
@@ -48,7 +49,7 @@ fn allowed_alphabet_only() {
 fn forced_presence() {
     let allow = [true, true, true, true];
     let force = [true, false, true, false]; // require lower and digit
-    let pol = policy::validate(&policy::Policy { min: 8, max: 8, allow, force }).unwrap();
+    let pol = policy::validate(&policy::Policy { min: 8, max: 8, allow, force, ..Default::default() }).unwrap();
     let s = generator::generate_password("m", "ex", None, &pol, 1).unwrap();
 
     let sets = policy::forced_sets(&pol);
@@ -107,9 +108,119 @@ fn edge_cases() {
     assert!(s.chars().all(|c| "!\"#$%&'()*+,-./:;<=>?@[\\]^_{|}~".contains(c)));
 
     // very small L with forced set exactly fitting
-    let p = policy::validate(&policy::Policy { min: 2, max: 2, allow: [true, true, false, false], force: [true, true, false, false] }).unwrap();
+    let p = policy::validate(&policy::Policy { min: 2, max: 2, allow: [true, true, false, false], force: [true, true, false, false], ..Default::default() }).unwrap();
     let s = generator::generate_password("m", "ex", None, &p, 1).unwrap();
     assert_eq!(s.len(), 2);
     assert!(s.chars().any(|c| ("abcdefghijklmnopqrstuvwxyz").contains(c)));
     assert!(s.chars().any(|c| ("ABCDEFGHIJKLMNOPQRSTUVWXYZ").contains(c)));
 }
+
+#[test]
+fn forks_are_isolated() {
+    let draw = |rng: &mut prng::HkdfStream| {
+        let mut out = [0u8; 48];
+        rng.fill(&mut out).unwrap();
+        out
+    };
+    let mut parent = prng::from_key_and_context(&[7u8; 32], b"pwgen-v2|site=4:test").unwrap();
+    let before = draw(&mut parent.fork(b"length"));
+    let parent_bytes = draw(&mut parent);
+    // Reading the parent does not move its forks
+    assert_eq!(draw(&mut parent.fork(b"length")), before);
+    assert_ne!(draw(&mut parent.fork(b"forced")), before);
+    assert_ne!(parent_bytes, before);
+    assert_ne!(draw(&mut parent.fork(b"length").fork(b"length")), before);
+}
+
+#[test]
+fn fill_matches_bytewise_reads() {
+    let mut bytewise = prng::from_key_and_context(&[3u8; 32], b"ctx").unwrap();
+    let mut blockwise = prng::from_key_and_context(&[3u8; 32], b"ctx").unwrap();
+    let expected: Vec<u8> = (0..200).map(|_| bytewise.next_u8().unwrap()).collect();
+    let mut got = vec![0u8; 200];
+    // Start mid-block, then cross several block boundaries
+    blockwise.fill(&mut got[..5]).unwrap();
+    blockwise.fill(&mut got[5..101]).unwrap();
+    blockwise.fill(&mut got[101..]).unwrap();
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn next_index_u32_covers_large_ranges() {
+    let mut rng = prng::from_key_and_context(&[5u8; 32], b"ctx").unwrap();
+    let draws: Vec<u32> = (0..1000).map(|_| rng.next_index_u32(7776).unwrap()).collect();
+    assert!(draws.iter().all(|&i| i < 7776));
+    assert!(draws.iter().filter(|&&i| i >= 256).count() > 900);
+    assert_eq!(rng.next_index_u32(1).unwrap(), 0);
+}
+
+#[test]
+fn stream_stops_at_the_hkdf_limit() {
+    let mut rng = prng::from_key_and_context(&[9u8; 32], b"ctx").unwrap();
+    assert_eq!(rng.remaining(), prng::PRNG_MAX_BYTES);
+    let mut out = vec![0u8; prng::PRNG_MAX_BYTES - 1];
+    rng.fill(&mut out).unwrap();
+    assert_eq!(rng.remaining(), 1);
+    // Too long a fill consumes nothing
+    assert!(matches!(rng.fill(&mut [0u8; 2]), Err(prng::PrngError::Exhausted)));
+    rng.next_u8().unwrap();
+    assert_eq!(rng.remaining(), 0);
+    assert!(matches!(rng.next_u8(), Err(prng::PrngError::Exhausted)));
+}
+
+#[test]
+fn sha512_stream_stops_at_255_blocks() {
+    let mut rng = prng::sha512_from_key_and_context(&[9u8; 32], b"ctx").unwrap();
+    assert_eq!(rng.remaining(), 255 * prng::PRNG_BLOCK_SHA512);
+    let mut out = vec![0u8; 255 * prng::PRNG_BLOCK_SHA512];
+    rng.fill(&mut out).unwrap();
+    assert!(matches!(rng.next_u8(), Err(prng::PrngError::Exhausted)));
+    // Independent of the SHA-256 stream over the same key and context
+    let mut sha256 = [0u8; 64];
+    prng::from_key_and_context(&[9u8; 32], b"ctx").unwrap().fill(&mut sha256).unwrap();
+    assert_ne!(&out[..64], &sha256[..]);
+}
+
+#[test]
+fn chacha_fill_matches_bytewise_reads() {
+    let mut bytewise = prng::chacha_from_key_and_context(&[3u8; 32], b"ctx").unwrap();
+    let mut blockwise = prng::chacha_from_key_and_context(&[3u8; 32], b"ctx").unwrap();
+    let expected: Vec<u8> = (0..400).map(|_| bytewise.next_u8().unwrap()).collect();
+    let mut got = vec![0u8; 400];
+    // Mid-block, then the whole-block path, then a tail
+    blockwise.fill(&mut got[..5]).unwrap();
+    blockwise.fill(&mut got[5..300]).unwrap();
+    blockwise.fill(&mut got[300..]).unwrap();
+    assert_eq!(got, expected);
+    let fork = |rng: &prng::ChaChaStream| {
+        let mut out = [0u8; 32];
+        rng.fork(b"label").fill(&mut out).unwrap();
+        out
+    };
+    assert_eq!(fork(&blockwise), fork(&bytewise));
+    assert_ne!(&fork(&blockwise)[..], &expected[..32]);
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn blake3_fill_matches_bytewise_reads() {
+    let mut bytewise = prng::blake3_from_key_and_context(&[3u8; 32], b"ctx");
+    let mut blockwise = prng::blake3_from_key_and_context(&[3u8; 32], b"ctx");
+    let expected: Vec<u8> = (0..400).map(|_| bytewise.next_u8().unwrap()).collect();
+    let mut got = vec![0u8; 400];
+    blockwise.fill(&mut got[..5]).unwrap();
+    blockwise.fill(&mut got[5..300]).unwrap();
+    blockwise.fill(&mut got[300..]).unwrap();
+    assert_eq!(got, expected);
+    let fork = |rng: &prng::Blake3Stream| {
+        let mut out = [0u8; 32];
+        rng.fork(b"label").fill(&mut out).unwrap();
+        out
+    };
+    assert_eq!(fork(&blockwise), fork(&bytewise));
+    assert_ne!(&fork(&blockwise)[..], &expected[..32]);
+    // The HKDF stream of the same key and context is unrelated
+    let mut hkdf = [0u8; 32];
+    prng::from_key_and_context(&[3u8; 32], b"ctx").unwrap().fill(&mut hkdf).unwrap();
+    assert_ne!(&hkdf[..], &expected[..32]);
+}
@@ -1,7 +1,7 @@
-use pwgen::{generator, policy, kdf, prng};
+use pwgen::{answer, fingerprint, format, generator, policy, kdf, prng, recovery, totp, username, wireguard, yubikey};
 
-/// Golden test vectors - frozen input→output pairs to guard against accidental changes.
-/// These tests ensure the implementation remains deterministic and consistent.
+// Golden test vectors - frozen input→output pairs to guard against accidental changes.
+// These tests ensure the implementation remains deterministic and consistent.
 
 /// Golden test vectors for KDF key derivation
 #[test]
@@ -80,6 +80,7 @@ fn policy_encoding_golden_vectors() {
         max: 12,
         allow: [true, true, false, true],
         force: [true, false, false, true],
+        ..Default::default()
     };
     let encoded = policy::encode(&pol);
     assert_eq!(encoded, "min=8;max=12;allow=lower,upper,symbol;force=lower,symbol", 
@@ -91,6 +92,7 @@ fn policy_encoding_golden_vectors() {
         max: 10,
         allow: [false, false, true, false],
         force: [false, false, true, false],
+        ..Default::default()
     };
     let encoded = policy::encode(&pol);
     assert_eq!(encoded, "min=10;max=10;allow=digit;force=digit", 
@@ -106,6 +108,7 @@ fn password_generation_golden_vectors() {
         max: 12,
         allow: [true, true, true, true],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let pol = policy::validate(&pol).unwrap();
     
@@ -130,6 +133,7 @@ fn password_generation_golden_vectors() {
         max: 8,
         allow: [true, true, true, true],
         force: [true, true, false, false],
+        ..Default::default()
     };
     let pol_forced = policy::validate(&pol_forced).unwrap();
     
@@ -142,6 +146,7 @@ fn password_generation_golden_vectors() {
         max: 16,
         allow: [true, true, true, true],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let pol_var = policy::validate(&pol_var).unwrap();
     
@@ -154,6 +159,7 @@ fn password_generation_golden_vectors() {
         max: 10,
         allow: [false, false, true, false],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let pol_single = policy::validate(&pol_single).unwrap();
     
@@ -170,6 +176,7 @@ fn edge_case_golden_vectors() {
         max: 2,
         allow: [true, true, false, false],
         force: [true, true, false, false],
+        ..Default::default()
     };
     let pol = policy::validate(&pol).unwrap();
     
@@ -182,6 +189,7 @@ fn edge_case_golden_vectors() {
         max: 8,
         allow: [false, false, false, true],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let pol = policy::validate(&pol).unwrap();
     
@@ -194,6 +202,7 @@ fn edge_case_golden_vectors() {
         max: 8,
         allow: [true, true, true, true],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let pol = policy::validate(&pol).unwrap();
     
@@ -212,6 +221,7 @@ fn character_set_golden_vectors() {
         max: 8,
         allow: [true, true, true, true],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let alphabet = policy::allowed_alphabet(&pol);
     let alphabet_str = String::from_utf8(alphabet).unwrap();
@@ -232,6 +242,7 @@ fn character_set_golden_vectors() {
         max: 8,
         allow: [true, false, true, false],
         force: [false, false, false, false],
+        ..Default::default()
     };
     let alphabet = policy::allowed_alphabet(&pol);
     let alphabet_str = String::from_utf8(alphabet).unwrap();
@@ -240,3 +251,199 @@ fn character_set_golden_vectors() {
     assert!(alphabet_str.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()), 
             "Should contain only lowercase letters and digits");
 }
+
+/// Golden test vectors for template pattern generation
+#[test]
+fn pattern_golden_vectors() {
+    let password = generator::generate_from_pattern("master123", "example.com", None, "Cvcc-nnnn-Cvcc", 1).unwrap();
+    assert_eq!(password, "Megm-1845-Xext", "Pattern golden vector 1 failed");
+
+    let password = generator::generate_from_pattern("master123", "example.com", None, "Cvcc-nnnn-Cvcc", 2).unwrap();
+    assert_eq!(password, "Yebh-4153-Xegc", "Pattern golden vector 2 (version) failed");
+}
+
+/// Golden test vectors for token output formats
+#[test]
+fn token_golden_vectors() {
+    let token = generator::generate_token("master123", "example.com", None, format::Format::Hex, 32, 1).unwrap();
+    assert_eq!(
+        token, "bf7a187725d2cd93527816955d5708cbf40f0d09d37179f27cc49c55c84b27ac",
+        "Hex golden vector 1 failed"
+    );
+
+    let token = generator::generate_token("master123", "example.com", None, format::Format::Hex, 16, 2).unwrap();
+    assert_eq!(token, "0a9b34a632ce187b207ea433e81db388", "Hex golden vector 2 (bytes, version) failed");
+
+    let token = generator::generate_token("master123", "example.com", None, format::Format::Base64Url, 32, 1).unwrap();
+    assert_eq!(token, "Ucp33IXEjjvF2tMMawU7juVAAJWY8g_NDX1SEkq02c4", "Base64url golden vector failed");
+
+    let token = generator::generate_token("master123", "example.com", None, format::Format::Base58, 32, 1).unwrap();
+    assert_eq!(token, "CuD1xdT2s6Cjpa6T7foHbRfNu875CBsVKZvE4teLCcA6", "Base58 golden vector failed");
+}
+
+/// Golden test vectors for raw key material
+#[test]
+fn bytes_golden_vectors() {
+    let key = generator::generate_bytes("master123", "example.com", None, 16, 1).unwrap();
+    let expected: [u8; 16] = [46, 242, 195, 163, 229, 121, 46, 25, 126, 96, 218, 31, 220, 128, 2, 97];
+    assert_eq!(key[..], expected, "Bytes golden vector 1 failed");
+}
+
+/// Golden test vectors for recovery codes
+#[test]
+fn recovery_golden_vectors() {
+    let codes = recovery::generate_codes("master123", "example.com", None, 4, 1).unwrap();
+    assert_eq!(codes, ["LWA3-9WAN", "VBTJ-5QFJ", "KN2D-HTBB", "D8HE-5VZK"], "Recovery golden vector failed");
+}
+
+/// Golden test vectors for WireGuard keypairs
+#[test]
+fn wireguard_golden_vectors() {
+    let keypair = wireguard::derive_keypair("master123", "vpn", None, 1).unwrap();
+    assert_eq!(
+        keypair.private_key_base64().as_str(),
+        "cAotwS9VmkKSGBJImr1+sxjzDhf33bcUQmwQCAzZl1E=",
+        "WireGuard private key golden vector failed"
+    );
+    assert_eq!(
+        keypair.public_key_base64(),
+        "T6zUuKpwYgc8mt0XxXLFv+3c0wJ1pKUm2Sm636Llg3I=",
+        "WireGuard public key golden vector failed"
+    );
+}
+
+/// Golden test vectors for TOTP secrets
+#[test]
+fn totp_golden_vectors() {
+    let secret = totp::derive_secret("master123", "example.com", None, 1).unwrap();
+    assert_eq!(
+        totp::secret_base32(&secret).as_str(),
+        "EMYHICMDAN2Z4FTZJACTPF3NYV5RR4ES",
+        "TOTP secret golden vector failed"
+    );
+}
+
+/// Golden test vectors for security-question answers
+#[test]
+fn answer_golden_vectors() {
+    let ans = answer::generate_answer("master123", "bank", None, "mother's maiden name", 1).unwrap();
+    assert_eq!(ans, "tewosi qezamu", "Answer golden vector failed");
+}
+
+/// Golden test vectors for usernames
+#[test]
+fn username_golden_vectors() {
+    let name = username::generate_username("master123", "forum", username::Style::WordWordDigits, 1).unwrap();
+    assert_eq!(name, "bicew-xaxuw-01", "Username golden vector 1 failed");
+
+    let name = username::generate_username("master123", "forum", username::Style::WordDigits, 1).unwrap();
+    assert_eq!(name, "vatuzo8177", "Username golden vector 2 (style) failed");
+}
+
+/// Golden test vectors for the non-default KDF profiles
+#[test]
+fn kdf_profile_golden_vectors() {
+    let key = kdf::derive_site_key_with("password123", "example.com", &kdf::KdfProfile::Fast.params()).unwrap();
+    let expected = [
+        68, 165, 36, 183, 168, 167, 32, 157, 44, 215, 233, 186, 93, 114, 160, 62,
+        43, 46, 58, 51, 242, 61, 155, 34, 41, 211, 3, 240, 63, 83, 170, 219
+    ];
+    assert_eq!(key, expected, "Fast profile golden vector failed");
+
+    let key = kdf::derive_site_key_with("password123", "example.com", &kdf::KdfProfile::Paranoid.params()).unwrap();
+    let expected = [
+        28, 92, 56, 144, 189, 29, 142, 199, 183, 182, 99, 70, 204, 96, 94, 112,
+        84, 160, 115, 158, 88, 152, 86, 162, 218, 231, 253, 163, 206, 135, 30, 193
+    ];
+    assert_eq!(key, expected, "Paranoid profile golden vector failed");
+}
+
+/// Golden test vectors for the PBKDF2 backend
+#[test]
+fn pbkdf2_golden_vectors() {
+    let key = kdf::derive_site_key_pbkdf2("password123", "example.com", kdf::PBKDF2_ITERATIONS).unwrap();
+    let expected = [
+        114, 24, 175, 76, 31, 28, 151, 62, 57, 43, 128, 56, 203, 51, 143, 66,
+        53, 48, 12, 230, 232, 40, 93, 108, 227, 62, 54, 245, 187, 10, 62, 255
+    ];
+    assert_eq!(key, expected, "PBKDF2 golden vector failed");
+}
+
+/// Golden test vectors for algorithm v2
+#[test]
+fn algo_v2_golden_vectors() {
+    let pol = policy::Policy {
+        min: 12,
+        max: 12,
+        allow: [true, true, true, true],
+        force: [false, false, false, false],
+        ..Default::default()
+    };
+    let pol = policy::validate(&pol).unwrap();
+
+    let master = kdf::MasterInput::new("master123", kdf::KdfScheme::default()).with_algo(generator::AlgoVersion::V2);
+    let password = generator::generate_password(master, "example.com", Some("alice"), &pol, 1).unwrap();
+    assert_eq!(password, "@W3,'K8>5_G\\", "Algorithm v2 golden vector 1 failed");
+
+    let codes = recovery::generate_codes(master, "example.com", None, 2, 1).unwrap();
+    assert_eq!(codes, ["7W2F-H4ZX", "WAHL-KNQG"], "Algorithm v2 golden vector 2 (recovery) failed");
+}
+
+/// Golden test vectors for keyfiles
+#[test]
+fn keyfile_golden_vectors() {
+    let digest = kdf::keyfile_digest(b"keyfile contents");
+    let expected = [
+        206, 37, 56, 133, 107, 78, 246, 61, 147, 255, 176, 72, 1, 33, 185, 106,
+        111, 99, 229, 0, 245, 246, 35, 90, 88, 83, 208, 167, 153, 9, 181, 70
+    ];
+    assert_eq!(digest, expected, "Keyfile digest golden vector failed");
+
+    let master = kdf::MasterInput::from("master123").with_keyfile(digest);
+    let password = generator::generate_password(master, "example.com", Some("alice"), &policy::default_policy(), 1).unwrap();
+    assert_eq!(password, "2M4VK6i::?ea", "Keyfile password golden vector failed");
+}
+
+/// Golden test vectors for the pepper
+#[test]
+fn pepper_golden_vectors() {
+    let master = kdf::MasterInput::from("master123").with_pepper(kdf::pepper_digest(b"machine secret"));
+    let password = generator::generate_password(master, "example.com", Some("alice"), &policy::default_policy(), 1).unwrap();
+    assert_eq!(password, "fTzUx@P\"F2Y;1m#", "Pepper golden vector failed");
+}
+
+/// Golden test vectors for YubiKey challenges
+#[test]
+fn yubikey_golden_vectors() {
+    let challenge = yubikey::challenge("example.com");
+    assert_eq!(format::Format::Hex.encode(&challenge), "cc4b8e76059b804225a48f8daa430a3fcf15d14643c45d5cb967dd1de4f6c49e", "YubiKey challenge golden vector failed");
+}
+
+/// Golden test vectors for Argon2 secret and associated data
+#[test]
+fn kdf_extras_golden_vectors() {
+    let extras = kdf::KdfExtras { secret: Some(b"pepper"), ad: Some(b"work") };
+    let key = kdf::derive_site_key_extras("password123", "example.com", &kdf::KdfParams::default(), &extras).unwrap();
+    let expected = [
+        176, 17, 142, 6, 97, 227, 23, 163, 236, 211, 189, 52, 51, 76, 145, 95,
+        170, 113, 29, 3, 220, 116, 17, 110, 205, 6, 16, 68, 59, 202, 7, 10
+    ];
+    assert_eq!(key, expected, "Argon2 secret/ad golden vector failed");
+}
+
+/// Golden test vectors for master fingerprints
+#[test]
+fn fingerprint_golden_vectors() {
+    let fp = fingerprint::master_fingerprint("master123").unwrap();
+    assert_eq!(fp, "BJ6Q-9Z9E", "Fingerprint golden vector failed");
+    let emoji = fingerprint::emoji_fingerprint("master123").unwrap();
+    assert_eq!(emoji, "🦉 🦈 🍌 🍁", "Emoji fingerprint golden vector failed");
+}
+
+/// Golden test vectors for the master verifier
+#[test]
+fn verifier_golden_vectors() {
+    let stored = "$argon2id$v=19$m=19456,t=2,p=1$xHDoIRNgAuntjsIc0dnYxg$13QzQeaElb4+ZRTOojNvYOuuaAoUNoNOsOnhmCsPT1g";
+    assert!(pwgen::verifier::verify(stored, "abc").unwrap(), "Verifier golden vector failed");
+    assert!(!pwgen::verifier::verify(stored, "abd").unwrap(), "Verifier golden vector failed");
+}